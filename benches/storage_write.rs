@@ -16,6 +16,7 @@ fn unique_config() -> StreamingConfig {
         max_batch_bytes: 1_000_000_000,
         sparse_interval: 64,
         entries_per_index_block: 256,
+        ..StreamingConfig::default()
     }
 }
 
@@ -61,6 +61,7 @@ fn run_point_query_benchmark(size: u64, run_id: usize) -> std::io::Result<f64> {
         sparse_interval: 1000,
         entries_per_index_block: 1000,
         segment_base_path: format!("data/point_query_benchmark_{}_{}", size, run_id),
+        ..StreamingConfig::default()
     };
 
     let mut storage = StreamingSegmentedStorage::new(config.clone())?;
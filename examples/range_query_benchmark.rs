@@ -114,6 +114,7 @@ fn run_range_query_benchmark(
         sparse_interval: 1000,
         entries_per_index_block: 1000,
         segment_base_path: format!("data/range_query_benchmark_{}_{}", observations, run_id),
+        ..StreamingConfig::default()
     };
 
     let mut storage = StreamingSegmentedStorage::new(config.clone())?;
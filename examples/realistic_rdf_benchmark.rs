@@ -109,6 +109,7 @@ fn run_single_benchmark(
         sparse_interval: 1000,
         entries_per_index_block: 1000,
         segment_base_path: format!("data/realistic_benchmark_{}_{}", size, run_id),
+        ..StreamingConfig::default()
     };
 
     let mut storage = StreamingSegmentedStorage::new(config.clone())?;
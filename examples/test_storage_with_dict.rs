@@ -9,6 +9,7 @@ fn main() {
         max_batch_events: 100_000,
         sparse_interval: 1000,
         entries_per_index_block: 1024,
+        ..StreamingConfig::default()
     };
 
     let storage = StreamingSegmentedStorage::new(config).expect("Failed to load storage");
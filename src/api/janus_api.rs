@@ -1,10 +1,13 @@
 use crate::{
     core::RDFEvent,
-    execution::{HistoricalExecutor, ResultConverter},
+    execution::{
+        pushdown::{readings_query, ReadingsQuery},
+        CompletenessPolicy, HistoricalExecutor, ResultConverter,
+    },
     parsing::janusql_parser::{JanusQLParser, WindowType},
     querying::oxigraph_adapter::OxigraphAdapter,
     registry::query_registry::{BaselineBootstrapMode, QueryId, QueryMetadata, QueryRegistry},
-    storage::segmented_storage::StreamingSegmentedStorage,
+    storage::{event_store::EventStore, rollup::Rollup},
     stream::{
         live_stream_processing::LiveStreamProcessing,
         mqtt_subscriber::{MqttSubscriber, MqttSubscriberConfig},
@@ -122,7 +125,11 @@ pub enum ExecutionStatus {
 pub struct JanusApi {
     parser: JanusQLParser,
     registry: Arc<QueryRegistry>,
-    storage: Arc<StreamingSegmentedStorage>,
+    storage: Arc<dyn EventStore>,
+    // How historical windows treat ranges the watermark has not passed yet
+    completeness: CompletenessPolicy,
+    // Whether baselines of readings queries are read from the storage's rollups
+    rollup_baselines: bool,
 
     // The queries map
     running: Arc<Mutex<HashMap<QueryId, RunningQuery>>>,
@@ -132,9 +139,37 @@ impl JanusApi {
     pub fn new(
         parser: JanusQLParser,
         registry: Arc<QueryRegistry>,
-        storage: Arc<StreamingSegmentedStorage>,
+        storage: Arc<dyn EventStore>,
     ) -> Result<Self, JanusApiError> {
-        Ok(JanusApi { parser, registry, storage, running: Arc::new(Mutex::new(HashMap::new())) })
+        Ok(JanusApi {
+            parser,
+            registry,
+            storage,
+            completeness: CompletenessPolicy::Ignore,
+            rollup_baselines: false,
+            running: Arc::new(Mutex::new(HashMap::new())),
+        })
+    }
+
+    /// Set how historical windows treat ranges the watermark of their stream has not
+    /// passed yet, e.g. to wait for late events before a fixed window is evaluated.
+    #[must_use]
+    pub fn with_completeness_policy(mut self, completeness: CompletenessPolicy) -> Self {
+        self.completeness = completeness;
+        self
+    }
+
+    /// Read the baselines of queries that only select one predicate's readings per subject
+    /// from the storage's rollups instead of executing their historical windows, which
+    /// avoids decoding the events when `rollup_bucket_ms` is configured.
+    ///
+    /// The rollups count every reading, while a window's query sees a reading repeated
+    /// with the same value in the same window only once, so the means differ when a
+    /// subject repeats its readings within a window.
+    #[must_use]
+    pub fn with_rollup_baselines(mut self, enabled: bool) -> Self {
+        self.rollup_baselines = enabled;
+        self
     }
 
     // Register a JanusQL Query within the Query Registry.
@@ -250,10 +285,12 @@ impl JanusApi {
             let storage = Arc::clone(&self.storage);
             let window_clone = window.clone();
             let query_id_clone = query_id.clone();
+            let completeness = self.completeness;
             let (shutdown_tx, shutdown_rx) = mpsc::channel::<()>();
 
             let handle = thread::spawn(move || {
-                let executor = HistoricalExecutor::new(storage, OxigraphAdapter::new());
+                let executor = HistoricalExecutor::new(storage, OxigraphAdapter::new())
+                    .with_completeness(completeness);
                 let converter = ResultConverter::new(query_id_clone);
 
                 match window_clone.window_type {
@@ -358,6 +395,7 @@ impl JanusApi {
                 let query_id_for_baseline = query_id.clone();
                 let baseline_mode = effective_baseline_mode;
                 let baseline_window = effective_baseline_window.clone();
+                let rollup_baselines = self.rollup_baselines;
                 let (baseline_shutdown_tx, baseline_shutdown_rx) = mpsc::channel::<()>();
 
                 baseline_handle =
@@ -367,6 +405,7 @@ impl JanusApi {
                             &parsed_clone,
                             baseline_mode,
                             baseline_window.as_deref(),
+                            rollup_baselines,
                             &baseline_shutdown_rx,
                         ) {
                             Ok(statements) => {
@@ -590,10 +629,11 @@ impl JanusApi {
 }
 
 fn collect_query_baseline_statements(
-    storage: &Arc<StreamingSegmentedStorage>,
+    storage: &Arc<dyn EventStore>,
     parsed: &crate::parsing::janusql_parser::ParsedJanusQuery,
     baseline_mode: BaselineBootstrapMode,
     baseline_window_name: Option<&str>,
+    rollup_baselines: bool,
     shutdown_rx: &Receiver<()>,
 ) -> Result<Vec<(String, String, String)>, JanusApiError> {
     if parsed.live_windows.is_empty() || parsed.historical_windows.is_empty() {
@@ -618,6 +658,19 @@ fn collect_query_baseline_statements(
             ))
         })?;
 
+        if let Some(readings) = readings_query(sparql_query).filter(|_| rollup_baselines) {
+            if let Some(rollup_statements) = collect_rollup_baseline_statements(
+                &executor,
+                window,
+                &readings,
+                baseline_mode,
+                shutdown_rx,
+            )? {
+                statements.extend(rollup_statements);
+                continue;
+            }
+        }
+
         match window.window_type {
             WindowType::HistoricalFixed => {
                 let bindings = executor.execute_fixed_window(window, sparql_query)?;
@@ -670,6 +723,80 @@ fn collect_sliding_window_baseline_statements(
     Ok(baseline_statements_from_accumulator(&accumulator))
 }
 
+// A query selecting one predicate's readings per subject only contributes the mean reading
+// of every subject to the baseline, which the storage's rollups provide without reading
+// the events. Only the rollups of the graphs the query matches are used. Unlike the query's
+// solutions, rollups count a reading repeated within a window every time, which is why
+// this path is opt-in, see `JanusApi::with_rollup_baselines`. Returns `None` when the
+// storage keeps no rollups or a reading is not numeric, so the windows have to be
+// executed instead.
+fn collect_rollup_baseline_statements(
+    executor: &HistoricalExecutor,
+    window: &crate::parsing::janusql_parser::WindowDefinition,
+    readings: &ReadingsQuery,
+    mode: BaselineBootstrapMode,
+    shutdown_rx: &Receiver<()>,
+) -> Result<Option<Vec<(String, String, String)>>, JanusApiError> {
+    let windows: Box<dyn Iterator<Item = Result<Option<Vec<Rollup>>, JanusApiError>>> =
+        match window.window_type {
+            WindowType::HistoricalFixed => {
+                Box::new(std::iter::once(executor.fixed_window_rollups(window)))
+            }
+            WindowType::HistoricalSliding => Box::new(executor.sliding_window_rollups(window)),
+            WindowType::Live => return Ok(None),
+        };
+
+    let mut accumulator = HashMap::new();
+    for window_rollups in windows {
+        if shutdown_rx.try_recv().is_ok() {
+            return Ok(Some(Vec::new()));
+        }
+        let Some(rollups) = window_rollups? else {
+            return Ok(None);
+        };
+
+        if mode == BaselineBootstrapMode::Last {
+            accumulator.clear();
+        }
+        if !accumulate_rollups_into_baseline(&mut accumulator, &rollups, readings) {
+            return Ok(None);
+        }
+    }
+
+    Ok(Some(baseline_statements_from_accumulator(&accumulator)))
+}
+
+fn accumulate_rollups_into_baseline(
+    accumulator: &mut HashMap<(String, String), BaselineAggregate>,
+    rollups: &[Rollup],
+    readings: &ReadingsQuery,
+) -> bool {
+    for rollup in rollups {
+        // Subjects without an IRI cannot anchor a baseline statement.
+        if rollup.predicate != readings.predicate || rollup.subject.starts_with("_:") {
+            continue;
+        }
+        // A `GRAPH ?g` pattern only matches named graphs, a plain one only the default graph.
+        if rollup.graph.is_empty() == readings.in_named_graph {
+            continue;
+        }
+        if !rollup.stats.all_numeric() {
+            return false;
+        }
+
+        let key = (rollup.subject.clone(), readings.value_variable.clone());
+        let entry = accumulator.entry(key).or_insert_with(|| BaselineAggregate {
+            last_value: String::new(),
+            numeric_sum: 0.0,
+            numeric_count: 0,
+            all_numeric: true,
+        });
+        entry.numeric_sum += rollup.stats.sum;
+        entry.numeric_count += rollup.stats.numeric_count as usize;
+    }
+    true
+}
+
 #[cfg(test)]
 fn materialize_bindings_as_static_baseline(
     processor: &mut LiveStreamProcessing,
@@ -889,8 +1016,25 @@ mod tests {
         baseline_statements_from_bindings, materialize_bindings_as_static_baseline,
         normalize_binding_term, parse_mqtt_uri, JANUS_BASELINE_NS,
     };
-    use crate::{core::RDFEvent, stream::live_stream_processing::LiveStreamProcessing};
-    use std::{collections::HashMap, thread, time::Duration};
+    use crate::{
+        core::RDFEvent,
+        execution::pushdown::ReadingsQuery,
+        parsing::janusql_parser::JanusQLParser,
+        registry::query_registry::BaselineBootstrapMode,
+        storage::{
+            event_store::EventStore,
+            rollup::{Rollup, RollupStats},
+            segmented_storage::StreamingSegmentedStorage,
+            util::StreamingConfig,
+        },
+        stream::live_stream_processing::LiveStreamProcessing,
+    };
+    use std::{
+        collections::HashMap,
+        sync::{mpsc, Arc},
+        thread,
+        time::Duration,
+    };
 
     #[test]
     fn test_parse_mqtt_uri_with_port() {
@@ -1071,4 +1215,157 @@ mod tests {
             )]
         );
     }
+
+    #[test]
+    fn test_rollup_baseline_averages_every_reading_of_a_subject() {
+        let readings = ReadingsQuery {
+            predicate: "http://example.org/temperature".to_string(),
+            subject_variable: "sensor".to_string(),
+            value_variable: "temp".to_string(),
+            in_named_graph: false,
+        };
+        let rollup = |bucket_start: u64, predicate: &str, values: &[Option<f64>]| {
+            let mut stats = RollupStats::default();
+            for value in values {
+                stats.add(*value);
+            }
+            Rollup {
+                bucket_start,
+                predicate: predicate.to_string(),
+                subject: "http://example.org/s1".to_string(),
+                graph: String::new(),
+                stats,
+            }
+        };
+
+        let mut accumulator = HashMap::new();
+        assert!(super::accumulate_rollups_into_baseline(
+            &mut accumulator,
+            &[
+                rollup(0, "http://example.org/temperature", &[Some(10.0), Some(20.0)]),
+                rollup(100, "http://example.org/temperature", &[Some(60.0)]),
+                rollup(100, "http://example.org/status", &[None]),
+                Rollup {
+                    graph: "http://example.org/graph1".to_string(),
+                    ..rollup(100, "http://example.org/temperature", &[Some(1_000.0)])
+                },
+            ],
+            &readings,
+        ));
+        assert_eq!(
+            super::baseline_statements_from_accumulator(&accumulator),
+            vec![(
+                "http://example.org/s1".to_string(),
+                format!("{JANUS_BASELINE_NS}temp"),
+                "30".to_string()
+            )]
+        );
+
+        // A reading that is not numeric has no mean, so the query has to run instead.
+        assert!(!super::accumulate_rollups_into_baseline(
+            &mut accumulator,
+            &[rollup(200, "http://example.org/temperature", &[Some(1.0), None])],
+            &readings,
+        ));
+    }
+
+    #[test]
+    fn test_rollup_baselines_are_opt_in_and_match_the_query_over_its_graphs() {
+        let temp_dir = tempfile::TempDir::new().expect("failed to create temp dir");
+        let storage = Arc::new(
+            StreamingSegmentedStorage::new(StreamingConfig {
+                segment_base_path: temp_dir.path().to_string_lossy().into_owned(),
+                rollup_bucket_ms: 100,
+                mmap_cache_capacity: 0,
+                ..StreamingConfig::default()
+            })
+            .expect("failed to create storage"),
+        );
+        let write = |timestamp: u64, sensor: &str, value: &str, graph: &str| {
+            storage
+                .write_rdf_to_stream(
+                    "http://example.org/store",
+                    timestamp,
+                    &format!("http://example.org/{}", sensor),
+                    "http://example.org/temperature",
+                    value,
+                    graph,
+                )
+                .expect("failed to write event");
+        };
+        // The log window only matches named graphs, so the default-graph readings of
+        // sensor1 do not count.
+        write(1_000, "sensor1", "10", "");
+        write(1_100, "sensor1", "20", "");
+        write(1_200, "sensor1", "30", "http://example.org/graph1");
+        write(1_300, "sensor2", "40", "http://example.org/graph1");
+        write(1_400, "sensor2", "50", "http://example.org/graph1");
+        storage.flush().expect("failed to flush");
+
+        let parsed = JanusQLParser::new()
+            .unwrap()
+            .parse(
+                r#"
+                PREFIX ex: <http://example.org/>
+                REGISTER RStream ex:out AS
+                SELECT ?sensor ?temp
+                FROM NAMED WINDOW ex:hist ON LOG ex:store [START 1000 END 2000]
+                FROM NAMED WINDOW ex:live ON STREAM ex:stream [RANGE 500 STEP 100]
+                WHERE {
+                    WINDOW ex:hist { ?sensor ex:temperature ?temp }
+                    WINDOW ex:live { ?sensor ex:temperature ?temp }
+                }
+                "#,
+            )
+            .expect("failed to parse query");
+        let (_shutdown_tx, shutdown_rx) = mpsc::channel();
+        let store: Arc<dyn EventStore> = storage.clone();
+        let baseline = |rollup_baselines: bool| {
+            super::collect_query_baseline_statements(
+                &store,
+                &parsed,
+                BaselineBootstrapMode::Aggregate,
+                None,
+                rollup_baselines,
+                &shutdown_rx,
+            )
+            .expect("failed to collect baseline")
+        };
+        let mean = |sensor: &str, value: &str| {
+            (
+                format!("http://example.org/{}", sensor),
+                format!("{JANUS_BASELINE_NS}temp"),
+                value.to_string(),
+            )
+        };
+
+        let expected = vec![mean("sensor1", "30"), mean("sensor2", "45")];
+        assert_eq!(baseline(false), expected);
+        assert_eq!(baseline(true), expected);
+
+        // A repeated reading is seen once by the query, so configured rollups do not change
+        // the baseline unless they are opted into, where every reading is counted.
+        write(1_450, "sensor2", "40", "http://example.org/graph1");
+        storage.flush().expect("failed to flush");
+        assert_eq!(baseline(false), expected);
+        assert_eq!(
+            baseline(true),
+            vec![mean("sensor1", "30"), mean("sensor2", &(130.0_f64 / 3.0).to_string())]
+        );
+
+        // The opted-in baseline is read from the rollups alone.
+        for segment in storage.segment_metadata() {
+            std::fs::remove_file(&segment.data_path).expect("failed to remove segment");
+        }
+        assert!(super::collect_query_baseline_statements(
+            &store,
+            &parsed,
+            BaselineBootstrapMode::Aggregate,
+            None,
+            false,
+            &shutdown_rx,
+        )
+        .is_err());
+        assert_eq!(baseline(true).len(), 2);
+    }
 }
@@ -9,7 +9,7 @@
 use clap::Parser;
 use janus::{
     api::janus_api::JanusApi,
-    http::start_server,
+    http::{start_server_with_options, ServerOptions},
     parsing::janusql_parser::JanusQLParser,
     registry::query_registry::QueryRegistry,
     storage::{segmented_storage::StreamingSegmentedStorage, util::StreamingConfig},
@@ -37,6 +37,24 @@ struct Args {
 
     #[arg(long, default_value = "1024")]
     max_total_memory_mb: usize,
+
+    /// Threads scanning segments in parallel for historical queries; defaults to the
+    /// number of available cores
+    #[arg(long)]
+    scan_threads: Option<usize>,
+
+    /// Move segments older than this many days to the archive directory
+    #[arg(long)]
+    archive_after_days: Option<u64>,
+
+    /// Archive directory; defaults to the `archive` subdirectory of the storage directory
+    #[arg(long)]
+    archive_dir: Option<String>,
+
+    /// Directory that `POST /api/admin/snapshot` writes snapshots into; the endpoint is
+    /// disabled without it
+    #[arg(long)]
+    snapshot_dir: Option<String>,
 }
 
 #[tokio::main]
@@ -51,6 +69,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Initialize storage
     println!("Initializing storage at: {}", args.storage_dir);
+    let scan_threads = args.scan_threads.unwrap_or_else(|| {
+        std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get)
+    });
     let storage_config = StreamingConfig {
         segment_base_path: args.storage_dir.clone(),
         max_batch_bytes: args.max_batch_size_bytes,
@@ -58,6 +79,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         max_batch_events: 100_000,
         sparse_interval: 1000,
         entries_per_index_block: 1024,
+        query_scan_threads: scan_threads,
+        archive_after_seconds: args.archive_after_days.map(|days| days * 24 * 60 * 60),
+        archive_path: args.archive_dir.clone(),
+        ..StreamingConfig::default()
     };
 
     let mut storage =
@@ -70,6 +95,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let storage = Arc::new(storage);
     println!("  - Max batch size: {} bytes", args.max_batch_size_bytes);
     println!("  - Max batch age: {} seconds", args.flush_interval_ms / 1000);
+    println!("  - Scan threads: {}", scan_threads);
+    if let Some(days) = args.archive_after_days {
+        println!("  - Archive after: {} days", days);
+    }
+    if let Some(snapshot_dir) = &args.snapshot_dir {
+        println!("  - Snapshot directory: {}", snapshot_dir);
+    }
     println!();
 
     // Initialize query registry
@@ -85,12 +117,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize Janus API
     println!("Initializing Janus API...");
     let janus_api = Arc::new(
-        JanusApi::new(parser, Arc::clone(&registry), Arc::clone(&storage))
+        JanusApi::new(parser, Arc::clone(&registry), storage.clone())
             .expect("Failed to initialize Janus API"),
     );
     println!();
 
     // Start HTTP server
+    let options = ServerOptions { snapshot_dir: args.snapshot_dir.map(Into::into) };
     let addr = format!("{}:{}", args.host, args.port);
     println!("Starting HTTP server...");
     println!();
@@ -104,7 +137,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Run server with graceful shutdown
     tokio::select! {
-        result = start_server(&addr, janus_api, registry, storage) => {
+        result = start_server_with_options(&addr, janus_api, registry, storage, options) => {
             if let Err(e) = result {
                 eprintln!("Server error: {}", e);
             }
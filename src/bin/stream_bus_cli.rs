@@ -49,6 +49,10 @@ struct Args {
     /// Storage path
     #[arg(long, default_value = "data/stream_bus_storage")]
     storage_path: String,
+
+    /// Stream IRI to store the events under, as named by `ON LOG <stream>` in JanusQL
+    #[arg(long)]
+    stream_name: Option<String>,
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -64,6 +68,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         sparse_interval: 1000,
         entries_per_index_block: 100,
         segment_base_path: args.storage_path.clone(),
+        ..StreamingConfig::default()
     };
 
     let mut storage = StreamingSegmentedStorage::new(storage_config)?;
@@ -97,6 +102,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             }),
             _ => None,
         },
+        stream_name: args.stream_name.clone(),
     };
 
     println!("Configuration:");
@@ -116,7 +122,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("  Storage: {}", args.storage_path);
     println!();
 
-    let bus = StreamBus::new(bus_config, Arc::clone(&storage));
+    let bus = StreamBus::new(bus_config, storage.clone());
 
     let should_stop = Arc::new(std::sync::atomic::AtomicBool::new(false));
     let should_stop_clone = Arc::clone(&should_stop);
@@ -145,6 +151,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     );
     println!("Publish errors:   {}", metrics.publish_errors);
     println!("Storage errors:   {}", metrics.storage_errors);
+    println!("Throttled writes: {}", metrics.throttled_writes);
     println!("Elapsed time:     {:.2}s", metrics.elapsed_seconds);
     println!("Throughput:       {:.1} events/sec", metrics.events_per_second());
 
@@ -1,6 +1,6 @@
 //! Binary encoding/decoding utilities for RDF events
 
-use crate::core::{Event, RDFEvent};
+use crate::core::{Event, RDFEvent, RdfTerm};
 use crate::storage::indexing::dictionary::Dictionary;
 
 /// Size of a single encoded record in bytes
@@ -33,16 +33,21 @@ pub fn decode_record(buffer: &[u8; RECORD_SIZE]) -> (u64, u32, u32, u32, u32) {
     (timestamp, subject, predicate, object, graph)
 }
 
+// Placeholder for IDs missing from the dictionary.
+fn unknown_term() -> RdfTerm {
+    RdfTerm::simple_literal("UNKNOWN")
+}
+
 /// Implement encoding and decoding methods for RDFEvent and Event to facilitate conversions and byte serialization.
 impl RDFEvent {
     /// Encode this RDF event to an internal Event using a dictionary
     pub fn encode(&self, dict: &mut Dictionary) -> Event {
         Event {
             timestamp: self.timestamp,
-            subject: dict.encode(&self.subject),
-            predicate: dict.encode(&self.predicate),
-            object: dict.encode(&self.object),
-            graph: dict.encode(&self.graph),
+            subject: dict.encode_term(&self.subject),
+            predicate: dict.encode_term(&self.predicate),
+            object: dict.encode_term(&self.object),
+            graph: dict.encode_term(&self.graph),
         }
     }
 }
@@ -53,10 +58,10 @@ impl Event {
     pub fn decode(&self, dict: &Dictionary) -> RDFEvent {
         RDFEvent {
             timestamp: self.timestamp,
-            subject: dict.decode(self.subject).unwrap_or("UNKNOWN").to_string(),
-            predicate: dict.decode(self.predicate).unwrap_or("UNKNOWN").to_string(),
-            object: dict.decode(self.object).unwrap_or("UNKNOWN").to_string(),
-            graph: dict.decode(self.graph).unwrap_or("UNKNOWN").to_string(),
+            subject: dict.decode_term(self.subject).unwrap_or_else(unknown_term),
+            predicate: dict.decode_term(self.predicate).unwrap_or_else(unknown_term),
+            object: dict.decode_term(self.object).unwrap_or_else(unknown_term),
+            graph: dict.decode_graph_term(self.graph).unwrap_or_else(unknown_term),
         }
     }
 
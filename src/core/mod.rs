@@ -12,28 +12,42 @@ pub struct Event {
     pub graph: u32,     // 4 bytes - dictionary-encoded (usually <100 unique)
 }
 
-/// User-facing RDF event with URI strings which is presented to client requesting for the data.
-#[derive(Debug, Clone)]
+/// User-facing RDF event with typed terms which is presented to client requesting for the data.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct RDFEvent {
     pub timestamp: u64,
-    pub subject: String,
-    pub predicate: String,
-    pub object: String,
-    pub graph: String,
+    pub subject: RdfTerm,
+    pub predicate: RdfTerm,
+    pub object: RdfTerm,
+    pub graph: RdfTerm,
 }
 
 /// Implement methods for RDFEvent struct.
 impl RDFEvent {
+    /// Create an event from terms in N-Triples syntax or bare values, see [`RdfTerm::parse`]
+    /// and [`RdfTerm::parse_resource`]. An empty graph or `default` is the default graph.
     pub fn new(timestamp: u64, subject: &str, predicate: &str, object: &str, graph: &str) -> Self {
         Self {
             timestamp,
-            subject: subject.to_string(),
-            predicate: predicate.to_string(),
-            object: object.to_string(),
-            graph: graph.to_string(),
+            subject: RdfTerm::parse_resource(subject),
+            predicate: RdfTerm::parse_resource(predicate),
+            object: RdfTerm::parse(object),
+            graph: RdfTerm::parse_graph(graph),
         }
     }
+
+    pub fn from_terms(
+        timestamp: u64,
+        subject: RdfTerm,
+        predicate: RdfTerm,
+        object: RdfTerm,
+        graph: RdfTerm,
+    ) -> Self {
+        Self { timestamp, subject, predicate, object, graph }
+    }
 }
 
 pub mod encoding;
+pub mod term;
 pub use encoding::*;
+pub use term::RdfTerm;
@@ -0,0 +1,363 @@
+//! Typed RDF terms of user-facing events.
+//!
+//! An [`RdfTerm`] keeps the kind of a term together with the datatype or language
+//! of a literal, so events converted to Oxigraph quads are the ones that were written.
+//! Terms are parsed from the strings handed to `RDFEvent::new` and `write_rdf`:
+//!
+//! - `<urn:sensor:1>` is a named node, and so is a bare subject, predicate or graph;
+//! - `_:b0` is a blank node;
+//! - `"23.5"^^<http://www.w3.org/2001/XMLSchema#double>`, `"hello"@en` and `"hello"`
+//!   are literals in N-Triples syntax;
+//! - a bare object is a named node when it is an `http://` or `https://` IRI, and
+//!   otherwise a literal, typed `xsd:decimal` when it is numeric so that aggregates
+//!   work on it, and a simple literal otherwise. Bare values such as `status:ok` thus
+//!   stay literals, as they have always been.
+//!
+//! The dictionary stores every term under its [`RdfTerm::dictionary_key`]: the IRI
+//! itself for named nodes and the N-Triples form for all other terms, which
+//! [`RdfTerm::from_dictionary_key`] reads back.
+
+use std::fmt;
+
+use oxigraph::model::{BlankNode, GraphName, Literal, NamedNode, NamedOrBlankNode, Quad, Term};
+
+use crate::core::RDFEvent;
+
+const XSD_DECIMAL: &str = "http://www.w3.org/2001/XMLSchema#decimal";
+const XSD_NAMESPACE: &str = "http://www.w3.org/2001/XMLSchema#";
+const XSD_NUMERIC_TYPES: &[&str] = &[
+    "integer",
+    "decimal",
+    "double",
+    "float",
+    "long",
+    "int",
+    "short",
+    "byte",
+    "nonNegativeInteger",
+    "positiveInteger",
+    "nonPositiveInteger",
+    "negativeInteger",
+    "unsignedLong",
+    "unsignedInt",
+    "unsignedShort",
+    "unsignedByte",
+];
+const XSD_STRING: &str = "http://www.w3.org/2001/XMLSchema#string";
+
+/// A subject, predicate, object or graph name of an RDF event.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RdfTerm {
+    NamedNode(String),
+    BlankNode(String),
+    /// A literal with either a datatype or a language tag; a literal with neither is
+    /// a simple `xsd:string` literal.
+    Literal {
+        value: String,
+        datatype: Option<String>,
+        language: Option<String>,
+    },
+    /// The default graph, only meaningful as the graph of an event.
+    DefaultGraph,
+}
+
+impl RdfTerm {
+    pub fn named_node(iri: &str) -> Self {
+        RdfTerm::NamedNode(iri.to_string())
+    }
+
+    pub fn blank_node(id: &str) -> Self {
+        RdfTerm::BlankNode(id.to_string())
+    }
+
+    pub fn simple_literal(value: &str) -> Self {
+        RdfTerm::Literal { value: value.to_string(), datatype: None, language: None }
+    }
+
+    pub fn typed_literal(value: &str, datatype: &str) -> Self {
+        let datatype = (datatype != XSD_STRING).then(|| datatype.to_string());
+        RdfTerm::Literal { value: value.to_string(), datatype, language: None }
+    }
+
+    pub fn language_tagged_literal(value: &str, language: &str) -> Self {
+        RdfTerm::Literal {
+            value: value.to_string(),
+            datatype: None,
+            language: Some(language.to_ascii_lowercase()),
+        }
+    }
+
+    /// Parse an object from N-Triples syntax or from a bare value, as described in the
+    /// module documentation.
+    pub fn parse(input: &str) -> Self {
+        if let Some(term) = parse_ntriples_term(input) {
+            term
+        } else if is_http_iri(input) {
+            RdfTerm::named_node(input)
+        } else if input.parse::<f64>().is_ok() {
+            RdfTerm::typed_literal(input, XSD_DECIMAL)
+        } else {
+            RdfTerm::simple_literal(input)
+        }
+    }
+
+    /// Parse a subject or predicate from N-Triples syntax or from a bare value, which is
+    /// always an IRI.
+    pub fn parse_resource(input: &str) -> Self {
+        parse_ntriples_term(input).unwrap_or_else(|| RdfTerm::named_node(input))
+    }
+
+    /// Parse the graph of an event, where an empty value or `default` is the default graph
+    /// and any other bare value an IRI.
+    pub fn parse_graph(input: &str) -> Self {
+        if input.is_empty() || input == "default" {
+            RdfTerm::DefaultGraph
+        } else {
+            Self::parse_resource(input)
+        }
+    }
+
+    /// Read a term back from its [`Self::dictionary_key`]. Keys not in N-Triples syntax are
+    /// the IRIs of named nodes, or values stored before terms were typed, which are read
+    /// like bare objects except that every valid absolute IRI is a named node.
+    pub fn from_dictionary_key(key: &str) -> Self {
+        match parse_ntriples_term(key) {
+            Some(term) => term,
+            None if NamedNode::new(key).is_ok() => RdfTerm::named_node(key),
+            None => Self::parse(key),
+        }
+    }
+
+    /// The IRI, blank node identifier or lexical form of the term; empty for the default graph.
+    pub fn value(&self) -> &str {
+        match self {
+            RdfTerm::NamedNode(value)
+            | RdfTerm::BlankNode(value)
+            | RdfTerm::Literal { value, .. } => value,
+            RdfTerm::DefaultGraph => "",
+        }
+    }
+
+    /// The string the term is stored under in the dictionary.
+    pub fn dictionary_key(&self) -> String {
+        match self {
+            RdfTerm::NamedNode(iri) => iri.clone(),
+            RdfTerm::DefaultGraph => String::new(),
+            _ => self.to_string(),
+        }
+    }
+
+    /// Convert the term to an Oxigraph term.
+    pub fn to_term(&self) -> Result<Term, String> {
+        match self {
+            RdfTerm::NamedNode(iri) => NamedNode::new(iri)
+                .map(Term::NamedNode)
+                .map_err(|e| format!("Invalid IRI '{}': {}", iri, e)),
+            RdfTerm::BlankNode(id) => BlankNode::new(id)
+                .map(Term::BlankNode)
+                .map_err(|e| format!("Invalid blank node '_:{}': {}", id, e)),
+            RdfTerm::Literal { value, datatype: _, language: Some(language) } => {
+                Literal::new_language_tagged_literal(value, language)
+                    .map(Term::Literal)
+                    .map_err(|e| format!("Invalid language tag '{}': {}", language, e))
+            }
+            RdfTerm::Literal { value, datatype: Some(datatype), language: None } => {
+                let datatype = NamedNode::new(datatype)
+                    .map_err(|e| format!("Invalid datatype IRI '{}': {}", datatype, e))?;
+                Ok(Term::Literal(Literal::new_typed_literal(value, datatype)))
+            }
+            RdfTerm::Literal { value, datatype: None, language: None } => {
+                Ok(Term::Literal(Literal::new_simple_literal(value)))
+            }
+            RdfTerm::DefaultGraph => Err("The default graph is not an RDF term".to_string()),
+        }
+    }
+
+    fn to_subject(&self) -> Result<NamedOrBlankNode, String> {
+        match self.to_term()? {
+            Term::NamedNode(node) => Ok(node.into()),
+            Term::BlankNode(node) => Ok(node.into()),
+            Term::Literal(_) => {
+                Err(format!("Subject must be an IRI or blank node, got literal {}", self))
+            }
+        }
+    }
+
+    fn to_named_node(&self) -> Result<NamedNode, String> {
+        match self.to_term()? {
+            Term::NamedNode(node) => Ok(node),
+            _ => Err(format!("Predicate must be an IRI, got {}", self)),
+        }
+    }
+
+    fn to_graph_name(&self) -> Result<GraphName, String> {
+        match self {
+            RdfTerm::DefaultGraph => Ok(GraphName::DefaultGraph),
+            _ => match self.to_term()? {
+                Term::NamedNode(node) => Ok(node.into()),
+                Term::BlankNode(node) => Ok(node.into()),
+                Term::Literal(_) => {
+                    Err(format!("Graph must be an IRI or blank node, got literal {}", self))
+                }
+            },
+        }
+    }
+}
+
+impl From<&Term> for RdfTerm {
+    fn from(term: &Term) -> Self {
+        match term {
+            Term::NamedNode(node) => RdfTerm::named_node(node.as_str()),
+            Term::BlankNode(node) => RdfTerm::blank_node(node.as_str()),
+            Term::Literal(literal) => match literal.language() {
+                Some(language) => RdfTerm::language_tagged_literal(literal.value(), language),
+                None => RdfTerm::typed_literal(literal.value(), literal.datatype().as_str()),
+            },
+        }
+    }
+}
+
+impl From<&GraphName> for RdfTerm {
+    fn from(graph: &GraphName) -> Self {
+        match graph {
+            GraphName::NamedNode(node) => RdfTerm::named_node(node.as_str()),
+            GraphName::BlankNode(node) => RdfTerm::blank_node(node.as_str()),
+            GraphName::DefaultGraph => RdfTerm::DefaultGraph,
+        }
+    }
+}
+
+/// N-Triples serialization of the term; the default graph is written as an empty string.
+impl fmt::Display for RdfTerm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RdfTerm::NamedNode(iri) => write!(f, "<{}>", iri),
+            RdfTerm::BlankNode(id) => write!(f, "_:{}", id),
+            RdfTerm::Literal { value, datatype, language } => {
+                f.write_str("\"")?;
+                for c in value.chars() {
+                    match c {
+                        '"' => f.write_str("\\\"")?,
+                        '\\' => f.write_str("\\\\")?,
+                        '\n' => f.write_str("\\n")?,
+                        '\r' => f.write_str("\\r")?,
+                        c => write!(f, "{}", c)?,
+                    }
+                }
+                f.write_str("\"")?;
+                if let Some(language) = language {
+                    write!(f, "@{}", language)
+                } else if let Some(datatype) = datatype {
+                    write!(f, "^^<{}>", datatype)
+                } else {
+                    Ok(())
+                }
+            }
+            RdfTerm::DefaultGraph => Ok(()),
+        }
+    }
+}
+
+impl RDFEvent {
+    /// Convert the event to an Oxigraph quad, checking that each term fits its position.
+    pub fn to_quad(&self) -> Result<Quad, String> {
+        Ok(Quad::new(
+            self.subject.to_subject()?,
+            self.predicate.to_named_node()?,
+            self.object.to_term()?,
+            self.graph.to_graph_name()?,
+        ))
+    }
+
+    /// Build an event from an Oxigraph quad without losing any term information.
+    pub fn from_quad(timestamp: u64, quad: &Quad) -> Self {
+        let subject = match &quad.subject {
+            NamedOrBlankNode::NamedNode(node) => RdfTerm::named_node(node.as_str()),
+            NamedOrBlankNode::BlankNode(node) => RdfTerm::blank_node(node.as_str()),
+        };
+        Self::from_terms(
+            timestamp,
+            subject,
+            RdfTerm::named_node(quad.predicate.as_str()),
+            RdfTerm::from(&quad.object),
+            RdfTerm::from(&quad.graph_name),
+        )
+    }
+}
+
+// Parse a literal in N-Triples syntax and return it with the unparsed remainder of the input.
+pub(crate) fn parse_literal(input: &str) -> Option<(RdfTerm, &str)> {
+    let mut value = String::new();
+    let mut chars = input.strip_prefix('"')?.char_indices();
+    let rest = loop {
+        let (index, c) = chars.next()?;
+        match c {
+            '"' => break &input[index + 2..],
+            '\\' => {
+                let escaped = match chars.next()?.1 {
+                    't' => '\t',
+                    'b' => '\u{8}',
+                    'n' => '\n',
+                    'r' => '\r',
+                    'f' => '\u{c}',
+                    '"' => '"',
+                    '\'' => '\'',
+                    '\\' => '\\',
+                    marker @ ('u' | 'U') => {
+                        let digits = if marker == 'u' { 4 } else { 8 };
+                        let hex: String = (0..digits)
+                            .map(|_| chars.next().map(|(_, c)| c))
+                            .collect::<Option<String>>()?;
+                        char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?
+                    }
+                    _ => return None,
+                };
+                value.push(escaped);
+            }
+            c => value.push(c),
+        }
+    };
+
+    if let Some(after) = rest.strip_prefix("^^<") {
+        let end = after.find('>')?;
+        Some((RdfTerm::typed_literal(&value, &after[..end]), &after[end + 1..]))
+    } else if let Some(after) = rest.strip_prefix('@') {
+        let end = after
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-'))
+            .unwrap_or(after.len());
+        if end == 0 {
+            return None;
+        }
+        Some((RdfTerm::language_tagged_literal(&value, &after[..end]), &after[end..]))
+    } else {
+        Some((RdfTerm::simple_literal(&value), rest))
+    }
+}
+
+// A named node, blank node or literal written in N-Triples syntax.
+fn parse_ntriples_term(input: &str) -> Option<RdfTerm> {
+    if let Some(iri) = input.strip_prefix('<').and_then(|rest| rest.strip_suffix('>')) {
+        return Some(RdfTerm::named_node(iri));
+    }
+    if let Some(id) = input.strip_prefix("_:") {
+        return Some(RdfTerm::blank_node(id));
+    }
+    if input.starts_with('"') {
+        if let Some((term, "")) = parse_literal(input) {
+            return Some(term);
+        }
+    }
+    None
+}
+
+// Whether the value is a valid `http://` or `https://` IRI.
+fn is_http_iri(value: &str) -> bool {
+    (value.starts_with("http://") || value.starts_with("https://")) && NamedNode::new(value).is_ok()
+}
+
+/// Whether the datatype IRI is one of the XSD numeric datatypes.
+pub fn is_xsd_numeric(datatype: &str) -> bool {
+    datatype
+        .strip_prefix(XSD_NAMESPACE)
+        .is_some_and(|local_name| XSD_NUMERIC_TYPES.contains(&local_name))
+}
@@ -13,17 +13,50 @@
 //! 5. Result formatting as structured bindings
 
 use crate::api::janus_api::JanusApiError;
-use crate::core::{Event, RDFEvent};
+use crate::core::{Event, RDFEvent, RdfTerm};
+use crate::execution::pushdown::{decompose_query, pushdown_patterns, DecomposedQuery};
 use crate::parsing::janusql_parser::WindowDefinition;
 use crate::querying::oxigraph_adapter::OxigraphAdapter;
-use crate::storage::segmented_storage::StreamingSegmentedStorage;
+use crate::storage::event_store::EventStore;
+use crate::storage::indexing::secondary::TriplePattern;
+use crate::storage::query_iter::QueryIter;
+use crate::storage::rollup::Rollup;
 use crate::stream::operators::historical_fixed_window::HistoricalFixedWindowOperator;
 use crate::stream::operators::historical_sliding_window::HistoricalSlidingWindowOperator;
-use oxigraph::model::{GraphName, NamedNode, Quad, Term};
+use oxigraph::model::Quad;
 use rsp_rs::QuadContainer;
 use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 use std::sync::Arc;
+use std::time::Duration;
+
+/// Number of events decoded and converted to quads at a time while a window is read.
+const EVENT_CHUNK_SIZE: usize = 8192;
+
+/// How a historical window treats a range that the watermark of its stream has not
+/// passed yet.
+///
+/// The watermark is the newest event time of the stream minus the storage's allowed
+/// lateness; a window is complete once the watermark is past its end. Stores that do not
+/// track event time report no watermark, so their windows are never complete.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CompletenessPolicy {
+    /// Read whatever is stored.
+    #[default]
+    Ignore,
+    /// Wait up to the given time for the window to complete, then read whatever is stored.
+    Wait(Duration),
+    /// Fail with an execution error unless the window is complete.
+    Require,
+}
+
+/// Event-time progress of the stream of a historical window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowProgress {
+    pub watermark: Option<u64>,
+    /// Whether the watermark has passed the end of the window.
+    pub complete: bool,
+}
 
 /// Executor for historical SPARQL queries over stored RDF data.
 ///
@@ -38,8 +71,9 @@ use std::sync::Arc;
 /// }
 /// ```
 pub struct HistoricalExecutor {
-    storage: Arc<StreamingSegmentedStorage>,
+    storage: Arc<dyn EventStore>,
     sparql_engine: OxigraphAdapter,
+    completeness: CompletenessPolicy,
 }
 
 impl HistoricalExecutor {
@@ -47,10 +81,50 @@ impl HistoricalExecutor {
     ///
     /// # Arguments
     ///
-    /// * `storage` - Shared reference to the segmented storage backend
+    /// * `storage` - Shared reference to the storage backend
     /// * `sparql_engine` - SPARQL query engine (OxigraphAdapter)
-    pub fn new(storage: Arc<StreamingSegmentedStorage>, sparql_engine: OxigraphAdapter) -> Self {
-        Self { storage, sparql_engine }
+    pub fn new(storage: Arc<dyn EventStore>, sparql_engine: OxigraphAdapter) -> Self {
+        Self { storage, sparql_engine, completeness: CompletenessPolicy::Ignore }
+    }
+
+    /// Sets how windows the watermark has not passed yet are executed.
+    #[must_use]
+    pub fn with_completeness(mut self, completeness: CompletenessPolicy) -> Self {
+        self.completeness = completeness;
+        self
+    }
+
+    /// Reports whether the watermark of a stream has passed the end of a window.
+    pub fn window_progress(&self, stream_name: &str, end: u64) -> WindowProgress {
+        let watermark = self.storage.watermark(Some(stream_name));
+        WindowProgress { watermark, complete: watermark.is_some_and(|watermark| watermark > end) }
+    }
+
+    /// Applies the completeness policy to a window ending at `end`.
+    fn await_completeness(&self, stream_name: &str, end: u64) -> Result<(), JanusApiError> {
+        match self.completeness {
+            CompletenessPolicy::Ignore => Ok(()),
+            CompletenessPolicy::Wait(timeout) => {
+                self.storage.wait_for_watermark(Some(stream_name), end, timeout);
+                Ok(())
+            }
+            CompletenessPolicy::Require => {
+                let progress = self.window_progress(stream_name, end);
+                if progress.complete {
+                    return Ok(());
+                }
+                Err(JanusApiError::ExecutionError(match progress.watermark {
+                    Some(watermark) => format!(
+                        "Window ending at {} is incomplete, the watermark of {} is at {}",
+                        end, stream_name, watermark
+                    ),
+                    None => format!(
+                        "Window ending at {} is incomplete, {} has no watermark",
+                        end, stream_name
+                    ),
+                }))
+            }
+        }
     }
 
     /// Execute a fixed window query that returns results once.
@@ -85,14 +159,16 @@ impl HistoricalExecutor {
             JanusApiError::ExecutionError("Fixed window requires end timestamp".to_string())
         })?;
 
+        self.await_completeness(&window.stream_name, end)?;
+
         // Query the storage for events in the fixed window
+        let patterns = pushdown_patterns(sparql_query);
         let events = self
-            .storage
-            .query(start, end)
+            .query_window_events(&window.stream_name, start, end, patterns.as_deref())
             .map_err(|e| JanusApiError::StorageError(format!("Failed to query storage: {}", e)))?;
 
         // Execute SPARQL on the events
-        self.execute_sparql_on_events(&events, sparql_query)
+        self.execute_sparql_on_events(events, sparql_query)
     }
 
     /// Execute a sliding window query that returns an iterator of results.
@@ -123,41 +199,116 @@ impl HistoricalExecutor {
         sparql_query: &'a str,
     ) -> impl Iterator<Item = Result<Vec<HashMap<String, String>>, JanusApiError>> + 'a {
         // Calculate sliding windows and query storage directly
-        let offset = window.offset.unwrap_or(0);
-        let width = window.width;
-        let slide = window.slide;
-
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_millis() as u64;
-
-        let start_time = now.saturating_sub(offset);
-        let end_bound = now;
+        let (start_time, end_bound) = Self::sliding_bounds(window);
 
         // Create an iterator that generates windows
         SlidingWindowIterator {
             executor: self,
             current_start: start_time,
             end_bound,
-            width,
-            slide,
+            width: window.width,
+            slide: window.slide,
+            stream_name: window.stream_name.clone(),
+            patterns: pushdown_patterns(sparql_query),
             sparql_query: sparql_query.to_string(),
         }
     }
 
+    /// Reads the aggregates of a fixed window's readings from the storage's rollups
+    /// instead of its events, for callers that only need aggregates.
+    ///
+    /// # Returns
+    ///
+    /// The rollups of the window's range, see [`EventStore::rollups`], or `None` when the
+    /// storage keeps no rollups and the window has to be executed instead.
+    pub fn fixed_window_rollups(
+        &self,
+        window: &WindowDefinition,
+    ) -> Result<Option<Vec<Rollup>>, JanusApiError> {
+        let (Some(start), Some(end)) = (window.start, window.end) else {
+            return Err(JanusApiError::ExecutionError(
+                "Fixed window requires start and end timestamps".to_string(),
+            ));
+        };
+        self.window_rollups(&window.stream_name, start, end)
+    }
+
+    /// Reads the aggregates of every sliding window's readings from the storage's rollups,
+    /// see [`Self::fixed_window_rollups`]. The windows are the ones
+    /// [`Self::execute_sliding_windows`] executes.
+    pub fn sliding_window_rollups<'a>(
+        &'a self,
+        window: &WindowDefinition,
+    ) -> impl Iterator<Item = Result<Option<Vec<Rollup>>, JanusApiError>> + 'a {
+        let (start_time, end_bound) = Self::sliding_bounds(window);
+        let (width, slide) = (window.width, window.slide);
+        let stream_name = window.stream_name.clone();
+        std::iter::successors(Some(start_time), move |start| start.checked_add(slide))
+            .take_while(move |start| *start <= end_bound)
+            .map(move |start| {
+                self.window_rollups(&stream_name, start, (start + width).min(end_bound))
+            })
+    }
+
+    fn window_rollups(
+        &self,
+        stream_name: &str,
+        start: u64,
+        end: u64,
+    ) -> Result<Option<Vec<Rollup>>, JanusApiError> {
+        self.await_completeness(stream_name, end)?;
+        self.storage
+            .rollups(stream_name, start, end)
+            .map_err(|e| JanusApiError::StorageError(format!("Failed to read rollups: {}", e)))
+    }
+
+    /// Start of the first sliding window and the bound the windows end at: `offset`
+    /// milliseconds before now, and now.
+    fn sliding_bounds(window: &WindowDefinition) -> (u64, u64) {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        (now.saturating_sub(window.offset.unwrap_or(0)), now)
+    }
+
+    /// Lazily reads the events of a window from the stream's log.
+    ///
+    /// When the query only touches triples with constant subjects or predicates, the
+    /// patterns are pushed down so that the storage skips every other event.
+    fn query_window_events(
+        &self,
+        stream_name: &str,
+        start: u64,
+        end: u64,
+        patterns: Option<&[TriplePattern]>,
+    ) -> std::io::Result<QueryIter> {
+        match patterns {
+            Some(patterns) => {
+                self.storage.query_stream_filtered_iter(stream_name, start, end, patterns)
+            }
+            None => self.storage.query_stream_iter(stream_name, start, end),
+        }
+    }
+
     /// Core conversion and execution logic for a set of events.
     ///
     /// # Process
     ///
-    /// 1. Decode Event → RDFEvent using Dictionary
-    /// 2. Convert RDFEvent → Quad with proper URI parsing
-    /// 3. Build QuadContainer for SPARQL engine
-    /// 4. Execute SPARQL query with structured bindings
+    /// 1. Read the events in chunks of `EVENT_CHUNK_SIZE`
+    /// 2. Decode Event → RDFEvent using Dictionary
+    /// 3. Convert RDFEvent → Quad with proper URI parsing
+    /// 4. Build QuadContainer for SPARQL engine
+    /// 5. Execute SPARQL query with structured bindings
+    ///
+    /// Only one chunk of events is decoded at a time. A query that [`decompose_query`]
+    /// recognizes is run on every chunk on its own, so memory holds one chunk's quads
+    /// besides the solutions. Any other query needs every distinct quad of the window in one
+    /// container, which holds as many quads as the window has distinct events.
     ///
     /// # Arguments
     ///
-    /// * `events` - Slice of internal Event structs (24-byte format)
+    /// * `events` - Lazy iterator over internal Event structs (24-byte format)
     /// * `sparql_query` - SPARQL SELECT query string
     ///
     /// # Returns
@@ -165,19 +316,34 @@ impl HistoricalExecutor {
     /// Vector of solution bindings (variable name → value)
     fn execute_sparql_on_events(
         &self,
-        events: &[Event],
+        events: QueryIter,
         sparql_query: &str,
     ) -> Result<Vec<HashMap<String, String>>, JanusApiError> {
-        // 1. Decode Event → RDFEvent
-        let rdf_events = self.decode_events(events)?;
+        if let Some(decomposed) = decompose_query(sparql_query) {
+            return self.execute_sparql_per_chunk(events, &decomposed);
+        }
 
-        // 2. Convert RDFEvent → Quad
-        let quads = self.rdf_events_to_quads(&rdf_events)?;
+        let mut quads = HashSet::new();
+        let mut max_timestamp = 0;
 
-        // 3. Build QuadContainer
-        let container = self.build_quad_container(quads, events)?;
+        // 1. Read the next chunk of events
+        for chunk in events.chunks(EVENT_CHUNK_SIZE) {
+            let chunk = chunk.map_err(|e| {
+                JanusApiError::StorageError(format!("Failed to query storage: {}", e))
+            })?;
+            max_timestamp = chunk.iter().map(|e| e.timestamp).fold(max_timestamp, u64::max);
+
+            // 2. Decode Event → RDFEvent
+            let rdf_events = self.decode_events(&chunk)?;
+
+            // 3. Convert RDFEvent → Quad
+            quads.extend(self.rdf_events_to_quads(&rdf_events)?);
+        }
 
-        // 4. Execute SPARQL with structured bindings
+        // 4. Build QuadContainer
+        let container = self.build_quad_container(quads, max_timestamp)?;
+
+        // 5. Execute SPARQL with structured bindings
         let result = self
             .sparql_engine
             .execute_query_bindings(sparql_query, &container)
@@ -186,6 +352,50 @@ impl HistoricalExecutor {
         result
     }
 
+    /// Runs a decomposed query on each chunk of events, see [`decompose_query`].
+    ///
+    /// A quad repeated in several chunks yields the same solution in each of them, so only
+    /// the first is kept before the solution is projected onto the selected variables.
+    fn execute_sparql_per_chunk(
+        &self,
+        events: QueryIter,
+        decomposed: &DecomposedQuery,
+    ) -> Result<Vec<HashMap<String, String>>, JanusApiError> {
+        let mut seen = HashSet::new();
+        let mut solutions = Vec::new();
+
+        for chunk in events.chunks(EVENT_CHUNK_SIZE) {
+            let chunk = chunk.map_err(|e| {
+                JanusApiError::StorageError(format!("Failed to query storage: {}", e))
+            })?;
+            let max_timestamp = chunk.iter().map(|e| e.timestamp).max().unwrap_or(0);
+            let rdf_events = self.decode_events(&chunk)?;
+            let quads = self.rdf_events_to_quads(&rdf_events)?.into_iter().collect();
+            let container = self.build_quad_container(quads, max_timestamp)?;
+
+            let bindings = self
+                .sparql_engine
+                .execute_query_bindings(&decomposed.query, &container)
+                .map_err(|e| {
+                    JanusApiError::ExecutionError(format!("SPARQL execution failed: {}", e))
+                })?;
+            for binding in bindings {
+                let mut projected = binding.clone();
+                projected.retain(|name, _| decomposed.variables.contains(name));
+                let identity = if decomposed.distinct {
+                    &projected
+                } else {
+                    &binding
+                };
+                if seen.insert(solution_key(identity)) {
+                    solutions.push(projected);
+                }
+            }
+        }
+
+        Ok(solutions)
+    }
+
     /// Decodes internal Event structs to RDFEvent using the Dictionary.
     ///
     /// # Arguments
@@ -194,7 +404,7 @@ impl HistoricalExecutor {
     ///
     /// # Returns
     ///
-    /// Vector of RDFEvent with full URI strings
+    /// Vector of RDFEvent with typed terms
     ///
     /// # Errors
     ///
@@ -207,49 +417,22 @@ impl HistoricalExecutor {
         let mut rdf_events = Vec::with_capacity(events.len());
 
         for event in events {
-            // Decode each field individually
-            let subject = dictionary
-                .decode(event.subject)
-                .ok_or_else(|| {
+            let decode = |id: u32, position: &str, term: Option<RdfTerm>| {
+                term.ok_or_else(|| {
                     JanusApiError::ExecutionError(format!(
-                        "Failed to decode subject ID: {}",
-                        event.subject
+                        "Failed to decode {} ID: {}",
+                        position, id
                     ))
-                })?
-                .to_string();
-
-            let predicate = dictionary
-                .decode(event.predicate)
-                .ok_or_else(|| {
-                    JanusApiError::ExecutionError(format!(
-                        "Failed to decode predicate ID: {}",
-                        event.predicate
-                    ))
-                })?
-                .to_string();
-
-            let object = dictionary
-                .decode(event.object)
-                .ok_or_else(|| {
-                    JanusApiError::ExecutionError(format!(
-                        "Failed to decode object ID: {}",
-                        event.object
-                    ))
-                })?
-                .to_string();
-
-            let graph = dictionary
-                .decode(event.graph)
-                .ok_or_else(|| {
-                    JanusApiError::ExecutionError(format!(
-                        "Failed to decode graph ID: {}",
-                        event.graph
-                    ))
-                })?
-                .to_string();
+                })
+            };
 
-            let rdf_event = RDFEvent::new(event.timestamp, &subject, &predicate, &object, &graph);
-            rdf_events.push(rdf_event);
+            rdf_events.push(RDFEvent::from_terms(
+                event.timestamp,
+                decode(event.subject, "subject", dictionary.decode_term(event.subject))?,
+                decode(event.predicate, "predicate", dictionary.decode_term(event.predicate))?,
+                decode(event.object, "object", dictionary.decode_term(event.object))?,
+                decode(event.graph, "graph", dictionary.decode_graph_term(event.graph))?,
+            ));
         }
 
         Ok(rdf_events)
@@ -281,102 +464,41 @@ impl HistoricalExecutor {
 
     /// Converts a single RDFEvent to an Oxigraph Quad.
     ///
-    /// # URI Handling
+    /// # Term Handling
     ///
-    /// - Subject: Must be a valid URI (NamedNode)
+    /// - Subject: Must be a valid URI (NamedNode) or blank node
     /// - Predicate: Must be a valid URI (NamedNode)
-    /// - Object: Can be URI (NamedNode) or literal value (Literal)
-    /// - Graph: Can be URI (NamedNode) or "default" (DefaultGraph)
+    /// - Object: Any term; literals keep their datatype or language tag
+    /// - Graph: URI (NamedNode), blank node or the default graph
     ///
     /// # Arguments
     ///
-    /// * `event` - RDFEvent with string URIs
+    /// * `event` - RDFEvent with typed terms
     ///
     /// # Returns
     ///
     /// Oxigraph Quad ready for SPARQL processing
     fn rdf_event_to_quad(&self, event: &RDFEvent) -> Result<Quad, JanusApiError> {
-        // Parse subject as NamedNode
-        let subject = NamedNode::new(&event.subject).map_err(|e| {
-            JanusApiError::ExecutionError(format!("Invalid subject URI '{}': {}", event.subject, e))
-        })?;
-
-        // Parse predicate as NamedNode
-        let predicate = NamedNode::new(&event.predicate).map_err(|e| {
-            JanusApiError::ExecutionError(format!(
-                "Invalid predicate URI '{}': {}",
-                event.predicate, e
-            ))
-        })?;
-
-        // Parse object - can be URI or literal
-        let object = if event.object.starts_with("http://") || event.object.starts_with("https://")
-        {
-            // Object is a URI
-            let object_node = NamedNode::new(&event.object).map_err(|e| {
-                JanusApiError::ExecutionError(format!(
-                    "Invalid object URI '{}': {}",
-                    event.object, e
-                ))
-            })?;
-            Term::NamedNode(object_node)
-        } else {
-            // Object is a literal value - check if it's numeric for SPARQL aggregations
-            let literal = if let Ok(_) = event.object.parse::<f64>() {
-                // It's a decimal number - create typed literal for SPARQL aggregations
-                oxigraph::model::Literal::new_typed_literal(
-                    &event.object,
-                    NamedNode::new("http://www.w3.org/2001/XMLSchema#decimal").unwrap(),
-                )
-            } else if let Ok(_) = event.object.parse::<i64>() {
-                // It's an integer
-                oxigraph::model::Literal::new_typed_literal(
-                    &event.object,
-                    NamedNode::new("http://www.w3.org/2001/XMLSchema#integer").unwrap(),
-                )
-            } else {
-                // Plain string literal
-                oxigraph::model::Literal::new_simple_literal(&event.object)
-            };
-            Term::Literal(literal)
-        };
-
-        // Parse graph - default or named
-        let graph = if event.graph.is_empty() || event.graph == "default" {
-            GraphName::DefaultGraph
-        } else {
-            let graph_node = NamedNode::new(&event.graph).map_err(|e| {
-                JanusApiError::ExecutionError(format!("Invalid graph URI '{}': {}", event.graph, e))
-            })?;
-            GraphName::NamedNode(graph_node)
-        };
-
-        Ok(Quad::new(subject, predicate, object, graph))
+        event.to_quad().map_err(JanusApiError::ExecutionError)
     }
 
     /// Builds a QuadContainer for SPARQL execution.
     ///
     /// # Arguments
     ///
-    /// * `quads` - Vector of Quad structs
-    /// * `events` - Original events (used for timestamp metadata)
+    /// * `quads` - Set of distinct Quad structs
+    /// * `max_timestamp` - Latest timestamp of the events the quads were built from
     ///
     /// # Returns
     ///
     /// QuadContainer with timestamp set to the latest event timestamp
     fn build_quad_container(
         &self,
-        quads: Vec<Quad>,
-        events: &[Event],
+        quads: HashSet<Quad>,
+        max_timestamp: u64,
     ) -> Result<QuadContainer, JanusApiError> {
-        // Find the maximum timestamp from events
-        let max_timestamp = events.iter().map(|e| e.timestamp).max().unwrap_or(0);
-
-        // Convert Vec<Quad> to HashSet<Quad>
-        let quad_set: HashSet<Quad> = quads.into_iter().collect();
-
         // Create QuadContainer with the timestamp
-        Ok(QuadContainer::new(quad_set, max_timestamp.try_into().unwrap_or(0)))
+        Ok(QuadContainer::new(quads, max_timestamp.try_into().unwrap_or(0)))
     }
 
     /// Extracts time range from window definition.
@@ -420,6 +542,14 @@ impl HistoricalExecutor {
     }
 }
 
+// The bindings of a solution in variable order, to compare solutions by.
+fn solution_key(binding: &HashMap<String, String>) -> Vec<(String, String)> {
+    let mut key: Vec<(String, String)> =
+        binding.iter().map(|(name, value)| (name.clone(), value.clone())).collect();
+    key.sort();
+    key
+}
+
 /// Iterator for sliding windows that queries storage directly
 struct SlidingWindowIterator<'a> {
     executor: &'a HistoricalExecutor,
@@ -427,6 +557,8 @@ struct SlidingWindowIterator<'a> {
     end_bound: u64,
     width: u64,
     slide: u64,
+    stream_name: String,
+    patterns: Option<Vec<TriplePattern>>,
     sparql_query: String,
 }
 
@@ -441,8 +573,18 @@ impl<'a> Iterator for SlidingWindowIterator<'a> {
         let window_start = self.current_start;
         let window_end = (window_start + self.width).min(self.end_bound);
 
+        if let Err(e) = self.executor.await_completeness(&self.stream_name, window_end) {
+            self.current_start += self.slide;
+            return Some(Err(e));
+        }
+
         // Query storage
-        let events = match self.executor.storage.query(window_start, window_end) {
+        let events = match self.executor.query_window_events(
+            &self.stream_name,
+            window_start,
+            window_end,
+            self.patterns.as_deref(),
+        ) {
             Ok(events) => events,
             Err(e) => {
                 return Some(Err(JanusApiError::StorageError(format!("Query failed: {}", e))))
@@ -450,7 +592,7 @@ impl<'a> Iterator for SlidingWindowIterator<'a> {
         };
 
         // Execute SPARQL
-        let result = self.executor.execute_sparql_on_events(&events, &self.sparql_query);
+        let result = self.executor.execute_sparql_on_events(events, &self.sparql_query);
 
         // Advance window
         self.current_start += self.slide;
@@ -462,25 +604,34 @@ impl<'a> Iterator for SlidingWindowIterator<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::storage::segmented_storage::StreamingSegmentedStorage;
+    use oxigraph::model::Term;
+
+    // Each test gets its own directory, since a storage locks the directory it writes to.
+    fn test_storage(temp_dir: &tempfile::TempDir) -> Arc<StreamingSegmentedStorage> {
+        Arc::new(
+            StreamingSegmentedStorage::new(crate::storage::util::StreamingConfig {
+                segment_base_path: temp_dir.path().to_string_lossy().into_owned(),
+                ..crate::storage::util::StreamingConfig::default()
+            })
+            .expect("Failed to create storage"),
+        )
+    }
 
     #[test]
     fn test_historical_executor_creation() {
         // This test verifies the executor can be created
         // Actual execution tests require full integration setup
-        let storage = Arc::new(
-            StreamingSegmentedStorage::new(crate::storage::util::StreamingConfig::default())
-                .expect("Failed to create storage"),
-        );
+        let temp_dir = tempfile::TempDir::new().expect("Failed to create temp dir");
+        let storage = test_storage(&temp_dir);
         let engine = OxigraphAdapter::new();
         let _executor = HistoricalExecutor::new(storage, engine);
     }
 
     #[test]
     fn test_extract_time_range_fixed_window() {
-        let storage = Arc::new(
-            StreamingSegmentedStorage::new(crate::storage::util::StreamingConfig::default())
-                .expect("Failed to create storage"),
-        );
+        let temp_dir = tempfile::TempDir::new().expect("Failed to create temp dir");
+        let storage = test_storage(&temp_dir);
         let engine = OxigraphAdapter::new();
         let executor = HistoricalExecutor::new(storage, engine);
 
@@ -505,10 +656,8 @@ mod tests {
 
     #[test]
     fn test_extract_time_range_sliding_window() {
-        let storage = Arc::new(
-            StreamingSegmentedStorage::new(crate::storage::util::StreamingConfig::default())
-                .expect("Failed to create storage"),
-        );
+        let temp_dir = tempfile::TempDir::new().expect("Failed to create temp dir");
+        let storage = test_storage(&temp_dir);
         let engine = OxigraphAdapter::new();
         let executor = HistoricalExecutor::new(storage, engine);
 
@@ -533,10 +682,8 @@ mod tests {
 
     #[test]
     fn test_rdf_event_to_quad_with_uri_object() {
-        let storage = Arc::new(
-            StreamingSegmentedStorage::new(crate::storage::util::StreamingConfig::default())
-                .expect("Failed to create storage"),
-        );
+        let temp_dir = tempfile::TempDir::new().expect("Failed to create temp dir");
+        let storage = test_storage(&temp_dir);
         let engine = OxigraphAdapter::new();
         let executor = HistoricalExecutor::new(storage, engine);
 
@@ -558,10 +705,8 @@ mod tests {
 
     #[test]
     fn test_rdf_event_to_quad_with_literal_object() {
-        let storage = Arc::new(
-            StreamingSegmentedStorage::new(crate::storage::util::StreamingConfig::default())
-                .expect("Failed to create storage"),
-        );
+        let temp_dir = tempfile::TempDir::new().expect("Failed to create temp dir");
+        let storage = test_storage(&temp_dir);
         let engine = OxigraphAdapter::new();
         let executor = HistoricalExecutor::new(storage, engine);
 
@@ -589,10 +734,8 @@ mod tests {
 
     #[test]
     fn test_rdf_event_to_quad_invalid_subject() {
-        let storage = Arc::new(
-            StreamingSegmentedStorage::new(crate::storage::util::StreamingConfig::default())
-                .expect("Failed to create storage"),
-        );
+        let temp_dir = tempfile::TempDir::new().expect("Failed to create temp dir");
+        let storage = test_storage(&temp_dir);
         let engine = OxigraphAdapter::new();
         let executor = HistoricalExecutor::new(storage, engine);
 
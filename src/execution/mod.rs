@@ -5,6 +5,7 @@
 //! # Components
 //!
 //! - **HistoricalExecutor** - Executes SPARQL queries over historical data using window operators
+//! - **pushdown** - Extracts the constant triple patterns a historical query can be restricted to
 //! - **ResultConverter** - Converts execution results to unified QueryResult format
 //!
 //! # Architecture
@@ -35,8 +36,9 @@
 //! ```
 
 pub mod historical_executor;
+pub mod pushdown;
 pub mod result_converter;
 
 // Re-export main types for convenience
-pub use historical_executor::HistoricalExecutor;
+pub use historical_executor::{CompletenessPolicy, HistoricalExecutor};
 pub use result_converter::ResultConverter;
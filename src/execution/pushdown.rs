@@ -0,0 +1,424 @@
+//! Triple pattern pushdown for historical queries.
+//!
+//! A historical window loads the events of its time range into an in-memory
+//! store before the SPARQL query runs. When every triple pattern of the query
+//! has a constant subject or predicate, only the events matching one of those
+//! patterns can contribute to the result, so the storage can skip all others
+//! through `StreamingSegmentedStorage::query_filtered`.
+//!
+//! A `FILTER` comparing the object variable of a triple pattern with numeric
+//! constants, such as `FILTER(?v > 30)`, further bounds the values of the pattern's
+//! events that can contribute. The bounds are attached to the pattern so that the
+//! storage can skip segments whose zone maps hold no value of the predicate within
+//! them. Bounds only pass down into the patterns whose every solution the filter
+//! sees, and are kept inclusive, since a lexical form rounded to the nearest `f64`
+//! may equal a strict bound it lies beyond.
+//!
+//! A query that only selects the subject and value of one predicate's readings can
+//! even be answered from the storage's rollups when only aggregates of its solutions
+//! are needed, see [`readings_query`].
+//!
+//! A query whose every solution comes from a single event can be run over the events
+//! of a window one chunk at a time, see [`decompose_query`].
+
+use std::collections::HashMap;
+
+use spargebra::algebra::{AggregateExpression, Expression, GraphPattern, OrderExpression};
+use spargebra::term::{NamedNodePattern, TermPattern};
+use spargebra::{Query, SparqlParser};
+
+use crate::core::term::is_xsd_numeric;
+use crate::storage::indexing::secondary::{TriplePattern, ValueRange};
+
+// Bounds on the values of variables, keyed by variable name.
+type ValueRanges = HashMap<String, ValueRange>;
+
+/// Extract the constant subject and predicate of every triple pattern in the query,
+/// with the range a `FILTER` bounds the pattern's numeric object to.
+///
+/// Returns `None` when the query cannot be restricted: it fails to parse, it is a
+/// `DESCRIBE` query, or it uses a triple pattern without any constant, a property
+/// path or a `SERVICE` call.
+pub fn pushdown_patterns(sparql_query: &str) -> Option<Vec<TriplePattern>> {
+    let query = SparqlParser::new().parse_query(sparql_query).ok()?;
+    let pattern = match &query {
+        Query::Select { pattern, .. }
+        | Query::Construct { pattern, .. }
+        | Query::Ask { pattern, .. } => pattern,
+        Query::Describe { .. } => return None,
+    };
+
+    let mut patterns = Vec::new();
+    collect_graph_pattern(pattern, &ValueRanges::new(), &mut patterns)?;
+    if patterns.is_empty()
+        || patterns
+            .iter()
+            .any(|pattern| pattern.subject.is_none() && pattern.predicate.is_none())
+    {
+        return None;
+    }
+
+    patterns.sort_by(|a, b| (&a.subject, &a.predicate).cmp(&(&b.subject, &b.predicate)));
+    patterns.dedup();
+    Some(patterns)
+}
+
+/// A query selecting the subject and object of every event with a constant predicate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadingsQuery {
+    pub predicate: String,
+    pub subject_variable: String,
+    pub value_variable: String,
+    /// Whether the triple pattern is inside a `GRAPH ?g` pattern, so that it only matches
+    /// events of named graphs; otherwise it only matches events of the default graph.
+    pub in_named_graph: bool,
+}
+
+/// Recognize a `SELECT ?subject ?value WHERE { ?subject <predicate> ?value }` query,
+/// optionally with the triple pattern inside a `GRAPH ?g` pattern whose variable is not
+/// selected, as historical windows over a stream's log are.
+pub fn readings_query(sparql_query: &str) -> Option<ReadingsQuery> {
+    let Query::Select { pattern: GraphPattern::Project { inner, variables }, .. } =
+        SparqlParser::new().parse_query(sparql_query).ok()?
+    else {
+        return None;
+    };
+    let (inner, in_named_graph) = match inner.as_ref() {
+        GraphPattern::Graph { name: NamedNodePattern::Variable(graph), inner }
+            if !variables.contains(graph) =>
+        {
+            (inner.as_ref(), true)
+        }
+        other => (other, false),
+    };
+    let GraphPattern::Bgp { patterns } = inner else {
+        return None;
+    };
+    let [triple] = patterns.as_slice() else {
+        return None;
+    };
+    let (
+        TermPattern::Variable(subject),
+        NamedNodePattern::NamedNode(predicate),
+        TermPattern::Variable(value),
+    ) = (&triple.subject, &triple.predicate, &triple.object)
+    else {
+        return None;
+    };
+    if subject == value
+        || variables.len() != 2
+        || !variables.contains(subject)
+        || !variables.contains(value)
+    {
+        return None;
+    }
+
+    Some(ReadingsQuery {
+        predicate: predicate.as_str().to_string(),
+        subject_variable: subject.as_str().to_string(),
+        value_variable: value.as_str().to_string(),
+        in_named_graph,
+    })
+}
+
+/// A query that can be run over the events of a window one chunk at a time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecomposedQuery {
+    /// The query selecting every variable of its triple pattern, so that each of its
+    /// solutions stands for exactly one quad
+    pub query: String,
+    /// The variables the original query selects
+    pub variables: Vec<String>,
+    /// Whether the original query only returns distinct solutions
+    pub distinct: bool,
+}
+
+/// Recognize a `SELECT` of one triple pattern, optionally inside a `GRAPH` pattern and
+/// under filters that do not use `EXISTS`.
+///
+/// The solutions of such a query over a window are the distinct solutions of its
+/// [`DecomposedQuery::query`] over the chunks of the window's events, projected onto the
+/// selected variables, and deduplicated again when the query is `DISTINCT`. Aggregates,
+/// ordering, slices and any other operator are never decomposed.
+pub fn decompose_query(sparql_query: &str) -> Option<DecomposedQuery> {
+    let Query::Select { dataset, pattern, base_iri } =
+        SparqlParser::new().parse_query(sparql_query).ok()?
+    else {
+        return None;
+    };
+    let (pattern, distinct) = match pattern {
+        GraphPattern::Distinct { inner } | GraphPattern::Reduced { inner } => (*inner, true),
+        pattern => (pattern, false),
+    };
+    let GraphPattern::Project { inner, variables } = pattern else {
+        return None;
+    };
+
+    let mut pattern_variables = Vec::new();
+    let mut in_graph = false;
+    let mut current = inner.as_ref();
+    let triple = loop {
+        match current {
+            GraphPattern::Filter { expr, inner } if is_row_expression(expr) => current = inner,
+            GraphPattern::Graph { name, inner } if !in_graph => {
+                if let NamedNodePattern::Variable(variable) = name {
+                    pattern_variables.push(variable.clone());
+                }
+                in_graph = true;
+                current = inner;
+            }
+            GraphPattern::Bgp { patterns } => match patterns.as_slice() {
+                [triple] => break triple,
+                _ => return None,
+            },
+            _ => return None,
+        }
+    };
+    for term in [&triple.subject, &triple.object] {
+        match term {
+            TermPattern::Variable(variable) => pattern_variables.push(variable.clone()),
+            TermPattern::NamedNode(_) | TermPattern::Literal(_) => {}
+            // Blank nodes are variables that cannot be selected.
+            _ => return None,
+        }
+    }
+    if let NamedNodePattern::Variable(variable) = &triple.predicate {
+        pattern_variables.push(variable.clone());
+    }
+
+    let mut selected = variables.clone();
+    for variable in pattern_variables {
+        if !selected.contains(&variable) {
+            selected.push(variable);
+        }
+    }
+    let query = Query::Select {
+        dataset,
+        pattern: GraphPattern::Project { inner, variables: selected },
+        base_iri,
+    };
+    Some(DecomposedQuery {
+        query: query.to_string(),
+        variables: variables.iter().map(|variable| variable.as_str().to_string()).collect(),
+        distinct,
+    })
+}
+
+// Collect the patterns of `pattern`, whose solutions all have to satisfy `ranges`.
+fn collect_graph_pattern(
+    pattern: &GraphPattern,
+    ranges: &ValueRanges,
+    patterns: &mut Vec<TriplePattern>,
+) -> Option<()> {
+    let unbounded = ValueRanges::new();
+    match pattern {
+        GraphPattern::Bgp { patterns: triples } => {
+            for triple in triples {
+                let subject = match &triple.subject {
+                    TermPattern::NamedNode(node) => Some(node.as_str()),
+                    _ => None,
+                };
+                let predicate = match &triple.predicate {
+                    NamedNodePattern::NamedNode(node) => Some(node.as_str()),
+                    NamedNodePattern::Variable(_) => None,
+                };
+                let mut pattern = TriplePattern::new(subject, predicate);
+                if let TermPattern::Variable(object) = &triple.object {
+                    pattern.object_range = ranges.get(object.as_str()).copied();
+                }
+                patterns.push(pattern);
+            }
+        }
+        GraphPattern::Join { left, right } | GraphPattern::Union { left, right } => {
+            collect_graph_pattern(left, ranges, patterns)?;
+            collect_graph_pattern(right, ranges, patterns)?;
+        }
+        GraphPattern::Minus { left, right } => {
+            collect_graph_pattern(left, ranges, patterns)?;
+            collect_graph_pattern(right, &unbounded, patterns)?;
+        }
+        GraphPattern::LeftJoin { left, right, expression } => {
+            collect_graph_pattern(left, ranges, patterns)?;
+            collect_graph_pattern(right, &unbounded, patterns)?;
+            if let Some(expression) = expression {
+                collect_expression(expression, patterns)?;
+            }
+        }
+        GraphPattern::Filter { expr, inner } => {
+            collect_expression(expr, patterns)?;
+            let mut ranges = ranges.clone();
+            value_ranges(expr, &mut ranges);
+            collect_graph_pattern(inner, &ranges, patterns)?;
+        }
+        GraphPattern::Extend { inner, expression, .. } => {
+            collect_expression(expression, patterns)?;
+            collect_graph_pattern(inner, ranges, patterns)?;
+        }
+        GraphPattern::OrderBy { inner, expression } => {
+            for order in expression {
+                match order {
+                    OrderExpression::Asc(expression) | OrderExpression::Desc(expression) => {
+                        collect_expression(expression, patterns)?;
+                    }
+                }
+            }
+            collect_graph_pattern(inner, &unbounded, patterns)?;
+        }
+        GraphPattern::Graph { inner, .. } => collect_graph_pattern(inner, ranges, patterns)?,
+        // Solutions a projection or slice drops are never seen by a filter above it.
+        GraphPattern::Project { inner, .. }
+        | GraphPattern::Distinct { inner }
+        | GraphPattern::Reduced { inner }
+        | GraphPattern::Slice { inner, .. } => collect_graph_pattern(inner, &unbounded, patterns)?,
+        GraphPattern::Group { inner, aggregates, .. } => {
+            for (_, aggregate) in aggregates {
+                if let AggregateExpression::FunctionCall { expr, .. } = aggregate {
+                    collect_expression(expr, patterns)?;
+                }
+            }
+            collect_graph_pattern(inner, &unbounded, patterns)?;
+        }
+        GraphPattern::Values { .. } => {}
+        // Property paths and federated or lateral patterns can read arbitrary triples.
+        _ => return None,
+    }
+    Some(())
+}
+
+// Narrow `ranges` by the comparisons of variables with numeric constants that the
+// expression requires to hold.
+fn value_ranges(expression: &Expression, ranges: &mut ValueRanges) {
+    let (variable, range) = match expression {
+        Expression::And(left, right) => {
+            value_ranges(left, ranges);
+            value_ranges(right, ranges);
+            return;
+        }
+        Expression::Equal(left, right) => match comparison(left, right) {
+            Some((variable, value, _)) => (variable, ValueRange::new(value, value)),
+            None => return,
+        },
+        Expression::Greater(left, right)
+        | Expression::GreaterOrEqual(left, right)
+        | Expression::Less(left, right)
+        | Expression::LessOrEqual(left, right) => {
+            let Some((variable, value, variable_first)) = comparison(left, right) else {
+                return;
+            };
+            let greater =
+                matches!(expression, Expression::Greater(..) | Expression::GreaterOrEqual(..));
+            if greater == variable_first {
+                (variable, ValueRange::new(value, f64::INFINITY))
+            } else {
+                (variable, ValueRange::new(f64::NEG_INFINITY, value))
+            }
+        }
+        _ => return,
+    };
+    let bounds = ranges.entry(variable.to_string()).or_default();
+    *bounds = bounds.intersect(&range);
+}
+
+// A comparison of a variable with a numeric constant, with whether the variable comes first.
+fn comparison<'a>(left: &'a Expression, right: &'a Expression) -> Option<(&'a str, f64, bool)> {
+    match (left, right) {
+        (Expression::Variable(variable), Expression::Literal(literal)) => {
+            Some((variable.as_str(), numeric_literal(literal)?, true))
+        }
+        (Expression::Literal(literal), Expression::Variable(variable)) => {
+            Some((variable.as_str(), numeric_literal(literal)?, false))
+        }
+        _ => None,
+    }
+}
+
+fn numeric_literal(literal: &spargebra::term::Literal) -> Option<f64> {
+    if !is_xsd_numeric(literal.datatype().as_str()) {
+        return None;
+    }
+    literal.value().parse().ok().filter(|value: &f64| !value.is_nan())
+}
+
+// Whether the expression only reads the solution it is evaluated on, that is, it does not
+// use `EXISTS`.
+fn is_row_expression(expression: &Expression) -> bool {
+    match expression {
+        Expression::Exists(_) => false,
+        Expression::Or(left, right)
+        | Expression::And(left, right)
+        | Expression::Equal(left, right)
+        | Expression::SameTerm(left, right)
+        | Expression::Greater(left, right)
+        | Expression::GreaterOrEqual(left, right)
+        | Expression::Less(left, right)
+        | Expression::LessOrEqual(left, right)
+        | Expression::Add(left, right)
+        | Expression::Subtract(left, right)
+        | Expression::Multiply(left, right)
+        | Expression::Divide(left, right) => is_row_expression(left) && is_row_expression(right),
+        Expression::UnaryPlus(inner) | Expression::UnaryMinus(inner) | Expression::Not(inner) => {
+            is_row_expression(inner)
+        }
+        Expression::In(inner, list) => {
+            is_row_expression(inner) && list.iter().all(is_row_expression)
+        }
+        Expression::If(condition, then, otherwise) => {
+            is_row_expression(condition) && is_row_expression(then) && is_row_expression(otherwise)
+        }
+        Expression::Coalesce(list) | Expression::FunctionCall(_, list) => {
+            list.iter().all(is_row_expression)
+        }
+        Expression::NamedNode(_)
+        | Expression::Literal(_)
+        | Expression::Variable(_)
+        | Expression::Bound(_) => true,
+    }
+}
+
+// Only `EXISTS` reads triples from within an expression.
+fn collect_expression(expression: &Expression, patterns: &mut Vec<TriplePattern>) -> Option<()> {
+    match expression {
+        Expression::Exists(pattern) => {
+            collect_graph_pattern(pattern, &ValueRanges::new(), patterns)?;
+        }
+        Expression::Or(left, right)
+        | Expression::And(left, right)
+        | Expression::Equal(left, right)
+        | Expression::SameTerm(left, right)
+        | Expression::Greater(left, right)
+        | Expression::GreaterOrEqual(left, right)
+        | Expression::Less(left, right)
+        | Expression::LessOrEqual(left, right)
+        | Expression::Add(left, right)
+        | Expression::Subtract(left, right)
+        | Expression::Multiply(left, right)
+        | Expression::Divide(left, right) => {
+            collect_expression(left, patterns)?;
+            collect_expression(right, patterns)?;
+        }
+        Expression::UnaryPlus(inner) | Expression::UnaryMinus(inner) | Expression::Not(inner) => {
+            collect_expression(inner, patterns)?;
+        }
+        Expression::In(inner, list) => {
+            collect_expression(inner, patterns)?;
+            for item in list {
+                collect_expression(item, patterns)?;
+            }
+        }
+        Expression::If(condition, then, otherwise) => {
+            collect_expression(condition, patterns)?;
+            collect_expression(then, patterns)?;
+            collect_expression(otherwise, patterns)?;
+        }
+        Expression::Coalesce(list) | Expression::FunctionCall(_, list) => {
+            for item in list {
+                collect_expression(item, patterns)?;
+            }
+        }
+        Expression::NamedNode(_)
+        | Expression::Literal(_)
+        | Expression::Variable(_)
+        | Expression::Bound(_) => {}
+    }
+    Some(())
+}
@@ -8,7 +8,8 @@
 pub mod server;
 
 pub use server::{
-    create_server, create_server_with_state, start_server, AppState, ErrorResponse,
-    ListQueriesResponse, QueryDetailsResponse, QueryResultBroadcast, RegisterQueryRequest,
-    RegisterQueryResponse, ReplayStatusResponse, StartReplayRequest, SuccessResponse,
+    create_server, create_server_with_options, create_server_with_state, start_server,
+    start_server_with_options, AppState, ErrorResponse, ListQueriesResponse, QueryDetailsResponse,
+    QueryResultBroadcast, RegisterQueryRequest, RegisterQueryResponse, ReplayStatusResponse,
+    ServerOptions, StartReplayRequest, SuccessResponse,
 };
@@ -6,7 +6,10 @@
 use crate::{
     api::janus_api::{JanusApi, JanusApiError, QueryHandle, QueryResult, ResultSource},
     registry::query_registry::{BaselineBootstrapMode, QueryId, QueryRegistry},
-    storage::segmented_storage::StreamingSegmentedStorage,
+    storage::{
+        erasure::ErasureProgress, event_store::EventStore, memory_tracker::ProcessMemory,
+        snapshot::SnapshotManifest, util::StorageComponentSizes,
+    },
     stream_bus::{BrokerType, MqttConfig, StreamBus, StreamBusConfig},
 };
 use axum::{
@@ -22,6 +25,7 @@ use axum::{
 use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
+    path::{Component, PathBuf},
     sync::{
         atomic::{AtomicU64, Ordering},
         Arc, Mutex,
@@ -89,6 +93,12 @@ pub struct HealthResponse {
 pub struct StorageStatusResponse {
     pub status: String,
     pub background_flush_error: Option<String>,
+    /// Oldest event timestamp still retained, when a retention policy is configured.
+    pub retention_horizon: Option<u64>,
+    /// Memory held by the storage's buffer, dictionary, segment indexes and mappings.
+    pub memory: StorageComponentSizes,
+    /// Memory of the whole process, when the platform reports it.
+    pub process_memory: Option<ProcessMemory>,
 }
 
 /// Error response
@@ -112,6 +122,9 @@ pub struct StartReplayRequest {
     #[serde(default = "default_true")]
     pub add_timestamps: bool,
     pub mqtt_config: Option<MqttConfigDto>,
+    /// Stream log the replayed events are stored in; unset stores them without a stream
+    #[serde(default)]
+    pub stream_name: Option<String>,
 }
 
 fn default_broker_type() -> String {
@@ -147,10 +160,44 @@ pub struct ReplayStatusResponse {
     pub events_stored: u64,
     pub publish_errors: u64,
     pub storage_errors: u64,
+    /// Writes the replay delayed because the storage's memory budget was filling up
+    pub throttled_writes: u64,
     pub events_per_second: f64,
     pub elapsed_seconds: f64,
 }
 
+/// Request to erase every stored event of some subjects or graphs
+#[derive(Debug, Deserialize)]
+pub struct ErasureRequest {
+    #[serde(default)]
+    pub subjects: Vec<String>,
+    #[serde(default)]
+    pub graphs: Vec<String>,
+}
+
+/// Response for erasure requests and progress
+#[derive(Debug, Serialize)]
+pub struct ErasureResponse {
+    pub message: String,
+    pub progress: ErasureProgress,
+}
+
+/// Request to snapshot historical storage into a directory under the snapshot directory
+/// of the server
+#[derive(Debug, Deserialize)]
+pub struct SnapshotRequest {
+    /// Relative path of the snapshot under [`ServerOptions::snapshot_dir`]
+    pub destination: String,
+}
+
+/// Response for a completed snapshot
+#[derive(Debug, Serialize)]
+pub struct SnapshotResponse {
+    pub message: String,
+    pub destination: String,
+    pub manifest: SnapshotManifest,
+}
+
 /// Query lifecycle status summary for ops surfaces.
 #[derive(Debug, Serialize)]
 pub struct QueryOpsStatusResponse {
@@ -173,13 +220,22 @@ pub struct OpsStatusResponse {
     pub queries: QueryOpsStatusResponse,
 }
 
+/// Optional features of the HTTP server
+#[derive(Debug, Clone, Default)]
+pub struct ServerOptions {
+    /// Directory that snapshots are written into. `POST /api/admin/snapshot` is only served
+    /// when it is set.
+    pub snapshot_dir: Option<PathBuf>,
+}
+
 /// Shared application state
 pub struct AppState {
     pub janus_api: Arc<JanusApi>,
     pub registry: Arc<QueryRegistry>,
-    pub storage: Arc<StreamingSegmentedStorage>,
+    pub storage: Arc<dyn EventStore>,
     pub replay_state: Arc<Mutex<ReplayState>>,
     pub query_streams: Arc<Mutex<HashMap<QueryId, QueryResultBroadcast>>>,
+    pub options: ServerOptions,
 }
 
 #[derive(Clone)]
@@ -197,6 +253,7 @@ pub struct ReplayState {
     pub events_stored: Arc<AtomicU64>,
     pub publish_errors: Arc<AtomicU64>,
     pub storage_errors: Arc<AtomicU64>,
+    pub throttled_writes: Arc<AtomicU64>,
 }
 
 impl Default for ReplayState {
@@ -211,6 +268,7 @@ impl Default for ReplayState {
             events_stored: Arc::new(AtomicU64::new(0)),
             publish_errors: Arc::new(AtomicU64::new(0)),
             storage_errors: Arc::new(AtomicU64::new(0)),
+            throttled_writes: Arc::new(AtomicU64::new(0)),
         }
     }
 }
@@ -248,7 +306,7 @@ impl From<JanusApiError> for ApiError {
 pub fn create_server(
     janus_api: Arc<JanusApi>,
     registry: Arc<QueryRegistry>,
-    storage: Arc<StreamingSegmentedStorage>,
+    storage: Arc<dyn EventStore>,
 ) -> Router {
     create_server_with_state(janus_api, registry, storage).0
 }
@@ -257,7 +315,17 @@ pub fn create_server(
 pub fn create_server_with_state(
     janus_api: Arc<JanusApi>,
     registry: Arc<QueryRegistry>,
-    storage: Arc<StreamingSegmentedStorage>,
+    storage: Arc<dyn EventStore>,
+) -> (Router, Arc<AppState>) {
+    create_server_with_options(janus_api, registry, storage, ServerOptions::default())
+}
+
+/// Create the HTTP server with optional features and return the shared state.
+pub fn create_server_with_options(
+    janus_api: Arc<JanusApi>,
+    registry: Arc<QueryRegistry>,
+    storage: Arc<dyn EventStore>,
+    options: ServerOptions,
 ) -> (Router, Arc<AppState>) {
     let state = Arc::new(AppState {
         janus_api,
@@ -265,12 +333,13 @@ pub fn create_server_with_state(
         storage,
         replay_state: Arc::new(Mutex::new(ReplayState::default())),
         query_streams: Arc::new(Mutex::new(HashMap::new())),
+        options,
     });
 
     // Configure CORS
     let cors = CorsLayer::new().allow_origin(Any).allow_methods(Any).allow_headers(Any);
 
-    let router = Router::new()
+    let mut router = Router::new()
         .route("/api/queries", post(register_query))
         .route("/api/queries", get(list_queries))
         .route("/api/queries/:id", get(get_query))
@@ -281,17 +350,22 @@ pub fn create_server_with_state(
         .route("/api/replay/start", post(start_replay))
         .route("/api/replay/stop", post(stop_replay))
         .route("/api/replay/status", get(replay_status))
+        .route("/api/admin/erasure", post(start_erasure))
+        .route("/api/admin/erasure", get(erasure_status))
         .route("/ops/status", get(ops_status))
-        .route("/health", get(health_check))
-        .layer(cors)
-        .with_state(Arc::clone(&state));
+        .route("/health", get(health_check));
+    // Snapshots write to the file system of the server, so only where it was configured.
+    if state.options.snapshot_dir.is_some() {
+        router = router.route("/api/admin/snapshot", post(create_snapshot));
+    }
+    let router = router.layer(cors).with_state(Arc::clone(&state));
 
     (router, state)
 }
 
 /// Health check endpoint
 async fn health_check(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    let storage = storage_status(&state.storage);
+    let storage = storage_status(state.storage.as_ref());
 
     if let Some(storage_error) = storage.background_flush_error.clone() {
         let response = HealthResponse {
@@ -317,7 +391,7 @@ async fn health_check(State(state): State<Arc<AppState>>) -> impl IntoResponse {
 
 /// Operational status endpoint.
 async fn ops_status(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    let storage = storage_status(&state.storage);
+    let storage = storage_status(state.storage.as_ref());
     let replay = replay_status_snapshot(&state.replay_state.lock().unwrap());
     let queries = query_ops_status(&state);
 
@@ -586,6 +660,7 @@ async fn start_replay(
         loop_file: payload.loop_file,
         add_timestamps: payload.add_timestamps,
         mqtt_config,
+        stream_name: payload.stream_name,
     };
 
     let storage = Arc::clone(&state.storage);
@@ -601,6 +676,7 @@ async fn start_replay(
     let events_stored = Arc::clone(&stream_bus.events_stored);
     let publish_errors = Arc::clone(&stream_bus.publish_errors);
     let storage_errors = Arc::clone(&stream_bus.storage_errors);
+    let throttled_writes = Arc::clone(&stream_bus.throttled_writes);
 
     let replay_state_clone = Arc::clone(&state.replay_state);
 
@@ -635,6 +711,7 @@ async fn start_replay(
     replay_state.events_stored = events_stored;
     replay_state.publish_errors = publish_errors;
     replay_state.storage_errors = storage_errors;
+    replay_state.throttled_writes = throttled_writes;
 
     Ok(Json(SuccessResponse {
         message: format!("Stream bus replay started with file: {}", payload.input_file),
@@ -672,6 +749,118 @@ async fn replay_status(
     Ok(Json(replay_status_snapshot(&replay_state)))
 }
 
+/// POST /api/admin/erasure - Erase subjects and graphs from historical storage
+///
+/// The events are hidden from queries before the response is sent; removing them from
+/// the storage files continues in the background and is reported by the GET endpoint.
+async fn start_erasure(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<ErasureRequest>,
+) -> Result<(StatusCode, Json<ErasureResponse>), ApiError> {
+    if payload.subjects.is_empty() && payload.graphs.is_empty() {
+        return Err(ApiError::BadRequest("No subjects or graphs to erase".to_string()));
+    }
+
+    let storage = Arc::clone(&state.storage);
+    let requested = payload.subjects.len() + payload.graphs.len();
+    tokio::task::spawn_blocking(move || -> std::io::Result<()> {
+        for subject in &payload.subjects {
+            storage.delete_by_subject(subject)?;
+        }
+        for graph in &payload.graphs {
+            storage.delete_by_graph(graph)?;
+        }
+        Ok(())
+    })
+    .await
+    .map_err(|e| ApiError::InternalError(e.to_string()))?
+    .map_err(|e| ApiError::InternalError(format!("Failed to record erasure: {}", e)))?;
+
+    let storage = Arc::clone(&state.storage);
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = storage.purge_erased() {
+            eprintln!("Erasure purge failed: {}", e);
+        }
+    });
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(ErasureResponse {
+            message: format!("Erasure of {} terms accepted", requested),
+            progress: state.storage.erasure_progress(),
+        }),
+    ))
+}
+
+/// GET /api/admin/erasure - Get the progress of erasing deleted events
+async fn erasure_status(State(state): State<Arc<AppState>>) -> Json<ErasureResponse> {
+    let progress = state.storage.erasure_progress();
+    Json(ErasureResponse {
+        message: format!("{} erasures pending", progress.pending_tombstones),
+        progress,
+    })
+}
+
+/// POST /api/admin/snapshot - Write a consistent copy of historical storage
+///
+/// The destination is a relative path under the snapshot directory of the server that must
+/// not exist yet or be empty. The response is sent once the snapshot is complete.
+async fn create_snapshot(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<SnapshotRequest>,
+) -> Result<(StatusCode, Json<SnapshotResponse>), ApiError> {
+    let snapshot_dir = state
+        .options
+        .snapshot_dir
+        .as_deref()
+        .ok_or_else(|| ApiError::NotFound("Snapshots are not enabled".to_string()))?;
+    let destination = resolve_snapshot_destination(snapshot_dir, &payload.destination)?;
+
+    let storage = Arc::clone(&state.storage);
+    let manifest = tokio::task::spawn_blocking(move || storage.snapshot(&destination))
+        .await
+        .map_err(|e| ApiError::InternalError(e.to_string()))?
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::AlreadyExists | std::io::ErrorKind::Unsupported => {
+                ApiError::BadRequest(e.to_string())
+            }
+            _ => ApiError::InternalError(format!("Failed to write snapshot: {}", e)),
+        })?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(SnapshotResponse {
+            message: format!(
+                "Snapshot of {} segments written to {}",
+                manifest.segments.len(),
+                payload.destination
+            ),
+            destination: payload.destination,
+            manifest,
+        }),
+    ))
+}
+
+// Resolve the destination of a snapshot under the snapshot directory, refusing anything that
+// could reach outside of it.
+fn resolve_snapshot_destination(
+    snapshot_dir: &std::path::Path,
+    destination: &str,
+) -> Result<PathBuf, ApiError> {
+    let relative = std::path::Path::new(destination.trim());
+    let mut components = relative.components().peekable();
+    if components.peek().is_none() {
+        return Err(ApiError::BadRequest("No snapshot destination given".to_string()));
+    }
+    if !components.all(|component| matches!(component, Component::Normal(_) | Component::CurDir)) {
+        return Err(ApiError::BadRequest(format!(
+            "Snapshot destination {} must be a relative path without '..'",
+            destination
+        )));
+    }
+    Ok(snapshot_dir.join(relative))
+}
+
 fn replay_status_snapshot(replay_state: &ReplayState) -> ReplayStatusResponse {
     let elapsed_seconds = if replay_state.is_running {
         replay_state.start_time.map_or(0.0, |t| t.elapsed().as_secs_f64())
@@ -684,6 +873,7 @@ fn replay_status_snapshot(replay_state: &ReplayState) -> ReplayStatusResponse {
     let events_stored = replay_state.events_stored.load(Ordering::Relaxed);
     let publish_errors = replay_state.publish_errors.load(Ordering::Relaxed);
     let storage_errors = replay_state.storage_errors.load(Ordering::Relaxed);
+    let throttled_writes = replay_state.throttled_writes.load(Ordering::Relaxed);
 
     let events_per_second = if elapsed_seconds > 0.0 {
         events_read as f64 / elapsed_seconds
@@ -698,12 +888,13 @@ fn replay_status_snapshot(replay_state: &ReplayState) -> ReplayStatusResponse {
         events_stored,
         publish_errors,
         storage_errors,
+        throttled_writes,
         events_per_second,
         elapsed_seconds,
     }
 }
 
-fn storage_status(storage: &StreamingSegmentedStorage) -> StorageStatusResponse {
+fn storage_status(storage: &dyn EventStore) -> StorageStatusResponse {
     StorageStatusResponse {
         status: if storage.background_flush_error().is_some() {
             "error".to_string()
@@ -711,6 +902,9 @@ fn storage_status(storage: &StreamingSegmentedStorage) -> StorageStatusResponse
             "ok".to_string()
         },
         background_flush_error: storage.background_flush_error(),
+        retention_horizon: storage.retention_horizon(),
+        memory: storage.component_sizes(),
+        process_memory: ProcessMemory::current(),
     }
 }
 
@@ -754,9 +948,21 @@ pub async fn start_server(
     addr: &str,
     janus_api: Arc<JanusApi>,
     registry: Arc<QueryRegistry>,
-    storage: Arc<StreamingSegmentedStorage>,
+    storage: Arc<dyn EventStore>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let app = create_server(janus_api, registry, storage);
+    start_server_with_options(addr, janus_api, registry, storage, ServerOptions::default()).await
+}
+
+/// Start the HTTP server with optional features on the specified address
+pub async fn start_server_with_options(
+    addr: &str,
+    janus_api: Arc<JanusApi>,
+    registry: Arc<QueryRegistry>,
+    storage: Arc<dyn EventStore>,
+    options: ServerOptions,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let snapshots_enabled = options.snapshot_dir.is_some();
+    let (app, _) = create_server_with_options(janus_api, registry, storage, options);
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
     println!("Janus HTTP API server listening on http://{}", addr);
@@ -772,6 +978,11 @@ pub async fn start_server(
     println!("  POST   /api/replay/start         - Start stream bus replay");
     println!("  POST   /api/replay/stop          - Stop stream bus replay");
     println!("  GET    /api/replay/status        - Get replay status");
+    println!("  POST   /api/admin/erasure        - Erase subjects or graphs from storage");
+    println!("  GET    /api/admin/erasure        - Get erasure progress");
+    if snapshots_enabled {
+        println!("  POST   /api/admin/snapshot       - Snapshot storage into a directory");
+    }
     println!("  GET    /ops/status               - Detailed operational status");
     println!("  GET    /health                   - Health check");
     println!();
@@ -8,9 +8,12 @@
 
 use clap::{Parser, Subcommand};
 use janus::core::Event;
+use janus::storage::archive::ArchiveFormat;
 use janus::storage::segmented_storage::StreamingSegmentedStorage;
 use janus::storage::util::StreamingConfig;
 use std::fs;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 const SEGMENT_BASE_PATH: &str = "data/rdf_benchmark";
@@ -34,6 +37,45 @@ enum Command {
     BenchmarkStorageRdf,
     /// Run the event storage benchmark matrix.
     BenchmarkStorage,
+    /// Move events in and out of a storage directory.
+    Storage {
+        #[command(subcommand)]
+        command: StorageCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum StorageCommand {
+    /// Write the events of a storage directory into an N-Quads or TriG archive.
+    Export {
+        /// Storage directory to export from.
+        #[arg(long)]
+        storage_dir: String,
+        /// Archive file to write.
+        #[arg(long)]
+        output: PathBuf,
+        /// `nquads` or `trig`; defaults to the format of the file extension, else `nquads`.
+        #[arg(long)]
+        format: Option<ArchiveFormat>,
+        /// First timestamp to export.
+        #[arg(long, default_value_t = 0)]
+        start: u64,
+        /// Last timestamp to export.
+        #[arg(long, default_value_t = u64::MAX)]
+        end: u64,
+    },
+    /// Write the events of an N-Quads or TriG archive into a storage directory.
+    Import {
+        /// Storage directory to import into; created if missing.
+        #[arg(long)]
+        storage_dir: String,
+        /// Archive file to read.
+        #[arg(long)]
+        input: PathBuf,
+        /// `nquads` or `trig`; defaults to the format of the file extension, else `nquads`.
+        #[arg(long)]
+        format: Option<ArchiveFormat>,
+    },
 }
 
 fn print_overview() {
@@ -51,6 +93,51 @@ fn print_overview() {
     println!("Benchmark subcommands:");
     println!("  cargo run --bin janus -- benchmark-storage-rdf");
     println!("  cargo run --bin janus -- benchmark-storage");
+    println!();
+    println!("Storage subcommands:");
+    println!(
+        "  cargo run --bin janus -- storage export --storage-dir ./data/storage --output backup.nq"
+    );
+    println!(
+        "  cargo run --bin janus -- storage import --storage-dir ./data/storage --input backup.nq"
+    );
+}
+
+fn open_storage(storage_dir: &str) -> std::io::Result<StreamingSegmentedStorage> {
+    StreamingSegmentedStorage::new(StreamingConfig {
+        segment_base_path: storage_dir.to_string(),
+        ..StreamingConfig::default()
+    })
+}
+
+fn archive_format(format: Option<ArchiveFormat>, path: &Path) -> ArchiveFormat {
+    format
+        .or_else(|| ArchiveFormat::from_path(path))
+        .unwrap_or(ArchiveFormat::NQuads)
+}
+
+fn run_storage_command(command: StorageCommand) -> std::io::Result<()> {
+    match command {
+        StorageCommand::Export { storage_dir, output, format, start, end } => {
+            if !Path::new(&storage_dir).is_dir() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("Storage directory {} does not exist", storage_dir),
+                ));
+            }
+            let storage = open_storage(&storage_dir)?;
+            let writer = BufWriter::new(fs::File::create(&output)?);
+            let exported = storage.export(start, end, writer, archive_format(format, &output))?;
+            println!("Exported {} events to {}", exported, output.display());
+        }
+        StorageCommand::Import { storage_dir, input, format } => {
+            let reader = BufReader::new(fs::File::open(&input)?);
+            let storage = open_storage(&storage_dir)?;
+            let imported = storage.import(reader, archive_format(format, &input))?;
+            println!("Imported {} events into {}", imported, storage_dir);
+        }
+    }
+    Ok(())
 }
 
 fn benchmark_segmented_storage_rdf() -> std::io::Result<()> {
@@ -64,6 +151,7 @@ fn benchmark_segmented_storage_rdf() -> std::io::Result<()> {
         sparse_interval: 1000,
         entries_per_index_block: 100,
         segment_base_path: SEGMENT_BASE_PATH.to_string(),
+        ..StreamingConfig::default()
     };
 
     let mut storage = StreamingSegmentedStorage::new(config)?;
@@ -126,6 +214,7 @@ fn benchmark_storage_performance() -> std::io::Result<()> {
             sparse_interval: 100,
             entries_per_index_block: 512,
             segment_base_path: format!("./benchmark_data_{}", num_records),
+            ..StreamingConfig::default()
         };
 
         let _ = std::fs::remove_dir_all(&config.segment_base_path);
@@ -186,5 +275,6 @@ fn main() -> std::io::Result<()> {
         }
         Command::BenchmarkStorageRdf => benchmark_segmented_storage_rdf(),
         Command::BenchmarkStorage => benchmark_storage_performance(),
+        Command::Storage { command } => run_storage_command(command),
     }
 }
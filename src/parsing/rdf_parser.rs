@@ -1,8 +1,9 @@
-use crate::core::RDFEvent;
+use crate::core::{term::parse_literal, RDFEvent, RdfTerm};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Parse a line of N-Quads/N-Triples into an RDFEvent
-/// Supports typed literals with datatype URIs (e.g., "23.5"^^<http://www.w3.org/2001/XMLSchema#decimal>)
+/// Supports typed literals with datatype URIs (e.g., "23.5"^^<http://www.w3.org/2001/XMLSchema#decimal>),
+/// language-tagged literals and blank nodes, which are all kept in the event terms
 pub fn parse_rdf_line(line: &str, add_timestamps: bool) -> Result<RDFEvent, String> {
     let trimmed = line.trim();
 
@@ -16,26 +17,26 @@ pub fn parse_rdf_line(line: &str, add_timestamps: bool) -> Result<RDFEvent, Stri
     // Check if the first token is a timestamp
     let (timestamp, remaining) = parse_optional_timestamp(trimmed, add_timestamps)?;
 
-    // Parse subject (URI in angle brackets)
-    let (subject, remaining) = parse_uri(remaining, "subject")?;
+    // Parse subject (URI in angle brackets or blank node)
+    let (subject, remaining) = parse_resource(remaining, "subject")?;
 
     // Parse predicate (URI in angle brackets)
     let (predicate, remaining) = parse_uri(remaining, "predicate")?;
 
-    // Parse object (can be URI, plain literal, or typed literal)
+    // Parse object (can be URI, blank node, plain literal, typed literal or language-tagged literal)
     let (object, remaining) = parse_object(remaining)?;
 
-    // Parse optional graph (URI in angle brackets)
-    let (graph, _) = if !remaining.trim().is_empty() {
-        match parse_uri(remaining, "graph") {
-            Ok((g, rest)) => (g.clone(), rest),
-            Err(_) => (String::new(), remaining),
+    // Parse optional graph (URI in angle brackets or blank node)
+    let graph = if !remaining.trim().is_empty() {
+        match parse_resource(remaining, "graph") {
+            Ok((g, _)) => g,
+            Err(_) => RdfTerm::DefaultGraph,
         }
     } else {
-        (String::new(), remaining)
+        RdfTerm::DefaultGraph
     };
 
-    Ok(RDFEvent::new(timestamp, &subject, &predicate, &object, &graph))
+    Ok(RDFEvent::from_terms(timestamp, subject, predicate, object, graph))
 }
 
 /// Parse optional timestamp at the beginning of the line
@@ -61,7 +62,7 @@ fn parse_optional_timestamp(input: &str, add_timestamps: bool) -> Result<(u64, &
 }
 
 /// Parse a URI enclosed in angle brackets
-fn parse_uri<'a>(input: &'a str, field_name: &str) -> Result<(String, &'a str), String> {
+fn parse_uri<'a>(input: &'a str, field_name: &str) -> Result<(RdfTerm, &'a str), String> {
     let input = input.trim_start();
 
     if !input.starts_with('<') {
@@ -72,99 +73,48 @@ fn parse_uri<'a>(input: &'a str, field_name: &str) -> Result<(String, &'a str),
         .find('>')
         .ok_or_else(|| format!("Missing closing '>' for {} URI", field_name))?;
 
-    let uri = input[1..end_idx].to_string();
+    let uri = RdfTerm::named_node(&input[1..end_idx]);
     let remaining = input[end_idx + 1..].trim_start();
 
     Ok((uri, remaining))
 }
 
+/// Parse a URI in angle brackets or a blank node label such as _:b0
+fn parse_resource<'a>(input: &'a str, field_name: &str) -> Result<(RdfTerm, &'a str), String> {
+    let input = input.trim_start();
+
+    if let Some(label) = input.strip_prefix("_:") {
+        let end_idx = label.find(char::is_whitespace).unwrap_or(label.len());
+        if end_idx == 0 {
+            return Err(format!("Empty blank node label for {}", field_name));
+        }
+        return Ok((RdfTerm::blank_node(&label[..end_idx]), label[end_idx..].trim_start()));
+    }
+
+    parse_uri(input, field_name)
+}
+
 /// Parse object which can be:
 /// - URI: <http://example.org/resource>
+/// - Blank node: _:b0
 /// - Plain literal: "some text"
 /// - Typed literal: "23.5"^^<http://www.w3.org/2001/XMLSchema#decimal>
 /// - Language-tagged literal: "hello"@en
-fn parse_object(input: &str) -> Result<(String, &str), String> {
+fn parse_object(input: &str) -> Result<(RdfTerm, &str), String> {
     let input = input.trim_start();
 
-    if input.starts_with('<') {
-        // It's a URI
-        return parse_uri(input, "object");
-    }
-
     if input.starts_with('"') {
         // It's a literal (plain, typed, or language-tagged)
-        return parse_literal(input);
-    }
-
-    Err(format!("Invalid object format: {}", input))
-}
-
-/// Parse a literal with optional datatype or language tag
-fn parse_literal(input: &str) -> Result<(String, &str), String> {
-    let input = input.trim_start();
-
-    if !input.starts_with('"') {
-        return Err("Literal must start with '\"'".to_string());
-    }
-
-    // Find the closing quote, handling escaped quotes
-    let mut end_idx = 1;
-    let chars: Vec<char> = input.chars().collect();
-
-    while end_idx < chars.len() {
-        if chars[end_idx] == '"' && (end_idx == 1 || chars[end_idx - 1] != '\\') {
-            break;
-        }
-        end_idx += 1;
+        let (literal, remaining) =
+            parse_literal(input).ok_or_else(|| format!("Invalid literal: {}", input))?;
+        return Ok((literal, remaining.trim_start()));
     }
 
-    if end_idx >= chars.len() {
-        return Err("Missing closing quote for literal".to_string());
+    if input.starts_with('<') || input.starts_with("_:") {
+        return parse_resource(input, "object");
     }
 
-    // Extract the literal value (without quotes)
-    let literal_value: String = chars[1..end_idx].iter().collect();
-    let after_quote = &input[end_idx + 1..];
-
-    // Check for datatype (^^<URI>) or language tag (@lang)
-    let (final_value, remaining) = if after_quote.trim_start().starts_with("^^") {
-        // Typed literal - extract just the base value without the datatype annotation
-        // The datatype is for SPARQL semantics, but we store just the numeric value
-        let after_caret = after_quote.trim_start()[2..].trim_start();
-
-        if after_caret.starts_with('<') {
-            // Parse the datatype URI
-            let (datatype_uri, rest) = parse_uri(after_caret, "datatype")?;
-
-            // For numeric datatypes, store just the numeric value
-            // SPARQL engines will interpret these as numbers for aggregation
-            if datatype_uri.contains("XMLSchema#decimal")
-                || datatype_uri.contains("XMLSchema#integer")
-                || datatype_uri.contains("XMLSchema#double")
-                || datatype_uri.contains("XMLSchema#float")
-            {
-                (literal_value, rest)
-            } else {
-                // For other datatypes, could append type info, but for now just store value
-                (literal_value, rest)
-            }
-        } else {
-            // Malformed datatype
-            (literal_value, after_quote)
-        }
-    } else if after_quote.trim_start().starts_with('@') {
-        // Language-tagged literal
-        let after_at = after_quote.trim_start()[1..].trim_start();
-        let lang_end =
-            after_at.find(|c: char| c.is_whitespace() || c == '.').unwrap_or(after_at.len());
-        let remaining = after_at[lang_end..].trim_start();
-        (literal_value, remaining)
-    } else {
-        // Plain literal
-        (literal_value, after_quote.trim_start())
-    };
-
-    Ok((final_value, remaining))
+    Err(format!("Invalid object format: {}", input))
 }
 
 #[cfg(test)]
@@ -176,10 +126,13 @@ mod tests {
         let line = r#"<http://example.org/sensor1> <http://example.org/temperature> "23.5"^^<http://www.w3.org/2001/XMLSchema#decimal> <http://example.org/sensorStream> ."#;
         let result = parse_rdf_line(line, false).unwrap();
 
-        assert_eq!(result.subject, "http://example.org/sensor1");
-        assert_eq!(result.predicate, "http://example.org/temperature");
-        assert_eq!(result.object, "23.5");
-        assert_eq!(result.graph, "http://example.org/sensorStream");
+        assert_eq!(result.subject, RdfTerm::named_node("http://example.org/sensor1"));
+        assert_eq!(result.predicate, RdfTerm::named_node("http://example.org/temperature"));
+        assert_eq!(
+            result.object,
+            RdfTerm::typed_literal("23.5", "http://www.w3.org/2001/XMLSchema#decimal")
+        );
+        assert_eq!(result.graph, RdfTerm::named_node("http://example.org/sensorStream"));
     }
 
     #[test]
@@ -187,7 +140,7 @@ mod tests {
         let line = r#"<http://example.org/sensor1> <http://example.org/name> "Temperature Sensor" <http://example.org/graph> ."#;
         let result = parse_rdf_line(line, false).unwrap();
 
-        assert_eq!(result.object, "Temperature Sensor");
+        assert_eq!(result.object, RdfTerm::simple_literal("Temperature Sensor"));
     }
 
     #[test]
@@ -195,7 +148,7 @@ mod tests {
         let line = r#"<http://example.org/sensor1> <http://example.org/type> <http://example.org/Sensor> ."#;
         let result = parse_rdf_line(line, false).unwrap();
 
-        assert_eq!(result.object, "http://example.org/Sensor");
+        assert_eq!(result.object, RdfTerm::named_node("http://example.org/Sensor"));
     }
 
     #[test]
@@ -204,7 +157,7 @@ mod tests {
         let result = parse_rdf_line(line, false).unwrap();
 
         assert_eq!(result.timestamp, 1234567890);
-        assert_eq!(result.subject, "http://example.org/s");
+        assert_eq!(result.subject, RdfTerm::named_node("http://example.org/s"));
     }
 
     #[test]
@@ -212,6 +165,16 @@ mod tests {
         let line = r#"<http://example.org/s> <http://example.org/p> "value" ."#;
         let result = parse_rdf_line(line, false).unwrap();
 
-        assert_eq!(result.graph, "");
+        assert_eq!(result.graph, RdfTerm::DefaultGraph);
+    }
+
+    #[test]
+    fn test_parse_language_tagged_literal_and_blank_nodes() {
+        let line = r#"_:sensor <http://example.org/label> "Capteur \"A\""@fr-BE _:g ."#;
+        let result = parse_rdf_line(line, false).unwrap();
+
+        assert_eq!(result.subject, RdfTerm::blank_node("sensor"));
+        assert_eq!(result.object, RdfTerm::language_tagged_literal("Capteur \"A\"", "fr-be"));
+        assert_eq!(result.graph, RdfTerm::blank_node("g"));
     }
 }
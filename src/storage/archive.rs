@@ -0,0 +1,229 @@
+//! Export and import of historical events as RDF archives.
+//!
+//! Two archive formats are supported:
+//!
+//! - [`ArchiveFormat::NQuads`] writes one event per line as an N-Quads statement
+//!   preceded by its timestamp, the line format `parse_rdf_line` reads, so an archive
+//!   can also be replayed by `stream_bus_cli`:
+//!
+//!   ```text
+//!   1000 <http://example.org/sensor1> <http://example.org/temperature> "21"^^<http://www.w3.org/2001/XMLSchema#integer> <http://example.org/graph1> .
+//!   # stream http://example.org/stream1
+//!   1001 <http://example.org/sensor2> <http://example.org/temperature> "22"^^<http://www.w3.org/2001/XMLSchema#integer> .
+//!   ```
+//!
+//!   A `# stream <name>` comment starts the events of a stream; the events before the
+//!   first one were written without a stream. Other comments are ignored.
+//! - [`ArchiveFormat::TriG`] writes every event's quad into its graph and describes the
+//!   event in the annotation graph `urn:janus:events`, with the timestamp, terms and
+//!   stream vocabulary of the [`OxigraphEventStore`](crate::storage::oxigraph_store::OxigraphEventStore).
+//!   The archive is a regular RDF dataset; loaded into an Oxigraph store it can be read
+//!   back through that backend. Importing only reads the annotation graph, whose
+//!   descriptions must each be contiguous, as exported.
+
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+
+use oxigraph::io::{RdfFormat, RdfParser, RdfSerializer, WriterQuadSerializer};
+use oxigraph::model::{GraphNameRef, NamedNode, NamedOrBlankNode, Term};
+
+use crate::core::{RDFEvent, RdfTerm};
+use crate::parsing::rdf_parser::parse_rdf_line;
+use crate::storage::oxigraph_store::{describe_event, read_description, EVENTS_GRAPH};
+
+const STREAM_COMMENT: &str = "# stream ";
+
+/// Serialization of an archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    /// Timestamped N-Quads lines.
+    NQuads,
+    /// TriG with an annotation graph.
+    TriG,
+}
+
+impl ArchiveFormat {
+    /// The format of a file with the usual extension of the format, `.nq` or `.trig`.
+    pub fn from_path(path: &std::path::Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "nq" | "nquads" => Some(ArchiveFormat::NQuads),
+            "trig" => Some(ArchiveFormat::TriG),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for ArchiveFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "nquads" | "n-quads" | "nq" => Ok(ArchiveFormat::NQuads),
+            "trig" => Ok(ArchiveFormat::TriG),
+            _ => Err(format!("Unknown archive format '{}', use 'nquads' or 'trig'", value)),
+        }
+    }
+}
+
+/// Writes events into an archive.
+pub struct ArchiveWriter<W: Write> {
+    output: Output<W>,
+    stream: Option<String>,
+    written: u64,
+}
+
+enum Output<W: Write> {
+    NQuads(W),
+    TriG(Box<WriterQuadSerializer<W>>),
+}
+
+impl<W: Write> ArchiveWriter<W> {
+    pub fn new(writer: W, format: ArchiveFormat) -> io::Result<Self> {
+        let output = match format {
+            ArchiveFormat::NQuads => Output::NQuads(writer),
+            ArchiveFormat::TriG => Output::TriG(Box::new(
+                RdfSerializer::from_format(RdfFormat::TriG)
+                    .with_prefix("janus", "urn:janus:")
+                    .map_err(io::Error::other)?
+                    .for_writer(writer),
+            )),
+        };
+        Ok(Self { output, stream: None, written: 0 })
+    }
+
+    /// Append an event of the given stream, or without a stream when `stream` is `None`.
+    ///
+    /// In N-Quads archives an event without a stream cannot follow the events of a
+    /// stream, so they must be written first.
+    pub fn write_event(&mut self, event: &RDFEvent, stream: Option<&str>) -> io::Result<()> {
+        let quad = event.to_quad().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        match &mut self.output {
+            Output::NQuads(writer) => {
+                if stream != self.stream.as_deref() {
+                    let Some(stream) = stream else {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            "Events without a stream must precede the streams in N-Quads",
+                        ));
+                    };
+                    writeln!(writer, "{}{}", STREAM_COMMENT, stream)?;
+                }
+                write!(
+                    writer,
+                    "{} {} {} {}",
+                    event.timestamp, event.subject, event.predicate, event.object
+                )?;
+                if event.graph != RdfTerm::DefaultGraph {
+                    write!(writer, " {}", event.graph)?;
+                }
+                writeln!(writer, " .")?;
+            }
+            Output::TriG(serializer) => {
+                serializer.serialize_quad(&quad)?;
+                for description in describe_event(self.written, &quad, event.timestamp, stream) {
+                    serializer.serialize_quad(&description)?;
+                }
+            }
+        }
+        self.stream = stream.map(str::to_string);
+        self.written += 1;
+        Ok(())
+    }
+
+    /// Number of events written so far.
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    /// Complete the archive and return the underlying writer.
+    pub fn finish(self) -> io::Result<W> {
+        match self.output {
+            Output::NQuads(writer) => Ok(writer),
+            Output::TriG(serializer) => serializer.finish(),
+        }
+    }
+}
+
+/// Read the events of an archive with the name of their stream, calling `on_event` for
+/// each one in archive order. Returns the number of events read.
+pub fn read_archive<R: BufRead>(
+    reader: R,
+    format: ArchiveFormat,
+    mut on_event: impl FnMut(RDFEvent, Option<&str>) -> io::Result<()>,
+) -> io::Result<u64> {
+    match format {
+        ArchiveFormat::NQuads => read_nquads(reader, &mut on_event),
+        ArchiveFormat::TriG => read_trig(reader, &mut on_event),
+    }
+}
+
+fn read_nquads<R: BufRead>(
+    reader: R,
+    on_event: &mut impl FnMut(RDFEvent, Option<&str>) -> io::Result<()>,
+) -> io::Result<u64> {
+    let mut stream: Option<String> = None;
+    let mut count = 0;
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        let trimmed = line.trim();
+        if let Some(name) = trimmed.strip_prefix(STREAM_COMMENT) {
+            stream = Some(name.trim().to_string());
+            continue;
+        }
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        let event = parse_rdf_line(trimmed, false).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("Line {}: {}", number + 1, e))
+        })?;
+        on_event(event, stream.as_deref())?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+fn read_trig<R: BufRead>(
+    reader: R,
+    on_event: &mut impl FnMut(RDFEvent, Option<&str>) -> io::Result<()>,
+) -> io::Result<u64> {
+    let events_graph = NamedNode::new_unchecked(EVENTS_GRAPH);
+    let mut current: Option<(NamedOrBlankNode, Vec<(NamedNode, Term)>)> = None;
+    let mut count = 0;
+
+    let mut emit = |resource: NamedOrBlankNode, properties: Vec<(NamedNode, Term)>| {
+        let incomplete = || {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Incomplete description of event {}", resource),
+            )
+        };
+        let event = read_description(properties).ok_or_else(incomplete)?;
+        let timestamp = event.timestamp.ok_or_else(incomplete)?;
+        count += 1;
+        on_event(RDFEvent::from_quad(timestamp, &event.quad), event.stream.as_deref())
+    };
+
+    for quad in RdfParser::from_format(RdfFormat::TriG).for_reader(reader) {
+        let quad = quad.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if quad.graph_name.as_ref() != GraphNameRef::NamedNode(events_graph.as_ref()) {
+            continue;
+        }
+        match &mut current {
+            Some((resource, properties)) if *resource == quad.subject => {
+                properties.push((quad.predicate, quad.object));
+            }
+            _ => {
+                if let Some((resource, properties)) =
+                    current.replace((quad.subject, vec![(quad.predicate, quad.object)]))
+                {
+                    emit(resource, properties)?;
+                }
+            }
+        }
+    }
+    if let Some((resource, properties)) = current {
+        emit(resource, properties)?;
+    }
+    Ok(count)
+}
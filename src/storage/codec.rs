@@ -0,0 +1,369 @@
+//! Encodings of the records in a segment data block.
+//!
+//! Every data block is encoded on its own, so the sparse index can still seek to
+//! any block by offset whatever the codec. The codec of a segment is recorded in
+//! its data file header.
+//!
+//! The fixed-width codec stores each event as a `RECORD_SIZE` record. The
+//! columnar codec splits a block into one column per field:
+//!
+//! ```text
+//! [record count: varint]
+//! [first timestamp: varint][timestamp delta: varint]...
+//! [subject: varint]...
+//! [predicate runs: ([run length: varint][predicate: varint])...]
+//! [object: varint]...
+//! [graph runs: ([run length: varint][graph: varint])...]
+//! ```
+//!
+//! Timestamps within a block are sorted, so their deltas are small, and the few
+//! distinct predicates and graphs of a stream collapse into long runs.
+//!
+//! The compressed columnar codec, meant for archived segments, further compresses
+//! each columnar block with LZ77 back-references. Sensors repeat their subjects,
+//! readings and timestamp deltas in cycles, which the matches pick up:
+//!
+//! ```text
+//! [columnar length: varint]
+//! ([literal count: varint][literal]...[match length: varint][match distance: varint])...
+//! [literal count: varint][literal]...[0]
+//! ```
+//!
+//! Matches are found through hash chains over 4-byte prefixes, so encoding costs
+//! noticeably more than the plain columnar codec, and every read pays for the
+//! decompression.
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::{
+    encoding::{decode_record, encode_record, RECORD_SIZE},
+    Event,
+};
+
+/// Encoding of the records in the data blocks of a segment.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SegmentCodec {
+    /// One fixed-size record per event.
+    #[default]
+    FixedWidth,
+    /// Delta-encoded timestamps, varint IDs and run-length-encoded predicates and graphs.
+    Columnar,
+    /// Columnar blocks compressed with LZ77; the smallest on disk, but the slowest to read.
+    CompressedColumnar,
+}
+
+impl SegmentCodec {
+    /// Identifier of the codec in the segment header.
+    pub fn id(self) -> u32 {
+        match self {
+            SegmentCodec::FixedWidth => 0,
+            SegmentCodec::Columnar => 1,
+            SegmentCodec::CompressedColumnar => 2,
+        }
+    }
+
+    pub fn from_id(id: u32) -> std::io::Result<Self> {
+        match id {
+            0 => Ok(SegmentCodec::FixedWidth),
+            1 => Ok(SegmentCodec::Columnar),
+            2 => Ok(SegmentCodec::CompressedColumnar),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Unknown segment codec {}", id),
+            )),
+        }
+    }
+
+    /// Encode a block of events, which must be sorted by timestamp.
+    pub fn encode_block(self, events: &[Event]) -> Vec<u8> {
+        match self {
+            SegmentCodec::FixedWidth => {
+                let mut block = Vec::with_capacity(events.len() * RECORD_SIZE);
+                let mut record = [0u8; RECORD_SIZE];
+                for event in events {
+                    encode_record(
+                        &mut record,
+                        event.timestamp,
+                        event.subject,
+                        event.predicate,
+                        event.object,
+                        event.graph,
+                    );
+                    block.extend_from_slice(&record);
+                }
+                block
+            }
+            SegmentCodec::Columnar => encode_columnar(events),
+            SegmentCodec::CompressedColumnar => compress(&encode_columnar(events)),
+        }
+    }
+
+    pub fn decode_block(self, block: &[u8]) -> std::io::Result<Vec<Event>> {
+        match self {
+            SegmentCodec::FixedWidth => {
+                if block.len() % RECORD_SIZE != 0 {
+                    return Err(invalid_block("Torn record in data block"));
+                }
+                Ok(block
+                    .chunks_exact(RECORD_SIZE)
+                    .map(|record| {
+                        let (timestamp, subject, predicate, object, graph) =
+                            decode_record(record.try_into().unwrap());
+                        Event { timestamp, subject, predicate, object, graph }
+                    })
+                    .collect())
+            }
+            SegmentCodec::Columnar => decode_columnar(block),
+            SegmentCodec::CompressedColumnar => decode_columnar(&decompress(block)?),
+        }
+    }
+}
+
+fn encode_columnar(events: &[Event]) -> Vec<u8> {
+    let mut block = Vec::new();
+    write_varint(&mut block, events.len() as u64);
+
+    let mut previous = 0u64;
+    for (position, event) in events.iter().enumerate() {
+        let value = if position == 0 {
+            event.timestamp
+        } else {
+            event.timestamp - previous
+        };
+        write_varint(&mut block, value);
+        previous = event.timestamp;
+    }
+    for event in events {
+        write_varint(&mut block, u64::from(event.subject));
+    }
+    write_runs(&mut block, events.iter().map(|event| event.predicate));
+    for event in events {
+        write_varint(&mut block, u64::from(event.object));
+    }
+    write_runs(&mut block, events.iter().map(|event| event.graph));
+    block
+}
+
+fn decode_columnar(block: &[u8]) -> std::io::Result<Vec<Event>> {
+    let mut reader = VarintReader { buffer: block, position: 0 };
+    let count = usize::try_from(reader.next()?)
+        .ok()
+        .filter(|&count| count <= block.len())
+        .ok_or_else(|| invalid_block("Record count exceeds data block size"))?;
+
+    let mut timestamps = Vec::with_capacity(count);
+    let mut previous = 0u64;
+    for position in 0..count {
+        let value = reader.next()?;
+        let timestamp = if position == 0 {
+            value
+        } else {
+            previous.checked_add(value).ok_or_else(|| invalid_block("Timestamp overflow"))?
+        };
+        timestamps.push(timestamp);
+        previous = timestamp;
+    }
+    let subjects = reader.ids(count)?;
+    let predicates = reader.runs(count)?;
+    let objects = reader.ids(count)?;
+    let graphs = reader.runs(count)?;
+
+    if reader.position != block.len() {
+        return Err(invalid_block("Trailing bytes after the last column"));
+    }
+
+    Ok((0..count)
+        .map(|i| Event {
+            timestamp: timestamps[i],
+            subject: subjects[i],
+            predicate: predicates[i],
+            object: objects[i],
+            graph: graphs[i],
+        })
+        .collect())
+}
+
+const MIN_MATCH: usize = 4;
+const HASH_BITS: u32 = 14;
+// Candidates tried per position; more find longer matches at a higher cost.
+const MAX_CHAIN: usize = 32;
+const NO_POSITION: usize = usize::MAX;
+
+fn compress(input: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(input.len() / 2 + 8);
+    write_varint(&mut output, input.len() as u64);
+
+    let mut heads = vec![NO_POSITION; 1 << HASH_BITS];
+    let mut chain = vec![NO_POSITION; input.len()];
+    let mut insert = |position: usize, chain: &mut Vec<usize>| {
+        let slot = prefix_hash(&input[position..]);
+        chain[position] = heads[slot];
+        heads[slot] = position;
+    };
+
+    let mut literal_start = 0;
+    let mut position = 0;
+    while position + MIN_MATCH <= input.len() {
+        let (mut best_length, mut best_distance) = (0, 0);
+        // Link the position to the earlier ones with the same prefix hash.
+        insert(position, &mut chain);
+        let mut candidate = chain[position];
+        for _ in 0..MAX_CHAIN {
+            if candidate == NO_POSITION {
+                break;
+            }
+            let length = input[candidate..]
+                .iter()
+                .zip(&input[position..])
+                .take_while(|(a, b)| a == b)
+                .count();
+            if length > best_length {
+                (best_length, best_distance) = (length, position - candidate);
+            }
+            candidate = chain[candidate];
+        }
+
+        if best_length < MIN_MATCH {
+            position += 1;
+            continue;
+        }
+        write_varint(&mut output, (position - literal_start) as u64);
+        output.extend_from_slice(&input[literal_start..position]);
+        write_varint(&mut output, best_length as u64);
+        write_varint(&mut output, best_distance as u64);
+        for covered in position + 1..(position + best_length).min(input.len() - MIN_MATCH + 1) {
+            insert(covered, &mut chain);
+        }
+        position += best_length;
+        literal_start = position;
+    }
+
+    write_varint(&mut output, (input.len() - literal_start) as u64);
+    output.extend_from_slice(&input[literal_start..]);
+    write_varint(&mut output, 0);
+    output
+}
+
+fn decompress(block: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut reader = VarintReader { buffer: block, position: 0 };
+    let length = usize::try_from(reader.next()?)
+        .map_err(|_| invalid_block("Compressed block length out of range"))?;
+    let too_long = || invalid_block("Compressed block expands beyond its length");
+    // A corrupt length must not allocate more than the block can expand to.
+    let mut output = Vec::with_capacity(length.min(block.len().saturating_mul(64)));
+
+    loop {
+        let literals = usize::try_from(reader.next()?)
+            .ok()
+            .filter(|&count| count <= block.len() - reader.position)
+            .ok_or_else(|| invalid_block("Literal run exceeds compressed block"))?;
+        if output.len() + literals > length {
+            return Err(too_long());
+        }
+        output.extend_from_slice(&block[reader.position..reader.position + literals]);
+        reader.position += literals;
+
+        let match_length =
+            usize::try_from(reader.next()?).map_err(|_| invalid_block("Match out of range"))?;
+        if match_length == 0 {
+            break;
+        }
+        let distance = usize::try_from(reader.next()?)
+            .ok()
+            .filter(|&distance| distance > 0 && distance <= output.len())
+            .ok_or_else(|| invalid_block("Match distance exceeds decompressed data"))?;
+        if output.len() + match_length > length {
+            return Err(too_long());
+        }
+        // The match may overlap the bytes it produces.
+        for _ in 0..match_length {
+            output.push(output[output.len() - distance]);
+        }
+    }
+
+    if reader.position != block.len() || output.len() != length {
+        return Err(invalid_block("Compressed block does not match its length"));
+    }
+    Ok(output)
+}
+
+// Hash table slot of the 4-byte prefix of `bytes`.
+fn prefix_hash(bytes: &[u8]) -> usize {
+    let prefix = u32::from_le_bytes(bytes[..MIN_MATCH].try_into().unwrap());
+    (prefix.wrapping_mul(0x9E37_79B1) >> (32 - HASH_BITS)) as usize
+}
+
+fn write_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+fn write_runs(buffer: &mut Vec<u8>, values: impl Iterator<Item = u32>) {
+    let mut run: Option<(u32, u64)> = None;
+    for value in values {
+        run = match run {
+            Some((current, length)) if current == value => Some((current, length + 1)),
+            Some((current, length)) => {
+                write_varint(buffer, length);
+                write_varint(buffer, u64::from(current));
+                Some((value, 1))
+            }
+            None => Some((value, 1)),
+        };
+    }
+    if let Some((current, length)) = run {
+        write_varint(buffer, length);
+        write_varint(buffer, u64::from(current));
+    }
+}
+
+struct VarintReader<'a> {
+    buffer: &'a [u8],
+    position: usize,
+}
+
+impl VarintReader<'_> {
+    fn next(&mut self) -> std::io::Result<u64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = *self
+                .buffer
+                .get(self.position)
+                .ok_or_else(|| invalid_block("Truncated varint in data block"))?;
+            self.position += 1;
+            value |= u64::from(byte & 0x7F) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid_block("Varint longer than 64 bits in data block"))
+    }
+
+    fn id(&mut self) -> std::io::Result<u32> {
+        u32::try_from(self.next()?).map_err(|_| invalid_block("Dictionary ID out of range"))
+    }
+
+    fn ids(&mut self, count: usize) -> std::io::Result<Vec<u32>> {
+        (0..count).map(|_| self.id()).collect()
+    }
+
+    fn runs(&mut self, count: usize) -> std::io::Result<Vec<u32>> {
+        let mut values = Vec::with_capacity(count);
+        while values.len() < count {
+            let length = usize::try_from(self.next()?)
+                .ok()
+                .filter(|&length| length > 0 && length <= count - values.len())
+                .ok_or_else(|| invalid_block("Run length exceeds record count"))?;
+            let value = self.id()?;
+            values.extend(std::iter::repeat_n(value, length));
+        }
+        Ok(values)
+    }
+}
+
+fn invalid_block(reason: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, reason)
+}
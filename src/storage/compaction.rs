@@ -0,0 +1,80 @@
+//! Planning of background segment compaction.
+//!
+//! Every flush creates a new segment, so frequent manual flushes and short batch
+//! ages leave many small segments that each cost an overlap check and a file open
+//! per query. Compaction merges runs of adjacent segments into one larger segment
+//! with a rebuilt two-level index. This module only decides which segments to
+//! merge; `StreamingSegmentedStorage::compact` writes the merged segment and swaps
+//! it in for its inputs.
+
+use std::collections::BTreeMap;
+
+use crate::storage::util::{EnhancedSegmentMetadata, StorageTier, StreamingConfig};
+
+/// Pick the runs of adjacent segments that should be merged.
+///
+/// `segments` must be ordered by start timestamp. Each run is a list of positions
+/// in `segments` that belong to the same stream and tier; segments of other streams
+/// or tiers in between do not break a run, so archived segments are only merged with
+/// each other. A run grows while its combined record count stays
+/// within `compaction_max_segment_records`, and is only merged once it holds at
+/// least `compaction_min_segments` segments. Segments without a readable index
+/// are never merged; `verify` is responsible for those.
+pub fn plan_compaction(
+    segments: &[EnhancedSegmentMetadata],
+    config: &StreamingConfig,
+) -> Vec<Vec<usize>> {
+    let mut streams: BTreeMap<(Option<&str>, StorageTier), Vec<usize>> = BTreeMap::new();
+    for (position, segment) in segments.iter().enumerate() {
+        streams
+            .entry((segment.stream.as_deref(), segment.tier))
+            .or_default()
+            .push(position);
+    }
+
+    let mut runs = Vec::new();
+    for positions in streams.values() {
+        plan_stream_runs(segments, positions, config, &mut runs);
+    }
+    runs
+}
+
+// Plan the runs among the segments of a single stream and tier.
+fn plan_stream_runs(
+    segments: &[EnhancedSegmentMetadata],
+    positions: &[usize],
+    config: &StreamingConfig,
+    runs: &mut Vec<Vec<usize>>,
+) {
+    let min_segments = config.compaction_min_segments.max(2);
+    let max_records = config.compaction_max_segment_records;
+
+    let mut run_start = 0;
+    let mut run_records = 0u64;
+
+    for (index, &position) in positions.iter().enumerate() {
+        let segment = &segments[position];
+        let mergeable = segment.end_timestamp != u64::MAX && segment.record_count < max_records;
+
+        if mergeable && run_records + segment.record_count <= max_records {
+            run_records += segment.record_count;
+            continue;
+        }
+
+        if index - run_start >= min_segments {
+            runs.push(positions[run_start..index].to_vec());
+        }
+
+        if mergeable {
+            run_start = index;
+            run_records = segment.record_count;
+        } else {
+            run_start = index + 1;
+            run_records = 0;
+        }
+    }
+
+    if positions.len().saturating_sub(run_start) >= min_segments {
+        runs.push(positions[run_start..].to_vec());
+    }
+}
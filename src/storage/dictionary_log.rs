@@ -0,0 +1,325 @@
+//! Incremental, crash-safe persistence of the storage dictionary.
+//!
+//! Rewriting the whole dictionary on every flush costs time proportional to its
+//! size and leaves a truncated file behind when the process dies mid-write. The
+//! dictionary is instead persisted as a checkpoint (`dictionary.bin`) plus an
+//! append-only log (`dictionary.log`) of the entries assigned since then:
+//!
+//! - every flush appends only the new `(id, string)` entries to the log and syncs it;
+//! - once the log holds `dictionary_checkpoint_interval` entries, the whole dictionary
+//!   is written to a temporary file, synced and renamed over the checkpoint, and the
+//!   log is truncated.
+//!
+//! Log records are framed like the write-ahead log, as
+//! `[payload length: u32][crc32: u32][id: u32][UTF-8 string]`. A torn record at the
+//! end of the log is what an interrupted append leaves behind and is dropped: its
+//! entries are still in the write-ahead log, which is only released once the append
+//! has been synced. Any other damage to the checkpoint or the log is an error, as a
+//! reset dictionary would make every segment undecodable.
+//!
+//! A read-only storage follows the dictionary of the writer process without writing to
+//! either file: it keeps its position in the log and applies the records appended since,
+//! leaving a torn tail for the next refresh, and reloads everything once the writer has
+//! replaced the checkpoint.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::storage::indexing::dictionary::Dictionary;
+
+const CHECKPOINT_FILE: &str = "dictionary.bin";
+const CHECKPOINT_TMP_FILE: &str = "dictionary.bin.tmp";
+const LOG_FILE: &str = "dictionary.log";
+
+const RECORD_HEADER_SIZE: usize = 8;
+
+/// Append-only log of dictionary entries on top of a periodic checkpoint.
+pub struct DictionaryLog {
+    directory: PathBuf,
+    /// `None` when following the log of a writer process.
+    writer: Option<BufWriter<File>>,
+    /// Every entry below this ID is stored in the checkpoint or the log.
+    persisted_next_id: u32,
+    entries_since_checkpoint: usize,
+    checkpoint_interval: usize,
+    /// Length of the log read so far, when following a writer.
+    read_position: u64,
+    /// Length and modification time of the checkpoint last loaded, when following a writer.
+    checkpoint_stamp: Option<(u64, SystemTime)>,
+}
+
+impl DictionaryLog {
+    /// Opens the dictionary persisted in `directory`, or an empty one if there is none yet.
+    ///
+    /// Fails with `InvalidData` when the checkpoint cannot be decoded or the log is
+    /// damaged anywhere but at its tail.
+    pub fn open(
+        directory: &Path,
+        checkpoint_interval: usize,
+    ) -> std::io::Result<(Self, Dictionary)> {
+        let checkpoint_path = directory.join(CHECKPOINT_FILE);
+        let mut dictionary = if checkpoint_path.exists() {
+            Dictionary::load_from_file(&checkpoint_path).map_err(|e| {
+                std::io::Error::new(
+                    e.kind(),
+                    format!("Failed to load dictionary checkpoint {:?}: {}", checkpoint_path, e),
+                )
+            })?
+        } else {
+            Dictionary::new()
+        };
+
+        let log_path = directory.join(LOG_FILE);
+        let entries_since_checkpoint = if log_path.exists() {
+            Self::replay(&log_path, &mut dictionary)?
+        } else {
+            0
+        };
+
+        let file = OpenOptions::new().create(true).append(true).open(&log_path)?;
+        let log = Self {
+            directory: directory.to_path_buf(),
+            writer: Some(BufWriter::new(file)),
+            persisted_next_id: dictionary.next_id,
+            entries_since_checkpoint,
+            checkpoint_interval,
+            read_position: 0,
+            checkpoint_stamp: None,
+        };
+        Ok((log, dictionary))
+    }
+
+    /// Loads the dictionary persisted in `directory` by a writer process without writing
+    /// to it, to be kept up to date with [`Self::refresh`].
+    pub fn open_read_only(directory: &Path) -> std::io::Result<(Self, Dictionary)> {
+        let mut log = Self {
+            directory: directory.to_path_buf(),
+            writer: None,
+            persisted_next_id: 0,
+            entries_since_checkpoint: 0,
+            checkpoint_interval: usize::MAX,
+            read_position: 0,
+            checkpoint_stamp: None,
+        };
+        let mut dictionary = Dictionary::new();
+        log.reload(&mut dictionary)?;
+        Ok((log, dictionary))
+    }
+
+    /// Apply the entries the writer process has persisted since the last call. Returns
+    /// whether the dictionary changed.
+    ///
+    /// Once the writer has replaced the checkpoint, the dictionary is loaded again as a
+    /// whole, so entries purged by the writer disappear as well.
+    pub fn refresh(&mut self, dictionary: &mut Dictionary) -> std::io::Result<bool> {
+        let log_length = match std::fs::metadata(self.directory.join(LOG_FILE)) {
+            Ok(metadata) => metadata.len(),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => 0,
+            Err(err) => return Err(err),
+        };
+        // The log is only truncated right after the checkpoint has been replaced.
+        if self.checkpoint_stamp()? != self.checkpoint_stamp || log_length < self.read_position {
+            let mut reloaded = Dictionary::new();
+            self.reload(&mut reloaded)?;
+            *dictionary = reloaded;
+            return Ok(true);
+        }
+        if log_length == self.read_position {
+            return Ok(false);
+        }
+
+        let log_path = self.directory.join(LOG_FILE);
+        let mut data = std::fs::read(&log_path)?;
+        let start = self.read_position as usize;
+        data.drain(..start.min(data.len()));
+        let (entries, valid_length) = Self::apply_records(&data, &log_path, start, dictionary)?;
+        self.read_position += valid_length as u64;
+        self.persisted_next_id = dictionary.next_id;
+        Ok(entries > 0)
+    }
+
+    // Load the checkpoint and the whole log, as far as it has been written completely.
+    fn reload(&mut self, dictionary: &mut Dictionary) -> std::io::Result<()> {
+        // A checkpoint replaced while it is read is loaded again.
+        loop {
+            let stamp = self.checkpoint_stamp()?;
+            let checkpoint_path = self.directory.join(CHECKPOINT_FILE);
+            *dictionary = match Dictionary::load_from_file(&checkpoint_path) {
+                Ok(dictionary) => dictionary,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Dictionary::new(),
+                Err(err) => {
+                    return Err(std::io::Error::new(
+                        err.kind(),
+                        format!(
+                            "Failed to load dictionary checkpoint {:?}: {}",
+                            checkpoint_path, err
+                        ),
+                    ))
+                }
+            };
+            if self.checkpoint_stamp()? == stamp {
+                self.checkpoint_stamp = stamp;
+                break;
+            }
+        }
+
+        let log_path = self.directory.join(LOG_FILE);
+        let data = match std::fs::read(&log_path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err),
+        };
+        let (_, valid_length) = Self::apply_records(&data, &log_path, 0, dictionary)?;
+        self.read_position = valid_length as u64;
+        self.persisted_next_id = dictionary.next_id;
+        Ok(())
+    }
+
+    fn checkpoint_stamp(&self) -> std::io::Result<Option<(u64, SystemTime)>> {
+        match std::fs::metadata(self.directory.join(CHECKPOINT_FILE)) {
+            Ok(metadata) => Ok(Some((metadata.len(), metadata.modified()?))),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn writer(&mut self) -> std::io::Result<&mut BufWriter<File>> {
+        self.writer.as_mut().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                format!("Dictionary of {:?} is opened read-only", self.directory),
+            )
+        })
+    }
+
+    /// Append the entries assigned since the last call and sync them to disk, writing
+    /// a new checkpoint once the log has grown past the checkpoint interval.
+    pub fn persist(&mut self, dictionary: &Dictionary) -> std::io::Result<()> {
+        if dictionary.next_id <= self.persisted_next_id {
+            return Ok(());
+        }
+
+        let first_id = self.persisted_next_id;
+        let writer = self.writer()?;
+        let mut appended = 0;
+        for id in first_id..dictionary.next_id {
+            if let Some(value) = dictionary.decode(id) {
+                let mut payload = Vec::with_capacity(4 + value.len());
+                payload.extend_from_slice(&id.to_le_bytes());
+                payload.extend_from_slice(value.as_bytes());
+                writer.write_all(&(payload.len() as u32).to_le_bytes())?;
+                writer.write_all(&crc32fast::hash(&payload).to_le_bytes())?;
+                writer.write_all(&payload)?;
+                appended += 1;
+            }
+        }
+        writer.flush()?;
+        writer.get_ref().sync_data()?;
+
+        self.persisted_next_id = dictionary.next_id;
+        self.entries_since_checkpoint += appended;
+        if self.entries_since_checkpoint >= self.checkpoint_interval {
+            self.checkpoint(dictionary)?;
+        }
+        Ok(())
+    }
+
+    /// Atomically replace the checkpoint with the whole dictionary and truncate the log.
+    ///
+    /// A crash between the rename and the truncation only leaves log entries behind that
+    /// the new checkpoint already holds, and replaying them again is harmless.
+    pub fn checkpoint(&mut self, dictionary: &Dictionary) -> std::io::Result<()> {
+        self.writer()?;
+        Self::write_checkpoint(&self.directory, dictionary)?;
+
+        let file = File::create(self.directory.join(LOG_FILE))?;
+        file.sync_all()?;
+        self.writer = Some(BufWriter::new(file));
+        self.persisted_next_id = self.persisted_next_id.max(dictionary.next_id);
+        self.entries_since_checkpoint = 0;
+        Ok(())
+    }
+
+    /// Atomically write the whole dictionary as the checkpoint of `directory`, such as
+    /// the directory of a snapshot, leaving any log there untouched.
+    pub fn write_checkpoint(directory: &Path, dictionary: &Dictionary) -> std::io::Result<()> {
+        let tmp_path = directory.join(CHECKPOINT_TMP_FILE);
+        dictionary.save_to_file(&tmp_path)?;
+        File::open(&tmp_path)?.sync_all()?;
+        std::fs::rename(&tmp_path, directory.join(CHECKPOINT_FILE))
+    }
+
+    // Apply every entry of the log to the dictionary and return how many there were.
+    // A torn tail is cut off so the next append starts right after the last valid record.
+    fn replay(path: &Path, dictionary: &mut Dictionary) -> std::io::Result<usize> {
+        let data = std::fs::read(path)?;
+        let (entries, valid_length) = Self::apply_records(&data, path, 0, dictionary)?;
+
+        if valid_length < data.len() {
+            eprintln!("Warning: Dropping torn dictionary log tail in {:?}", path);
+            let file = OpenOptions::new().write(true).open(path)?;
+            file.set_len(valid_length as u64)?;
+            file.sync_all()?;
+        }
+        Ok(entries)
+    }
+
+    // Apply the records in `data`, read from `path` at `offset`, to the dictionary. Returns
+    // the number of entries and the length of the records up to a torn tail.
+    fn apply_records(
+        data: &[u8],
+        path: &Path,
+        offset: usize,
+        dictionary: &mut Dictionary,
+    ) -> std::io::Result<(usize, usize)> {
+        let mut position = 0;
+        let mut entries = 0;
+
+        while position < data.len() {
+            let corrupt = |reason: &str| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!(
+                        "{} at offset {} of dictionary log {:?}",
+                        reason,
+                        offset + position,
+                        path
+                    ),
+                )
+            };
+
+            let Some(header) = data.get(position..position + RECORD_HEADER_SIZE) else {
+                break;
+            };
+            let length = u32::from_le_bytes(header[0..4].try_into().unwrap()) as usize;
+            let checksum = u32::from_le_bytes(header[4..8].try_into().unwrap());
+            let end = position + RECORD_HEADER_SIZE + length;
+            let Some(payload) = data.get(position + RECORD_HEADER_SIZE..end) else {
+                break;
+            };
+
+            if crc32fast::hash(payload) != checksum || payload.len() < 4 {
+                if end == data.len() {
+                    break;
+                }
+                return Err(corrupt("Checksum mismatch"));
+            }
+
+            let id = u32::from_le_bytes(payload[0..4].try_into().unwrap());
+            let value =
+                std::str::from_utf8(&payload[4..]).map_err(|_| corrupt("Invalid UTF-8 entry"))?;
+            let conflicting = dictionary.decode(id).is_some_and(|existing| existing != value)
+                || dictionary.string_to_id.get(value).is_some_and(|&existing| existing != id);
+            if conflicting {
+                return Err(corrupt("Conflicting dictionary entry"));
+            }
+            dictionary.insert(id, value);
+
+            entries += 1;
+            position = end;
+        }
+        Ok((entries, position))
+    }
+}
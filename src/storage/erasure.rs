@@ -0,0 +1,186 @@
+//! Exact erasure of a subject's or a graph's events from historical storage.
+//!
+//! Segments are immutable, so deleting events happens in two steps:
+//!
+//! - `delete_by_subject` / `delete_by_graph` record a [`Tombstone`] in `tombstones.json`.
+//!   It covers every segment listed when it was recorded, identified by the highest
+//!   segment ID at that time, and queries skip the matching events of covered segments
+//!   from then on.
+//! - Purging rewrites each covered segment that still holds matching events, then
+//!   removes the dictionary strings that no remaining event refers to and writes a new
+//!   dictionary checkpoint. The tombstone is dropped once its segments are rewritten.
+//!
+//! Compaction applies the tombstones while merging, so a merged segment never brings
+//! erased events back. Segments written after a tombstone are not covered by it, so a
+//! subject written again after its deletion stays visible.
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::Event;
+
+const TOMBSTONES_FILE: &str = "tombstones.json";
+const TOMBSTONES_TMP_FILE: &str = "tombstones.json.tmp";
+
+/// The position of the term a tombstone erases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TombstoneScope {
+    Subject,
+    Graph,
+}
+
+/// A pending deletion of every event with a given subject or graph.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tombstone {
+    pub scope: TombstoneScope,
+    /// Dictionary key of the erased term.
+    pub term: String,
+    /// Dictionary ID of the erased term.
+    pub term_id: u32,
+    /// Highest ID of the segments listed when the tombstone was recorded.
+    pub up_to_segment: u64,
+}
+
+impl Tombstone {
+    /// Whether the tombstone applies to a segment. Segments without an ID in their file
+    /// name predate every tombstone.
+    pub fn covers(&self, segment_id: Option<u64>) -> bool {
+        segment_id.is_none_or(|id| id <= self.up_to_segment)
+    }
+}
+
+/// The dictionary IDs of the terms erased from one segment.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ErasedTerms {
+    subjects: HashSet<u32>,
+    graphs: HashSet<u32>,
+}
+
+impl ErasedTerms {
+    pub fn matches(&self, event: &Event) -> bool {
+        self.subjects.contains(&event.subject) || self.graphs.contains(&event.graph)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.subjects.is_empty() && self.graphs.is_empty()
+    }
+}
+
+/// The tombstones of a storage directory, persisted atomically on every change.
+#[derive(Debug)]
+pub struct Tombstones {
+    directory: PathBuf,
+    entries: Vec<Tombstone>,
+    // Incremented by every new tombstone, so a merge can tell that a tombstone was
+    // recorded after it read its inputs.
+    generation: u64,
+}
+
+impl Tombstones {
+    /// Load the tombstones recorded in `directory`, or none if there is no file yet.
+    pub fn open(directory: &Path) -> std::io::Result<Self> {
+        let entries = match std::fs::read(directory.join(TOMBSTONES_FILE)) {
+            Ok(data) => serde_json::from_slice(&data)
+                .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err),
+        };
+        Ok(Self { directory: directory.to_path_buf(), entries, generation: 0 })
+    }
+
+    pub fn entries(&self) -> &[Tombstone] {
+        &self.entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Record a tombstone. Recording the same deletion twice only widens its coverage.
+    pub fn add(&mut self, tombstone: Tombstone) -> std::io::Result<()> {
+        match self
+            .entries
+            .iter_mut()
+            .find(|entry| entry.scope == tombstone.scope && entry.term_id == tombstone.term_id)
+        {
+            Some(entry) => entry.up_to_segment = entry.up_to_segment.max(tombstone.up_to_segment),
+            None => self.entries.push(tombstone),
+        }
+        self.generation += 1;
+        self.save_into(&self.directory)
+    }
+
+    /// Drop tombstones whose segments have all been rewritten. A tombstone that was
+    /// widened in the meantime is kept.
+    pub fn remove(&mut self, purged: &[Tombstone]) -> std::io::Result<()> {
+        self.entries.retain(|entry| !purged.contains(entry));
+        self.save_into(&self.directory)
+    }
+
+    /// The terms erased from the segment with the given ID, or `None` when no tombstone
+    /// covers it.
+    pub fn erased_in(&self, segment_id: Option<u64>) -> Option<Arc<ErasedTerms>> {
+        let mut erased = ErasedTerms::default();
+        for tombstone in self.entries.iter().filter(|entry| entry.covers(segment_id)) {
+            match tombstone.scope {
+                TombstoneScope::Subject => erased.subjects.insert(tombstone.term_id),
+                TombstoneScope::Graph => erased.graphs.insert(tombstone.term_id),
+            };
+        }
+        (!erased.is_empty()).then(|| Arc::new(erased))
+    }
+
+    /// Write the tombstones into another storage directory, such as a snapshot.
+    pub fn save_into(&self, directory: &Path) -> std::io::Result<()> {
+        let tmp_path = directory.join(TOMBSTONES_TMP_FILE);
+        let path = directory.join(TOMBSTONES_FILE);
+        if self.entries.is_empty() {
+            return match std::fs::remove_file(path) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+                _ => Ok(()),
+            };
+        }
+
+        let encoded = serde_json::to_vec(&self.entries).map_err(std::io::Error::other)?;
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&encoded)?;
+        file.sync_all()?;
+        std::fs::rename(tmp_path, path)
+    }
+}
+
+/// State of the most recent purge.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErasureState {
+    #[default]
+    Idle,
+    Running,
+    Completed,
+    Failed,
+}
+
+/// Progress of erasing the recorded tombstones from storage.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ErasureProgress {
+    pub state: ErasureState,
+    /// Tombstones recorded but not yet purged.
+    pub pending_tombstones: usize,
+    /// Segments covered by the tombstones being purged.
+    pub segments_total: usize,
+    pub segments_scanned: usize,
+    pub segments_rewritten: usize,
+    pub events_removed: u64,
+    /// Dictionary strings removed because no event refers to them any more.
+    pub terms_purged: usize,
+    pub error: Option<String>,
+}
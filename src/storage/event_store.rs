@@ -0,0 +1,306 @@
+//! Storage backends for historical events.
+//!
+//! The executor, the window operators, the stream bus and the HTTP server only depend on
+//! the [`EventStore`] trait, so a deployment can choose where its events live:
+//!
+//! - [`StreamingSegmentedStorage`], the default, writes segment files with sparse indexes.
+//! - [`InMemoryEventStore`](crate::storage::in_memory_store::InMemoryEventStore) keeps
+//!   every event in memory, which is convenient for tests.
+//! - [`OxigraphEventStore`](crate::storage::oxigraph_store::OxigraphEventStore) keeps the
+//!   events in an Oxigraph store, persisted in RocksDB when opened from a directory.
+//!
+//! Every backend hands out dictionary-encoded [`Event`]s; the IDs are only meaningful
+//! together with the backend's own dictionary.
+
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::core::{Event, RDFEvent};
+use crate::storage::erasure::ErasureProgress;
+use crate::storage::indexing::dictionary::Dictionary;
+use crate::storage::indexing::secondary::{EventFilter, TriplePattern};
+use crate::storage::memory_tracker::MemoryTracker;
+use crate::storage::query_iter::QueryIter;
+use crate::storage::rollup::Rollup;
+use crate::storage::segmented_storage::StreamingSegmentedStorage;
+use crate::storage::snapshot::SnapshotManifest;
+use crate::storage::util::StorageComponentSizes;
+
+/// A store of timestamped RDF events that historical queries read from.
+pub trait EventStore: Send + Sync {
+    /// Write an event that does not belong to a stream.
+    fn write_rdf_event(&self, event: RDFEvent) -> io::Result<()>;
+
+    /// Write an event into the log of the named stream.
+    fn write_rdf_event_to_stream(&self, stream_name: &str, event: RDFEvent) -> io::Result<()>;
+
+    /// Lazily iterate over the events in a timestamp range in timestamp order.
+    ///
+    /// Without a stream name every event is read; with one, only the events of that
+    /// stream and the events written without a stream. When patterns are given, only the
+    /// events matching one of them are returned.
+    fn scan(
+        &self,
+        stream_name: Option<&str>,
+        start_timestamp: u64,
+        end_timestamp: u64,
+        patterns: Option<&[TriplePattern]>,
+    ) -> io::Result<QueryIter>;
+
+    /// Persist everything written so far.
+    fn flush(&self) -> io::Result<()>;
+
+    /// The dictionary the IDs of the stored events refer to.
+    fn get_dictionary(&self) -> &Arc<RwLock<Dictionary>>;
+
+    /// Erase every event whose subject is `subject`, given like the subject of
+    /// [`RDFEvent::new`]. Queries stop returning the events as soon as this returns.
+    fn delete_by_subject(&self, subject: &str) -> io::Result<()>;
+
+    /// Erase every event in the graph `graph`, given like the graph of [`RDFEvent::new`].
+    fn delete_by_graph(&self, graph: &str) -> io::Result<()>;
+
+    /// Write RDF data given as strings, see [`RDFEvent::new`].
+    fn write_rdf(
+        &self,
+        timestamp: u64,
+        subject: &str,
+        predicate: &str,
+        object: &str,
+        graph: &str,
+    ) -> io::Result<()> {
+        self.write_rdf_event(RDFEvent::new(timestamp, subject, predicate, object, graph))
+    }
+
+    /// Query every event in a timestamp range.
+    fn query(&self, start_timestamp: u64, end_timestamp: u64) -> io::Result<Vec<Event>> {
+        self.scan(None, start_timestamp, end_timestamp, None)?.collect()
+    }
+
+    /// Query the events of a single stream's log.
+    fn query_stream(
+        &self,
+        stream_name: &str,
+        start_timestamp: u64,
+        end_timestamp: u64,
+    ) -> io::Result<Vec<Event>> {
+        self.scan(Some(stream_name), start_timestamp, end_timestamp, None)?.collect()
+    }
+
+    /// Lazily iterate over the log of a single stream.
+    fn query_stream_iter(
+        &self,
+        stream_name: &str,
+        start_timestamp: u64,
+        end_timestamp: u64,
+    ) -> io::Result<QueryIter> {
+        self.scan(Some(stream_name), start_timestamp, end_timestamp, None)
+    }
+
+    /// Lazily iterate over the events of a stream's log matching any of the patterns.
+    fn query_stream_filtered_iter(
+        &self,
+        stream_name: &str,
+        start_timestamp: u64,
+        end_timestamp: u64,
+        patterns: &[TriplePattern],
+    ) -> io::Result<QueryIter> {
+        self.scan(Some(stream_name), start_timestamp, end_timestamp, Some(patterns))
+    }
+
+    /// Aggregates of a stream's log per time bucket, predicate, subject and graph, for stores
+    /// that keep rollups, see [`crate::storage::rollup`]. `None` means that the events have
+    /// to be aggregated instead.
+    fn rollups(
+        &self,
+        _stream_name: &str,
+        _start_timestamp: u64,
+        _end_timestamp: u64,
+    ) -> io::Result<Option<Vec<Rollup>>> {
+        Ok(None)
+    }
+
+    /// Tracker of the bytes buffered against the store's memory budget, if it has one.
+    fn memory_tracker(&self) -> Option<&MemoryTracker> {
+        None
+    }
+
+    /// Error of the last failed background flush, for stores that flush in the background.
+    fn background_flush_error(&self) -> Option<String> {
+        None
+    }
+
+    /// Watermark of a stream's log, or of the events without a stream when `stream_name`
+    /// is `None`, for stores that track event time. Ranges ending before it are complete.
+    fn watermark(&self, _stream_name: Option<&str>) -> Option<u64> {
+        None
+    }
+
+    /// Wait until the watermark of a stream's log passes `timestamp`, or at most
+    /// `timeout`, and return the watermark reached. Stores that do not track event time
+    /// return at once.
+    fn wait_for_watermark(
+        &self,
+        stream_name: Option<&str>,
+        _timestamp: u64,
+        _timeout: Duration,
+    ) -> Option<u64> {
+        self.watermark(stream_name)
+    }
+
+    /// Oldest event timestamp still retained, for stores with a retention policy.
+    fn retention_horizon(&self) -> Option<u64> {
+        None
+    }
+
+    /// Remove erased events from the store's files, for stores that only hide them at
+    /// first, and return the final progress.
+    fn purge_erased(&self) -> io::Result<ErasureProgress> {
+        Ok(self.erasure_progress())
+    }
+
+    /// Progress of erasing deleted events from the store's files.
+    fn erasure_progress(&self) -> ErasureProgress {
+        ErasureProgress::default()
+    }
+
+    /// Write a consistent copy of the store into the new or empty directory `destination`.
+    fn snapshot(&self, _destination: &Path) -> io::Result<SnapshotManifest> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Snapshots are not supported by this store",
+        ))
+    }
+
+    /// Breakdown of the memory held by the store.
+    fn component_sizes(&self) -> StorageComponentSizes {
+        let dictionary_bytes = self.get_dictionary().read().unwrap().heap_size_bytes();
+        StorageComponentSizes {
+            dictionary_bytes,
+            estimated_total_bytes: dictionary_bytes,
+            ..StorageComponentSizes::default()
+        }
+    }
+}
+
+impl EventStore for StreamingSegmentedStorage {
+    fn write_rdf_event(&self, event: RDFEvent) -> io::Result<()> {
+        StreamingSegmentedStorage::write_rdf_event(self, event)
+    }
+
+    fn write_rdf_event_to_stream(&self, stream_name: &str, event: RDFEvent) -> io::Result<()> {
+        StreamingSegmentedStorage::write_rdf_event_to_stream(self, stream_name, event)
+    }
+
+    fn scan(
+        &self,
+        stream_name: Option<&str>,
+        start_timestamp: u64,
+        end_timestamp: u64,
+        patterns: Option<&[TriplePattern]>,
+    ) -> io::Result<QueryIter> {
+        self.query_iter_partitions(stream_name, start_timestamp, end_timestamp, patterns)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        StreamingSegmentedStorage::flush(self)
+    }
+
+    fn get_dictionary(&self) -> &Arc<RwLock<Dictionary>> {
+        StreamingSegmentedStorage::get_dictionary(self)
+    }
+
+    fn delete_by_subject(&self, subject: &str) -> io::Result<()> {
+        StreamingSegmentedStorage::delete_by_subject(self, subject)
+    }
+
+    fn delete_by_graph(&self, graph: &str) -> io::Result<()> {
+        StreamingSegmentedStorage::delete_by_graph(self, graph)
+    }
+
+    fn rollups(
+        &self,
+        stream_name: &str,
+        start_timestamp: u64,
+        end_timestamp: u64,
+    ) -> io::Result<Option<Vec<Rollup>>> {
+        self.query_rollups(Some(stream_name), start_timestamp, end_timestamp)
+    }
+
+    fn memory_tracker(&self) -> Option<&MemoryTracker> {
+        Some(StreamingSegmentedStorage::memory_tracker(self))
+    }
+
+    fn background_flush_error(&self) -> Option<String> {
+        StreamingSegmentedStorage::background_flush_error(self)
+    }
+
+    fn watermark(&self, stream_name: Option<&str>) -> Option<u64> {
+        StreamingSegmentedStorage::watermark(self, stream_name)
+    }
+
+    fn wait_for_watermark(
+        &self,
+        stream_name: Option<&str>,
+        timestamp: u64,
+        timeout: Duration,
+    ) -> Option<u64> {
+        StreamingSegmentedStorage::wait_for_watermark(self, stream_name, timestamp, timeout)
+    }
+
+    fn retention_horizon(&self) -> Option<u64> {
+        StreamingSegmentedStorage::retention_horizon(self)
+    }
+
+    fn purge_erased(&self) -> io::Result<ErasureProgress> {
+        StreamingSegmentedStorage::purge_erased(self)
+    }
+
+    fn erasure_progress(&self) -> ErasureProgress {
+        StreamingSegmentedStorage::erasure_progress(self)
+    }
+
+    fn snapshot(&self, destination: &Path) -> io::Result<SnapshotManifest> {
+        StreamingSegmentedStorage::snapshot(self, destination)
+    }
+
+    fn component_sizes(&self) -> StorageComponentSizes {
+        StreamingSegmentedStorage::component_sizes(self)
+    }
+}
+
+/// Select the events of a scan from events held in memory with the dictionary ID of
+/// their stream, following the semantics of [`EventStore::scan`].
+///
+/// The events must be sorted by timestamp; the selection keeps their order.
+pub fn select_events<'a>(
+    events: impl IntoIterator<Item = (&'a Event, Option<u32>)>,
+    dictionary: &Dictionary,
+    stream_name: Option<&str>,
+    start_timestamp: u64,
+    end_timestamp: u64,
+    patterns: Option<&[TriplePattern]>,
+) -> QueryIter {
+    // A stream that was never written has no dictionary ID, so only events without a
+    // stream can match it.
+    let stream_id = stream_name.map(|name| dictionary.string_to_id.get(name).copied());
+    let filter = patterns.map(|patterns| EventFilter::resolve(patterns, dictionary));
+
+    let selected = events
+        .into_iter()
+        .filter(|(event, stream)| {
+            let in_stream = match (stream_id, stream) {
+                (None, _) | (_, None) => true,
+                (Some(wanted), Some(stream)) => wanted == Some(*stream),
+            };
+            in_stream
+                && event.timestamp >= start_timestamp
+                && event.timestamp <= end_timestamp
+                && filter.as_ref().is_none_or(|filter| filter.matches(event))
+        })
+        .map(|(event, _)| event.clone())
+        .collect();
+    QueryIter::from_events(selected)
+}
@@ -0,0 +1,115 @@
+//! Event-time progress of the stored streams.
+//!
+//! Sensors report late, so events do not reach the storage in timestamp order. A batch
+//! is sorted before it becomes a segment, but a late event can still land in a segment
+//! whose range overlaps segments written before it. Queries merge the segments, so the
+//! results stay ordered; what they cannot tell on their own is whether a range is
+//! complete.
+//!
+//! Every stream therefore has a watermark: the newest event timestamp seen in the stream
+//! minus the configured allowed lateness. Events older than the watermark are late and
+//! handled by the [`LateEventPolicy`]; a range ending before the watermark only changes
+//! through such late events. A segment holding events older than the newest event
+//! persisted before it in the same stream records that part of its range as an
+//! [`OutOfOrderRange`] in its footer.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+/// What a write does with an event older than the watermark of its stream.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LateEventPolicy {
+    /// Store the event and count it as late.
+    #[default]
+    Accept,
+    /// Discard the event and count it as dropped.
+    Drop,
+    /// Fail the write with `ErrorKind::InvalidInput`.
+    Reject,
+}
+
+/// Part of a segment's range that overlaps events persisted before it in the same stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutOfOrderRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl OutOfOrderRange {
+    /// The out-of-order part of a new segment spanning `start..=end`, given the newest
+    /// timestamp already persisted in its stream.
+    pub fn of_segment(start: u64, end: u64, persisted_end: Option<u64>) -> Option<Self> {
+        let persisted_end = persisted_end?;
+        (start < persisted_end).then(|| Self { start, end: end.min(persisted_end) })
+    }
+
+    /// The smallest range covering every range, for a segment merged from several.
+    pub fn union(ranges: impl IntoIterator<Item = Self>) -> Option<Self> {
+        ranges
+            .into_iter()
+            .reduce(|a, b| Self { start: a.start.min(b.start), end: a.end.max(b.end) })
+    }
+}
+
+/// Number of late events seen since the storage was opened.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct LateEventCounts {
+    pub accepted: u64,
+    pub dropped: u64,
+    pub rejected: u64,
+}
+
+/// The newest event timestamp of every stream, keyed by the dictionary ID of the stream
+/// name; `None` is the log of the events written without a stream.
+#[derive(Debug, Default)]
+pub struct Watermarks {
+    allowed_lateness: u64,
+    newest: HashMap<Option<u32>, u64>,
+    late: LateEventCounts,
+}
+
+impl Watermarks {
+    pub fn new(allowed_lateness: u64) -> Self {
+        Self { allowed_lateness, ..Self::default() }
+    }
+
+    /// The watermark of a stream, or `None` before its first event.
+    pub fn watermark(&self, stream: Option<u32>) -> Option<u64> {
+        self.newest
+            .get(&stream)
+            .map(|newest| newest.saturating_sub(self.allowed_lateness))
+    }
+
+    /// Whether an event of the stream with this timestamp is older than the watermark.
+    pub fn is_late(&self, stream: Option<u32>, timestamp: u64) -> bool {
+        self.watermark(stream).is_some_and(|watermark| timestamp < watermark)
+    }
+
+    /// Account for an event of the stream. Returns whether the watermark advanced.
+    pub fn observe(&mut self, stream: Option<u32>, timestamp: u64) -> bool {
+        match self.newest.get_mut(&stream) {
+            Some(newest) if *newest >= timestamp => false,
+            Some(newest) => {
+                *newest = timestamp;
+                true
+            }
+            None => {
+                self.newest.insert(stream, timestamp);
+                true
+            }
+        }
+    }
+
+    pub fn late_events(&self) -> LateEventCounts {
+        self.late
+    }
+
+    pub fn count_late(&mut self, policy: LateEventPolicy) {
+        match policy {
+            LateEventPolicy::Accept => self.late.accepted += 1,
+            LateEventPolicy::Drop => self.late.dropped += 1,
+            LateEventPolicy::Reject => self.late.rejected += 1,
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! An [`EventStore`] that keeps every event in memory.
+//!
+//! Nothing is written to disk, so the store suits tests and short-lived deployments
+//! whose history fits in memory.
+
+use std::io;
+use std::sync::{Arc, RwLock};
+
+use crate::core::{Event, RDFEvent, RdfTerm};
+use crate::storage::event_store::{select_events, EventStore};
+use crate::storage::indexing::dictionary::Dictionary;
+use crate::storage::indexing::secondary::TriplePattern;
+use crate::storage::query_iter::QueryIter;
+use crate::storage::util::StorageComponentSizes;
+
+/// Events held in memory, sorted by timestamp.
+#[derive(Default)]
+pub struct InMemoryEventStore {
+    // Each event with the dictionary ID of its stream, sorted by timestamp; events with
+    // equal timestamps keep their write order.
+    events: RwLock<Vec<(Event, Option<u32>)>>,
+    dictionary: Arc<RwLock<Dictionary>>,
+}
+
+impl InMemoryEventStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of events in the store.
+    pub fn len(&self) -> usize {
+        self.events.read().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn insert(&self, event: RDFEvent, stream_name: Option<&str>) {
+        let (event, stream) = {
+            let mut dict = self.dictionary.write().unwrap();
+            let encoded = event.encode(&mut dict);
+            (encoded, stream_name.map(|name| dict.encode(name)))
+        };
+        let mut events = self.events.write().unwrap();
+        let position = events.partition_point(|(stored, _)| stored.timestamp <= event.timestamp);
+        events.insert(position, (event, stream));
+    }
+
+    // Remove every event matching the dictionary ID of the given term key, if it has one.
+    fn remove_events(&self, key: &str, matches: impl Fn(&Event, u32) -> bool) {
+        let Some(id) = self.dictionary.read().unwrap().string_to_id.get(key).copied() else {
+            return;
+        };
+        self.events.write().unwrap().retain(|(event, _)| !matches(event, id));
+    }
+}
+
+impl EventStore for InMemoryEventStore {
+    fn write_rdf_event(&self, event: RDFEvent) -> io::Result<()> {
+        self.insert(event, None);
+        Ok(())
+    }
+
+    fn write_rdf_event_to_stream(&self, stream_name: &str, event: RDFEvent) -> io::Result<()> {
+        self.insert(event, Some(stream_name));
+        Ok(())
+    }
+
+    fn scan(
+        &self,
+        stream_name: Option<&str>,
+        start_timestamp: u64,
+        end_timestamp: u64,
+        patterns: Option<&[TriplePattern]>,
+    ) -> io::Result<QueryIter> {
+        let dict = self.dictionary.read().unwrap();
+        let events = self.events.read().unwrap();
+        let first = events.partition_point(|(event, _)| event.timestamp < start_timestamp);
+        Ok(select_events(
+            events[first..].iter().map(|(event, stream)| (event, *stream)),
+            &dict,
+            stream_name,
+            start_timestamp,
+            end_timestamp,
+            patterns,
+        ))
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn get_dictionary(&self) -> &Arc<RwLock<Dictionary>> {
+        &self.dictionary
+    }
+
+    fn delete_by_subject(&self, subject: &str) -> io::Result<()> {
+        self.remove_events(&RdfTerm::parse_resource(subject).dictionary_key(), |event, id| {
+            event.subject == id
+        });
+        Ok(())
+    }
+
+    fn delete_by_graph(&self, graph: &str) -> io::Result<()> {
+        self.remove_events(&RdfTerm::parse_graph(graph).dictionary_key(), |event, id| {
+            event.graph == id
+        });
+        Ok(())
+    }
+
+    fn component_sizes(&self) -> StorageComponentSizes {
+        let batch_buffer_bytes =
+            self.events.read().unwrap().capacity() * std::mem::size_of::<(Event, Option<u32>)>();
+        let dictionary_bytes = self.dictionary.read().unwrap().heap_size_bytes();
+        StorageComponentSizes {
+            batch_buffer_bytes,
+            dictionary_bytes,
+            estimated_total_bytes: batch_buffer_bytes + dictionary_bytes,
+            ..StorageComponentSizes::default()
+        }
+    }
+}
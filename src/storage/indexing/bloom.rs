@@ -0,0 +1,103 @@
+//! Per-segment Bloom filters over the subject and object dictionary IDs.
+//!
+//! A query for the events of one subject has to open every segment overlapping its
+//! range, even though most segments never mention that subject. Every segment is
+//! therefore written with a Bloom filter over the IDs of its subjects and objects,
+//! sized for `bloom_filter_false_positive_rate`. The filters are small enough to be
+//! kept in memory with the segment metadata, so a segment whose filter rules the
+//! subject out is skipped without any I/O.
+//!
+//! The filter is stored as a section of the `.idx` file after the rollups, located
+//! and checksummed through the footer:
+//!
+//! ```text
+//! [hash count: u32][word count: u32][bits: u64]...
+//! ```
+//!
+//! Bit positions are derived from a 64-bit mix of the ID by double hashing.
+
+const HEADER_SIZE: usize = 8;
+const MAX_HASH_COUNT: u32 = 16;
+
+/// A Bloom filter over dictionary IDs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BloomFilter {
+    hash_count: u32,
+    words: Vec<u64>,
+}
+
+impl BloomFilter {
+    /// An empty filter sized to hold `items` IDs with the given false positive rate.
+    pub fn with_capacity(items: usize, false_positive_rate: f64) -> Self {
+        let items = items.max(1) as f64;
+        let rate = false_positive_rate.clamp(f64::MIN_POSITIVE, 0.5);
+        let ln2 = std::f64::consts::LN_2;
+        let bits = (-items * rate.ln() / (ln2 * ln2)).ceil().max(64.0);
+        let hash_count = ((bits / items) * ln2).round().clamp(1.0, f64::from(MAX_HASH_COUNT));
+        Self { hash_count: hash_count as u32, words: vec![0; (bits as usize).div_ceil(64)] }
+    }
+
+    pub fn insert(&mut self, id: u32) {
+        for bit in self.bit_positions(id) {
+            self.words[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    /// Whether the ID may have been inserted. `false` means it certainly was not.
+    pub fn may_contain(&self, id: u32) -> bool {
+        self.bit_positions(id).all(|bit| self.words[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    /// Bytes the filter occupies in memory.
+    pub fn heap_size_bytes(&self) -> usize {
+        self.words.capacity() * std::mem::size_of::<u64>()
+    }
+
+    fn bit_positions(&self, id: u32) -> impl Iterator<Item = usize> {
+        let bit_count = (self.words.len() * 64) as u64;
+        let first = mix(u64::from(id));
+        let step = mix(first) | 1;
+        (0..u64::from(self.hash_count))
+            .map(move |i| (first.wrapping_add(i.wrapping_mul(step)) % bit_count) as usize)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(HEADER_SIZE + self.words.len() * 8);
+        buffer.extend_from_slice(&self.hash_count.to_le_bytes());
+        buffer.extend_from_slice(&(self.words.len() as u32).to_le_bytes());
+        for word in &self.words {
+            buffer.extend_from_slice(&word.to_le_bytes());
+        }
+        buffer
+    }
+
+    pub fn decode(buffer: &[u8]) -> std::io::Result<Self> {
+        let invalid = |reason: &str| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{} Bloom filter", reason))
+        };
+        if buffer.len() < HEADER_SIZE {
+            return Err(invalid("Truncated"));
+        }
+        let hash_count = u32::from_le_bytes(buffer[0..4].try_into().unwrap());
+        let word_count = u32::from_le_bytes(buffer[4..8].try_into().unwrap()) as usize;
+        if buffer.len() != HEADER_SIZE + word_count * 8 {
+            return Err(invalid("Truncated"));
+        }
+        if word_count == 0 || hash_count == 0 || hash_count > MAX_HASH_COUNT {
+            return Err(invalid("Malformed"));
+        }
+        let words = buffer[HEADER_SIZE..]
+            .chunks_exact(8)
+            .map(|chunk| u64::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+        Ok(Self { hash_count, words })
+    }
+}
+
+// The splitmix64 finalizer, which spreads consecutive IDs over the whole range.
+fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
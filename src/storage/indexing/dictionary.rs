@@ -6,9 +6,9 @@ use std::path::Path;
 use bincode;
 use serde::{Deserialize, Serialize};
 
-use crate::core::Event;
+use crate::core::{Event, RdfTerm};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Dictionary {
     pub string_to_id: HashMap<String, u32>,
     pub id_to_uri: HashMap<u32, String>,
@@ -32,14 +32,63 @@ impl Dictionary {
         }
     }
 
+    /// Insert an entry with a known ID, e.g. when replaying the write-ahead log.
+    /// Existing entries are left untouched.
+    pub fn insert(&mut self, id: u32, value: &str) {
+        if self.id_to_uri.contains_key(&id) {
+            return;
+        }
+        self.string_to_id.insert(value.to_string(), id);
+        self.id_to_uri.insert(id, value.to_string());
+        self.next_id = self.next_id.max(id + 1);
+    }
+
+    /// Remove an entry, e.g. once no stored event refers to it any more. Its ID is
+    /// never handed out again.
+    pub fn remove(&mut self, id: u32) -> Option<String> {
+        let value = self.id_to_uri.remove(&id)?;
+        self.string_to_id.remove(&value);
+        Some(value)
+    }
+
     pub fn decode(&self, id: u32) -> Option<&str> {
         self.id_to_uri.get(&id).map(|s| s.as_str())
     }
 
+    /// Encode a typed term under its dictionary key, which keeps the kind of the term
+    /// and the datatype or language of a literal.
+    pub fn encode_term(&mut self, term: &RdfTerm) -> u32 {
+        self.encode(&term.dictionary_key())
+    }
+
+    /// Decode the term of a subject, predicate or object.
+    pub fn decode_term(&self, id: u32) -> Option<RdfTerm> {
+        self.decode(id).map(RdfTerm::from_dictionary_key)
+    }
+
+    /// Decode the term of a graph, where an empty key, or `default` as stored before
+    /// terms were typed, is the default graph.
+    pub fn decode_graph_term(&self, id: u32) -> Option<RdfTerm> {
+        self.decode(id).map(|key| match key {
+            "" | "default" => RdfTerm::DefaultGraph,
+            key => RdfTerm::from_dictionary_key(key),
+        })
+    }
+
     pub fn size(&self) -> usize {
         self.string_to_id.len()
     }
 
+    /// Estimated heap bytes held by both maps: the string buffers plus the hash table slots,
+    /// each with one control byte.
+    pub fn heap_size_bytes(&self) -> usize {
+        let strings: usize = self.string_to_id.keys().map(String::capacity).sum::<usize>()
+            + self.id_to_uri.values().map(String::capacity).sum::<usize>();
+        let tables = self.string_to_id.capacity() * (std::mem::size_of::<(String, u32)>() + 1)
+            + self.id_to_uri.capacity() * (std::mem::size_of::<(u32, String)>() + 1);
+        strings + tables
+    }
+
     pub fn save_to_file(&self, path: &Path) -> std::io::Result<()> {
         let encoded = bincode::serialize(self)
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
@@ -0,0 +1,220 @@
+//! Per-segment secondary indexes over subjects and predicates.
+//!
+//! The sparse index only narrows a query down by time. A secondary index maps
+//! every dictionary-encoded predicate and subject of a segment to the posting
+//! list of data blocks that contain it, so a query restricted to a few constant
+//! terms only reads the blocks that can hold a match.
+//!
+//! The index is stored as a section of the `.idx` file between the index blocks
+//! and the footer, which records its location and checksum:
+//!
+//! ```text
+//! [predicate count: u32]([predicate id: u32][block count: u32][block: u32]...)...
+//! [subject count: u32]([subject id: u32][block count: u32][block: u32]...)...
+//! ```
+//!
+//! Blocks are numbered in index entry order across the whole segment.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::core::Event;
+use crate::storage::indexing::dictionary::Dictionary;
+
+/// Constant terms an event has to match; `None` matches any term.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TriplePattern {
+    pub subject: Option<String>,
+    pub predicate: Option<String>,
+    /// Range the numeric object of a matching event has to lie within to be of any use.
+    /// Events are not filtered on it, but segments whose values of the predicate all lie
+    /// outside it are skipped, see [`crate::storage::indexing::zone_map`].
+    pub object_range: Option<ValueRange>,
+}
+
+impl TriplePattern {
+    pub fn new(subject: Option<&str>, predicate: Option<&str>) -> Self {
+        Self {
+            subject: subject.map(str::to_string),
+            predicate: predicate.map(str::to_string),
+            object_range: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_object_range(mut self, range: ValueRange) -> Self {
+        self.object_range = Some(range);
+        self
+    }
+}
+
+/// Inclusive bounds on a numeric value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ValueRange {
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Default for ValueRange {
+    fn default() -> Self {
+        Self { min: f64::NEG_INFINITY, max: f64::INFINITY }
+    }
+}
+
+impl ValueRange {
+    pub fn new(min: f64, max: f64) -> Self {
+        Self { min, max }
+    }
+
+    /// The values within both ranges.
+    #[must_use]
+    pub fn intersect(&self, other: &Self) -> Self {
+        Self { min: self.min.max(other.min), max: self.max.min(other.max) }
+    }
+
+    /// Whether some value between `min` and `max` lies within the range.
+    pub fn overlaps(&self, min: f64, max: f64) -> bool {
+        min <= self.max && max >= self.min
+    }
+}
+
+/// A [`TriplePattern`] with its terms resolved to dictionary IDs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EncodedPattern {
+    pub subject: Option<u32>,
+    pub predicate: Option<u32>,
+    pub object_range: Option<ValueRange>,
+}
+
+impl EncodedPattern {
+    pub fn matches(&self, event: &Event) -> bool {
+        self.subject.is_none_or(|subject| subject == event.subject)
+            && self.predicate.is_none_or(|predicate| predicate == event.predicate)
+    }
+}
+
+/// Events matching any of a set of patterns.
+#[derive(Debug, Clone, Default)]
+pub struct EventFilter {
+    pub patterns: Vec<EncodedPattern>,
+}
+
+impl EventFilter {
+    /// Resolve the patterns against the dictionary.
+    ///
+    /// Patterns naming a term that was never stored cannot match and are dropped,
+    /// so the filter may end up matching nothing at all.
+    pub fn resolve(patterns: &[TriplePattern], dictionary: &Dictionary) -> Self {
+        let lookup = |term: &Option<String>| match term {
+            Some(term) => dictionary.string_to_id.get(term).copied().map(Some),
+            None => Some(None),
+        };
+
+        let patterns = patterns
+            .iter()
+            .filter_map(|pattern| {
+                Some(EncodedPattern {
+                    subject: lookup(&pattern.subject)?,
+                    predicate: lookup(&pattern.predicate)?,
+                    object_range: pattern.object_range,
+                })
+            })
+            .collect();
+        Self { patterns }
+    }
+
+    pub fn matches(&self, event: &Event) -> bool {
+        self.patterns.iter().any(|pattern| pattern.matches(event))
+    }
+
+    /// Whether some pattern has no constant term and therefore matches every event.
+    pub fn matches_all(&self) -> bool {
+        self.patterns
+            .iter()
+            .any(|pattern| pattern.subject.is_none() && pattern.predicate.is_none())
+    }
+}
+
+/// Posting lists of the data blocks holding each predicate and subject of a segment.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SecondaryIndex {
+    pub predicates: BTreeMap<u32, Vec<u32>>,
+    pub subjects: BTreeMap<u32, Vec<u32>>,
+}
+
+impl SecondaryIndex {
+    /// Record that `event` is stored in data block `block`. Blocks must be added in order.
+    pub fn add(&mut self, block: u32, event: &Event) {
+        for (postings, id) in
+            [(&mut self.predicates, event.predicate), (&mut self.subjects, event.subject)]
+        {
+            let blocks = postings.entry(id).or_default();
+            if blocks.last() != Some(&block) {
+                blocks.push(block);
+            }
+        }
+    }
+
+    /// Data blocks that may hold an event matching the filter, or `None` when every
+    /// block has to be read.
+    pub fn matching_blocks(&self, filter: &EventFilter) -> Option<BTreeSet<u32>> {
+        if filter.matches_all() {
+            return None;
+        }
+
+        let mut blocks = BTreeSet::new();
+        for pattern in &filter.patterns {
+            let by_predicate = pattern.predicate.map(|id| Self::postings(&self.predicates, id));
+            let by_subject = pattern.subject.map(|id| Self::postings(&self.subjects, id));
+            match (by_predicate, by_subject) {
+                (Some(predicate), Some(subject)) => {
+                    let subject: BTreeSet<u32> = subject.iter().copied().collect();
+                    blocks.extend(predicate.iter().filter(|block| subject.contains(block)));
+                }
+                (Some(only), None) | (None, Some(only)) => blocks.extend(only.iter().copied()),
+                (None, None) => unreachable!("patterns without constants match every block"),
+            }
+        }
+        Some(blocks)
+    }
+
+    fn postings(postings: &BTreeMap<u32, Vec<u32>>, id: u32) -> &[u32] {
+        postings.get(&id).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        for postings in [&self.predicates, &self.subjects] {
+            buffer.extend_from_slice(&(postings.len() as u32).to_le_bytes());
+            for (id, blocks) in postings {
+                buffer.extend_from_slice(&id.to_le_bytes());
+                buffer.extend_from_slice(&(blocks.len() as u32).to_le_bytes());
+                for block in blocks {
+                    buffer.extend_from_slice(&block.to_le_bytes());
+                }
+            }
+        }
+        buffer
+    }
+
+    pub fn decode(buffer: &[u8]) -> std::io::Result<Self> {
+        let mut words = buffer
+            .chunks_exact(4)
+            .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()));
+        let mut next = || {
+            words.next().ok_or_else(|| {
+                std::io::Error::new(std::io::ErrorKind::InvalidData, "Truncated secondary index")
+            })
+        };
+
+        let mut index = Self::default();
+        for postings in [&mut index.predicates, &mut index.subjects] {
+            for _ in 0..next()? {
+                let id = next()?;
+                let count = next()?;
+                let blocks = (0..count).map(|_| next()).collect::<std::io::Result<Vec<u32>>>()?;
+                postings.insert(id, blocks);
+            }
+        }
+        Ok(index)
+    }
+}
@@ -0,0 +1,114 @@
+//! Per-segment statistics of the numeric values of every predicate.
+//!
+//! Anomaly queries mostly keep the readings of one predicate beyond a threshold,
+//! such as `FILTER(?v > 30)`, and most segments of a long range hold no such
+//! reading. Every segment is therefore written with a zone map holding, for each
+//! predicate, the number of its events and the minimum and maximum of the numeric
+//! ones. The maps are kept in memory with the segment metadata, so a segment whose
+//! values of the predicate all lie outside a pattern's [`ValueRange`] is skipped
+//! without any I/O.
+//!
+//! The zone map is stored as a section of the `.idx` file after the Bloom filter,
+//! located and checksummed through the footer:
+//!
+//! ```text
+//! [predicate count: u32]
+//! ([predicate id: u32][count: u64][numeric count: u64][sum: f64][min: f64][max: f64])...
+//! ```
+//!
+//! Values are read like those of the rollups: only literals with an XSD numeric
+//! datatype and a finite value are numbers, see [`numeric_value`]. A segment with
+//! any other value of the predicate, such as `"35"^^xsd:string`, `"12"@en` or a
+//! `NaN`, is never skipped on its values, since the query may still compare it with
+//! the range in its own way.
+//!
+//! [`numeric_value`]: crate::storage::rollup::numeric_value
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::core::Event;
+use crate::storage::indexing::dictionary::Dictionary;
+use crate::storage::indexing::secondary::ValueRange;
+use crate::storage::rollup::{numeric_value, RollupStats};
+
+const ROW_SIZE: usize = 44;
+
+/// Statistics of the values of each predicate of a segment.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ZoneMap {
+    pub predicates: BTreeMap<u32, RollupStats>,
+}
+
+impl ZoneMap {
+    /// Account for the events, resolving their objects against the dictionary.
+    pub fn add_events<'a>(
+        &mut self,
+        events: impl IntoIterator<Item = &'a Event>,
+        dictionary: &Dictionary,
+    ) {
+        let mut values: HashMap<u32, Option<f64>> = HashMap::new();
+        for event in events {
+            let value = *values.entry(event.object).or_insert_with(|| {
+                dictionary.decode_term(event.object).as_ref().and_then(numeric_value)
+            });
+            self.predicates.entry(event.predicate).or_default().add(value);
+        }
+    }
+
+    /// Whether no event of the predicate can have a value within the range, because
+    /// the segment holds none of its events or only numbers outside the range.
+    pub fn rules_out(&self, predicate: u32, range: &ValueRange) -> bool {
+        self.predicates
+            .get(&predicate)
+            .is_none_or(|stats| stats.all_numeric() && !range.overlaps(stats.min, stats.max))
+    }
+
+    /// Bytes the zone map occupies in memory, roughly.
+    pub fn heap_size_bytes(&self) -> usize {
+        self.predicates.len() * (std::mem::size_of::<u32>() + std::mem::size_of::<RollupStats>())
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(4 + self.predicates.len() * ROW_SIZE);
+        buffer.extend_from_slice(&(self.predicates.len() as u32).to_le_bytes());
+        for (predicate, stats) in &self.predicates {
+            buffer.extend_from_slice(&predicate.to_le_bytes());
+            buffer.extend_from_slice(&stats.count.to_le_bytes());
+            buffer.extend_from_slice(&stats.numeric_count.to_le_bytes());
+            for value in [stats.sum, stats.min, stats.max] {
+                buffer.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        buffer
+    }
+
+    pub fn decode(buffer: &[u8]) -> std::io::Result<Self> {
+        let truncated =
+            || std::io::Error::new(std::io::ErrorKind::InvalidData, "Truncated zone map section");
+        if buffer.len() < 4 {
+            return Err(truncated());
+        }
+        let u64_at =
+            |offset: usize| u64::from_le_bytes(buffer[offset..offset + 8].try_into().unwrap());
+        let u32_at =
+            |offset: usize| u32::from_le_bytes(buffer[offset..offset + 4].try_into().unwrap());
+
+        let predicate_count = u32_at(0) as usize;
+        if buffer.len() != 4 + predicate_count * ROW_SIZE {
+            return Err(truncated());
+        }
+        let mut zone_map = Self::default();
+        for row in 0..predicate_count {
+            let offset = 4 + row * ROW_SIZE;
+            let stats = RollupStats {
+                count: u64_at(offset + 4),
+                numeric_count: u64_at(offset + 12),
+                sum: f64::from_bits(u64_at(offset + 20)),
+                min: f64::from_bits(u64_at(offset + 28)),
+                max: f64::from_bits(u64_at(offset + 36)),
+            };
+            zone_map.predicates.insert(u32_at(offset), stats);
+        }
+        Ok(zone_map)
+    }
+}
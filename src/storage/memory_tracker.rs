@@ -1,14 +1,30 @@
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
 
-/// Memory usage tracker for benchmarking purposes
+use serde::Serialize;
+
+/// Memory usage tracker for benchmarking purposes.
+///
+/// The storage also uses it to account for the bytes of events buffered in memory
+/// against its memory budget. Clones share their state, so producers such as the
+/// stream bus can watch the budget fill up and slow down before writes block.
 #[derive(Debug, Clone)]
 pub struct MemoryTracker {
     peak_memory_bytes: Arc<AtomicUsize>,
     current_memory_bytes: Arc<AtomicUsize>,
     measurements: Arc<std::sync::Mutex<Vec<MemoryMeasurement>>>,
+    budget_bytes: Option<usize>,
+    buffered_bytes: Arc<AtomicUsize>,
+    // Signalled whenever buffered bytes are released.
+    released: Arc<(Mutex<()>, Condvar)>,
 }
 
+/// Share of the memory budget above which producers are asked to slow down.
+const THROTTLE_THRESHOLD: f64 = 0.5;
+/// Delay asked of producers when the memory budget is exhausted.
+const MAX_THROTTLE_DELAY: Duration = Duration::from_millis(50);
+
 #[derive(Debug, Clone)]
 pub struct MemoryMeasurement {
     pub timestamp: std::time::Instant,
@@ -25,15 +41,153 @@ pub struct MemoryStats {
     pub measurements: Vec<MemoryMeasurement>,
 }
 
+/// Memory usage of the running process as reported by the kernel.
+///
+/// The counters of `/proc/self/statm` are always present; the ones of
+/// `/proc/self/smaps_rollup` are `None` on kernels older than 4.14.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ProcessMemory {
+    pub virtual_bytes: usize,
+    pub resident_bytes: usize,
+    /// Resident pages backed by files, which includes memory-mapped segments.
+    pub shared_bytes: usize,
+    /// Data and stack pages, resident or not.
+    pub data_bytes: usize,
+    /// Resident bytes with shared pages split among the processes mapping them.
+    pub proportional_bytes: Option<usize>,
+    pub anonymous_bytes: Option<usize>,
+    pub swap_bytes: Option<usize>,
+}
+
+impl ProcessMemory {
+    /// Read the memory usage of the current process; `None` outside of Linux.
+    pub fn current() -> Option<Self> {
+        #[cfg(target_os = "linux")]
+        {
+            let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+            let smaps_rollup = std::fs::read_to_string("/proc/self/smaps_rollup").ok();
+            Self::from_proc(&statm, smaps_rollup.as_deref(), page_size())
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            None
+        }
+    }
+
+    /// Build the usage from the contents of `statm` and `smaps_rollup`.
+    ///
+    /// `statm` counts pages; `smaps_rollup` reports kilobytes per field.
+    pub fn from_proc(statm: &str, smaps_rollup: Option<&str>, page_size: usize) -> Option<Self> {
+        let pages = statm
+            .split_whitespace()
+            .map(str::parse::<usize>)
+            .collect::<Result<Vec<_>, _>>()
+            .ok()?;
+        // size resident shared text lib data dt
+        if pages.len() < 6 {
+            return None;
+        }
+
+        let rollup_field = |name: &str| {
+            smaps_rollup?.lines().find_map(|line| {
+                let value = line.strip_prefix(name)?.strip_prefix(':')?;
+                let kilobytes = value.split_whitespace().next()?.parse::<usize>().ok()?;
+                Some(kilobytes * 1024)
+            })
+        };
+
+        Some(Self {
+            virtual_bytes: pages[0] * page_size,
+            resident_bytes: pages[1] * page_size,
+            shared_bytes: pages[2] * page_size,
+            data_bytes: pages[5] * page_size,
+            proportional_bytes: rollup_field("Pss"),
+            anonymous_bytes: rollup_field("Anonymous"),
+            swap_bytes: rollup_field("Swap"),
+        })
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn page_size() -> usize {
+    // SAFETY: sysconf has no preconditions and only reads system configuration.
+    let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    usize::try_from(size).ok().filter(|&size| size > 0).unwrap_or(4096)
+}
+
 impl MemoryTracker {
     pub fn new() -> Self {
+        Self::with_budget(None)
+    }
+
+    /// Create a tracker that accounts buffered bytes against a budget; `None` is unbounded.
+    pub fn with_budget(budget_bytes: Option<usize>) -> Self {
         Self {
             peak_memory_bytes: Arc::new(AtomicUsize::new(0)),
             current_memory_bytes: Arc::new(AtomicUsize::new(0)),
             measurements: Arc::new(std::sync::Mutex::new(Vec::new())),
+            budget_bytes,
+            buffered_bytes: Arc::new(AtomicUsize::new(0)),
+            released: Arc::new((Mutex::new(()), Condvar::new())),
         }
     }
 
+    /// The memory budget for buffered events, if there is one.
+    pub fn budget_bytes(&self) -> Option<usize> {
+        self.budget_bytes
+    }
+
+    /// Bytes of events currently buffered in memory.
+    pub fn buffered_bytes(&self) -> usize {
+        self.buffered_bytes.load(Ordering::Acquire)
+    }
+
+    /// Update the buffered bytes, waking up writers waiting for room when they went down.
+    pub fn set_buffered_bytes(&self, bytes: usize) {
+        let previous = self.buffered_bytes.swap(bytes, Ordering::AcqRel);
+        if bytes < previous {
+            let (lock, released) = &*self.released;
+            let _guard = lock.lock().unwrap();
+            released.notify_all();
+        }
+    }
+
+    /// Whether `additional_bytes` more can be buffered without exceeding the budget.
+    pub fn has_room_for(&self, additional_bytes: usize) -> bool {
+        self.budget_bytes
+            .is_none_or(|budget| self.buffered_bytes().saturating_add(additional_bytes) <= budget)
+    }
+
+    /// Share of the budget in use, from 0.0 up; always 0.0 without a budget.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn budget_usage(&self) -> f64 {
+        match self.budget_bytes {
+            Some(budget) if budget > 0 => self.buffered_bytes() as f64 / budget as f64,
+            Some(_) => 1.0,
+            None => 0.0,
+        }
+    }
+
+    /// How long a producer should pause before its next write.
+    ///
+    /// Returns `None` while less than half of the budget is in use, and a delay growing
+    /// up to 50 ms as the buffered bytes approach the budget.
+    pub fn throttle_delay(&self) -> Option<Duration> {
+        let usage = self.budget_usage();
+        if usage < THROTTLE_THRESHOLD {
+            return None;
+        }
+        let pressure = ((usage - THROTTLE_THRESHOLD) / (1.0 - THROTTLE_THRESHOLD)).min(1.0);
+        Some(MAX_THROTTLE_DELAY.mul_f64(pressure).max(Duration::from_millis(1)))
+    }
+
+    /// Wait until buffered bytes are released or the timeout expires.
+    pub fn wait_for_release(&self, timeout: Duration) {
+        let (lock, released) = &*self.released;
+        let guard = lock.lock().unwrap();
+        let _ = released.wait_timeout(guard, timeout).unwrap();
+    }
+
     /// Record current memory usage with a description
     pub fn record(&self, description: &str) {
         let current = self.estimate_current_memory();
@@ -199,21 +353,7 @@ impl MemoryTracker {
 
     #[cfg(target_os = "linux")]
     fn get_memory_linux(&self) -> usize {
-        use std::fs;
-
-        if let Ok(contents) = fs::read_to_string("/proc/self/status") {
-            for line in contents.lines() {
-                if line.starts_with("VmRSS:") {
-                    let parts: Vec<&str> = line.split_whitespace().collect();
-                    if parts.len() >= 2 {
-                        if let Ok(kb) = parts[1].parse::<usize>() {
-                            return kb * 1024; // Convert KB to bytes
-                        }
-                    }
-                }
-            }
-        }
-        0
+        ProcessMemory::current().map_or(0, |memory| memory.resident_bytes)
     }
 
     /// Format bytes in human-readable format
@@ -0,0 +1,79 @@
+//! Memory-mapped access to segment data files.
+//!
+//! Dashboards re-query the same recent segments over and over. Instead of opening,
+//! seeking and reading a data file on every query, the read path maps it once and
+//! decodes records straight from the mapped bytes. The [`MmapCache`] keeps the most
+//! recently used mappings open, up to `mmap_cache_capacity` of them.
+//!
+//! Mapping a file is an optimization only: when it fails, or the cache is disabled
+//! with a capacity of zero, segments are read through regular file IO.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::sync::{Arc, Mutex};
+
+use memmap2::Mmap;
+
+/// Least-recently-used cache of memory-mapped segment data files.
+pub struct MmapCache {
+    capacity: usize,
+    // Most recently used mapping first.
+    mappings: Mutex<VecDeque<(String, Arc<Mmap>)>>,
+}
+
+impl MmapCache {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, mappings: Mutex::new(VecDeque::with_capacity(capacity)) }
+    }
+
+    /// The mapping of the data file at `path`, mapping it if it is not cached yet.
+    ///
+    /// Returns `None` when the cache is disabled or the file cannot be mapped, in which
+    /// case the caller falls back to file IO.
+    pub fn get(&self, path: &str) -> Option<Arc<Mmap>> {
+        if self.capacity == 0 {
+            return None;
+        }
+        let len = std::fs::metadata(path).ok()?.len();
+
+        let mut mappings = self.mappings.lock().unwrap();
+        if let Some(position) = mappings.iter().position(|(cached, _)| cached == path) {
+            let (cached, mapping) = mappings.remove(position).unwrap();
+            // A file of a different length is not the one that was mapped.
+            if mapping.len() as u64 == len {
+                mappings.push_front((cached, Arc::clone(&mapping)));
+                return Some(mapping);
+            }
+        }
+
+        let file = File::open(path).ok()?;
+        // SAFETY: segment data files are written to a temporary file and renamed into place,
+        // and are never modified afterwards; a replaced segment gets a new file.
+        let mapping = Arc::new(unsafe { Mmap::map(&file) }.ok()?);
+        mappings.push_front((path.to_string(), Arc::clone(&mapping)));
+        mappings.truncate(self.capacity);
+        Some(mapping)
+    }
+
+    /// Drop the cached mapping of a data file that was deleted or replaced, so that it does
+    /// not keep the file's disk space in use.
+    ///
+    /// Queries that are still reading the file keep their own reference to the mapping.
+    pub fn evict(&self, path: &str) {
+        self.mappings.lock().unwrap().retain(|(cached, _)| cached != path);
+    }
+
+    /// Number of data files currently mapped by the cache.
+    pub fn len(&self) -> usize {
+        self.mappings.lock().unwrap().len()
+    }
+
+    /// Total length of the mapped data files.
+    pub fn mapped_bytes(&self) -> usize {
+        self.mappings.lock().unwrap().iter().map(|(_, mapping)| mapping.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
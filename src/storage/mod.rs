@@ -1,8 +1,27 @@
+pub mod archive;
+pub mod codec;
+pub mod compaction;
+pub mod dictionary_log;
+pub mod erasure;
+pub mod event_store;
+pub mod event_time;
+pub mod in_memory_store;
 pub mod memory_tracker;
+pub mod mmap_cache;
+pub mod oxigraph_store;
+pub mod query_iter;
+pub mod rollup;
+pub mod scan_pool;
+pub mod segment_format;
 pub mod segmented_storage;
+pub mod snapshot;
 pub mod util;
+pub mod wal;
 pub mod indexing {
+    pub mod bloom;
     pub mod dense;
     pub mod dictionary;
+    pub mod secondary;
     pub mod sparse;
+    pub mod zone_map;
 }
@@ -0,0 +1,318 @@
+//! An [`EventStore`] backed by an Oxigraph store.
+//!
+//! Each event's quad is inserted into its own graph, so the store can also be queried
+//! as a regular RDF dataset. Oxigraph quads carry no time, so every event is described
+//! as well in the [`EVENTS_GRAPH`] graph by a resource holding its timestamp, terms and
+//! stream:
+//!
+//! ```text
+//! <urn:janus:event:00000000000000000042> <urn:janus:timestamp> "1000"^^xsd:unsignedLong ;
+//!     <urn:janus:subject> <http://example.org/sensor1> ;
+//!     <urn:janus:predicate> <http://example.org/temperature> ;
+//!     <urn:janus:object> "21.5"^^xsd:double ;
+//!     <urn:janus:graph> <http://example.org/graph1> ;
+//!     <urn:janus:stream> "http://example.org/stream1" .
+//! ```
+//!
+//! The graph is omitted for the default graph and the stream for events written without
+//! one. Queries scan the timestamps of the events graph, so this backend trades query
+//! speed for keeping the events in a standard RDF database. The store lives in memory
+//! unless it is opened from a directory with the `rocksdb` feature.
+//!
+//! Deleting a subject or a graph removes the matching quads and event descriptions
+//! right away, so there is nothing left to purge afterwards.
+
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use oxigraph::model::vocab::xsd;
+use oxigraph::model::{
+    GraphName, GraphNameRef, Literal, NamedNode, NamedNodeRef, NamedOrBlankNode, Quad, Term,
+};
+use oxigraph::store::Store;
+
+use crate::core::{Event, RDFEvent, RdfTerm};
+use crate::storage::event_store::{select_events, EventStore};
+use crate::storage::indexing::dictionary::Dictionary;
+use crate::storage::indexing::secondary::TriplePattern;
+use crate::storage::query_iter::QueryIter;
+
+/// Graph holding the description of every stored event.
+pub const EVENTS_GRAPH: &str = "urn:janus:events";
+
+const EVENT_PREFIX: &str = "urn:janus:event:";
+const TIMESTAMP: NamedNodeRef<'static> = NamedNodeRef::new_unchecked("urn:janus:timestamp");
+const SUBJECT: NamedNodeRef<'static> = NamedNodeRef::new_unchecked("urn:janus:subject");
+const PREDICATE: NamedNodeRef<'static> = NamedNodeRef::new_unchecked("urn:janus:predicate");
+const OBJECT: NamedNodeRef<'static> = NamedNodeRef::new_unchecked("urn:janus:object");
+const GRAPH: NamedNodeRef<'static> = NamedNodeRef::new_unchecked("urn:janus:graph");
+const STREAM: NamedNodeRef<'static> = NamedNodeRef::new_unchecked("urn:janus:stream");
+
+/// Events kept in an Oxigraph store.
+pub struct OxigraphEventStore {
+    store: Store,
+    events_graph: NamedNode,
+    // Sequence number of the next event, which orders events with equal timestamps.
+    next_sequence: AtomicU64,
+    dictionary: Arc<RwLock<Dictionary>>,
+}
+
+impl OxigraphEventStore {
+    /// Keep the events in an Oxigraph store living in memory.
+    pub fn new() -> io::Result<Self> {
+        Self::with_store(Store::new().map_err(io::Error::other)?)
+    }
+
+    /// Keep the events in an Oxigraph store persisted in RocksDB at `path`, picking up the
+    /// events already stored there.
+    #[cfg(feature = "rocksdb")]
+    pub fn open(path: impl AsRef<std::path::Path>) -> io::Result<Self> {
+        Self::with_store(Store::open(path).map_err(io::Error::other)?)
+    }
+
+    /// Keep the events in an existing Oxigraph store.
+    pub fn with_store(store: Store) -> io::Result<Self> {
+        let events_graph = NamedNode::new_unchecked(EVENTS_GRAPH);
+        let mut next_sequence = 0;
+        for quad in
+            store.quads_for_pattern(None, Some(TIMESTAMP), None, Some(events_graph.as_ref().into()))
+        {
+            let quad = quad.map_err(io::Error::other)?;
+            if let Some(sequence) = event_sequence(&quad.subject) {
+                next_sequence = next_sequence.max(sequence + 1);
+            }
+        }
+
+        Ok(Self {
+            store,
+            events_graph,
+            next_sequence: AtomicU64::new(next_sequence),
+            dictionary: Arc::new(RwLock::new(Dictionary::new())),
+        })
+    }
+
+    /// The underlying Oxigraph store.
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+
+    fn insert(&self, event: &RDFEvent, stream_name: Option<&str>) -> io::Result<()> {
+        let quad = event.to_quad().map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let sequence = self.next_sequence.fetch_add(1, Ordering::Relaxed);
+        let description = describe_event(sequence, &quad, event.timestamp, stream_name);
+
+        let mut transaction = self.store.start_transaction().map_err(io::Error::other)?;
+        transaction.insert(&quad);
+        for description in &description {
+            transaction.insert(description);
+        }
+        transaction.commit().map_err(io::Error::other)
+    }
+
+    // Read the description of an event back into its quad and stream.
+    fn read_event(
+        &self,
+        resource: &NamedOrBlankNode,
+    ) -> io::Result<Option<(Quad, Option<String>)>> {
+        let events_graph: GraphNameRef<'_> = self.events_graph.as_ref().into();
+        let mut properties = Vec::new();
+        for quad in
+            self.store
+                .quads_for_pattern(Some(resource.as_ref()), None, None, Some(events_graph))
+        {
+            let quad = quad.map_err(io::Error::other)?;
+            properties.push((quad.predicate, quad.object));
+        }
+        Ok(read_description(properties).map(|event| (event.quad, event.stream)))
+    }
+
+    // Remove every event whose quad matches, together with its description.
+    fn remove_events(&self, matches: impl Fn(&RDFEvent) -> bool) -> io::Result<()> {
+        let events_graph: GraphNameRef<'_> = self.events_graph.as_ref().into();
+        let mut removed = Vec::new();
+        for quad in self.store.quads_for_pattern(None, Some(TIMESTAMP), None, Some(events_graph)) {
+            let quad = quad.map_err(io::Error::other)?;
+            if let Some((event_quad, _)) = self.read_event(&quad.subject)? {
+                if matches(&RDFEvent::from_quad(0, &event_quad)) {
+                    removed.push((quad.subject, event_quad));
+                }
+            }
+        }
+
+        let mut transaction = self.store.start_transaction().map_err(io::Error::other)?;
+        for (resource, event_quad) in &removed {
+            transaction.remove(event_quad);
+            for description in self.store.quads_for_pattern(
+                Some(resource.as_ref()),
+                None,
+                None,
+                Some(events_graph),
+            ) {
+                transaction.remove(&description.map_err(io::Error::other)?);
+            }
+        }
+        transaction.commit().map_err(io::Error::other)
+    }
+}
+
+/// An event rebuilt from its description in the events graph.
+pub(crate) struct DescribedEvent {
+    pub timestamp: Option<u64>,
+    pub quad: Quad,
+    pub stream: Option<String>,
+}
+
+/// The quads describing an event in the events graph, under the resource of its sequence
+/// number.
+pub(crate) fn describe_event(
+    sequence: u64,
+    quad: &Quad,
+    timestamp: u64,
+    stream_name: Option<&str>,
+) -> Vec<Quad> {
+    let resource = NamedNode::new_unchecked(format!("{}{:020}", EVENT_PREFIX, sequence));
+    let timestamp = Literal::new_typed_literal(timestamp.to_string(), xsd::UNSIGNED_LONG);
+
+    let mut description = vec![
+        (TIMESTAMP, Term::from(timestamp)),
+        (SUBJECT, Term::from(quad.subject.clone())),
+        (PREDICATE, Term::from(quad.predicate.clone())),
+        (OBJECT, quad.object.clone()),
+    ];
+    match &quad.graph_name {
+        GraphName::NamedNode(graph) => description.push((GRAPH, graph.clone().into())),
+        GraphName::BlankNode(graph) => description.push((GRAPH, graph.clone().into())),
+        GraphName::DefaultGraph => {}
+    }
+    if let Some(stream_name) = stream_name {
+        description.push((STREAM, Literal::new_simple_literal(stream_name).into()));
+    }
+
+    let events_graph = NamedNode::new_unchecked(EVENTS_GRAPH);
+    description
+        .into_iter()
+        .map(|(property, value)| Quad::new(resource.clone(), property, value, events_graph.clone()))
+        .collect()
+}
+
+/// Rebuild an event from the properties of its description, or `None` when its subject,
+/// predicate or object is missing.
+pub(crate) fn read_description(
+    properties: impl IntoIterator<Item = (NamedNode, Term)>,
+) -> Option<DescribedEvent> {
+    let mut timestamp = None;
+    let mut subject: Option<NamedOrBlankNode> = None;
+    let mut predicate: Option<NamedNode> = None;
+    let mut object: Option<Term> = None;
+    let mut graph = GraphName::DefaultGraph;
+    let mut stream = None;
+    for (property, value) in properties {
+        let property = property.as_ref();
+        match value {
+            Term::Literal(literal) if property == TIMESTAMP => {
+                timestamp = literal.value().parse().ok();
+            }
+            Term::NamedNode(node) if property == SUBJECT => subject = Some(node.into()),
+            Term::BlankNode(node) if property == SUBJECT => subject = Some(node.into()),
+            Term::NamedNode(node) if property == PREDICATE => predicate = Some(node),
+            Term::NamedNode(node) if property == GRAPH => graph = node.into(),
+            Term::BlankNode(node) if property == GRAPH => graph = node.into(),
+            Term::Literal(literal) if property == STREAM => {
+                stream = Some(literal.value().to_string());
+            }
+            term if property == OBJECT => object = Some(term),
+            _ => {}
+        }
+    }
+
+    Some(DescribedEvent {
+        timestamp,
+        quad: Quad::new(subject?, predicate?, object?, graph),
+        stream,
+    })
+}
+
+// Sequence number of an event resource, parsed from its IRI.
+fn event_sequence(resource: &NamedOrBlankNode) -> Option<u64> {
+    match resource {
+        NamedOrBlankNode::NamedNode(node) => node.as_str().strip_prefix(EVENT_PREFIX)?.parse().ok(),
+        NamedOrBlankNode::BlankNode(_) => None,
+    }
+}
+
+impl EventStore for OxigraphEventStore {
+    fn write_rdf_event(&self, event: RDFEvent) -> io::Result<()> {
+        self.insert(&event, None)
+    }
+
+    fn write_rdf_event_to_stream(&self, stream_name: &str, event: RDFEvent) -> io::Result<()> {
+        self.insert(&event, Some(stream_name))
+    }
+
+    fn scan(
+        &self,
+        stream_name: Option<&str>,
+        start_timestamp: u64,
+        end_timestamp: u64,
+        patterns: Option<&[TriplePattern]>,
+    ) -> io::Result<QueryIter> {
+        let events_graph: GraphNameRef<'_> = self.events_graph.as_ref().into();
+        let mut found = Vec::new();
+        for quad in self.store.quads_for_pattern(None, Some(TIMESTAMP), None, Some(events_graph)) {
+            let quad = quad.map_err(io::Error::other)?;
+            let timestamp = match &quad.object {
+                Term::Literal(literal) => literal.value().parse::<u64>().ok(),
+                _ => None,
+            };
+            let (Some(timestamp), Some(sequence)) = (timestamp, event_sequence(&quad.subject))
+            else {
+                continue;
+            };
+            if timestamp < start_timestamp || timestamp > end_timestamp {
+                continue;
+            }
+            if let Some((event_quad, stream)) = self.read_event(&quad.subject)? {
+                found.push((timestamp, sequence, event_quad, stream));
+            }
+        }
+        found.sort_by_key(|(timestamp, sequence, _, _)| (*timestamp, *sequence));
+
+        let mut dict = self.dictionary.write().unwrap();
+        let events: Vec<(Event, Option<u32>)> = found
+            .iter()
+            .map(|(timestamp, _, quad, stream)| {
+                let event = RDFEvent::from_quad(*timestamp, quad).encode(&mut dict);
+                (event, stream.as_deref().map(|name| dict.encode(name)))
+            })
+            .collect();
+        Ok(select_events(
+            events.iter().map(|(event, stream)| (event, *stream)),
+            &dict,
+            stream_name,
+            start_timestamp,
+            end_timestamp,
+            patterns,
+        ))
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        #[cfg(feature = "rocksdb")]
+        self.store.flush().map_err(io::Error::other)?;
+        Ok(())
+    }
+
+    fn get_dictionary(&self) -> &Arc<RwLock<Dictionary>> {
+        &self.dictionary
+    }
+
+    fn delete_by_subject(&self, subject: &str) -> io::Result<()> {
+        let subject = RdfTerm::parse_resource(subject);
+        self.remove_events(|event| event.subject == subject)
+    }
+
+    fn delete_by_graph(&self, graph: &str) -> io::Result<()> {
+        let graph = RdfTerm::parse_graph(graph);
+        self.remove_events(|event| event.graph == graph)
+    }
+}
@@ -0,0 +1,655 @@
+//! Lazy iteration over the events of a query.
+//!
+//! A [`QueryIter`] merges the events of every segment overlapping the query range
+//! with the events that were buffered when the query started, in timestamp order.
+//! Each segment is read through a cursor that holds at most one index block and
+//! one decoded data block, so memory use does not grow with the length of the
+//! range. Events with equal timestamps come from the segments in segment order,
+//! then from the buffer. This bounds the storage side only: a historical window still
+//! loads every distinct quad of its range for a query that cannot run on one chunk of
+//! events at a time, see [`crate::execution::pushdown::decompose_query`].
+//!
+//! The segment files are opened when the query starts, so segments that are
+//! compacted away or expired while the iterator is alive stay readable. Data files
+//! are read from their memory mapping when the storage's [`MmapCache`] can provide
+//! one, and through file IO otherwise.
+//!
+//! With a [`ScanPool`], segments are instead scanned by the pool's workers, a few
+//! segments ahead of the merge. A scanned segment joins the merge once the merge
+//! reaches the earliest timestamp it can hold, so the order of the events is the same.
+//! Each scan job reads one chunk of at most [`SCAN_CHUNK_EVENTS`] events, decoding one
+//! data block at a time, and hands the chunk back over the segment's channel; the next
+//! chunk is scanned while the merge consumes the previous one. A segment therefore
+//! holds at most two chunks in memory however large it is, and workers never wait on
+//! a slow consumer. Dropping the iterator stops the jobs of its segments.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, BinaryHeap, VecDeque};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+
+use memmap2::Mmap;
+
+use crate::core::{encoding::decode_record, Event, RECORD_SIZE};
+use crate::storage::codec::SegmentCodec;
+use crate::storage::erasure::ErasedTerms;
+use crate::storage::indexing::secondary::EventFilter;
+use crate::storage::mmap_cache::MmapCache;
+use crate::storage::scan_pool::ScanPool;
+use crate::storage::segment_format::{IndexEntry, LEGACY_SEGMENT_VERSION};
+use crate::storage::segmented_storage::StreamingSegmentedStorage;
+use crate::storage::util::{EnhancedSegmentMetadata, IndexBlock};
+
+/// Maximum number of events a scan job of the [`ScanPool`] reads from a segment at once.
+pub const SCAN_CHUNK_EVENTS: usize = 4_096;
+
+/// Iterator over the events of a query in timestamp order.
+pub struct QueryIter {
+    sources: Vec<Source>,
+    heads: BinaryHeap<Reverse<(u64, usize)>>,
+    pending: Vec<Option<Event>>,
+    filter: Option<EventFilter>,
+    primed: bool,
+    failed: bool,
+    // Segments left to the scan pool, with the earliest timestamp each can hold, in the
+    // order they join the merge. The first `scans_ahead` of them have been submitted.
+    deferred: VecDeque<(u64, usize)>,
+    scans_ahead: usize,
+    scan_pool: Option<Arc<ScanPool>>,
+    scan_state: Arc<ScanState>,
+}
+
+// State shared between an iterator and its scan jobs.
+#[derive(Default)]
+struct ScanState {
+    // Set once the iterator is dropped, so that its jobs stop reading.
+    cancelled: AtomicBool,
+    // Events scanned by the jobs that the merge has not used up yet.
+    held_events: AtomicUsize,
+}
+
+// A chunk of a segment's events read by a scan job, with the cursor to read the next
+// chunk from unless the segment is exhausted.
+struct ScannedChunk {
+    events: Vec<Event>,
+    rest: Option<SegmentCursor>,
+}
+
+impl QueryIter {
+    pub(crate) fn empty() -> Self {
+        Self::new(Vec::new(), Vec::new(), None)
+    }
+
+    /// Iterate over events already held in memory, which must be sorted by timestamp.
+    ///
+    /// Stores that do not read segment files hand out their query results this way.
+    pub fn from_events(events: Vec<Event>) -> Self {
+        Self::new(Vec::new(), events, None)
+    }
+
+    // `buffered` must already be filtered and sorted by timestamp; the filter is applied
+    // to the segment events as they are read.
+    pub(crate) fn new(
+        segments: Vec<SegmentCursor>,
+        buffered: Vec<Event>,
+        filter: Option<EventFilter>,
+    ) -> Self {
+        let mut sources: Vec<Source> = segments.into_iter().map(Source::Segment).collect();
+        sources.push(Source::Buffered(buffered.into_iter()));
+        Self {
+            pending: vec![None; sources.len()],
+            sources,
+            heads: BinaryHeap::new(),
+            filter,
+            primed: false,
+            failed: false,
+            deferred: VecDeque::new(),
+            scans_ahead: 0,
+            scan_pool: None,
+            scan_state: Arc::default(),
+        }
+    }
+
+    // Scan the segments on the pool's workers instead of reading them in the consuming
+    // thread, keeping as many segments scanned ahead as the pool has threads.
+    pub(crate) fn scanned_by(mut self, pool: Arc<ScanPool>) -> Self {
+        if pool.threads() < 2 {
+            return self;
+        }
+        let mut deferred: Vec<(u64, usize)> = Vec::new();
+        self.sources = std::mem::take(&mut self.sources)
+            .into_iter()
+            .enumerate()
+            .map(|(index, source)| match source {
+                Source::Segment(cursor) => {
+                    deferred.push((cursor.earliest_timestamp(), index));
+                    Source::Queued(Some(cursor))
+                }
+                source => source,
+            })
+            .collect();
+        // Sources with equal timestamps keep their order.
+        deferred.sort_by_key(|&(earliest, index)| (earliest, index));
+        self.deferred = deferred.into();
+        self.scan_pool = Some(pool);
+        self
+    }
+
+    /// Number of events the scan pool has read ahead of the merge that are held in memory.
+    pub fn scanned_events_held(&self) -> usize {
+        self.scan_state.held_events.load(Ordering::Relaxed)
+    }
+
+    /// Group the events into chunks of at most `chunk_size` events.
+    pub fn chunks(self, chunk_size: usize) -> EventChunks {
+        EventChunks { events: self, chunk_size: chunk_size.max(1) }
+    }
+
+    // Read the next event of a source that passes the filter into its pending slot.
+    fn advance(&mut self, source: usize) -> std::io::Result<()> {
+        let event = match &mut self.sources[source] {
+            Source::Buffered(events) => events.next(),
+            Source::Scanning { chunk, chunk_len, next } => loop {
+                if let Some(event) = chunk.next() {
+                    break Some(event);
+                }
+                self.scan_state.held_events.fetch_sub(*chunk_len, Ordering::Relaxed);
+                *chunk_len = 0;
+                let Some(result) = next.take() else {
+                    break None;
+                };
+                let scanned = result.recv().map_err(|_| {
+                    std::io::Error::other("A segment scan worker failed before finishing its scan")
+                })??;
+                *chunk_len = scanned.events.len();
+                *chunk = scanned.events.into_iter();
+                if let (Some(cursor), Some(pool)) = (scanned.rest, &self.scan_pool) {
+                    *next = Some(Self::scan_chunk(
+                        pool,
+                        cursor,
+                        self.filter.as_ref(),
+                        &self.scan_state,
+                    ));
+                }
+            },
+            Source::Segment(cursor) => loop {
+                match cursor.next_event()? {
+                    Some(event) if self.filter.as_ref().is_some_and(|f| !f.matches(&event)) => {}
+                    event => break event,
+                }
+            },
+            Source::Queued(_) => {
+                unreachable!("deferred segments are submitted before they join the merge")
+            }
+        };
+        self.push(source, event);
+        Ok(())
+    }
+
+    // Keep the scan pool busy with the next deferred segments.
+    fn submit_scans(&mut self) {
+        let Some(pool) = &self.scan_pool else {
+            return;
+        };
+        while self.scans_ahead < pool.threads().min(self.deferred.len()) {
+            let (_, source) = self.deferred[self.scans_ahead];
+            let Source::Queued(cursor) = &mut self.sources[source] else {
+                unreachable!("deferred segments are submitted once")
+            };
+            let cursor = cursor.take().expect("deferred segment without a cursor");
+            let next = Self::scan_chunk(pool, cursor, self.filter.as_ref(), &self.scan_state);
+            self.sources[source] =
+                Source::Scanning { chunk: Vec::new().into_iter(), chunk_len: 0, next: Some(next) };
+            self.scans_ahead += 1;
+        }
+    }
+
+    // Read the next chunk of a segment on the pool. Every job sends exactly one result
+    // and the next job is only submitted once it was received, so sending never blocks.
+    fn scan_chunk(
+        pool: &ScanPool,
+        cursor: SegmentCursor,
+        filter: Option<&EventFilter>,
+        state: &Arc<ScanState>,
+    ) -> Receiver<std::io::Result<ScannedChunk>> {
+        let filter = filter.cloned();
+        let state = Arc::clone(state);
+        let (sender, result) = mpsc::sync_channel(1);
+        pool.execute(move || {
+            if state.cancelled.load(Ordering::Relaxed) {
+                return;
+            }
+            let scanned = cursor.scan_chunk(filter.as_ref(), &state.cancelled);
+            if let Ok(scanned) = &scanned {
+                state.held_events.fetch_add(scanned.events.len(), Ordering::Relaxed);
+            }
+            // The receiver is gone once the iterator was dropped.
+            let _ = sender.send(scanned);
+        });
+        result
+    }
+
+    // Merge in the deferred segments that can hold events up to the next event to return.
+    fn join_due_segments(&mut self) -> std::io::Result<()> {
+        while let Some(&(earliest, source)) = self.deferred.front() {
+            if self.heads.peek().is_some_and(|Reverse((timestamp, _))| *timestamp < earliest) {
+                break;
+            }
+            self.deferred.pop_front();
+            self.scans_ahead -= 1;
+            self.submit_scans();
+            self.advance(source)?;
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, source: usize, event: Option<Event>) {
+        if let Some(event) = &event {
+            self.heads.push(Reverse((event.timestamp, source)));
+        }
+        self.pending[source] = event;
+    }
+
+    fn try_next(&mut self) -> std::io::Result<Option<Event>> {
+        if !self.primed {
+            self.primed = true;
+            self.submit_scans();
+            for source in 0..self.sources.len() {
+                if !matches!(self.sources[source], Source::Scanning { .. } | Source::Queued(_)) {
+                    self.advance(source)?;
+                }
+            }
+        }
+        self.join_due_segments()?;
+
+        let Some(Reverse((_, source))) = self.heads.pop() else {
+            return Ok(None);
+        };
+        let event = self.pending[source].take();
+        self.advance(source)?;
+        Ok(event)
+    }
+}
+
+impl Drop for QueryIter {
+    fn drop(&mut self) {
+        self.scan_state.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Iterator for QueryIter {
+    type Item = std::io::Result<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        match self.try_next() {
+            Ok(event) => event.map(Ok),
+            Err(err) => {
+                self.failed = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// Chunks of consecutive events of a [`QueryIter`].
+pub struct EventChunks {
+    events: QueryIter,
+    chunk_size: usize,
+}
+
+impl Iterator for EventChunks {
+    type Item = std::io::Result<Vec<Event>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chunk = Vec::with_capacity(self.chunk_size);
+        for event in self.events.by_ref() {
+            match event {
+                Ok(event) => chunk.push(event),
+                Err(err) => return Some(Err(err)),
+            }
+            if chunk.len() == self.chunk_size {
+                break;
+            }
+        }
+        (!chunk.is_empty()).then_some(Ok(chunk))
+    }
+}
+
+enum Source {
+    Buffered(std::vec::IntoIter<Event>),
+    Segment(SegmentCursor),
+    // A segment waiting for the scan pool, then scanned by it: the chunk being merged,
+    // with its length, and the result of the scan of the next chunk.
+    Queued(Option<SegmentCursor>),
+    Scanning {
+        chunk: std::vec::IntoIter<Event>,
+        chunk_len: usize,
+        next: Option<Receiver<std::io::Result<ScannedChunk>>>,
+    },
+}
+
+/// Reads the events of one segment within a time range, one data block at a time.
+pub(crate) struct SegmentCursor {
+    reader: SegmentReader,
+    start_timestamp: u64,
+    end_timestamp: u64,
+    // Start timestamp of the segment.
+    segment_start: u64,
+    done: bool,
+    // Terms erased by tombstones covering the segment, whose events are skipped.
+    erased: Option<Arc<ErasedTerms>>,
+}
+
+enum SegmentReader {
+    Blocks(Box<BlockReader>),
+    // Legacy segments are unchecksummed runs of fixed-width records.
+    Records(RecordReader),
+}
+
+enum DataFile {
+    Mapped(Arc<Mmap>),
+    File(File),
+}
+
+enum RecordReader {
+    Mapped { mapping: Arc<Mmap>, position: usize },
+    File(BufReader<File>),
+}
+
+struct BlockReader {
+    data_file: DataFile,
+    data_path: String,
+    index_file: File,
+    index_path: String,
+    format_version: u32,
+    codec: SegmentCodec,
+    index_blocks: VecDeque<IndexBlock>,
+    entries: VecDeque<(u32, IndexEntry)>,
+    next_block_number: u32,
+    // Data blocks listed by the secondary index for the filter, if it has one.
+    matching_blocks: Option<BTreeSet<u32>>,
+    events: std::vec::IntoIter<Event>,
+}
+
+impl SegmentCursor {
+    /// Open the files of a segment and position the cursor at the first data block that
+    /// can hold events at or after `start_timestamp`.
+    ///
+    /// Returns `None` when the secondary index rules out every data block.
+    pub(crate) fn open(
+        segment: &EnhancedSegmentMetadata,
+        start_timestamp: u64,
+        end_timestamp: u64,
+        filter: Option<&EventFilter>,
+        mmap_cache: Option<&MmapCache>,
+    ) -> std::io::Result<Option<Self>> {
+        let mapping = mmap_cache.and_then(|cache| cache.get(&segment.data_path));
+        let reader = if segment.format_version == LEGACY_SEGMENT_VERSION {
+            let offset = Self::legacy_start_offset(segment, start_timestamp, end_timestamp)?;
+            SegmentReader::Records(if let Some(mapping) = mapping {
+                RecordReader::Mapped { mapping, position: offset as usize }
+            } else {
+                let mut file = File::open(&segment.data_path)?;
+                file.seek(SeekFrom::Start(offset))?;
+                RecordReader::File(BufReader::new(file))
+            })
+        } else if segment.index_directory.is_empty() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "Segment {} has no readable index; run verify to repair it",
+                    segment.data_path
+                ),
+            ));
+        } else {
+            let mut index_file = File::open(&segment.index_path)?;
+            let matching_blocks = match (&segment.secondary_index, filter) {
+                (Some(location), Some(filter)) => StreamingSegmentedStorage::read_secondary_index(
+                    &mut index_file,
+                    &segment.index_path,
+                    location,
+                )?
+                .matching_blocks(filter),
+                _ => None,
+            };
+            if matching_blocks.as_ref().is_some_and(BTreeSet::is_empty) {
+                return Ok(None);
+            }
+
+            // The records of an index block run up to the first checkpoint of the next one,
+            // so start at the last block whose successor begins at or after the start.
+            let directory = &segment.index_directory;
+            let first_block = directory
+                .partition_point(|block| block.min_timestamp < start_timestamp)
+                .saturating_sub(1);
+            let next_block_number =
+                directory[..first_block].iter().map(|block| block.entry_count).sum();
+
+            SegmentReader::Blocks(Box::new(BlockReader {
+                data_file: match mapping {
+                    Some(mapping) => DataFile::Mapped(mapping),
+                    None => DataFile::File(File::open(&segment.data_path)?),
+                },
+                data_path: segment.data_path.clone(),
+                index_file,
+                index_path: segment.index_path.clone(),
+                format_version: segment.format_version,
+                codec: segment.codec,
+                index_blocks: directory[first_block..].iter().cloned().collect(),
+                entries: VecDeque::new(),
+                next_block_number,
+                matching_blocks,
+                events: Vec::new().into_iter(),
+            }))
+        };
+
+        Ok(Some(Self {
+            reader,
+            start_timestamp,
+            end_timestamp,
+            segment_start: segment.start_timstamp,
+            done: false,
+            erased: None,
+        }))
+    }
+
+    // Earliest timestamp of the events the cursor can return.
+    fn earliest_timestamp(&self) -> u64 {
+        self.start_timestamp.max(self.segment_start)
+    }
+
+    // Read up to `SCAN_CHUNK_EVENTS` of the remaining events that pass the filter,
+    // stopping early once the scan is cancelled.
+    fn scan_chunk(
+        mut self,
+        filter: Option<&EventFilter>,
+        cancelled: &AtomicBool,
+    ) -> std::io::Result<ScannedChunk> {
+        let mut events = Vec::with_capacity(SCAN_CHUNK_EVENTS);
+        while events.len() < SCAN_CHUNK_EVENTS {
+            if cancelled.load(Ordering::Relaxed) {
+                return Ok(ScannedChunk { events, rest: None });
+            }
+            match self.next_event()? {
+                Some(event) if filter.is_none_or(|filter| filter.matches(&event)) => {
+                    events.push(event);
+                }
+                Some(_) => {}
+                None => return Ok(ScannedChunk { events, rest: None }),
+            }
+        }
+        Ok(ScannedChunk { events, rest: Some(self) })
+    }
+
+    // Skip the events of the erased terms.
+    pub(crate) fn erasing(mut self, erased: Option<Arc<ErasedTerms>>) -> Self {
+        self.erased = erased;
+        self
+    }
+
+    // Offset of the sparse checkpoint at or before the start timestamp in a legacy data file.
+    fn legacy_start_offset(
+        segment: &EnhancedSegmentMetadata,
+        start_timestamp: u64,
+        end_timestamp: u64,
+    ) -> std::io::Result<u64> {
+        let mut offset = 0;
+        if !segment.index_directory.is_empty() {
+            let first_block = segment
+                .index_directory
+                .partition_point(|block| block.min_timestamp <= start_timestamp)
+                .saturating_sub(1);
+            let mut index_file = File::open(&segment.index_path)?;
+            let mut entries = Vec::new();
+            for block in segment.index_directory[first_block..]
+                .iter()
+                .take_while(|block| block.min_timestamp <= end_timestamp)
+            {
+                entries.extend(StreamingSegmentedStorage::read_index_block(
+                    &mut index_file,
+                    &segment.index_path,
+                    block,
+                    segment.format_version,
+                )?);
+            }
+            entries.sort_by_key(|entry| entry.timestamp);
+            let checkpoint = entries
+                .partition_point(|entry| entry.timestamp < start_timestamp)
+                .saturating_sub(1);
+            if let Some(entry) = entries.get(checkpoint) {
+                offset = entry.offset;
+            }
+        }
+        Ok(offset)
+    }
+
+    fn next_event(&mut self) -> std::io::Result<Option<Event>> {
+        while !self.done {
+            let event = match &mut self.reader {
+                SegmentReader::Blocks(reader) => {
+                    reader.next_event(self.start_timestamp, self.end_timestamp)?
+                }
+                SegmentReader::Records(reader) => reader.next_event(),
+            };
+
+            match event {
+                Some(event) if event.timestamp > self.end_timestamp => self.done = true,
+                Some(event)
+                    if self.erased.as_ref().is_some_and(|erased| erased.matches(&event)) => {}
+                Some(event) if event.timestamp >= self.start_timestamp => return Ok(Some(event)),
+                Some(_) => {}
+                None => self.done = true,
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl BlockReader {
+    // Return the next event of the current data block, reading the next data block that can
+    // hold events in the range once the current one is exhausted.
+    fn next_event(
+        &mut self,
+        start_timestamp: u64,
+        end_timestamp: u64,
+    ) -> std::io::Result<Option<Event>> {
+        loop {
+            if let Some(event) = self.events.next() {
+                return Ok(Some(event));
+            }
+
+            let Some((block_number, entry)) = self.next_entry()? else {
+                return Ok(None);
+            };
+            if entry.timestamp > end_timestamp {
+                return Ok(None);
+            }
+            // The block ends where the next one begins, so it only holds earlier events.
+            let next_timestamp = self
+                .entries
+                .front()
+                .map(|(_, next)| next.timestamp)
+                .or_else(|| self.index_blocks.front().map(|block| block.min_timestamp));
+            if next_timestamp.is_some_and(|next| next < start_timestamp)
+                || self
+                    .matching_blocks
+                    .as_ref()
+                    .is_some_and(|blocks| !blocks.contains(&block_number))
+            {
+                continue;
+            }
+
+            self.events = match &mut self.data_file {
+                DataFile::Mapped(mapping) => {
+                    let block = usize::try_from(entry.offset)
+                        .ok()
+                        .and_then(|offset| mapping.get(offset..offset + entry.length as usize))
+                        .ok_or_else(|| {
+                            std::io::Error::new(
+                                std::io::ErrorKind::UnexpectedEof,
+                                format!(
+                                    "Data block at offset {} runs past the end of {}",
+                                    entry.offset, self.data_path
+                                ),
+                            )
+                        })?;
+                    StreamingSegmentedStorage::decode_data_block(
+                        block,
+                        &self.data_path,
+                        &entry,
+                        self.codec,
+                    )?
+                }
+                DataFile::File(file) => StreamingSegmentedStorage::read_data_block(
+                    file,
+                    &self.data_path,
+                    &entry,
+                    self.codec,
+                )?,
+            }
+            .into_iter();
+        }
+    }
+
+    fn next_entry(&mut self) -> std::io::Result<Option<(u32, IndexEntry)>> {
+        if self.entries.is_empty() {
+            let Some(block) = self.index_blocks.pop_front() else {
+                return Ok(None);
+            };
+            for entry in StreamingSegmentedStorage::read_index_block(
+                &mut self.index_file,
+                &self.index_path,
+                &block,
+                self.format_version,
+            )? {
+                self.entries.push_back((self.next_block_number, entry));
+                self.next_block_number += 1;
+            }
+        }
+        Ok(self.entries.pop_front())
+    }
+}
+
+impl RecordReader {
+    fn next_event(&mut self) -> Option<Event> {
+        let (timestamp, subject, predicate, object, graph) = match self {
+            RecordReader::Mapped { mapping, position } => {
+                let record = mapping.get(*position..*position + RECORD_SIZE)?;
+                *position += RECORD_SIZE;
+                decode_record(record.try_into().unwrap())
+            }
+            RecordReader::File(reader) => {
+                let mut record = [0u8; RECORD_SIZE];
+                reader.read_exact(&mut record).ok()?;
+                decode_record(&record)
+            }
+        };
+        Some(Event { timestamp, subject, predicate, object, graph })
+    }
+}
@@ -0,0 +1,191 @@
+//! Precomputed aggregates of the numeric readings of a segment.
+//!
+//! Baselines and other aggregate-only reads over months of high-rate sensor data
+//! should not have to decode every event. When `rollup_bucket_ms` is configured,
+//! every segment is written with the count, sum, minimum and maximum of the values
+//! of each predicate, subject and graph per time bucket. Buckets are aligned to multiples
+//! of the bucket width. The rollups are stored as a section of the `.idx` file
+//! after the secondary index, located and checksummed through the footer:
+//!
+//! ```text
+//! [bucket width: u64][row count: u32]
+//! ([bucket start: u64][predicate id: u32][subject id: u32][graph id: u32]
+//!  [count: u64][numeric count: u64][sum: f64][min: f64][max: f64])...
+//! ```
+//!
+//! A reading is numeric when its object is a literal with an XSD numeric datatype
+//! whose lexical form parses as a finite number, see [`numeric_value`]; the count
+//! covers every event, the other aggregates only numeric readings. Every reading is
+//! counted, also one repeated with the same value within a bucket.
+//! Rollups are rebuilt whenever a segment is rewritten, so merged and purged
+//! segments never count erased events.
+
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Serialize;
+
+use crate::core::term::is_xsd_numeric;
+use crate::core::{Event, RdfTerm};
+use crate::storage::indexing::dictionary::Dictionary;
+
+const ROW_SIZE: usize = 60;
+
+/// Aggregates of the readings of one predicate, subject and graph in a time bucket.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct RollupStats {
+    /// Number of events, numeric or not.
+    pub count: u64,
+    /// Number of events with a numeric object, which `sum`, `min` and `max` cover.
+    pub numeric_count: u64,
+    pub sum: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Default for RollupStats {
+    fn default() -> Self {
+        Self { count: 0, numeric_count: 0, sum: 0.0, min: f64::INFINITY, max: f64::NEG_INFINITY }
+    }
+}
+
+impl RollupStats {
+    /// Account for one reading, with its value if it is numeric.
+    pub fn add(&mut self, value: Option<f64>) {
+        self.count += 1;
+        if let Some(value) = value {
+            self.numeric_count += 1;
+            self.sum += value;
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        self.count += other.count;
+        self.numeric_count += other.numeric_count;
+        self.sum += other.sum;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    /// Mean of the numeric readings, if there are any.
+    pub fn mean(&self) -> Option<f64> {
+        (self.numeric_count > 0).then(|| self.sum / self.numeric_count as f64)
+    }
+
+    /// Whether every reading was numeric.
+    pub fn all_numeric(&self) -> bool {
+        self.numeric_count == self.count
+    }
+}
+
+/// Rollups keyed by bucket start, predicate ID, subject ID and graph ID.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Rollups {
+    pub bucket_ms: u64,
+    pub rows: BTreeMap<(u64, u32, u32, u32), RollupStats>,
+}
+
+impl Rollups {
+    pub fn new(bucket_ms: u64) -> Self {
+        Self { bucket_ms, rows: BTreeMap::new() }
+    }
+
+    /// Start of the bucket holding `timestamp`.
+    pub fn bucket_start(&self, timestamp: u64) -> u64 {
+        timestamp - timestamp % self.bucket_ms
+    }
+
+    /// Roll up the events, resolving their objects against the dictionary.
+    pub fn add_events<'a>(
+        &mut self,
+        events: impl IntoIterator<Item = &'a Event>,
+        dictionary: &Dictionary,
+    ) {
+        let mut values: HashMap<u32, Option<f64>> = HashMap::new();
+        for event in events {
+            let value = *values.entry(event.object).or_insert_with(|| {
+                dictionary.decode_term(event.object).as_ref().and_then(numeric_value)
+            });
+            let key =
+                (self.bucket_start(event.timestamp), event.predicate, event.subject, event.graph);
+            self.rows.entry(key).or_default().add(value);
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buffer = Vec::with_capacity(12 + self.rows.len() * ROW_SIZE);
+        buffer.extend_from_slice(&self.bucket_ms.to_le_bytes());
+        buffer.extend_from_slice(&(self.rows.len() as u32).to_le_bytes());
+        for ((bucket_start, predicate, subject, graph), stats) in &self.rows {
+            buffer.extend_from_slice(&bucket_start.to_le_bytes());
+            buffer.extend_from_slice(&predicate.to_le_bytes());
+            buffer.extend_from_slice(&subject.to_le_bytes());
+            buffer.extend_from_slice(&graph.to_le_bytes());
+            buffer.extend_from_slice(&stats.count.to_le_bytes());
+            buffer.extend_from_slice(&stats.numeric_count.to_le_bytes());
+            for value in [stats.sum, stats.min, stats.max] {
+                buffer.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        buffer
+    }
+
+    pub fn decode(buffer: &[u8]) -> std::io::Result<Self> {
+        let truncated =
+            || std::io::Error::new(std::io::ErrorKind::InvalidData, "Truncated rollup section");
+        if buffer.len() < 12 {
+            return Err(truncated());
+        }
+        let u64_at =
+            |offset: usize| u64::from_le_bytes(buffer[offset..offset + 8].try_into().unwrap());
+        let u32_at =
+            |offset: usize| u32::from_le_bytes(buffer[offset..offset + 4].try_into().unwrap());
+
+        let mut rollups = Self::new(u64_at(0));
+        let row_count = u32_at(8) as usize;
+        if buffer.len() != 12 + row_count * ROW_SIZE {
+            return Err(truncated());
+        }
+        for row in 0..row_count {
+            let offset = 12 + row * ROW_SIZE;
+            let stats = RollupStats {
+                count: u64_at(offset + 20),
+                numeric_count: u64_at(offset + 28),
+                sum: f64::from_bits(u64_at(offset + 36)),
+                min: f64::from_bits(u64_at(offset + 44)),
+                max: f64::from_bits(u64_at(offset + 52)),
+            };
+            let key =
+                (u64_at(offset), u32_at(offset + 8), u32_at(offset + 12), u32_at(offset + 16));
+            rollups.rows.insert(key, stats);
+        }
+        Ok(rollups)
+    }
+}
+
+/// Aggregates of one subject's readings of a predicate in a graph and time bucket, with
+/// the terms given by their dictionary keys. The default graph has an empty key.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Rollup {
+    pub bucket_start: u64,
+    pub predicate: String,
+    pub subject: String,
+    pub graph: String,
+    #[serde(flatten)]
+    pub stats: RollupStats,
+}
+
+/// The number a reading's object stands for, if it is a literal with an XSD numeric
+/// datatype and a finite value. Plain, language-tagged and otherwise typed literals
+/// are not numeric, whatever their lexical form, and neither are `NaN` and infinities,
+/// which no mean or range could account for.
+pub fn numeric_value(term: &RdfTerm) -> Option<f64> {
+    let RdfTerm::Literal { value, datatype: Some(datatype), .. } = term else {
+        return None;
+    };
+    if !is_xsd_numeric(datatype) {
+        return None;
+    }
+    value.trim().parse::<f64>().ok().filter(|value| value.is_finite())
+}
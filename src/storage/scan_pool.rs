@@ -0,0 +1,75 @@
+//! Worker threads scanning segments for queries in parallel.
+//!
+//! A long historical range overlaps many segments, and reading them one after the
+//! other keeps a single core busy decoding blocks. With `query_scan_threads` above
+//! one, the storage keeps a pool of that many threads; a [`QueryIter`] hands the
+//! segments ahead of its position to the pool, which scans them a chunk at a time,
+//! and merges their events in timestamp order as before. Every query only keeps a
+//! bounded number of segments scanned ahead and a bounded number of events per
+//! segment, and the pool bounds the threads across all queries. Jobs never block, so
+//! queries sharing the pool take turns chunk by chunk.
+//!
+//! [`QueryIter`]: crate::storage::query_iter::QueryIter
+
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// A fixed number of threads running scan jobs in submission order.
+pub struct ScanPool {
+    sender: Option<Sender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+    threads: usize,
+}
+
+impl ScanPool {
+    pub fn new(threads: usize) -> Self {
+        let threads = threads.max(1);
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..threads)
+            .map(|_| {
+                let receiver = Arc::clone(&receiver);
+                std::thread::spawn(move || Self::work(&receiver))
+            })
+            .collect();
+        Self { sender: Some(sender), workers, threads }
+    }
+
+    /// Number of worker threads.
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Run `job` on the next idle worker.
+    pub fn execute(&self, job: impl FnOnce() + Send + 'static) {
+        if let Some(sender) = &self.sender {
+            // The workers only stop once the sender is dropped, so this cannot fail.
+            let _ = sender.send(Box::new(job));
+        }
+    }
+
+    fn work(receiver: &Mutex<Receiver<Job>>) {
+        loop {
+            let job = receiver.lock().unwrap().recv();
+            match job {
+                // A failed scan reports itself through its dropped result channel.
+                Ok(job) => {
+                    let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(job));
+                }
+                Err(_) => return,
+            }
+        }
+    }
+}
+
+impl Drop for ScanPool {
+    fn drop(&mut self) {
+        drop(self.sender.take());
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
@@ -0,0 +1,313 @@
+//! On-disk layout of segment files.
+//!
+//! A segment is stored as a `.log` data file and an `.idx` index file. The data
+//! file starts with a small header carrying a magic number, the format version and
+//! the block codec, followed by the encoded event records. The records are grouped
+//! into data blocks of `sparse_interval` records, each encoded on its own by the
+//! segment's [`SegmentCodec`]; every block has one entry in the index file holding
+//! its first timestamp, offset, length and CRC32:
+//!
+//! ```text
+//! .log: [SEGMENT_MAGIC][version: u32][codec: u32][block]...
+//! .idx: [index block 0][index block 1]...[secondary index][rollups][Bloom filter][zone map][footer JSON][crc32: u32][footer length: u32][FOOTER_MAGIC]
+//! ```
+//!
+//! Index entries are grouped into index blocks whose checksums live in the footer,
+//! and the footer itself is protected by the CRC in the trailer. A torn write or a
+//! flipped bit is therefore reported as `InvalidData` instead of being decoded as
+//! garbage events. The optional secondary index, rollup, Bloom filter and zone map
+//! sections are located and checksummed through the footer as well.
+//!
+//! Segments written before the header existed (format version 0) have raw records
+//! from offset zero and 16-byte index entries without checksums. They are still
+//! readable, but can only be verified for torn records. Version 1 segments left the
+//! codec word reserved and always hold fixed-width records.
+
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::storage::codec::SegmentCodec;
+use crate::storage::event_time::OutOfOrderRange;
+use crate::storage::util::IndexBlock;
+
+/// Magic number at the start of every segment data file.
+pub const SEGMENT_MAGIC: [u8; 8] = *b"JANUSSEG";
+
+/// Format version of segments without a header.
+pub const LEGACY_SEGMENT_VERSION: u32 = 0;
+
+/// Version of segments whose header does not record the codec.
+pub const FIXED_WIDTH_SEGMENT_VERSION: u32 = 1;
+
+/// Current version of the segment data and index layout.
+pub const SEGMENT_VERSION: u32 = 2;
+
+/// Size of the data file header.
+pub const SEGMENT_HEADER_SIZE: u64 = 16;
+
+/// Size of an index entry in the current format.
+pub const INDEX_ENTRY_SIZE: usize = 24;
+
+/// Size of an index entry in legacy segments: `(timestamp, offset)` only.
+pub const LEGACY_INDEX_ENTRY_SIZE: usize = 16;
+
+/// Magic trailer identifying an index file that ends with a [`SegmentFooter`].
+pub const FOOTER_MAGIC: [u8; 8] = *b"JANUSIDX";
+
+/// Current version of the segment footer.
+pub const FOOTER_VERSION: u32 = 2;
+
+const FOOTER_TRAILER_SIZE: u64 = 16;
+
+/// Write the data file header for the current segment version.
+pub fn write_segment_header<W: Write>(writer: &mut W, codec: SegmentCodec) -> std::io::Result<()> {
+    writer.write_all(&SEGMENT_MAGIC)?;
+    writer.write_all(&SEGMENT_VERSION.to_le_bytes())?;
+    writer.write_all(&codec.id().to_le_bytes())?;
+    Ok(())
+}
+
+/// Read the format version and block codec from the header of a segment data file.
+///
+/// Files without the magic number are legacy segments and report
+/// [`LEGACY_SEGMENT_VERSION`] with fixed-width records.
+pub fn read_segment_header<R: Read + Seek>(reader: &mut R) -> std::io::Result<(u32, SegmentCodec)> {
+    let mut header = [0u8; SEGMENT_HEADER_SIZE as usize];
+    reader.seek(SeekFrom::Start(0))?;
+    match reader.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => {
+            return Ok((LEGACY_SEGMENT_VERSION, SegmentCodec::FixedWidth))
+        }
+        Err(err) => return Err(err),
+    }
+
+    if header[0..8] != SEGMENT_MAGIC {
+        return Ok((LEGACY_SEGMENT_VERSION, SegmentCodec::FixedWidth));
+    }
+
+    let version = u32::from_le_bytes(header[8..12].try_into().unwrap());
+    if version > SEGMENT_VERSION {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("Unsupported segment format version {}", version),
+        ));
+    }
+    if version == FIXED_WIDTH_SEGMENT_VERSION {
+        return Ok((version, SegmentCodec::FixedWidth));
+    }
+    let codec = SegmentCodec::from_id(u32::from_le_bytes(header[12..16].try_into().unwrap()))?;
+    Ok((version, codec))
+}
+
+/// Sparse index entry pointing at one data block of a segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexEntry {
+    /// Timestamp of the first record in the block.
+    pub timestamp: u64,
+    /// Offset of the block in the data file.
+    pub offset: u64,
+    /// Length of the block in bytes; zero for legacy entries.
+    pub length: u32,
+    /// CRC32 of the block; zero for legacy entries.
+    pub checksum: u32,
+}
+
+impl IndexEntry {
+    pub fn encode(&self) -> [u8; INDEX_ENTRY_SIZE] {
+        let mut buffer = [0u8; INDEX_ENTRY_SIZE];
+        buffer[0..8].copy_from_slice(&self.timestamp.to_le_bytes());
+        buffer[8..16].copy_from_slice(&self.offset.to_be_bytes());
+        buffer[16..20].copy_from_slice(&self.length.to_le_bytes());
+        buffer[20..24].copy_from_slice(&self.checksum.to_le_bytes());
+        buffer
+    }
+
+    /// Decode the entries of an index block written with the given segment version.
+    pub fn decode_all(buffer: &[u8], version: u32) -> Vec<IndexEntry> {
+        if version == LEGACY_SEGMENT_VERSION {
+            return buffer
+                .chunks_exact(LEGACY_INDEX_ENTRY_SIZE)
+                .map(|chunk| IndexEntry {
+                    timestamp: u64::from_le_bytes(chunk[0..8].try_into().unwrap()),
+                    offset: u64::from_be_bytes(chunk[8..16].try_into().unwrap()),
+                    length: 0,
+                    checksum: 0,
+                })
+                .collect();
+        }
+
+        buffer
+            .chunks_exact(INDEX_ENTRY_SIZE)
+            .map(|chunk| IndexEntry {
+                timestamp: u64::from_le_bytes(chunk[0..8].try_into().unwrap()),
+                offset: u64::from_be_bytes(chunk[8..16].try_into().unwrap()),
+                length: u32::from_le_bytes(chunk[16..20].try_into().unwrap()),
+                checksum: u32::from_le_bytes(chunk[20..24].try_into().unwrap()),
+            })
+            .collect()
+    }
+
+    /// Size of one encoded entry for the given segment version.
+    pub fn size_for_version(version: u32) -> usize {
+        if version == LEGACY_SEGMENT_VERSION {
+            LEGACY_INDEX_ENTRY_SIZE
+        } else {
+            INDEX_ENTRY_SIZE
+        }
+    }
+}
+
+/// Segment metadata persisted at the end of the `.idx` file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SegmentFooter {
+    pub version: u32,
+    pub start_timestamp: u64,
+    pub end_timestamp: u64,
+    pub record_count: u64,
+    pub index_directory: Vec<IndexBlock>,
+    /// IDs of the segments this one was merged or rewritten from. Any of them still on
+    /// disk when the storage is reopened are leftovers of an interrupted swap.
+    #[serde(default)]
+    pub replaces: Vec<u64>,
+    /// Stream whose log the segment belongs to.
+    #[serde(default)]
+    pub stream: Option<String>,
+    /// Location of the subject and predicate posting lists, if they were written.
+    #[serde(default)]
+    pub secondary_index: Option<SectionLocation>,
+    /// Part of the segment's range that overlaps events persisted before it in its stream.
+    #[serde(default)]
+    pub out_of_order: Option<OutOfOrderRange>,
+    /// Location of the segment's rollups, if they were written.
+    #[serde(default)]
+    pub rollups: Option<SectionLocation>,
+    /// Location of the Bloom filter over the segment's subjects and objects, if it was written.
+    #[serde(default)]
+    pub bloom_filter: Option<SectionLocation>,
+    /// Location of the value statistics of each predicate, if they were written.
+    #[serde(default)]
+    pub zone_map: Option<SectionLocation>,
+}
+
+/// Location and checksum of an optional section of the index file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SectionLocation {
+    pub offset: u64,
+    pub length: u64,
+    pub checksum: u32,
+}
+
+impl SegmentFooter {
+    /// Placeholder for a segment whose index is missing or unreadable.
+    pub fn unindexed() -> Self {
+        Self {
+            version: 0,
+            start_timestamp: 0,
+            end_timestamp: u64::MAX,
+            record_count: 0,
+            index_directory: Vec::new(),
+            replaces: Vec::new(),
+            stream: None,
+            secondary_index: None,
+            out_of_order: None,
+            rollups: None,
+            bloom_filter: None,
+            zone_map: None,
+        }
+    }
+
+    /// Append the footer and its trailer to an index file positioned after the last block.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        let encoded = serde_json::to_vec(self)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        writer.write_all(&encoded)?;
+        writer.write_all(&crc32fast::hash(&encoded).to_le_bytes())?;
+        writer.write_all(&(encoded.len() as u32).to_le_bytes())?;
+        writer.write_all(&FOOTER_MAGIC)?;
+        Ok(())
+    }
+
+    /// Read the footer from the end of an index file.
+    ///
+    /// Returns `Ok(None)` for index files written before footers were introduced
+    /// and an `InvalidData` error when the footer fails its checksum.
+    pub fn read_from<R: Read + Seek>(reader: &mut R) -> std::io::Result<Option<Self>> {
+        let file_len = reader.seek(SeekFrom::End(0))?;
+        if file_len < FOOTER_TRAILER_SIZE {
+            return Ok(None);
+        }
+
+        let mut trailer = [0u8; FOOTER_TRAILER_SIZE as usize];
+        reader.seek(SeekFrom::Start(file_len - FOOTER_TRAILER_SIZE))?;
+        reader.read_exact(&mut trailer)?;
+        if trailer[8..16] != FOOTER_MAGIC {
+            return Ok(None);
+        }
+
+        let checksum = u32::from_le_bytes(trailer[0..4].try_into().unwrap());
+        let footer_len = u32::from_le_bytes(trailer[4..8].try_into().unwrap()) as u64;
+        if footer_len > file_len - FOOTER_TRAILER_SIZE {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Segment footer length exceeds index file size",
+            ));
+        }
+
+        let mut encoded = vec![0u8; footer_len as usize];
+        reader.seek(SeekFrom::Start(file_len - FOOTER_TRAILER_SIZE - footer_len))?;
+        reader.read_exact(&mut encoded)?;
+        if crc32fast::hash(&encoded) != checksum {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Segment footer checksum mismatch",
+            ));
+        }
+
+        let footer: SegmentFooter = serde_json::from_slice(&encoded)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        if footer.version > FOOTER_VERSION {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Unsupported segment footer version {}", footer.version),
+            ));
+        }
+        Ok(Some(footer))
+    }
+}
+
+/// How [`verify`](crate::storage::segmented_storage::StreamingSegmentedStorage::verify)
+/// repairs a corrupt segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentRepair {
+    /// Move the segment files into the `quarantine` directory and stop serving them.
+    Quarantine,
+    /// Rewrite the segment with the records of its intact leading blocks. Segments
+    /// without any intact block are quarantined instead.
+    Truncate,
+}
+
+/// A segment that failed verification.
+#[derive(Debug, Clone)]
+pub struct CorruptSegment {
+    pub data_path: String,
+    pub reason: String,
+    /// Number of records in the intact leading blocks.
+    pub valid_records: u64,
+    /// Repair applied to the segment, if any.
+    pub repair: Option<SegmentRepair>,
+}
+
+/// Result of verifying every segment of a storage.
+#[derive(Debug, Clone, Default)]
+pub struct VerifyReport {
+    pub segments_checked: usize,
+    pub corrupt_segments: Vec<CorruptSegment>,
+}
+
+impl VerifyReport {
+    pub fn is_clean(&self) -> bool {
+        self.corrupt_segments.is_empty()
+    }
+}
@@ -1,85 +1,445 @@
 use std::{
-    collections::VecDeque,
-    io::{BufWriter, Read, Seek, SeekFrom, Write},
-    sync::{Arc, Mutex, RwLock},
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    io::{BufRead, BufWriter, Read, Seek, SeekFrom, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Condvar, Mutex, RwLock,
+    },
     thread::JoinHandle,
-    time::{Duration, SystemTime, UNIX_EPOCH},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use crate::{
     core::{
         encoding::{decode_record, encode_record, RECORD_SIZE},
-        Event, RDFEvent,
+        Event, RDFEvent, RdfTerm,
     },
     storage::{
-        indexing::dictionary::Dictionary,
-        util::{BatchBuffer, EnhancedSegmentMetadata, IndexBlock, StreamingConfig},
+        archive::{read_archive, ArchiveFormat, ArchiveWriter},
+        codec::SegmentCodec,
+        compaction::plan_compaction,
+        dictionary_log::DictionaryLog,
+        erasure::{ErasureProgress, ErasureState, Tombstone, TombstoneScope, Tombstones},
+        event_time::{LateEventCounts, LateEventPolicy, OutOfOrderRange, Watermarks},
+        indexing::{
+            bloom::BloomFilter,
+            dictionary::Dictionary,
+            secondary::{EventFilter, SecondaryIndex, TriplePattern},
+            zone_map::ZoneMap,
+        },
+        memory_tracker::MemoryTracker,
+        mmap_cache::MmapCache,
+        query_iter::{QueryIter, SegmentCursor},
+        rollup::{Rollup, Rollups},
+        scan_pool::ScanPool,
+        segment_format::{
+            read_segment_header, write_segment_header, CorruptSegment, IndexEntry, SectionLocation,
+            SegmentFooter, SegmentRepair, VerifyReport, FOOTER_VERSION, INDEX_ENTRY_SIZE,
+            LEGACY_SEGMENT_VERSION, SEGMENT_HEADER_SIZE, SEGMENT_VERSION,
+        },
+        snapshot::{link_or_copy, prepare_destination, SnapshotManifest, SnapshotSegment},
+        util::{
+            BackpressurePolicy, BatchBuffer, EnhancedSegmentMetadata, IndexBlock,
+            StorageComponentSizes, StorageTier, StreamingConfig, ARCHIVE_DIRECTORY,
+        },
+        wal::WriteAheadLog,
     },
 };
 
+// Last segment ID handed out in this process, shared by every storage instance.
+static LAST_SEGMENT_ID: AtomicU64 = AtomicU64::new(0);
+
+// Events decoded at a time while exporting or rolling up, so the dictionary lock is not
+// held throughout.
+const DECODE_CHUNK_EVENTS: usize = 1024;
+
+// Held exclusively by the process writing to a storage directory.
+const WRITER_LOCK_FILE: &str = "writer.lock";
+
 #[doc = "Struct for the Implementation of the Segmented Storage of RDF Streams."]
 pub struct StreamingSegmentedStorage {
     batch_buffer: Arc<RwLock<BatchBuffer>>,
     segments: Arc<RwLock<Vec<EnhancedSegmentMetadata>>>,
     dictionary: Arc<RwLock<Dictionary>>,
+    dictionary_log: Arc<Mutex<DictionaryLog>>,
     flush_handle: Option<JoinHandle<()>>,
     shutdown_signal: Arc<Mutex<bool>>,
     background_flush_error: Arc<Mutex<Option<String>>>,
+    wal: Option<Arc<Mutex<WriteAheadLog>>>,
+    flush_lock: Arc<Mutex<()>>,
+    mmap_cache: Arc<MmapCache>,
+    memory_tracker: MemoryTracker,
+    tombstones: Arc<RwLock<Tombstones>>,
+    erasure_progress: Mutex<ErasureProgress>,
+    purge_lock: Mutex<()>,
+    // Held shared while an event is encoded and buffered, and exclusively while the
+    // dictionary is purged, so a purge never removes a term an incoming event refers to.
+    write_gate: RwLock<()>,
+    watermarks: Arc<Mutex<Watermarks>>,
+    watermark_advanced: Arc<Condvar>,
+    // The lock on the storage directory, or `None` when the storage was opened read-only or
+    // has been shut down.
+    writer_lock: Option<std::fs::File>,
+    read_only: bool,
+    // Workers scanning segments for queries when `query_scan_threads` is above one.
+    scan_pool: Option<Arc<ScanPool>>,
     config: StreamingConfig,
 }
 
 // Implementation of the Segmented Storage System with Two-Level Indexing and Background Flushing to store the RDF Stream events.
 impl StreamingSegmentedStorage {
-    #[doc = ""]
+    /// Open the storage in `config.segment_base_path` for writing, creating the directory
+    /// if needed.
+    ///
+    /// Fails with `ErrorKind::ResourceBusy` while another storage, in this or another
+    /// process, has the directory open for writing. See [`Self::open_read_only`] for
+    /// reading alongside the writer. Fails with `ErrorKind::InvalidInput` when
+    /// `config.memory_budget_bytes` cannot hold a single event.
     pub fn new(config: StreamingConfig) -> std::io::Result<Self> {
+        // No flush could ever make room for a write under such a budget.
+        if let Some(budget) = config.memory_budget_bytes {
+            if budget < std::mem::size_of::<Event>() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!(
+                        "Memory budget of {} bytes cannot hold an event of {} bytes",
+                        budget,
+                        std::mem::size_of::<Event>()
+                    ),
+                ));
+            }
+        }
         std::fs::create_dir_all(&config.segment_base_path)?;
+        let writer_lock = Self::lock_directory(&config.segment_base_path)?;
+
+        // Load the dictionary checkpoint and log. Segments cannot be decoded without the
+        // dictionary, so a failure to load it is fatal instead of starting an empty one.
+        let (dictionary_log, mut dictionary) = DictionaryLog::open(
+            std::path::Path::new(&config.segment_base_path),
+            config.dictionary_checkpoint_interval,
+        )?;
+        if dictionary.size() > 0 {
+            println!("✓ Dictionary loaded with {} entries", dictionary.size());
+        }
 
-        // Load or create dictionary
-        let dict_path = std::path::Path::new(&config.segment_base_path).join("dictionary.bin");
-        let dictionary = if dict_path.exists() {
-            println!("Loading existing dictionary from {:?}", dict_path);
-            match Dictionary::load_from_file(&dict_path) {
-                Ok(dict) => {
-                    println!("✓ Dictionary loaded with {} entries", dict.size());
-                    dict
-                }
-                Err(e) => {
-                    eprintln!("Warning: Failed to load dictionary: {}, creating new one", e);
-                    Dictionary::new()
-                }
+        let mut batch_buffer = BatchBuffer {
+            events: VecDeque::new(),
+            streams: VecDeque::new(),
+            total_bytes: 0,
+            oldest_timestamp_bound: None,
+            newest_timestamp_bound: None,
+        };
+
+        // Replay events that were accepted but never flushed before the last shutdown or crash
+        let wal = if config.wal_enabled {
+            let (wal, replay) = WriteAheadLog::open(
+                std::path::Path::new(&config.segment_base_path),
+                config.wal_sync_on_write,
+            )?;
+            for (id, value) in &replay.terms {
+                dictionary.insert(*id, value);
             }
+            if !replay.events.is_empty() {
+                println!("✓ Replayed {} events from the write-ahead log", replay.events.len());
+            }
+            for (event, stream) in replay.events.into_iter().zip(replay.streams) {
+                Self::buffer_event(&mut batch_buffer, event, stream);
+            }
+            Some(Arc::new(Mutex::new(wal)))
         } else {
-            println!("Creating new dictionary");
-            Dictionary::new()
+            None
+        };
+
+        let memory_tracker = MemoryTracker::with_budget(config.memory_budget_bytes);
+        memory_tracker.set_buffered_bytes(batch_buffer.total_bytes);
+
+        let tombstones = Tombstones::open(std::path::Path::new(&config.segment_base_path))?;
+        let erasure_progress = ErasureProgress {
+            pending_tombstones: tombstones.entries().len(),
+            ..Default::default()
+        };
+
+        let storage = Self {
+            batch_buffer: Arc::new(RwLock::new(batch_buffer)),
+
+            segments: Arc::new(RwLock::new(Vec::new())),
+            dictionary: Arc::new(RwLock::new(dictionary)),
+            dictionary_log: Arc::new(Mutex::new(dictionary_log)),
+            flush_handle: None,
+            shutdown_signal: Arc::new(Mutex::new(false)),
+            background_flush_error: Arc::new(Mutex::new(None)),
+            wal,
+            flush_lock: Arc::new(Mutex::new(())),
+            mmap_cache: Arc::new(MmapCache::new(config.mmap_cache_capacity)),
+            memory_tracker,
+            tombstones: Arc::new(RwLock::new(tombstones)),
+            erasure_progress: Mutex::new(erasure_progress),
+            purge_lock: Mutex::new(()),
+            write_gate: RwLock::new(()),
+            watermarks: Arc::new(Mutex::new(Watermarks::new(config.allowed_lateness_ms))),
+            watermark_advanced: Arc::new(Condvar::new()),
+            writer_lock: Some(writer_lock),
+            read_only: false,
+            scan_pool: Self::scan_pool(&config),
+            config,
+        };
+        storage.load_existing_segments()?;
+        storage.restore_watermarks();
+        Ok(storage)
+    }
+
+    /// Open the storage in `config.segment_base_path` for reading while another process,
+    /// such as the HTTP server, writes to it.
+    ///
+    /// Nothing in the directory is locked or modified. Queries see the segments, dictionary
+    /// entries and erasures the writer had persisted when the storage was opened or last
+    /// refreshed, see [`Self::refresh`] and [`Self::start_tailing`]; events the writer has
+    /// not flushed yet are not visible. Writes, flushes, compaction, purges and snapshots
+    /// fail with `ErrorKind::PermissionDenied`.
+    pub fn open_read_only(config: StreamingConfig) -> std::io::Result<Self> {
+        let directory = std::path::Path::new(&config.segment_base_path);
+        if !directory.is_dir() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("Storage directory {} does not exist", config.segment_base_path),
+            ));
+        }
+        let (dictionary_log, dictionary) = DictionaryLog::open_read_only(directory)?;
+        let tombstones = Tombstones::open(directory)?;
+        let erasure_progress = ErasureProgress {
+            pending_tombstones: tombstones.entries().len(),
+            ..Default::default()
         };
 
         let storage = Self {
             batch_buffer: Arc::new(RwLock::new(BatchBuffer {
                 events: VecDeque::new(),
+                streams: VecDeque::new(),
                 total_bytes: 0,
                 oldest_timestamp_bound: None,
                 newest_timestamp_bound: None,
             })),
-
             segments: Arc::new(RwLock::new(Vec::new())),
             dictionary: Arc::new(RwLock::new(dictionary)),
+            dictionary_log: Arc::new(Mutex::new(dictionary_log)),
             flush_handle: None,
             shutdown_signal: Arc::new(Mutex::new(false)),
             background_flush_error: Arc::new(Mutex::new(None)),
+            wal: None,
+            flush_lock: Arc::new(Mutex::new(())),
+            mmap_cache: Arc::new(MmapCache::new(config.mmap_cache_capacity)),
+            memory_tracker: MemoryTracker::with_budget(config.memory_budget_bytes),
+            tombstones: Arc::new(RwLock::new(tombstones)),
+            erasure_progress: Mutex::new(erasure_progress),
+            purge_lock: Mutex::new(()),
+            write_gate: RwLock::new(()),
+            watermarks: Arc::new(Mutex::new(Watermarks::new(config.allowed_lateness_ms))),
+            watermark_advanced: Arc::new(Condvar::new()),
+            writer_lock: None,
+            read_only: true,
+            scan_pool: Self::scan_pool(&config),
             config,
         };
         storage.load_existing_segments()?;
+        storage.restore_watermarks();
         Ok(storage)
     }
 
+    fn scan_pool(config: &StreamingConfig) -> Option<Arc<ScanPool>> {
+        (config.query_scan_threads > 1).then(|| Arc::new(ScanPool::new(config.query_scan_threads)))
+    }
+
+    // Take the writer lock of the storage directory, recording the process ID in the lock
+    // file for whoever finds the directory locked.
+    fn lock_directory(segment_dir: &str) -> std::io::Result<std::fs::File> {
+        let lock_path = std::path::Path::new(segment_dir).join(WRITER_LOCK_FILE);
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(&lock_path)?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(std::fs::TryLockError::WouldBlock) => {
+                // The holder may not have written its process ID yet.
+                let mut holder = String::new();
+                let _ = file.read_to_string(&mut holder);
+                let holder = match holder.trim() {
+                    "" => String::new(),
+                    pid => format!(" (process {})", pid),
+                };
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::ResourceBusy,
+                    format!(
+                        "Storage directory {} is locked by another writer{}",
+                        segment_dir, holder
+                    ),
+                ));
+            }
+            Err(std::fs::TryLockError::Error(err)) => return Err(err),
+        }
+        file.set_len(0)?;
+        write!(file, "{}", std::process::id())?;
+        Ok(file)
+    }
+
+    /// Whether the storage was opened with [`Self::open_read_only`].
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn ensure_writable(&self) -> std::io::Result<()> {
+        if self.is_read_only() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                format!("Storage {} is opened read-only", self.config.segment_base_path),
+            ));
+        }
+        if self.writer_lock.is_none() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                format!("Storage {} has been shut down", self.config.segment_base_path),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Pick up the segments, dictionary entries and erasures the writer has persisted
+    /// since the storage was opened or last refreshed. Returns whether anything changed.
+    ///
+    /// Only needed for a storage opened with [`Self::open_read_only`]. A query that runs
+    /// into a segment the writer has merged or deleted since the last refresh fails with
+    /// `ErrorKind::NotFound` and succeeds once the storage has been refreshed.
+    pub fn refresh(&self) -> std::io::Result<bool> {
+        if !self.is_read_only() {
+            return Ok(false);
+        }
+        let changed = Self::refresh_from_directory(
+            &self.segments,
+            &self.dictionary,
+            &self.dictionary_log,
+            &self.tombstones,
+            &self.mmap_cache,
+            &self.watermarks,
+            &self.watermark_advanced,
+            &self.config,
+        )?;
+        self.erasure_progress.lock().unwrap().pending_tombstones =
+            self.tombstones.read().unwrap().entries().len();
+        Ok(changed)
+    }
+
+    /// Start a thread that refreshes a read-only storage every `interval`, until
+    /// [`Self::shutdown`]. A failed refresh is reported by [`Self::background_flush_error`]
+    /// and retried.
+    pub fn start_tailing(&mut self, interval: Duration) -> std::io::Result<()> {
+        if !self.is_read_only() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Only a read-only storage can tail the writer",
+            ));
+        }
+        let segments_clone = Arc::clone(&self.segments);
+        let dictionary_clone = Arc::clone(&self.dictionary);
+        let dictionary_log_clone = Arc::clone(&self.dictionary_log);
+        let tombstones_clone = Arc::clone(&self.tombstones);
+        let mmap_cache_clone = Arc::clone(&self.mmap_cache);
+        let watermarks_clone = Arc::clone(&self.watermarks);
+        let watermark_advanced_clone = Arc::clone(&self.watermark_advanced);
+        let shutdown_clone = Arc::clone(&self.shutdown_signal);
+        let background_error_clone = Arc::clone(&self.background_flush_error);
+        let config_clone = self.config.clone();
+
+        let handle = std::thread::spawn(move || {
+            while !*shutdown_clone.lock().unwrap() {
+                std::thread::sleep(interval);
+                let refreshed = Self::refresh_from_directory(
+                    &segments_clone,
+                    &dictionary_clone,
+                    &dictionary_log_clone,
+                    &tombstones_clone,
+                    &mmap_cache_clone,
+                    &watermarks_clone,
+                    &watermark_advanced_clone,
+                    &config_clone,
+                );
+                *background_error_clone.lock().unwrap() =
+                    refreshed.err().map(|e| format!("Refreshing the storage failed: {}", e));
+            }
+        });
+        self.flush_handle = Some(handle);
+        Ok(())
+    }
+
+    // Bring a read-only storage up to date with its directory. New segments only become
+    // visible once the writer has logged their dictionary entries, so the segments are
+    // listed before the dictionary is read.
+    #[allow(clippy::too_many_arguments)]
+    fn refresh_from_directory(
+        segments: &RwLock<Vec<EnhancedSegmentMetadata>>,
+        dictionary: &RwLock<Dictionary>,
+        dictionary_log: &Mutex<DictionaryLog>,
+        tombstones: &RwLock<Tombstones>,
+        mmap_cache: &MmapCache,
+        watermarks: &Mutex<Watermarks>,
+        watermark_advanced: &Condvar,
+        config: &StreamingConfig,
+    ) -> std::io::Result<bool> {
+        let known = segments.read().unwrap().clone();
+        let scanned = Self::scan_segments(config, true, &known)?;
+        let dictionary_changed =
+            dictionary_log.lock().unwrap().refresh(&mut dictionary.write().unwrap())?;
+        let reloaded_tombstones =
+            Tombstones::open(std::path::Path::new(&config.segment_base_path))?;
+
+        let paths = |list: &[EnhancedSegmentMetadata]| -> HashSet<String> {
+            list.iter().map(|segment| segment.data_path.clone()).collect()
+        };
+        let segments_changed = paths(&known) != paths(&scanned);
+        let tombstones_changed = {
+            let mut tombstones = tombstones.write().unwrap();
+            let changed = tombstones.entries() != reloaded_tombstones.entries();
+            if changed {
+                *tombstones = reloaded_tombstones;
+            }
+            changed
+        };
+        if segments_changed {
+            let current = paths(&scanned);
+            *segments.write().unwrap() = scanned;
+            for segment in known.iter().filter(|s| !current.contains(&s.data_path)) {
+                mmap_cache.evict(&segment.data_path);
+            }
+
+            let dictionary = dictionary.read().unwrap();
+            let segments = segments.read().unwrap();
+            let mut watermarks = watermarks.lock().unwrap();
+            if Self::observe_segments(&mut watermarks, &segments, &dictionary) {
+                watermark_advanced.notify_all();
+            }
+        }
+        Ok(segments_changed || dictionary_changed || tombstones_changed)
+    }
+
     #[doc = "Start the background flushing thread for the storage system."]
     pub fn start_background_flushing(&mut self) {
+        // A read-only storage has nothing to flush, see `start_tailing`.
+        if self.is_read_only() {
+            return;
+        }
         let batch_buffer_clone = Arc::clone(&self.batch_buffer);
         let segments_clone = Arc::clone(&self.segments);
         let shutdown_clone = Arc::clone(&self.shutdown_signal);
         let background_error_clone = Arc::clone(&self.background_flush_error);
         let config_clone = self.config.clone();
         let dictionary_clone = Arc::clone(&self.dictionary);
+        let dictionary_log_clone = Arc::clone(&self.dictionary_log);
+        let wal_clone = self.wal.clone();
+        let flush_lock_clone = Arc::clone(&self.flush_lock);
+        let mmap_cache_clone = Arc::clone(&self.mmap_cache);
+        let memory_tracker_clone = self.memory_tracker.clone();
+        let tombstones_clone = Arc::clone(&self.tombstones);
 
         let handle = std::thread::spawn(move || {
             Self::background_flush_loop(
@@ -89,17 +449,462 @@ impl StreamingSegmentedStorage {
                 background_error_clone,
                 config_clone,
                 dictionary_clone,
+                dictionary_log_clone,
+                wal_clone,
+                flush_lock_clone,
+                mmap_cache_clone,
+                memory_tracker_clone,
+                tombstones_clone,
             );
         });
 
         self.flush_handle = Some(handle);
     }
 
+    /// Tracker of the bytes buffered in memory against the configured memory budget.
+    ///
+    /// Producers can clone it and pause for [`MemoryTracker::throttle_delay`] before each
+    /// write, so that they slow down before the budget forces writes to wait or fail.
+    pub fn memory_tracker(&self) -> &MemoryTracker {
+        &self.memory_tracker
+    }
+
     /// Get a reference to the dictionary for decoding events
     pub fn get_dictionary(&self) -> &Arc<RwLock<Dictionary>> {
         &self.dictionary
     }
 
+    /// Snapshot of the metadata of every persisted segment, ordered by start timestamp.
+    pub fn segment_metadata(&self) -> Vec<EnhancedSegmentMetadata> {
+        self.segments.read().unwrap().clone()
+    }
+
+    /// Number of segment data files currently kept memory-mapped for queries.
+    pub fn mapped_segment_count(&self) -> usize {
+        self.mmap_cache.len()
+    }
+
+    /// Live breakdown of the memory held by the storage's components.
+    pub fn component_sizes(&self) -> StorageComponentSizes {
+        let batch_buffer_bytes = {
+            let buffer = self.batch_buffer.read().unwrap();
+            buffer.events.capacity() * std::mem::size_of::<Event>()
+                + buffer.streams.capacity() * std::mem::size_of::<Option<u32>>()
+        };
+        let (segments_count, index_directory_bytes) = {
+            let segments = self.segments.read().unwrap();
+            (
+                segments.len(),
+                segments.iter().map(EnhancedSegmentMetadata::heap_size_bytes).sum(),
+            )
+        };
+        let dictionary_bytes = self.dictionary.read().unwrap().heap_size_bytes();
+        let mmap_bytes = self.mmap_cache.mapped_bytes();
+
+        StorageComponentSizes {
+            batch_buffer_bytes,
+            segments_count,
+            dictionary_bytes,
+            index_directory_bytes,
+            mmap_bytes,
+            estimated_total_bytes: batch_buffer_bytes
+                + dictionary_bytes
+                + index_directory_bytes
+                + mmap_bytes,
+        }
+    }
+
+    /// Oldest event timestamp still held in segments when a retention policy is configured.
+    ///
+    /// Events before the horizon may have been deleted by retention. Returns `None` when
+    /// retention is disabled or no segment has been persisted yet.
+    pub fn retention_horizon(&self) -> Option<u64> {
+        if !self.config.retention_enabled() {
+            return None;
+        }
+        self.segments.read().unwrap().iter().map(|segment| segment.start_timstamp).min()
+    }
+
+    /// Delete the segments that fall outside the configured retention limits.
+    ///
+    /// The background flush thread runs this after every flush; it is exposed for
+    /// deployments that flush synchronously. Returns the number of deleted segments.
+    pub fn enforce_retention(&self) -> std::io::Result<usize> {
+        self.ensure_writable()?;
+        Self::apply_retention(&self.segments, &self.config, &self.mmap_cache)
+    }
+
+    /// Move the segments whose newest event is older than `archive_after_seconds`, measured
+    /// against the newest persisted event, to the archive tier.
+    ///
+    /// Each segment is rewritten into the archive directory with `archive_codec` and swapped
+    /// in for the original, which queries then read transparently, if more slowly. The
+    /// background flush thread runs this after every flush; it is exposed for deployments
+    /// that flush synchronously. Returns the number of archived segments.
+    pub fn archive(&self) -> std::io::Result<usize> {
+        self.ensure_writable()?;
+        Self::apply_tiering(
+            &self.segments,
+            &self.tombstones,
+            &self.dictionary,
+            &self.config,
+            &self.mmap_cache,
+        )
+    }
+
+    /// Merge runs of adjacent small segments into larger ones.
+    ///
+    /// The background flush thread runs this after every flush when `compaction_enabled`
+    /// is set. Pending erasures are purged as well, see [`Self::purge_erased`]. Returns the
+    /// number of segments that were merged away.
+    pub fn compact(&self) -> std::io::Result<usize> {
+        self.ensure_writable()?;
+        let merged_away = Self::run_compaction(
+            &self.segments,
+            &self.tombstones,
+            &self.dictionary,
+            &self.config,
+            &self.mmap_cache,
+        )?;
+        if !self.tombstones.read().unwrap().is_empty() {
+            self.purge_erased()?;
+        }
+        Ok(merged_away)
+    }
+
+    /// Erase every event whose subject is `subject`, given like the subject of
+    /// [`RDFEvent::new`].
+    ///
+    /// The events stop being returned by queries as soon as this returns; they are removed
+    /// from the segment files by [`Self::purge_erased`]. Events written afterwards are kept.
+    pub fn delete_by_subject(&self, subject: &str) -> std::io::Result<()> {
+        self.record_tombstone(
+            TombstoneScope::Subject,
+            RdfTerm::parse_resource(subject).dictionary_key(),
+        )
+    }
+
+    /// Erase every event in the graph `graph`, given like the graph of [`RDFEvent::new`].
+    ///
+    /// See [`Self::delete_by_subject`].
+    pub fn delete_by_graph(&self, graph: &str) -> std::io::Result<()> {
+        self.record_tombstone(TombstoneScope::Graph, RdfTerm::parse_graph(graph).dictionary_key())
+    }
+
+    /// Remove the events of every recorded deletion from the segment files, together with
+    /// the dictionary strings no remaining event refers to.
+    ///
+    /// Segments holding erased events are rewritten without them, then the dictionary is
+    /// checkpointed without the purged strings. Dictionary IDs are only protected from the
+    /// purge while they are written through the `write_rdf*` methods; callers of
+    /// [`Self::write`] must not purge concurrently. Returns the final progress, which is
+    /// also reported by [`Self::erasure_progress`] while the purge runs.
+    pub fn purge_erased(&self) -> std::io::Result<ErasureProgress> {
+        self.ensure_writable()?;
+        let _purging = self.purge_lock.lock().unwrap();
+        let result = self.run_purge();
+
+        let mut progress = self.erasure_progress.lock().unwrap();
+        progress.pending_tombstones = self.tombstones.read().unwrap().entries().len();
+        match result {
+            Ok(()) => {
+                progress.state = ErasureState::Completed;
+                Ok(progress.clone())
+            }
+            Err(err) => {
+                progress.state = ErasureState::Failed;
+                progress.error = Some(err.to_string());
+                Err(err)
+            }
+        }
+    }
+
+    /// Progress of the most recent purge and the number of deletions still to purge.
+    pub fn erasure_progress(&self) -> ErasureProgress {
+        self.erasure_progress.lock().unwrap().clone()
+    }
+
+    /// Watermark of a stream's log, or of the events written without a stream when
+    /// `stream_name` is `None`: the newest event timestamp minus `allowed_lateness_ms`.
+    ///
+    /// A range ending before the watermark only changes through late events, see
+    /// [`crate::storage::event_time`]. Returns `None` before the first event.
+    pub fn watermark(&self, stream_name: Option<&str>) -> Option<u64> {
+        let stream = self.stream_id(stream_name)?;
+        self.watermarks.lock().unwrap().watermark(stream)
+    }
+
+    /// Wait until the watermark of a stream's log passes `timestamp`, or at most `timeout`,
+    /// and return the watermark reached.
+    pub fn wait_for_watermark(
+        &self,
+        stream_name: Option<&str>,
+        timestamp: u64,
+        timeout: Duration,
+    ) -> Option<u64> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            // A stream only gets its dictionary ID with its first event, so an unknown stream
+            // is looked up again after every wait instead of waiting for a notification.
+            let stream = self.stream_id(stream_name);
+            let watermarks = self.watermarks.lock().unwrap();
+            let watermark = stream.and_then(|stream| watermarks.watermark(stream));
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if watermark.is_some_and(|watermark| watermark > timestamp) || remaining.is_zero() {
+                return watermark;
+            }
+            let wait = if stream.is_some() {
+                remaining
+            } else {
+                remaining.min(Duration::from_millis(50))
+            };
+            drop(self.watermark_advanced.wait_timeout(watermarks, wait).unwrap());
+        }
+    }
+
+    /// Number of events older than the watermark of their stream since the storage was opened.
+    pub fn late_events(&self) -> LateEventCounts {
+        self.watermarks.lock().unwrap().late_events()
+    }
+
+    // Dictionary ID of a stream name, `Some(None)` for the events without a stream and
+    // `None` for a stream that was never written.
+    #[allow(clippy::option_option)]
+    fn stream_id(&self, stream_name: Option<&str>) -> Option<Option<u32>> {
+        match stream_name {
+            None => Some(None),
+            Some(name) => {
+                self.dictionary.read().unwrap().string_to_id.get(name).map(|&id| Some(id))
+            }
+        }
+    }
+
+    // Rebuild the watermarks from the persisted segments and the events replayed from the
+    // write-ahead log.
+    fn restore_watermarks(&self) {
+        let dictionary = self.dictionary.read().unwrap();
+        let batch_buffer = self.batch_buffer.read().unwrap();
+        let segments = self.segments.read().unwrap();
+        let mut watermarks = self.watermarks.lock().unwrap();
+        Self::observe_segments(&mut watermarks, &segments, &dictionary);
+        for (event, stream) in batch_buffer.events.iter().zip(&batch_buffer.streams) {
+            watermarks.observe(*stream, event.timestamp);
+        }
+    }
+
+    // Advance the watermarks to the ends of the segments. Returns whether any advanced.
+    fn observe_segments(
+        watermarks: &mut Watermarks,
+        segments: &[EnhancedSegmentMetadata],
+        dictionary: &Dictionary,
+    ) -> bool {
+        let mut advanced = false;
+        for segment in segments.iter().filter(|s| s.end_timestamp != u64::MAX) {
+            let stream = match &segment.stream {
+                None => None,
+                Some(name) => match dictionary.string_to_id.get(name) {
+                    Some(&id) => Some(id),
+                    None => continue,
+                },
+            };
+            advanced |= watermarks.observe(stream, segment.end_timestamp);
+        }
+        advanced
+    }
+
+    // Record a tombstone for the term with the given dictionary key, covering every segment
+    // listed once the buffered events have been flushed.
+    fn record_tombstone(&self, scope: TombstoneScope, term: String) -> std::io::Result<()> {
+        self.ensure_writable()?;
+        // A term that was never written has no events to erase.
+        let Some(term_id) = self.dictionary.read().unwrap().string_to_id.get(&term).copied() else {
+            return Ok(());
+        };
+        self.flush()?;
+
+        // The segments lock keeps merges from swapping in a segment newer than the bound.
+        let pending = {
+            let segments = self.segments.write().unwrap();
+            let Some(up_to_segment) = segments.iter().filter_map(|s| s.segment_id()).max() else {
+                return Ok(());
+            };
+            let mut tombstones = self.tombstones.write().unwrap();
+            tombstones.add(Tombstone { scope, term, term_id, up_to_segment })?;
+            tombstones.entries().len()
+        };
+        self.erasure_progress.lock().unwrap().pending_tombstones = pending;
+        Ok(())
+    }
+
+    fn run_purge(&self) -> std::io::Result<()> {
+        let purged = self.tombstones.read().unwrap().entries().to_vec();
+        let covered: Vec<EnhancedSegmentMetadata> = self
+            .segments
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|segment| purged.iter().any(|t| t.covers(segment.segment_id())))
+            .cloned()
+            .collect();
+        *self.erasure_progress.lock().unwrap() = ErasureProgress {
+            state: ErasureState::Running,
+            pending_tombstones: purged.len(),
+            segments_total: covered.len(),
+            ..ErasureProgress::default()
+        };
+        if purged.is_empty() {
+            return Ok(());
+        }
+
+        // Every term of an erased event may have lost its last reference.
+        let mut candidates: HashSet<u32> = purged.iter().map(|t| t.term_id).collect();
+        for segment in &covered {
+            self.purge_segment(segment, &mut candidates)?;
+        }
+        self.tombstones.write().unwrap().remove(&purged)?;
+
+        let terms_purged = self.purge_dictionary(&candidates)?;
+        self.erasure_progress.lock().unwrap().terms_purged = terms_purged;
+        Ok(())
+    }
+
+    // Rewrite a covered segment without its erased events, collecting their terms.
+    fn purge_segment(
+        &self,
+        segment: &EnhancedSegmentMetadata,
+        candidates: &mut HashSet<u32>,
+    ) -> std::io::Result<()> {
+        loop {
+            // A segment merged away in the meantime had its erased events left out.
+            let (cursor, erased) = {
+                let segments = self.segments.read().unwrap();
+                if !segments.iter().any(|s| s.data_path == segment.data_path) {
+                    break;
+                }
+                let erased = self.tombstones.read().unwrap().erased_in(segment.segment_id());
+                (SegmentCursor::open(segment, 0, u64::MAX, None, None)?, erased)
+            };
+            let Some(erased) = erased else {
+                break;
+            };
+
+            let mut removed = 0;
+            for event in QueryIter::new(cursor.into_iter().collect(), Vec::new(), None) {
+                let event = event?;
+                if erased.matches(&event) {
+                    removed += 1;
+                    candidates.extend([event.subject, event.predicate, event.object, event.graph]);
+                }
+            }
+            if removed == 0 {
+                break;
+            }
+
+            // A merge only fails when the segment or the tombstones changed, so try again.
+            if Self::merge_segments(
+                &self.segments,
+                &self.tombstones,
+                &self.dictionary,
+                &self.config,
+                &self.mmap_cache,
+                std::slice::from_ref(segment),
+                segment.tier,
+            )? {
+                let mut progress = self.erasure_progress.lock().unwrap();
+                progress.segments_rewritten += 1;
+                progress.events_removed += removed;
+                break;
+            }
+        }
+
+        self.erasure_progress.lock().unwrap().segments_scanned += 1;
+        Ok(())
+    }
+
+    // Remove the candidate terms that no segment, buffered event or pending tombstone refers
+    // to any more from the dictionary, and checkpoint it. Returns the number of removed terms.
+    fn purge_dictionary(&self, candidates: &HashSet<u32>) -> std::io::Result<usize> {
+        let mut referenced = HashSet::new();
+        let mut scanned = HashSet::new();
+        self.collect_references(candidates, &mut referenced, &mut scanned)?;
+
+        // With writers held off, only the segments written since the first scan remain.
+        let _gate = self.write_gate.write().unwrap();
+        self.collect_references(candidates, &mut referenced, &mut scanned)?;
+
+        let removed = {
+            let mut dict = self.dictionary.write().unwrap();
+            for tombstone in self.tombstones.read().unwrap().entries() {
+                referenced.insert(tombstone.term_id);
+            }
+            for stream in self.segments.read().unwrap().iter().filter_map(|s| s.stream.as_ref()) {
+                if let Some(&id) = dict.string_to_id.get(stream) {
+                    referenced.insert(id);
+                }
+            }
+            let mut removed = 0;
+            for &id in candidates.difference(&referenced) {
+                if dict.remove(id).is_some() {
+                    removed += 1;
+                }
+            }
+            removed
+        };
+
+        // The old checkpoint and log still hold the removed strings.
+        if removed > 0 {
+            self.dictionary_log
+                .lock()
+                .unwrap()
+                .checkpoint(&self.dictionary.read().unwrap())?;
+        }
+        Ok(removed)
+    }
+
+    // Collect the candidate terms referred to by the buffered events and the segments that
+    // are not in `scanned` yet, adding those segments to `scanned`.
+    fn collect_references(
+        &self,
+        candidates: &HashSet<u32>,
+        referenced: &mut HashSet<u32>,
+        scanned: &mut HashSet<String>,
+    ) -> std::io::Result<()> {
+        let mut reference = |id: u32| {
+            if candidates.contains(&id) {
+                referenced.insert(id);
+            }
+        };
+
+        // Both locks are held together so that no event moves from the buffer into a
+        // segment unseen; the files are opened before compaction can remove them.
+        let cursors = {
+            let batch_buffer = self.batch_buffer.read().unwrap();
+            let segments = self.segments.read().unwrap();
+            for (event, stream) in batch_buffer.events.iter().zip(&batch_buffer.streams) {
+                [event.subject, event.predicate, event.object, event.graph]
+                    .into_iter()
+                    .chain(*stream)
+                    .for_each(&mut reference);
+            }
+
+            let mut cursors = Vec::new();
+            for segment in segments.iter() {
+                if scanned.insert(segment.data_path.clone()) {
+                    cursors.extend(SegmentCursor::open(segment, 0, u64::MAX, None, None)?);
+                }
+            }
+            cursors
+        };
+
+        for event in QueryIter::new(cursors, Vec::new(), None) {
+            let event = event?;
+            [event.subject, event.predicate, event.object, event.graph]
+                .into_iter()
+                .for_each(&mut reference);
+        }
+        Ok(())
+    }
+
     /// Return the most recent background flush error, if one has occurred.
     pub fn background_flush_error(&self) -> Option<String> {
         self.background_flush_error.lock().unwrap().clone()
@@ -115,24 +920,117 @@ impl StreamingSegmentedStorage {
 
     // Write an event into the storage system
     pub fn write(&self, event: Event) -> std::io::Result<()> {
-        self.ensure_background_flush_healthy()?;
-        let event_size = std::mem::size_of::<Event>();
+        self.write_to_stream_id(event, None)
+    }
 
-        {
-            let mut batch_buffer = self.batch_buffer.write().unwrap();
+    // Write an event into the log of the stream with the given dictionary ID, or without a
+    // stream when `stream` is `None`.
+    //
+    // When the memory budget is exhausted the configured backpressure policy decides whether
+    // the write waits, fails or flushes the buffer first.
+    fn write_to_stream_id(&self, event: Event, stream: Option<u32>) -> std::io::Result<()> {
+        self.ensure_writable()?;
+        loop {
+            self.ensure_background_flush_healthy()?;
+
+            {
+                let mut batch_buffer = self.batch_buffer.write().unwrap();
+
+                // Lateness is judged under the buffer lock, in the order events are buffered.
+                let late = self.watermarks.lock().unwrap().is_late(stream, event.timestamp);
+                if late && self.config.late_event_policy != LateEventPolicy::Accept {
+                    return self.refuse_late_event(&event, stream);
+                }
+
+                // The budget is checked under the buffer lock so that concurrent writers
+                // cannot overshoot it together.
+                if self.memory_tracker.has_room_for(std::mem::size_of::<Event>()) {
+                    // The event must reach the write-ahead log before it becomes visible in the buffer.
+                    // Holding the buffer lock keeps the append ordered with respect to a flush sealing the log.
+                    if let Some(wal) = &self.wal {
+                        wal.lock().unwrap().append_event(&event, stream)?;
+                    }
+
+                    let timestamp = event.timestamp;
+                    Self::buffer_event(&mut batch_buffer, event, stream);
+                    self.memory_tracker.set_buffered_bytes(batch_buffer.total_bytes);
 
-            if batch_buffer.oldest_timestamp_bound.is_none() {
-                batch_buffer.oldest_timestamp_bound = Some(event.timestamp);
+                    let mut watermarks = self.watermarks.lock().unwrap();
+                    if late {
+                        watermarks.count_late(LateEventPolicy::Accept);
+                    }
+                    if watermarks.observe(stream, timestamp) {
+                        self.watermark_advanced.notify_all();
+                    }
+                    return Ok(());
+                }
             }
 
-            batch_buffer.newest_timestamp_bound = Some(event.timestamp);
+            self.make_room_in_buffer()?;
+        }
+    }
+
+    // Drop or reject an event older than the watermark of its stream.
+    fn refuse_late_event(&self, event: &Event, stream: Option<u32>) -> std::io::Result<()> {
+        let mut watermarks = self.watermarks.lock().unwrap();
+        watermarks.count_late(self.config.late_event_policy);
+        match self.config.late_event_policy {
+            LateEventPolicy::Reject => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "Event at {} is older than the watermark {} of its stream",
+                    event.timestamp,
+                    watermarks.watermark(stream).unwrap_or_default()
+                ),
+            )),
+            _ => Ok(()),
+        }
+    }
 
-            batch_buffer.total_bytes += event_size;
+    // Apply the backpressure policy to a write that found the memory budget exhausted.
+    fn make_room_in_buffer(&self) -> std::io::Result<()> {
+        match self.config.backpressure_policy {
+            BackpressurePolicy::WouldBlock => Err(std::io::Error::new(
+                std::io::ErrorKind::WouldBlock,
+                format!(
+                    "Storage memory budget of {} bytes is exhausted",
+                    self.memory_tracker.budget_bytes().unwrap_or_default()
+                ),
+            )),
+            BackpressurePolicy::SyncFlush => self.flush(),
+            // Nothing else would ever drain the buffer without the background flush thread.
+            BackpressurePolicy::Block if self.flush_handle.is_none() => self.flush(),
+            BackpressurePolicy::Block => {
+                self.memory_tracker.wait_for_release(Duration::from_millis(100));
+                Ok(())
+            }
+        }
+    }
 
-            batch_buffer.events.push_back(event);
+    // Append an event to the batch buffer and update its bookkeeping.
+    fn buffer_event(batch_buffer: &mut BatchBuffer, event: Event, stream: Option<u32>) {
+        if batch_buffer.oldest_timestamp_bound.is_none() {
+            batch_buffer.oldest_timestamp_bound = Some(event.timestamp);
+        }
+
+        batch_buffer.newest_timestamp_bound = Some(event.timestamp);
+
+        batch_buffer.total_bytes += std::mem::size_of::<Event>();
+
+        batch_buffer.events.push_back(event);
+        batch_buffer.streams.push_back(stream);
+    }
+
+    // Record the dictionary entries assigned since `first_new_id` in the write-ahead log,
+    // so that replayed events can still be decoded if the dictionary was never saved.
+    fn log_new_terms(&self, dict: &Dictionary, first_new_id: u32) -> std::io::Result<()> {
+        if let Some(wal) = &self.wal {
+            if dict.next_id > first_new_id {
+                let terms = (first_new_id..dict.next_id)
+                    .filter_map(|id| dict.decode(id).map(|value| (id, value)));
+                wal.lock().unwrap().append_terms(terms)?;
+            }
         }
-        // Note: Synchronous flushing removed for high throughput.
-        // Background thread handles all flushing based on time limits.
         Ok(())
     }
 
@@ -146,37 +1044,73 @@ impl StreamingSegmentedStorage {
         graph: &str,
     ) -> std::io::Result<()> {
         let rdf_event = RDFEvent::new(timestamp, subject, predicate, object, graph);
-        let encoded_event = {
-            let mut dict = self.dictionary.write().unwrap();
-            rdf_event.encode(&mut dict)
-        };
-        self.write(encoded_event)
+        self.write_rdf_event(rdf_event)
     }
 
     /// User-friendly API: Write an RDFEvent directly
     pub fn write_rdf_event(&self, event: RDFEvent) -> std::io::Result<()> {
+        self.ensure_writable()?;
+        let _writing = self.write_gate.read().unwrap();
         let encoded_event = {
             let mut dict = self.dictionary.write().unwrap();
-            event.encode(&mut dict)
+            let first_new_id = dict.next_id;
+            let encoded = event.encode(&mut dict);
+            self.log_new_terms(&dict, first_new_id)?;
+            encoded
         };
         self.write(encoded_event)
     }
 
+    /// Write RDF data into the log of the named stream.
+    ///
+    /// Historical windows declared `ON LOG <stream>` only read the events of their own
+    /// stream, plus the events written without a stream.
+    pub fn write_rdf_to_stream(
+        &self,
+        stream_name: &str,
+        timestamp: u64,
+        subject: &str,
+        predicate: &str,
+        object: &str,
+        graph: &str,
+    ) -> std::io::Result<()> {
+        let rdf_event = RDFEvent::new(timestamp, subject, predicate, object, graph);
+        self.write_rdf_event_to_stream(stream_name, rdf_event)
+    }
+
+    /// Write an RDFEvent into the log of the named stream
+    pub fn write_rdf_event_to_stream(
+        &self,
+        stream_name: &str,
+        event: RDFEvent,
+    ) -> std::io::Result<()> {
+        self.ensure_writable()?;
+        let _writing = self.write_gate.read().unwrap();
+        let (encoded_event, stream_id) = {
+            let mut dict = self.dictionary.write().unwrap();
+            let first_new_id = dict.next_id;
+            let encoded = event.encode(&mut dict);
+            let stream_id = dict.encode(stream_name);
+            self.log_new_terms(&dict, first_new_id)?;
+            (encoded, stream_id)
+        };
+        self.write_to_stream_id(encoded_event, Some(stream_id))
+    }
+
     /// Force flush the current batch buffer to disk
     /// This is useful when you need to ensure data is persisted immediately
     pub fn flush(&self) -> std::io::Result<()> {
+        self.ensure_writable()?;
         self.ensure_background_flush_healthy()?;
         self.flush_batch_buffer_to_segment()?;
         self.save_dictionary()?;
         Ok(())
     }
 
-    /// Save the dictionary to disk
+    /// Append the dictionary entries that are not yet on disk to the dictionary log
     fn save_dictionary(&self) -> std::io::Result<()> {
-        let dict_path = std::path::Path::new(&self.config.segment_base_path).join("dictionary.bin");
         let dict = self.dictionary.read().unwrap();
-        dict.save_to_file(&dict_path)?;
-        Ok(())
+        self.dictionary_log.lock().unwrap().persist(&dict)
     }
 
     // Get the current timestamp in milliseconds since UNIX_EPOCH
@@ -186,157 +1120,765 @@ impl StreamingSegmentedStorage {
 
     // Flush the current batch buffer to a new segment
     fn flush_batch_buffer_to_segment(&self) -> std::io::Result<()> {
-        // Automatically extract events from the batch buffer.
-
-        let events_to_flush = {
-            let mut batch_buffer = self.batch_buffer.write().unwrap();
-            if batch_buffer.events.is_empty() {
-                return Ok(());
-            }
-
-            let events: Vec<Event> = batch_buffer.events.drain(..).collect();
-
-            batch_buffer.total_bytes = 0;
-            batch_buffer.oldest_timestamp_bound = None;
-            batch_buffer.newest_timestamp_bound = None;
-            events
-        };
-
-        let segment = self.create_segment_with_two_level_index(events_to_flush)?;
-
-        {
-            let mut segments = self.segments.write().unwrap();
-            segments.push(segment);
-        }
-
-        // Save dictionary after each flush
-        self.save_dictionary()?;
-
-        Ok(())
+        Self::flush_background(
+            &self.batch_buffer,
+            &self.segments,
+            &self.config,
+            &self.dictionary,
+            &self.dictionary_log,
+            self.wal.as_ref(),
+            &self.flush_lock,
+            &self.memory_tracker,
+        )
     }
 
-    /// Create a segment with two-level indexing from the given events
-    fn create_segment_with_two_level_index(
-        &self,
+    // Write the events to a new segment in the directory of `tier`: the data log, the sparse
+    // index blocks and the footer that records the exact index directory so it can be restored
+    // when the storage is reopened. Both files are written under temporary names and renamed
+    // into place once complete, so a crash never leaves a half-written segment behind.
+    fn write_segment(
+        config: &StreamingConfig,
         mut events: Vec<Event>,
+        stream: Option<&str>,
+        replaces: &[u64],
+        out_of_order: Option<OutOfOrderRange>,
+        dictionary: &RwLock<Dictionary>,
+        tier: StorageTier,
     ) -> std::io::Result<EnhancedSegmentMetadata> {
         events.sort_by_key(|e| e.timestamp);
 
         let segment_id = Self::generate_segment_id();
+        let directory = config.tier_directory(tier);
+        if tier == StorageTier::Archive {
+            std::fs::create_dir_all(&directory)?;
+        }
+        let codec = config.tier_codec(tier);
 
-        let data_path = format!("{}/segment-{}.log", self.config.segment_base_path, segment_id);
-        let index_path = format!("{}/segment-{}.idx", self.config.segment_base_path, segment_id);
+        let data_path = format!("{}/segment-{}.log", directory, segment_id);
+        let index_path = format!("{}/segment-{}.idx", directory, segment_id);
+        let data_tmp_path = format!("{}.tmp", data_path);
+        let index_tmp_path = format!("{}.tmp", index_path);
 
-        let mut data_file = BufWriter::new(std::fs::File::create(&data_path)?);
-        let mut index_file = BufWriter::new(std::fs::File::create(&index_path)?);
+        let mut data_file = BufWriter::new(std::fs::File::create(&data_tmp_path)?);
+        let mut index_file = BufWriter::new(std::fs::File::create(&index_tmp_path)?);
+
+        write_segment_header(&mut data_file, codec)?;
 
         let mut index_directory = Vec::new();
         let mut current_block_entries = Vec::new();
 
-        let mut current_block_min_ts = None;
         let mut current_block_max_ts = 0u64;
 
-        let mut data_offset = 0u64;
-
-        for (record_count, event) in events.iter().enumerate() {
-            let record_bytes = self.serialize_event_to_fixed_size(event);
-            data_file.write_all(&record_bytes)?;
+        let mut data_offset = SEGMENT_HEADER_SIZE;
+        let mut secondary_index = SecondaryIndex::default();
 
-            if record_count % self.config.sparse_interval == 0 {
-                let sparse_entry = (event.timestamp, data_offset);
-
-                if current_block_min_ts.is_none() {
-                    current_block_min_ts = Some(event.timestamp);
-                }
+        // Every run of `sparse_interval` records forms a checksummed data block with one
+        // sparse index entry.
+        for (block_number, chunk) in events.chunks(config.sparse_interval).enumerate() {
+            let block = codec.encode_block(chunk);
+            for event in chunk {
+                secondary_index.add(block_number as u32, event);
+            }
+            data_file.write_all(&block)?;
 
-                current_block_max_ts = event.timestamp;
-                current_block_entries.push(sparse_entry);
+            current_block_entries.push(IndexEntry {
+                timestamp: chunk[0].timestamp,
+                offset: data_offset,
+                length: block.len() as u32,
+                checksum: crc32fast::hash(&block),
+            });
+            current_block_max_ts = chunk[chunk.len() - 1].timestamp;
+            data_offset += block.len() as u64;
 
-                if current_block_entries.len() >= self.config.entries_per_index_block {
-                    let block_metadata = self.flush_index_block(
-                        &mut index_file,
-                        &current_block_entries,
-                        current_block_min_ts.unwrap(),
-                        current_block_max_ts,
-                    )?;
+            if current_block_entries.len() >= config.entries_per_index_block {
+                let block_metadata = Self::flush_index_block_static(
+                    &mut index_file,
+                    &current_block_entries,
+                    current_block_max_ts,
+                )?;
 
-                    index_directory.push(block_metadata);
+                index_directory.push(block_metadata);
 
-                    current_block_entries.clear();
-                    current_block_min_ts = None;
-                }
+                current_block_entries.clear();
             }
-            data_offset += record_bytes.len() as u64;
         }
 
         if !current_block_entries.is_empty() {
-            let block_metadata = self.flush_index_block(
+            let block_metadata = Self::flush_index_block_static(
                 &mut index_file,
                 &current_block_entries,
-                current_block_min_ts.unwrap(),
                 current_block_max_ts,
             )?;
 
             index_directory.push(block_metadata);
         }
 
-        data_file.flush()?;
-        index_file.flush()?;
+        let secondary_index = if config.secondary_indexes_enabled {
+            let encoded = secondary_index.encode();
+            let offset = index_file.stream_position()?;
+            index_file.write_all(&encoded)?;
+            Some(SectionLocation {
+                offset,
+                length: encoded.len() as u64,
+                checksum: crc32fast::hash(&encoded),
+            })
+        } else {
+            None
+        };
 
-        Ok(EnhancedSegmentMetadata {
-            start_timstamp: events.first().unwrap().timestamp,
+        let rollups = if config.rollup_bucket_ms > 0 {
+            let mut rollups = Rollups::new(config.rollup_bucket_ms);
+            rollups.add_events(&events, &dictionary.read().unwrap());
+            let encoded = rollups.encode();
+            let offset = index_file.stream_position()?;
+            index_file.write_all(&encoded)?;
+            Some(SectionLocation {
+                offset,
+                length: encoded.len() as u64,
+                checksum: crc32fast::hash(&encoded),
+            })
+        } else {
+            None
+        };
+
+        let bloom_filter = if config.bloom_filter_false_positive_rate > 0.0 {
+            let terms: HashSet<u32> =
+                events.iter().flat_map(|event| [event.subject, event.object]).collect();
+            let mut filter =
+                BloomFilter::with_capacity(terms.len(), config.bloom_filter_false_positive_rate);
+            for term in terms {
+                filter.insert(term);
+            }
+            let encoded = filter.encode();
+            let offset = index_file.stream_position()?;
+            index_file.write_all(&encoded)?;
+            let location = SectionLocation {
+                offset,
+                length: encoded.len() as u64,
+                checksum: crc32fast::hash(&encoded),
+            };
+            Some((location, Arc::new(filter)))
+        } else {
+            None
+        };
+
+        let zone_map = if config.zone_maps_enabled {
+            let mut zone_map = ZoneMap::default();
+            zone_map.add_events(&events, &dictionary.read().unwrap());
+            let encoded = zone_map.encode();
+            let offset = index_file.stream_position()?;
+            index_file.write_all(&encoded)?;
+            let location = SectionLocation {
+                offset,
+                length: encoded.len() as u64,
+                checksum: crc32fast::hash(&encoded),
+            };
+            Some((location, Arc::new(zone_map)))
+        } else {
+            None
+        };
+
+        let footer = SegmentFooter {
+            version: FOOTER_VERSION,
+            start_timestamp: events.first().unwrap().timestamp,
             end_timestamp: events.last().unwrap().timestamp,
-            data_path,
-            index_path,
             record_count: events.len() as u64,
             index_directory,
+            replaces: replaces.to_vec(),
+            stream: stream.map(str::to_string),
+            secondary_index,
+            out_of_order,
+            rollups,
+            bloom_filter: bloom_filter.as_ref().map(|(location, _)| *location),
+            zone_map: zone_map.as_ref().map(|(location, _)| *location),
+        };
+        footer.write_to(&mut index_file)?;
+
+        // The index goes into place first: a data file is only ever visible with its index.
+        Self::persist_segment_file(index_file, &index_tmp_path, &index_path)?;
+        Self::persist_segment_file(data_file, &data_tmp_path, &data_path)?;
+
+        Ok(EnhancedSegmentMetadata {
+            start_timstamp: footer.start_timestamp,
+            end_timestamp: footer.end_timestamp,
+            data_path,
+            index_path,
+            record_count: footer.record_count,
+            index_directory: footer.index_directory,
+            format_version: SEGMENT_VERSION,
+            codec,
+            stream: footer.stream,
+            secondary_index: footer.secondary_index,
+            out_of_order: footer.out_of_order,
+            rollups: footer.rollups,
+            replaces: footer.replaces,
+            bloom_filter: bloom_filter.map(|(_, filter)| filter),
+            zone_map: zone_map.map(|(_, zone_map)| zone_map),
+            tier,
         })
     }
 
-    /// Flush an index block to the index file
-    fn flush_index_block(
-        &self,
-        index_file: &mut BufWriter<std::fs::File>,
-        entries: &[(u64, u64)],
-        min_ts: u64,
-        max_ts: u64,
-    ) -> std::io::Result<IndexBlock> {
-        Self::flush_index_block_static(index_file, entries, min_ts, max_ts)
+    // Sync a fully written segment file and atomically move it to its final name.
+    fn persist_segment_file(
+        file: BufWriter<std::fs::File>,
+        tmp_path: &str,
+        path: &str,
+    ) -> std::io::Result<()> {
+        let file = file.into_inner().map_err(|e| e.into_error())?;
+        file.sync_all()?;
+        std::fs::rename(tmp_path, path)
     }
 
     // Query events within a timestamp range from the storage system but result in encoded Events and not RDFEvents.
     pub fn query(&self, start_timestamp: u64, end_timestamp: u64) -> std::io::Result<Vec<Event>> {
+        self.query_partitions(None, start_timestamp, end_timestamp, None)
+    }
+
+    /// Query the log of a single stream.
+    ///
+    /// Returns the events written to `stream_name` together with the events written without
+    /// a stream, which belong to every log.
+    pub fn query_stream(
+        &self,
+        stream_name: &str,
+        start_timestamp: u64,
+        end_timestamp: u64,
+    ) -> std::io::Result<Vec<Event>> {
+        self.query_partitions(Some(stream_name), start_timestamp, end_timestamp, None)
+    }
+
+    /// Query the events matching any of the patterns.
+    ///
+    /// Segments written with a secondary index only read the data blocks that hold one of
+    /// the constant subjects or predicates; other segments are scanned and filtered.
+    /// Segments whose Bloom filter rules out the subject of every pattern are skipped, as
+    /// are segments holding no value of a pattern's predicate within its object range.
+    pub fn query_filtered(
+        &self,
+        start_timestamp: u64,
+        end_timestamp: u64,
+        patterns: &[TriplePattern],
+    ) -> std::io::Result<Vec<Event>> {
+        self.query_partitions(None, start_timestamp, end_timestamp, Some(patterns))
+    }
+
+    /// Query the events of a single stream's log matching any of the patterns.
+    pub fn query_stream_filtered(
+        &self,
+        stream_name: &str,
+        start_timestamp: u64,
+        end_timestamp: u64,
+        patterns: &[TriplePattern],
+    ) -> std::io::Result<Vec<Event>> {
+        self.query_partitions(Some(stream_name), start_timestamp, end_timestamp, Some(patterns))
+    }
+
+    /// Query the events whose subject is `subject`, given like the subject of
+    /// [`RDFEvent::new`], such as the readings of one sensor.
+    ///
+    /// Segments whose Bloom filter rules the subject out are skipped without being read.
+    pub fn query_subject(
+        &self,
+        subject: &str,
+        start_timestamp: u64,
+        end_timestamp: u64,
+    ) -> std::io::Result<Vec<Event>> {
+        let patterns = [Self::subject_pattern(subject)];
+        self.query_partitions(None, start_timestamp, end_timestamp, Some(&patterns))
+    }
+
+    /// Query the events of a single stream's log whose subject is `subject`, see
+    /// [`Self::query_subject`].
+    pub fn query_stream_subject(
+        &self,
+        stream_name: &str,
+        subject: &str,
+        start_timestamp: u64,
+        end_timestamp: u64,
+    ) -> std::io::Result<Vec<Event>> {
+        let patterns = [Self::subject_pattern(subject)];
+        self.query_partitions(Some(stream_name), start_timestamp, end_timestamp, Some(&patterns))
+    }
+
+    fn subject_pattern(subject: &str) -> TriplePattern {
+        TriplePattern::new(Some(&RdfTerm::parse_resource(subject).dictionary_key()), None)
+    }
+
+    /// Lazily iterate over the events in a timestamp range in timestamp order.
+    ///
+    /// Unlike [`Self::query`], the events of the segments are read one data block at a
+    /// time while the iterator is consumed, so memory use stays bounded for long ranges.
+    /// Events buffered when the iterator is created are included; later writes are not.
+    /// With `query_scan_threads` above one, the next few segments are instead scanned
+    /// whole on the storage's worker pool while earlier events are consumed.
+    pub fn query_iter(
+        &self,
+        start_timestamp: u64,
+        end_timestamp: u64,
+    ) -> std::io::Result<QueryIter> {
+        self.query_iter_partitions(None, start_timestamp, end_timestamp, None)
+    }
+
+    /// Lazily iterate over the log of a single stream, see [`Self::query_stream`].
+    pub fn query_stream_iter(
+        &self,
+        stream_name: &str,
+        start_timestamp: u64,
+        end_timestamp: u64,
+    ) -> std::io::Result<QueryIter> {
+        self.query_iter_partitions(Some(stream_name), start_timestamp, end_timestamp, None)
+    }
+
+    /// Lazily iterate over the events of a stream's log matching any of the patterns.
+    pub fn query_stream_filtered_iter(
+        &self,
+        stream_name: &str,
+        start_timestamp: u64,
+        end_timestamp: u64,
+        patterns: &[TriplePattern],
+    ) -> std::io::Result<QueryIter> {
+        self.query_iter_partitions(
+            Some(stream_name),
+            start_timestamp,
+            end_timestamp,
+            Some(patterns),
+        )
+    }
+
+    // Query every stream when `stream_name` is `None`, otherwise only the named stream and
+    // the events without a stream. Without patterns every event in the range matches.
+    fn query_partitions(
+        &self,
+        stream_name: Option<&str>,
+        start_timestamp: u64,
+        end_timestamp: u64,
+        patterns: Option<&[TriplePattern]>,
+    ) -> std::io::Result<Vec<Event>> {
+        self.query_iter_partitions(stream_name, start_timestamp, end_timestamp, patterns)?
+            .collect()
+    }
+
+    pub(crate) fn query_iter_partitions(
+        &self,
+        stream_name: Option<&str>,
+        start_timestamp: u64,
+        end_timestamp: u64,
+        patterns: Option<&[TriplePattern]>,
+    ) -> std::io::Result<QueryIter> {
         self.ensure_background_flush_healthy()?;
-        let mut results = Vec::new();
 
-        // First try to query the immediate batch buffer which has the fastest visibility.
+        // A stream that was never written has no dictionary ID, so only untagged buffered
+        // events can match it.
+        let (stream_id, filter) = {
+            let dict = self.dictionary.read().unwrap();
+            (
+                stream_name.map(|name| dict.string_to_id.get(name).copied()),
+                patterns.map(|patterns| EventFilter::resolve(patterns, &dict)),
+            )
+        };
+        if filter.as_ref().is_some_and(|filter| filter.patterns.is_empty()) {
+            return Ok(QueryIter::empty());
+        }
+        let matches = |event: &Event| filter.as_ref().is_none_or(|filter| filter.matches(event));
+
+        // The segment lock is taken before the buffer lock is released, so a flush cannot move
+        // events from the buffer into a segment between the two reads. The segment files are
+        // opened under the lock as well, so compaction cannot remove them first.
+        let batch_buffer = self.batch_buffer.read().unwrap();
+        let segments = self.segments.read().unwrap();
+
+        let buffered = Self::select_buffered(
+            &batch_buffer,
+            stream_id,
+            start_timestamp,
+            end_timestamp,
+            matches,
+        );
+        drop(batch_buffer);
+
+        let cursors = self.open_cursors(
+            segments.iter().filter(|segment| Self::segment_in_stream(segment, stream_name)),
+            start_timestamp,
+            end_timestamp,
+            filter.as_ref(),
+        )?;
+        drop(segments);
+
+        let events = QueryIter::new(cursors, buffered, filter);
+        Ok(match &self.scan_pool {
+            Some(pool) => events.scanned_by(Arc::clone(pool)),
+            None => events,
+        })
+    }
+
+    // The buffered events of a stream in a timestamp range that match, in timestamp order.
+    // `stream_id` is `Some(None)` for a stream that was never written.
+    #[allow(clippy::option_option)]
+    fn select_buffered(
+        batch_buffer: &BatchBuffer,
+        stream_id: Option<Option<u32>>,
+        start_timestamp: u64,
+        end_timestamp: u64,
+        matches: impl Fn(&Event) -> bool,
+    ) -> Vec<Event> {
+        let mut buffered = Vec::new();
+        for (event, stream) in batch_buffer.events.iter().zip(&batch_buffer.streams) {
+            let in_stream = match (stream_id, stream) {
+                (None, _) | (_, None) => true,
+                (Some(wanted), Some(stream)) => wanted == Some(*stream),
+            };
+            if in_stream
+                && event.timestamp >= start_timestamp
+                && event.timestamp <= end_timestamp
+                && matches(event)
+            {
+                buffered.push(event.clone());
+            }
+        }
+        buffered.sort_by_key(|e| e.timestamp);
+        buffered
+    }
+
+    // Whether a segment belongs to the log of the stream; segments without a stream and
+    // queries without one match any.
+    fn segment_in_stream(segment: &EnhancedSegmentMetadata, stream_name: Option<&str>) -> bool {
+        match (stream_name, &segment.stream) {
+            (None, _) | (_, None) => true,
+            (Some(wanted), Some(stream)) => wanted == stream,
+        }
+    }
+
+    // Whether the segment's Bloom filter or zone map shows that it holds no event matching
+    // the filter, because every pattern names a subject the segment does not hold or bounds
+    // the values of a predicate the segment holds no value of within the bounds.
+    fn filter_rules_out(segment: &EnhancedSegmentMetadata, filter: Option<&EventFilter>) -> bool {
+        let Some(filter) = filter else {
+            return false;
+        };
+        filter.patterns.iter().all(|pattern| {
+            let by_subject = pattern
+                .subject
+                .zip(segment.bloom_filter.as_ref())
+                .is_some_and(|(subject, bloom_filter)| !bloom_filter.may_contain(subject));
+            let by_value = match (pattern.predicate, &pattern.object_range, &segment.zone_map) {
+                (Some(predicate), Some(range), Some(zone_map)) => {
+                    zone_map.rules_out(predicate, range)
+                }
+                _ => false,
+            };
+            by_subject || by_value
+        })
+    }
 
+    // Open a cursor over every segment overlapping the range that skips erased events.
+    fn open_cursors<'a>(
+        &self,
+        segments: impl IntoIterator<Item = &'a EnhancedSegmentMetadata>,
+        start_timestamp: u64,
+        end_timestamp: u64,
+        filter: Option<&EventFilter>,
+    ) -> std::io::Result<Vec<SegmentCursor>> {
+        let tombstones = self.tombstones.read().unwrap();
+        let mut cursors = Vec::new();
+        for segment in segments {
+            if !self.segment_overlaps(segment, start_timestamp, end_timestamp)
+                || Self::filter_rules_out(segment, filter)
+            {
+                continue;
+            }
+            let cursor = SegmentCursor::open(
+                segment,
+                start_timestamp,
+                end_timestamp,
+                filter,
+                Some(&self.mmap_cache),
+            )?;
+            cursors.extend(
+                cursor.map(|cursor| cursor.erasing(tombstones.erased_in(segment.segment_id()))),
+            );
+        }
+        Ok(cursors)
+    }
+
+    /// Aggregates of the events in a timestamp range per time bucket, predicate, subject
+    /// and graph, see [`crate::storage::rollup`]. With a stream name, only that stream's
+    /// log is rolled up, as in [`Self::query_stream`].
+    ///
+    /// Whole buckets are read from the segments' rollups. The buckets the range only
+    /// partly covers, buffered events and segments without usable rollups, e.g. with
+    /// pending erasures, are rolled up from their events, so the result is exact and the
+    /// first and last bucket only aggregate the events inside the range. Returns `None`
+    /// when `rollup_bucket_ms` is not configured.
+    pub fn query_rollups(
+        &self,
+        stream_name: Option<&str>,
+        start_timestamp: u64,
+        end_timestamp: u64,
+    ) -> std::io::Result<Option<Vec<Rollup>>> {
+        let bucket_ms = self.config.rollup_bucket_ms;
+        if bucket_ms == 0 {
+            return Ok(None);
+        }
+        self.ensure_background_flush_healthy()?;
+
+        let mut rollups = Rollups::new(bucket_ms);
+        if start_timestamp <= end_timestamp {
+            // Whole buckets run from `first_bucket` up to `end_bucket`, exclusive.
+            let first_bucket = start_timestamp.div_ceil(bucket_ms).saturating_mul(bucket_ms);
+            let end_bucket = end_timestamp.saturating_add(1) / bucket_ms * bucket_ms;
+            let mut partial_ranges = Vec::new();
+            if first_bucket < end_bucket {
+                if start_timestamp < first_bucket {
+                    partial_ranges.push((start_timestamp, first_bucket - 1));
+                }
+                if end_bucket <= end_timestamp {
+                    partial_ranges.push((end_bucket, end_timestamp));
+                }
+                self.roll_up_whole_buckets(
+                    stream_name,
+                    first_bucket,
+                    end_bucket - 1,
+                    &mut rollups,
+                )?;
+            } else {
+                partial_ranges.push((start_timestamp, end_timestamp));
+            }
+            for (start, end) in partial_ranges {
+                let events = self.query_iter_partitions(stream_name, start, end, None)?;
+                self.roll_up_events(events, &mut rollups)?;
+            }
+        }
+
+        let dict = self.dictionary.read().unwrap();
+        Ok(Some(
+            rollups
+                .rows
+                .into_iter()
+                .filter_map(|((bucket_start, predicate, subject, graph), stats)| {
+                    Some(Rollup {
+                        bucket_start,
+                        predicate: dict.decode(predicate)?.to_string(),
+                        subject: dict.decode(subject)?.to_string(),
+                        graph: dict.decode(graph)?.to_string(),
+                        stats,
+                    })
+                })
+                .collect(),
+        ))
+    }
+
+    // Roll up a range of whole buckets, reading the stored rollups of every segment that
+    // has them with the configured bucket width and no pending erasures. Like a query, the
+    // buffer and the segments are read under their locks together.
+    fn roll_up_whole_buckets(
+        &self,
+        stream_name: Option<&str>,
+        start_timestamp: u64,
+        end_timestamp: u64,
+        rollups: &mut Rollups,
+    ) -> std::io::Result<()> {
+        let stream_id = {
+            let dict = self.dictionary.read().unwrap();
+            stream_name.map(|name| dict.string_to_id.get(name).copied())
+        };
+        let batch_buffer = self.batch_buffer.read().unwrap();
+        let segments = self.segments.read().unwrap();
+        let buffered =
+            Self::select_buffered(&batch_buffer, stream_id, start_timestamp, end_timestamp, |_| {
+                true
+            });
+        drop(batch_buffer);
+
+        let mut unrolled = Vec::new();
         {
-            let batch_buffer = self.batch_buffer.read().unwrap();
+            let tombstones = self.tombstones.read().unwrap();
+            for segment in segments.iter().filter(|segment| {
+                Self::segment_in_stream(segment, stream_name)
+                    && self.segment_overlaps(segment, start_timestamp, end_timestamp)
+            }) {
+                let stored = match &segment.rollups {
+                    Some(location) if tombstones.erased_in(segment.segment_id()).is_none() => {
+                        let mut index_file = std::fs::File::open(&segment.index_path)?;
+                        Some(Self::read_rollups(&mut index_file, &segment.index_path, location)?)
+                            .filter(|stored| stored.bucket_ms == rollups.bucket_ms)
+                    }
+                    _ => None,
+                };
+                match stored {
+                    Some(stored) => {
+                        let rows = stored.rows.range(
+                            (start_timestamp, 0, 0, 0)
+                                ..=(end_timestamp, u32::MAX, u32::MAX, u32::MAX),
+                        );
+                        for (key, stats) in rows {
+                            rollups.rows.entry(*key).or_default().merge(stats);
+                        }
+                    }
+                    None => unrolled.push(segment),
+                }
+            }
+        }
+        let cursors = self.open_cursors(unrolled, start_timestamp, end_timestamp, None)?;
+        drop(segments);
+
+        self.roll_up_events(QueryIter::new(cursors, buffered, None), rollups)
+    }
+
+    fn roll_up_events(&self, events: QueryIter, rollups: &mut Rollups) -> std::io::Result<()> {
+        for chunk in events.chunks(DECODE_CHUNK_EVENTS) {
+            let chunk = chunk?;
+            rollups.add_events(&chunk, &self.dictionary.read().unwrap());
+        }
+        Ok(())
+    }
+
+    /// Write the events in a timestamp range into an archive, see [`crate::storage::archive`].
+    ///
+    /// Events keep their stream; the events without a stream come first, then the events
+    /// of each stream in turn, each in timestamp order. Buffered events are included.
+    /// Returns the number of exported events.
+    pub fn export<W: Write>(
+        &self,
+        start_timestamp: u64,
+        end_timestamp: u64,
+        writer: W,
+        format: ArchiveFormat,
+    ) -> std::io::Result<u64> {
+        self.ensure_background_flush_healthy()?;
 
-            for event in &batch_buffer.events {
+        // Both locks are held together, as in a query, so every event is read exactly once.
+        let mut streams: BTreeMap<Option<String>, (Vec<&EnhancedSegmentMetadata>, Vec<Event>)> =
+            BTreeMap::new();
+        let batch_buffer = self.batch_buffer.read().unwrap();
+        let segments = self.segments.read().unwrap();
+        {
+            let dict = self.dictionary.read().unwrap();
+            for (event, stream) in batch_buffer.events.iter().zip(&batch_buffer.streams) {
                 if event.timestamp >= start_timestamp && event.timestamp <= end_timestamp {
-                    results.push(event.clone());
+                    let stream = stream.and_then(|id| dict.decode(id)).map(str::to_string);
+                    streams.entry(stream).or_default().1.push(event.clone());
+                }
+            }
+        }
+        drop(batch_buffer);
+        for segment in segments.iter() {
+            streams.entry(segment.stream.clone()).or_default().0.push(segment);
+        }
+        let mut partitions = Vec::with_capacity(streams.len());
+        for (stream, (stream_segments, mut buffered)) in streams {
+            buffered.sort_by_key(|e| e.timestamp);
+            let cursors =
+                self.open_cursors(stream_segments, start_timestamp, end_timestamp, None)?;
+            partitions.push((stream, QueryIter::new(cursors, buffered, None)));
+        }
+        drop(segments);
+
+        let mut archive = ArchiveWriter::new(writer, format)?;
+        for (stream, events) in partitions {
+            for chunk in events.chunks(DECODE_CHUNK_EVENTS) {
+                let chunk = chunk?;
+                let decoded: Vec<RDFEvent> = {
+                    let dict = self.dictionary.read().unwrap();
+                    chunk.iter().map(|event| event.decode(&dict)).collect()
+                };
+                for event in &decoded {
+                    archive.write_event(event, stream.as_deref())?;
                 }
             }
         }
+        let exported = archive.written();
+        archive.finish()?.flush()?;
+        Ok(exported)
+    }
 
-        // Then querying the relevant segment with a two level indexing
-        {
-            let segments = self.segments.read().unwrap();
+    /// Write every event of an archive into the storage, in its stream, and flush them.
+    /// Returns the number of imported events.
+    pub fn import<R: BufRead>(&self, reader: R, format: ArchiveFormat) -> std::io::Result<u64> {
+        self.ensure_writable()?;
+        let imported = read_archive(reader, format, |event, stream| match stream {
+            Some(stream) => self.write_rdf_event_to_stream(stream, event),
+            None => self.write_rdf_event(event),
+        })?;
+        self.flush()?;
+        Ok(imported)
+    }
 
-            for segment in segments.iter() {
-                if self.segment_overlaps(segment, start_timestamp, end_timestamp) {
-                    let segment_results =
-                        self.query_segment_two_level(segment, start_timestamp, end_timestamp)?;
-                    results.extend(segment_results);
+    /// Write a consistent copy of the storage into the new or empty directory
+    /// `destination`, which [`Self::new`] can open.
+    ///
+    /// The buffered events are flushed first; events written while the snapshot runs may
+    /// be left out. Segments are hard-linked where possible and copied otherwise. Flushes,
+    /// compaction and purges wait until the segments are in place, so a snapshot across
+    /// file systems holds them up for the duration of the copy.
+    pub fn snapshot(&self, destination: &std::path::Path) -> std::io::Result<SnapshotManifest> {
+        self.ensure_writable()?;
+        prepare_destination(destination)?;
+        let _purging = self.purge_lock.lock().unwrap();
+        self.flush()?;
+
+        let segments = self.segments.read().unwrap();
+        let tombstones = self.tombstones.read().unwrap();
+        let mut manifest = SnapshotManifest {
+            created_at: Self::current_timestamp(),
+            segments: Vec::with_capacity(segments.len()),
+            record_count: 0,
+            dictionary_entries: 0,
+            pending_tombstones: tombstones.entries().len(),
+            files_linked: 0,
+            files_copied: 0,
+        };
+
+        for segment in segments.iter() {
+            let directory = match segment.tier {
+                StorageTier::Hot => std::path::PathBuf::new(),
+                StorageTier::Archive => std::path::PathBuf::from(ARCHIVE_DIRECTORY),
+            };
+            if segment.tier == StorageTier::Archive {
+                std::fs::create_dir_all(destination.join(&directory))?;
+            }
+            let mut file_names = Vec::with_capacity(2);
+            for path in [&segment.data_path, &segment.index_path] {
+                let path = std::path::Path::new(path);
+                let file_name = path.file_name().ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("Segment path {} has no file name", path.display()),
+                    )
+                })?;
+                let file_name = directory.join(file_name);
+                if link_or_copy(path, &destination.join(&file_name))? {
+                    manifest.files_linked += 1;
+                } else {
+                    manifest.files_copied += 1;
                 }
+                file_names.push(file_name.to_string_lossy().into_owned());
             }
+            let index_file = file_names.pop().unwrap_or_default();
+            let data_file = file_names.pop().unwrap_or_default();
+            manifest.record_count += segment.record_count;
+            manifest.segments.push(SnapshotSegment {
+                data_file,
+                index_file,
+                start_timestamp: segment.start_timstamp,
+                end_timestamp: segment.end_timestamp,
+                record_count: segment.record_count,
+                stream: segment.stream.clone(),
+                tier: segment.tier,
+            });
         }
 
-        results.sort_by_key(|e| e.timestamp);
+        // The dictionary only grows while purges are held off, so it covers every segment.
+        {
+            let dictionary = self.dictionary.read().unwrap();
+            DictionaryLog::write_checkpoint(destination, &dictionary)?;
+            manifest.dictionary_entries = dictionary.size();
+        }
+        tombstones.save_into(destination)?;
+        drop(tombstones);
+        drop(segments);
 
-        Ok(results)
+        manifest.write(destination)?;
+        Ok(manifest)
     }
 
     /// User-friendly API: Query and return RDF events with URI strings
@@ -351,115 +1893,129 @@ impl StreamingSegmentedStorage {
         Ok(encoded_events.into_iter().map(|event| event.decode(&dict)).collect())
     }
 
-    // Query a segment using two-level indexing
-    fn query_segment_two_level(
-        &self,
-        segment: &EnhancedSegmentMetadata,
-        start_timestamp: u64,
-        end_timestamp: u64,
-    ) -> std::io::Result<Vec<Event>> {
-        // If we have index directory, use two-level indexing
-        if !segment.index_directory.is_empty() {
-            // Step 1 : Find relevant index blocks using in-memory directory
-            let relevant_blocks: Vec<&IndexBlock> = segment
-                .index_directory
-                .iter()
-                .filter(|block| {
-                    block.min_timestamp <= end_timestamp && block.max_timestamp >= start_timestamp
-                })
-                .collect();
-
-            if relevant_blocks.is_empty() {
-                return Ok(Vec::new());
-            }
+    // Read the secondary index section of an index file and check it against its checksum.
+    pub(crate) fn read_secondary_index(
+        index_file: &mut std::fs::File,
+        index_path: &str,
+        location: &SectionLocation,
+    ) -> std::io::Result<SecondaryIndex> {
+        let buffer = Self::read_section(index_file, index_path, location, "secondary index")?;
+        SecondaryIndex::decode(&buffer)
+    }
 
-            // Step 2 : Load only the relevant blocks from the disk
-            let sparse_entries =
-                self.load_relevant_index_blocks(&segment.index_path, &relevant_blocks)?;
+    // Read the rollup section of an index file and check it against its checksum.
+    fn read_rollups(
+        index_file: &mut std::fs::File,
+        index_path: &str,
+        location: &SectionLocation,
+    ) -> std::io::Result<Rollups> {
+        let buffer = Self::read_section(index_file, index_path, location, "rollups")?;
+        Rollups::decode(&buffer)
+    }
 
-            // If no entries loaded, fall back to full scan
-            if sparse_entries.is_empty() {
-                return self.scan_data_from_offset(
-                    &segment.data_path,
-                    0,
-                    start_timestamp,
-                    end_timestamp,
-                );
-            }
+    // Read the Bloom filter section of an index file and check it against its checksum.
+    fn read_bloom_filter(
+        index_file: &mut std::fs::File,
+        index_path: &str,
+        location: &SectionLocation,
+    ) -> std::io::Result<BloomFilter> {
+        let buffer = Self::read_section(index_file, index_path, location, "Bloom filter")?;
+        BloomFilter::decode(&buffer)
+    }
 
-            // Step 3 : Binary search the loaded entries
-            let lb = sparse_entries.partition_point(|(ts, _)| *ts < start_timestamp);
-            let start_position = lb.saturating_sub(1);
-            let start_offset = sparse_entries[start_position].1;
+    // Read the zone map section of an index file and check it against its checksum.
+    fn read_zone_map(
+        index_file: &mut std::fs::File,
+        index_path: &str,
+        location: &SectionLocation,
+    ) -> std::io::Result<ZoneMap> {
+        let buffer = Self::read_section(index_file, index_path, location, "zone map")?;
+        ZoneMap::decode(&buffer)
+    }
 
-            // Step 4 : Sequential Scan from the checkpoint
-            self.scan_data_from_offset(
-                &segment.data_path,
-                start_offset,
-                start_timestamp,
-                end_timestamp,
-            )
-        } else {
-            // Fallback: Full scan of the data file (for segments without loaded index)
-            self.scan_data_from_offset(&segment.data_path, 0, start_timestamp, end_timestamp)
+    fn read_section(
+        index_file: &mut std::fs::File,
+        index_path: &str,
+        location: &SectionLocation,
+        section: &str,
+    ) -> std::io::Result<Vec<u8>> {
+        index_file.seek(SeekFrom::Start(location.offset))?;
+
+        let mut buffer = vec![0u8; location.length as usize];
+        index_file.read_exact(&mut buffer)?;
+
+        if crc32fast::hash(&buffer) != location.checksum {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Checksum mismatch in {} of {}", section, index_path),
+            ));
         }
+        Ok(buffer)
     }
 
-    // Load only the relevant index blocks from disk
-    fn load_relevant_index_blocks(
-        &self,
+    // Read the entries of one index block, checking them against the block checksum.
+    pub(crate) fn read_index_block(
+        index_file: &mut std::fs::File,
         index_path: &str,
-        blocks: &[&IndexBlock],
-    ) -> std::io::Result<Vec<(u64, u64)>> {
-        let mut index_file = std::fs::File::open(index_path)?;
-        let mut sparse_entries = Vec::new();
-
-        for block in blocks {
-            index_file.seek(SeekFrom::Start(block.file_offset))?;
-
-            let block_size = block.entry_count as usize * 16; // 16 bytes per entry.
-            let mut buffer = vec![0u8; block_size];
-            index_file.read_exact(&mut buffer)?;
-
-            // Parse the entries.
-
-            for chunk in buffer.chunks_exact(16) {
-                let timestamp = u64::from_le_bytes(chunk[0..8].try_into().unwrap());
-                let offset = u64::from_be_bytes(chunk[8..16].try_into().unwrap());
-                sparse_entries.push((timestamp, offset));
-            }
+        block: &IndexBlock,
+        format_version: u32,
+    ) -> std::io::Result<Vec<IndexEntry>> {
+        index_file.seek(SeekFrom::Start(block.file_offset))?;
+
+        let block_size = block.entry_count as usize * IndexEntry::size_for_version(format_version);
+        let mut buffer = vec![0u8; block_size];
+        index_file.read_exact(&mut buffer)?;
+
+        if format_version != LEGACY_SEGMENT_VERSION && crc32fast::hash(&buffer) != block.checksum {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "Checksum mismatch in index block at offset {} of {}",
+                    block.file_offset, index_path
+                ),
+            ));
         }
 
-        sparse_entries.sort_by_key(|&(ts, _)| ts);
-        Ok(sparse_entries)
+        Ok(IndexEntry::decode_all(&buffer, format_version))
     }
 
-    // Scan data file from a given offset to retrieve events within the timestamp range
-    fn scan_data_from_offset(
-        &self,
+    // Read one data block through file IO and decode it.
+    pub(crate) fn read_data_block(
+        data_file: &mut std::fs::File,
         data_path: &str,
-        start_offset: u64,
-        start_timestamp: u64,
-        end_timestamp: u64,
+        entry: &IndexEntry,
+        codec: SegmentCodec,
     ) -> std::io::Result<Vec<Event>> {
-        let mut file = std::fs::File::open(data_path)?;
-        file.seek(SeekFrom::Start(start_offset))?;
+        data_file.seek(SeekFrom::Start(entry.offset))?;
 
-        let mut results = Vec::new();
-        let mut record = [0u8; RECORD_SIZE];
-
-        while file.read_exact(&mut record).is_ok() {
-            let (timestamp, subject, predicate, object, graph) = decode_record(&record);
-
-            if timestamp > end_timestamp {
-                break;
-            }
+        let mut buffer = vec![0u8; entry.length as usize];
+        data_file.read_exact(&mut buffer)?;
+        Self::decode_data_block(&buffer, data_path, entry, codec)
+    }
 
-            if timestamp >= start_timestamp {
-                results.push(Event { timestamp, subject, predicate, object, graph });
-            }
+    // Check the bytes of a data block against the checksum stored in its index entry and
+    // decode its records.
+    pub(crate) fn decode_data_block(
+        buffer: &[u8],
+        data_path: &str,
+        entry: &IndexEntry,
+        codec: SegmentCodec,
+    ) -> std::io::Result<Vec<Event>> {
+        if crc32fast::hash(buffer) != entry.checksum {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "Checksum mismatch in data block at offset {} of {}",
+                    entry.offset, data_path
+                ),
+            ));
         }
-        Ok(results)
+        codec.decode_block(buffer).map_err(|e| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Data block at offset {} of {}: {}", entry.offset, data_path, e),
+            )
+        })
     }
 
     // Check if a segment overlaps with the given timestamp range
@@ -475,6 +2031,7 @@ impl StreamingSegmentedStorage {
     // Start the background flush loop that periodically checks and flushes the batch buffer if needed.
     // This runs in a separate thread and checks the flush conditions based on the configuration.
     // Good for high-throughput scenarios where synchronous flushing may be a bottleneck as this runs in the background asynchronously.
+    #[allow(clippy::too_many_arguments)]
     fn background_flush_loop(
         batch_buffer: Arc<RwLock<BatchBuffer>>,
         segments: Arc<RwLock<Vec<EnhancedSegmentMetadata>>>,
@@ -482,7 +2039,18 @@ impl StreamingSegmentedStorage {
         background_flush_error: Arc<Mutex<Option<String>>>,
         config: StreamingConfig,
         dictionary: Arc<RwLock<Dictionary>>,
+        dictionary_log: Arc<Mutex<DictionaryLog>>,
+        wal: Option<Arc<Mutex<WriteAheadLog>>>,
+        flush_lock: Arc<Mutex<()>>,
+        mmap_cache: Arc<MmapCache>,
+        memory_tracker: MemoryTracker,
+        tombstones: Arc<RwLock<Tombstones>>,
     ) {
+        // Apply the retention limits to the segments left behind by earlier runs.
+        if let Err(e) = Self::apply_retention(&segments, &config, &mmap_cache) {
+            eprintln!("Warning: Failed to enforce segment retention: {}", e);
+        }
+
         while !*shutdown_signal.lock().unwrap() {
             std::thread::sleep(Duration::from_millis(100));
 
@@ -493,6 +2061,10 @@ impl StreamingSegmentedStorage {
 
                 batch_buffer.events.len() >= config.max_batch_events.try_into().unwrap()
                     || batch_buffer.total_bytes >= config.max_batch_bytes
+                    // Start flushing well before writers run into the memory budget.
+                    || config
+                        .memory_budget_bytes
+                        .is_some_and(|budget| batch_buffer.total_bytes >= budget / 2)
                     || batch_buffer.oldest_timestamp_bound.map_or(false, |oldest| {
                         let current_timestamp = Self::current_timestamp();
                         current_timestamp.saturating_sub(oldest)
@@ -502,205 +2074,558 @@ impl StreamingSegmentedStorage {
 
             if should_flush {
                 if let Err(e) = Self::flush_background(
-                    batch_buffer.clone(),
-                    segments.clone(),
-                    config.clone(),
-                    dictionary.clone(),
+                    &batch_buffer,
+                    &segments,
+                    &config,
+                    &dictionary,
+                    &dictionary_log,
+                    wal.as_ref(),
+                    &flush_lock,
+                    &memory_tracker,
                 ) {
                     let message = format!("Background flush failed: {}", e);
                     eprintln!("{}", message);
                     *background_flush_error.lock().unwrap() = Some(message);
                     break;
                 }
+
+                if let Err(e) = Self::apply_retention(&segments, &config, &mmap_cache) {
+                    eprintln!("Warning: Failed to enforce segment retention: {}", e);
+                }
+
+                if let Err(e) =
+                    Self::apply_tiering(&segments, &tombstones, &dictionary, &config, &mmap_cache)
+                {
+                    eprintln!("Warning: Failed to archive segments: {}", e);
+                }
+
+                if config.compaction_enabled {
+                    if let Err(e) = Self::run_compaction(
+                        &segments,
+                        &tombstones,
+                        &dictionary,
+                        &config,
+                        &mmap_cache,
+                    ) {
+                        eprintln!("Warning: Segment compaction failed: {}", e);
+                    }
+                }
             }
         }
     }
 
     // Flush the batch buffer to a new segment in the background thread.
-    // This function is called by the background flush loop.
+    // This function is called by the background flush loop and by `flush`.
+    //
+    // The flushed events stay in the buffer until their segment is listed; both changes are
+    // made under the buffer and segment locks together, so a concurrent query sees every event
+    // exactly once. Flushes are serialized so the same events are never written twice.
+    #[allow(clippy::too_many_arguments)]
     fn flush_background(
-        batch_buffer: Arc<RwLock<BatchBuffer>>,
-        segments: Arc<RwLock<Vec<EnhancedSegmentMetadata>>>,
-        config: StreamingConfig,
-        dictionary: Arc<RwLock<Dictionary>>,
+        batch_buffer: &RwLock<BatchBuffer>,
+        segments: &RwLock<Vec<EnhancedSegmentMetadata>>,
+        config: &StreamingConfig,
+        dictionary: &RwLock<Dictionary>,
+        dictionary_log: &Mutex<DictionaryLog>,
+        wal: Option<&Arc<Mutex<WriteAheadLog>>>,
+        flush_lock: &Mutex<()>,
+        memory_tracker: &MemoryTracker,
     ) -> std::io::Result<()> {
-        // Automatically extract events from the batch buffer.
+        let _flushing = flush_lock.lock().unwrap();
 
-        let events_to_flush = {
-            let mut batch_buffer = batch_buffer.write().unwrap();
+        let (events_to_flush, streams_to_flush, sealed_generation) = {
+            let batch_buffer = batch_buffer.read().unwrap();
             if batch_buffer.events.is_empty() {
                 return Ok(());
             }
 
-            let events: Vec<Event> = batch_buffer.events.drain(..).collect();
-
-            batch_buffer.total_bytes = 0;
-            batch_buffer.oldest_timestamp_bound = None;
-            batch_buffer.newest_timestamp_bound = None;
-            events
+            // Holding the buffer lock keeps writers from appending to the generation being sealed.
+            let sealed_generation = Self::seal_wal(wal)?;
+            let events: Vec<Event> = batch_buffer.events.iter().cloned().collect();
+            let streams: Vec<Option<u32>> = batch_buffer.streams.iter().copied().collect();
+            (events, streams, sealed_generation)
         };
+        let flushed_count = events_to_flush.len();
 
-        let flush_result = (|| -> std::io::Result<()> {
-            let segment_id = Self::current_timestamp();
-            let data_path = format!("{}/segment-{}.log", config.segment_base_path, segment_id);
-            let index_path = format!("{}/segment-{}.idx", config.segment_base_path, segment_id);
+        // Log the dictionary entries of the flushed events before their WAL terms are released,
+        // and before their segments appear where a read-only storage can list them.
+        dictionary_log.lock().unwrap().persist(&dictionary.read().unwrap())?;
 
-            let mut data_file = BufWriter::new(std::fs::File::create(&data_path)?);
-            let mut index_file = BufWriter::new(std::fs::File::create(&index_path)?);
+        // Every segment holds the events of a single stream, so a stream's log can be read
+        // without touching the segments of the others.
+        let mut partitions: BTreeMap<Option<u32>, Vec<Event>> = BTreeMap::new();
+        for (event, stream) in events_to_flush.into_iter().zip(streams_to_flush) {
+            partitions.entry(stream).or_default().push(event);
+        }
+        let partitions: Vec<(Option<String>, Vec<Event>)> = {
+            let dict = dictionary.read().unwrap();
+            partitions
+                .into_iter()
+                .map(|(stream, events)| {
+                    (stream.and_then(|id| dict.decode(id).map(str::to_string)), events)
+                })
+                .collect()
+        };
 
-            let mut index_directory = Vec::new();
-            let mut current_block_entries = Vec::new();
-            let mut current_block_min_ts = None;
-            let mut current_block_max_ts = 0u64;
-            let mut data_offset = 0u64;
+        // A stream's new segment is out of order where it reaches back before the newest
+        // event already persisted in the stream. Flushes are serialized, so no other flush
+        // can add a newer segment meanwhile.
+        let persisted_ends: Vec<Option<u64>> = {
+            let segments = segments.read().unwrap();
+            partitions
+                .iter()
+                .map(|(stream, _)| {
+                    segments
+                        .iter()
+                        .filter(|s| &s.stream == stream && s.end_timestamp != u64::MAX)
+                        .map(|s| s.end_timestamp)
+                        .max()
+                })
+                .collect()
+        };
 
-            for (record_count, event) in events_to_flush.iter().enumerate() {
-                let record_bytes = Self::serialize_event_to_fixed_size_static(event);
-                data_file.write_all(&record_bytes)?;
+        let mut new_segments = Vec::with_capacity(partitions.len());
+        for ((stream, events), persisted_end) in partitions.into_iter().zip(persisted_ends) {
+            let start = events.iter().map(|e| e.timestamp).min().unwrap_or_default();
+            let end = events.iter().map(|e| e.timestamp).max().unwrap_or_default();
+            let out_of_order = OutOfOrderRange::of_segment(start, end, persisted_end);
+            match Self::write_segment(
+                config,
+                events,
+                stream.as_deref(),
+                &[],
+                out_of_order,
+                dictionary,
+                StorageTier::Hot,
+            ) {
+                Ok(segment) => new_segments.push(segment),
+                Err(err) => {
+                    for segment in &new_segments {
+                        let _ = Self::remove_segment_files(segment);
+                    }
+                    return Err(err);
+                }
+            }
+        }
 
-                if record_count % config.sparse_interval == 0 {
-                    let sparse_entry = (event.timestamp, data_offset);
+        {
+            let mut batch_buffer = batch_buffer.write().unwrap();
+            let mut segments = segments.write().unwrap();
+            segments.extend(new_segments);
+            segments.sort_by_key(|s| s.start_timstamp);
+
+            batch_buffer.streams.drain(..flushed_count);
+            batch_buffer.events.drain(..flushed_count);
+            batch_buffer.total_bytes = batch_buffer
+                .total_bytes
+                .saturating_sub(flushed_count * std::mem::size_of::<Event>());
+            batch_buffer.oldest_timestamp_bound =
+                batch_buffer.events.front().map(|event| event.timestamp);
+            if batch_buffer.events.is_empty() {
+                batch_buffer.newest_timestamp_bound = None;
+            }
+            memory_tracker.set_buffered_bytes(batch_buffer.total_bytes);
+        }
 
-                    if current_block_min_ts.is_none() {
-                        current_block_min_ts = Some(event.timestamp);
-                    }
+        // The events are now durable in the segment, so their log generations can go.
+        Self::release_wal(wal, sealed_generation)
+    }
 
-                    current_block_max_ts = event.timestamp;
-                    current_block_entries.push(sparse_entry);
+    // Remove the segments that exceed the retention limits from the segment list, oldest first,
+    // and delete their files once no query can reach them any more.
+    fn apply_retention(
+        segments: &RwLock<Vec<EnhancedSegmentMetadata>>,
+        config: &StreamingConfig,
+        mmap_cache: &MmapCache,
+    ) -> std::io::Result<usize> {
+        if !config.retention_enabled() {
+            return Ok(0);
+        }
 
-                    if current_block_entries.len() >= config.entries_per_index_block {
-                        let block_metadata = Self::flush_index_block_static(
-                            &mut index_file,
-                            &current_block_entries,
-                            current_block_min_ts.unwrap(),
-                            current_block_max_ts,
-                        )?;
+        let expired = {
+            let mut segments = segments.write().unwrap();
+            segments.sort_by_key(|s| s.start_timstamp);
+
+            let mut expired_count = 0;
+
+            if let Some(max_age_seconds) = config.retention_max_age_seconds {
+                // Segments without a readable index report `u64::MAX` and never expire by age.
+                let newest = segments
+                    .iter()
+                    .map(|s| s.end_timestamp)
+                    .filter(|&end| end != u64::MAX)
+                    .max()
+                    .unwrap_or(0);
+                let cutoff = newest.saturating_sub(max_age_seconds.saturating_mul(1_000));
+                expired_count = segments.iter().take_while(|s| s.end_timestamp < cutoff).count();
+            }
 
-                        index_directory.push(block_metadata);
+            if let Some(max_segments) = config.retention_max_segments {
+                expired_count = expired_count.max(segments.len().saturating_sub(max_segments));
+            }
 
-                        current_block_entries.clear();
-                        current_block_min_ts = None;
-                    }
+            if let Some(max_bytes) = config.retention_max_bytes {
+                let sizes: Vec<u64> = segments.iter().map(Self::segment_size_bytes).collect();
+                let mut total_bytes: u64 = sizes[expired_count..].iter().sum();
+                while total_bytes > max_bytes && expired_count < segments.len() {
+                    total_bytes -= sizes[expired_count];
+                    expired_count += 1;
                 }
-                data_offset += record_bytes.len() as u64;
             }
 
-            if !current_block_entries.is_empty() {
-                let block_metadata = Self::flush_index_block_static(
-                    &mut index_file,
-                    &current_block_entries,
-                    current_block_min_ts.unwrap(),
-                    current_block_max_ts,
-                )?;
+            segments.drain(..expired_count).collect::<Vec<_>>()
+        };
 
-                index_directory.push(block_metadata);
+        for segment in &expired {
+            mmap_cache.evict(&segment.data_path);
+            Self::remove_segment_files(segment)?;
+        }
+
+        Ok(expired.len())
+    }
+
+    // Rewrite the hot segments older than the archive age into the archive tier, one at a time.
+    // A segment merged away or covered by a new tombstone in the meantime is left to the next run.
+    fn apply_tiering(
+        segments: &RwLock<Vec<EnhancedSegmentMetadata>>,
+        tombstones: &RwLock<Tombstones>,
+        dictionary: &RwLock<Dictionary>,
+        config: &StreamingConfig,
+        mmap_cache: &MmapCache,
+    ) -> std::io::Result<usize> {
+        let Some(archive_after_seconds) = config.archive_after_seconds else {
+            return Ok(0);
+        };
+
+        let due: Vec<EnhancedSegmentMetadata> = {
+            let segments = segments.read().unwrap();
+            // Segments without a readable index report `u64::MAX` and are never archived.
+            let newest = segments
+                .iter()
+                .map(|s| s.end_timestamp)
+                .filter(|&end| end != u64::MAX)
+                .max()
+                .unwrap_or(0);
+            let cutoff = newest.saturating_sub(archive_after_seconds.saturating_mul(1_000));
+            segments
+                .iter()
+                .filter(|s| s.tier == StorageTier::Hot && s.end_timestamp < cutoff)
+                .cloned()
+                .collect()
+        };
+
+        let mut archived = 0;
+        for segment in due {
+            if Self::merge_segments(
+                segments,
+                tombstones,
+                dictionary,
+                config,
+                mmap_cache,
+                std::slice::from_ref(&segment),
+                StorageTier::Archive,
+            )? {
+                archived += 1;
             }
+        }
+        Ok(archived)
+    }
 
-            data_file.flush()?;
-            index_file.flush()?;
+    // Merge every run of segments picked by the compaction planner.
+    fn run_compaction(
+        segments: &RwLock<Vec<EnhancedSegmentMetadata>>,
+        tombstones: &RwLock<Tombstones>,
+        dictionary: &RwLock<Dictionary>,
+        config: &StreamingConfig,
+        mmap_cache: &MmapCache,
+    ) -> std::io::Result<usize> {
+        let runs: Vec<Vec<EnhancedSegmentMetadata>> = {
+            let mut segments = segments.write().unwrap();
+            segments.sort_by_key(|s| s.start_timstamp);
+            plan_compaction(&segments, config)
+                .into_iter()
+                .map(|run| run.into_iter().map(|position| segments[position].clone()).collect())
+                .collect()
+        };
 
-            let new_segment = EnhancedSegmentMetadata {
-                start_timstamp: events_to_flush.first().unwrap().timestamp,
-                end_timestamp: events_to_flush.last().unwrap().timestamp,
-                data_path,
-                index_path,
-                record_count: events_to_flush.len() as u64,
-                index_directory,
-            };
+        let mut merged_away = 0;
+        for inputs in runs {
+            // The compaction planner only groups segments of the same tier.
+            let tier = inputs[0].tier;
+            if Self::merge_segments(
+                segments, tombstones, dictionary, config, mmap_cache, &inputs, tier,
+            )? {
+                merged_away += inputs.len() - 1;
+            }
+        }
+        Ok(merged_away)
+    }
 
-            {
-                let mut segments = segments.write().unwrap();
-                segments.push(new_segment);
-                segments.sort_by_key(|s| s.start_timstamp);
+    // Write one segment holding every event of `inputs` to the directory of `tier` and swap it
+    // in for them under the segments lock, so queries see either the inputs or the merged
+    // segment but never both. Erased events are left out, and inputs left without events are
+    // removed.
+    // Returns false when an input disappeared in the meantime, e.g. through retention, or
+    // when a tombstone was recorded after the inputs were read.
+    fn merge_segments(
+        segments: &RwLock<Vec<EnhancedSegmentMetadata>>,
+        tombstones: &RwLock<Tombstones>,
+        dictionary: &RwLock<Dictionary>,
+        config: &StreamingConfig,
+        mmap_cache: &MmapCache,
+        inputs: &[EnhancedSegmentMetadata],
+        tier: StorageTier,
+    ) -> std::io::Result<bool> {
+        let mut cursors = Vec::new();
+        let generation = {
+            let tombstones = tombstones.read().unwrap();
+            for input in inputs {
+                let erased = tombstones.erased_in(input.segment_id());
+                cursors.extend(
+                    SegmentCursor::open(input, 0, u64::MAX, None, None)?
+                        .map(|cursor| cursor.erasing(erased)),
+                );
             }
+            tombstones.generation()
+        };
+        let events =
+            QueryIter::new(cursors, Vec::new(), None).collect::<std::io::Result<Vec<_>>>()?;
 
-            let dict_path = std::path::Path::new(&config.segment_base_path).join("dictionary.bin");
-            let dict = dictionary.read().unwrap();
-            dict.save_to_file(&dict_path)?;
+        let replaces: Vec<u64> = inputs.iter().filter_map(|s| s.segment_id()).collect();
+        // The compaction planner only groups segments of the same stream.
+        let merged = if events.is_empty() {
+            None
+        } else {
+            let out_of_order = OutOfOrderRange::union(inputs.iter().filter_map(|s| s.out_of_order));
+            Some(Self::write_segment(
+                config,
+                events,
+                inputs[0].stream.as_deref(),
+                &replaces,
+                out_of_order,
+                dictionary,
+                tier,
+            )?)
+        };
 
-            Ok(())
-        })();
+        {
+            let mut segments = segments.write().unwrap();
+            let inputs_present = inputs
+                .iter()
+                .all(|input| segments.iter().any(|s| s.data_path == input.data_path));
+            // The merged segment is newer than a tombstone recorded in the meantime, so it
+            // would not be covered by it.
+            let tombstones_unchanged = tombstones.read().unwrap().generation() == generation;
+
+            if !inputs_present || !tombstones_unchanged {
+                drop(segments);
+                if let Some(merged) = &merged {
+                    Self::remove_segment_files(merged)?;
+                }
+                return Ok(false);
+            }
 
-        if let Err(err) = flush_result {
-            Self::restore_failed_background_flush(&batch_buffer, &events_to_flush);
-            return Err(err);
+            segments.retain(|s| !inputs.iter().any(|input| input.data_path == s.data_path));
+            segments.extend(merged);
+            segments.sort_by_key(|s| s.start_timstamp);
         }
 
-        Ok(())
-    }
-
-    fn restore_failed_background_flush(batch_buffer: &Arc<RwLock<BatchBuffer>>, events: &[Event]) {
-        if events.is_empty() {
-            return;
+        // No query can reach the inputs any more once they have left the segment list.
+        for input in inputs {
+            mmap_cache.evict(&input.data_path);
+            Self::remove_segment_files(input)?;
         }
+        Ok(true)
+    }
 
-        let mut buffer = batch_buffer.write().unwrap();
-        for event in events.iter().rev().cloned() {
-            buffer.events.push_front(event);
-            buffer.total_bytes += std::mem::size_of::<Event>();
+    // Delete the data and index files of a segment that is no longer listed.
+    fn remove_segment_files(segment: &EnhancedSegmentMetadata) -> std::io::Result<()> {
+        for path in [&segment.data_path, &segment.index_path] {
+            match std::fs::remove_file(path) {
+                Ok(()) => {}
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err),
+            }
         }
+        Ok(())
+    }
 
-        let restored_oldest = events.first().map(|event| event.timestamp);
-        let restored_newest = events.last().map(|event| event.timestamp);
+    // Size of a segment's data and index files on disk.
+    fn segment_size_bytes(segment: &EnhancedSegmentMetadata) -> u64 {
+        [&segment.data_path, &segment.index_path]
+            .iter()
+            .filter_map(|path| std::fs::metadata(path).ok())
+            .map(|metadata| metadata.len())
+            .sum()
+    }
 
-        buffer.oldest_timestamp_bound = match (buffer.oldest_timestamp_bound, restored_oldest) {
-            (Some(existing), Some(restored)) => Some(existing.min(restored)),
-            (None, restored) => restored,
-            (existing, None) => existing,
-        };
+    // Seal the active write-ahead log generation while the batch buffer is being drained.
+    fn seal_wal(wal: Option<&Arc<Mutex<WriteAheadLog>>>) -> std::io::Result<Option<u64>> {
+        wal.map(|wal| wal.lock().unwrap().seal()).transpose()
+    }
 
-        buffer.newest_timestamp_bound = match (buffer.newest_timestamp_bound, restored_newest) {
-            (Some(existing), Some(restored)) => Some(existing.max(restored)),
-            (None, restored) => restored,
-            (existing, None) => existing,
-        };
+    // Drop the write-ahead log generations whose events have been persisted in a segment.
+    fn release_wal(
+        wal: Option<&Arc<Mutex<WriteAheadLog>>>,
+        sealed_generation: Option<u64>,
+    ) -> std::io::Result<()> {
+        if let (Some(wal), Some(generation)) = (wal, sealed_generation) {
+            wal.lock().unwrap().release_through(generation)?;
+        }
+        Ok(())
     }
 
     fn load_existing_segments(&self) -> std::io::Result<()> {
-        use std::fs;
+        let segments = Self::scan_segments(&self.config, self.is_read_only(), &[])?;
+        *self.segments.write().unwrap() = segments;
+        Ok(())
+    }
 
-        let segment_dir = &self.config.segment_base_path;
-        if !fs::metadata(segment_dir).is_ok() {
-            return Ok(());
-        }
+    // List the segments in the directories of both tiers, ordered by start timestamp. The
+    // metadata of the `known` segments is reused, as segment files never change once renamed
+    // into place.
+    //
+    // A writer removes the leftovers of crashed flushes and merges. A reader leaves them to
+    // the writer and skips segments deleted while the directories are scanned.
+    fn scan_segments(
+        config: &StreamingConfig,
+        read_only: bool,
+        known: &[EnhancedSegmentMetadata],
+    ) -> std::io::Result<Vec<EnhancedSegmentMetadata>> {
+        use std::fs;
 
-        let entries = fs::read_dir(segment_dir)?;
+        let known: HashMap<&str, &EnhancedSegmentMetadata> =
+            known.iter().map(|segment| (segment.data_path.as_str(), segment)).collect();
         let mut segments = Vec::new();
+        let mut replaced_ids = std::collections::HashSet::new();
 
-        for entry in entries {
-            let entry = entry?;
-            let path = entry.path();
-
-            if let Some(filename) = path.file_name().and_then(|n| n.to_str()) {
-                if filename.starts_with("segment-") && filename.ends_with(".log") {
-                    // Extract segment ID from filename
-                    if let Some(id_str) =
-                        filename.strip_prefix("segment-").and_then(|s| s.strip_suffix(".log"))
-                    {
-                        if let Ok(segment_id) = id_str.parse::<u64>() {
-                            // Try to load the segment metadata by reading the data file
-                            let data_path = format!("{}/segment-{}.log", segment_dir, segment_id);
-                            let index_path = format!("{}/segment-{}.idx", segment_dir, segment_id);
-
-                            if let Ok(_metadata) = fs::metadata(&data_path) {
-                                // Load index directory if index file exists
-                                let (index_directory, start_ts, end_ts, record_count) =
-                                    if fs::metadata(&index_path).is_ok() {
+        for tier in [StorageTier::Hot, StorageTier::Archive] {
+            let segment_dir = config.tier_directory(tier);
+            if !fs::metadata(&segment_dir).is_ok() {
+                continue;
+            }
+            for entry in fs::read_dir(&segment_dir)? {
+                let entry = entry?;
+                let path = entry.path();
+
+                if let Some(filename) = path.file_name().and_then(|n| n.to_str()) {
+                    // Leftovers of a flush that crashed before its files were renamed into place
+                    if filename.starts_with("segment-") && filename.ends_with(".tmp") {
+                        if !read_only {
+                            fs::remove_file(&path)?;
+                        }
+                        continue;
+                    }
+
+                    if filename.starts_with("segment-") && filename.ends_with(".log") {
+                        // Extract segment ID from filename
+                        if let Some(id_str) =
+                            filename.strip_prefix("segment-").and_then(|s| s.strip_suffix(".log"))
+                        {
+                            if let Ok(segment_id) = id_str.parse::<u64>() {
+                                LAST_SEGMENT_ID.fetch_max(segment_id, Ordering::SeqCst);
+
+                                // Try to load the segment metadata by reading the data file
+                                let data_path =
+                                    format!("{}/segment-{}.log", segment_dir, segment_id);
+                                let index_path =
+                                    format!("{}/segment-{}.idx", segment_dir, segment_id);
+
+                                if let Some(&segment) = known.get(data_path.as_str()) {
+                                    replaced_ids.extend(segment.replaces.iter().copied());
+                                    segments.push((segment_id, segment.clone()));
+                                    continue;
+                                }
+
+                                if let Ok(_metadata) = fs::metadata(&data_path) {
+                                    let (format_version, codec) = match fs::File::open(&data_path) {
+                                        Ok(mut file) => read_segment_header(&mut file)?,
+                                        Err(e)
+                                            if read_only
+                                                && e.kind() == std::io::ErrorKind::NotFound =>
+                                        {
+                                            continue
+                                        }
+                                        Err(e) => return Err(e),
+                                    };
+
+                                    // Load index directory if index file exists. A segment whose index
+                                    // cannot be read stays listed so that queries report it and
+                                    // `verify` can repair it.
+                                    let footer = if fs::metadata(&index_path).is_ok() {
                                         Self::load_index_directory_from_file(&index_path)
-                                            .unwrap_or_else(|_| (Vec::new(), 0, u64::MAX, 0))
+                                            .unwrap_or_else(|e| {
+                                                eprintln!(
+                                                    "Warning: Failed to load index {}: {}",
+                                                    index_path, e
+                                                );
+                                                SegmentFooter::unindexed()
+                                            })
                                     } else {
-                                        (Vec::new(), 0, u64::MAX, 0)
+                                        SegmentFooter::unindexed()
                                     };
 
-                                let segment = EnhancedSegmentMetadata {
-                                    start_timstamp: start_ts,
-                                    end_timestamp: end_ts,
-                                    data_path,
-                                    index_path,
-                                    record_count,
-                                    index_directory,
-                                };
-                                segments.push(segment);
+                                    // A segment whose filter cannot be read is always scanned.
+                                    let bloom_filter = footer.bloom_filter.and_then(|location| {
+                                        let filter =
+                                            fs::File::open(&index_path).and_then(|mut file| {
+                                                Self::read_bloom_filter(
+                                                    &mut file,
+                                                    &index_path,
+                                                    &location,
+                                                )
+                                            });
+                                        filter
+                                            .inspect_err(|e| {
+                                                eprintln!(
+                                                    "Warning: Failed to load Bloom filter {}: {}",
+                                                    index_path, e
+                                                );
+                                            })
+                                            .ok()
+                                            .map(Arc::new)
+                                    });
+                                    let zone_map = footer.zone_map.and_then(|location| {
+                                        let zone_map =
+                                            fs::File::open(&index_path).and_then(|mut file| {
+                                                Self::read_zone_map(
+                                                    &mut file,
+                                                    &index_path,
+                                                    &location,
+                                                )
+                                            });
+                                        zone_map
+                                            .inspect_err(|e| {
+                                                eprintln!(
+                                                    "Warning: Failed to load zone map {}: {}",
+                                                    index_path, e
+                                                );
+                                            })
+                                            .ok()
+                                            .map(Arc::new)
+                                    });
+
+                                    let segment = EnhancedSegmentMetadata {
+                                        start_timstamp: footer.start_timestamp,
+                                        end_timestamp: footer.end_timestamp,
+                                        data_path,
+                                        index_path,
+                                        record_count: footer.record_count,
+                                        index_directory: footer.index_directory,
+                                        format_version,
+                                        codec,
+                                        stream: footer.stream,
+                                        secondary_index: footer.secondary_index,
+                                        out_of_order: footer.out_of_order,
+                                        rollups: footer.rollups,
+                                        replaces: footer.replaces,
+                                        bloom_filter,
+                                        zone_map,
+                                        tier,
+                                    };
+                                    replaced_ids.extend(segment.replaces.iter().copied());
+                                    segments.push((segment_id, segment));
+                                }
                             }
                         }
                     }
@@ -708,32 +2633,46 @@ impl StreamingSegmentedStorage {
             }
         }
 
-        // Sort segments by start timestamp
-        segments.sort_by_key(|s| s.start_timstamp);
-
-        {
-            let mut self_segments = self.segments.write().unwrap();
-            *self_segments = segments;
+        // A merged segment is only renamed into place once complete, so inputs that are still
+        // around were left behind by a crash between the swap and their deletion, or are
+        // about to be deleted by the writer.
+        let mut retained = Vec::with_capacity(segments.len());
+        for (segment_id, segment) in segments {
+            if replaced_ids.contains(&segment_id) {
+                if !read_only {
+                    Self::remove_segment_files(&segment)?;
+                }
+            } else {
+                retained.push(segment);
+            }
         }
+        let mut segments = retained;
 
-        Ok(())
+        // Sort segments by start timestamp
+        segments.sort_by_key(|s| s.start_timstamp);
+        Ok(segments)
     }
 
     // Loading the index directory from an existing index file on disk
-    fn load_index_directory_from_file(
-        index_path: &str,
-    ) -> std::io::Result<(Vec<IndexBlock>, u64, u64, u64)> {
+    fn load_index_directory_from_file(index_path: &str) -> std::io::Result<SegmentFooter> {
         use std::io::Read;
 
         let mut file = std::fs::File::open(index_path)?;
+
+        // Segments written with a footer carry their exact index directory and bounds.
+        if let Some(footer) = SegmentFooter::read_from(&mut file)? {
+            return Ok(footer);
+        }
+
         let mut buffer = Vec::new();
+        file.seek(SeekFrom::Start(0))?;
         file.read_to_end(&mut buffer)?;
 
-        // Index file format: each block is stored as consecutive (timestamp, offset) pairs (16 bytes each)
+        // Legacy index file format: each block is stored as consecutive (timestamp, offset) pairs (16 bytes each)
         // We need to reconstruct the IndexBlock directory structure
 
         if buffer.is_empty() {
-            return Ok((Vec::new(), 0, u64::MAX, 0));
+            return Ok(SegmentFooter::unindexed());
         }
 
         let mut index_directory = Vec::new();
@@ -776,16 +2715,250 @@ impl StreamingSegmentedStorage {
                 max_timestamp: last_ts,
                 file_offset,
                 entry_count,
+                checksum: 0,
             });
 
             file_offset += block_size as u64;
             current_block_start = block_end;
         }
 
-        Ok((index_directory, global_min_ts, global_max_ts, total_records))
+        Ok(SegmentFooter {
+            version: 0,
+            start_timestamp: global_min_ts,
+            end_timestamp: global_max_ts,
+            record_count: total_records,
+            index_directory,
+            replaces: Vec::new(),
+            stream: None,
+            secondary_index: None,
+            out_of_order: None,
+            rollups: None,
+            bloom_filter: None,
+            zone_map: None,
+        })
+    }
+
+    /// Check every segment against its header and checksums.
+    ///
+    /// Corrupt segments are always reported. With `repair` set they are also quarantined,
+    /// or truncated to the records of their intact leading blocks, and stop being served.
+    pub fn verify(&self, repair: Option<SegmentRepair>) -> std::io::Result<VerifyReport> {
+        if repair.is_some() {
+            self.ensure_writable()?;
+        }
+        let segments = self.segment_metadata();
+        let mut report = VerifyReport { segments_checked: segments.len(), ..Default::default() };
+
+        for segment in &segments {
+            let mut valid_events = Vec::new();
+            let reason = match Self::check_segment(segment, &mut valid_events) {
+                Ok(()) => continue,
+                Err(err) => err.to_string(),
+            };
+            let valid_records = valid_events.len() as u64;
+
+            let applied = match repair {
+                None => None,
+                Some(SegmentRepair::Truncate) if !valid_events.is_empty() => {
+                    self.truncate_segment(segment, valid_events)?;
+                    Some(SegmentRepair::Truncate)
+                }
+                Some(_) => {
+                    self.quarantine_segment(segment)?;
+                    Some(SegmentRepair::Quarantine)
+                }
+            };
+
+            report.corrupt_segments.push(CorruptSegment {
+                data_path: segment.data_path.clone(),
+                reason,
+                valid_records,
+                repair: applied,
+            });
+        }
+
+        Ok(report)
+    }
+
+    // Verify a segment, collecting the records of its intact leading blocks into `valid_events`.
+    fn check_segment(
+        segment: &EnhancedSegmentMetadata,
+        valid_events: &mut Vec<Event>,
+    ) -> std::io::Result<()> {
+        let mut data_file = std::fs::File::open(&segment.data_path)?;
+        let data_len = data_file.metadata()?.len();
+
+        if segment.format_version == LEGACY_SEGMENT_VERSION {
+            return Self::check_legacy_segment(&mut data_file, data_len, valid_events);
+        }
+
+        if read_segment_header(&mut data_file)? != (segment.format_version, segment.codec) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Segment header does not match the loaded format version",
+            ));
+        }
+
+        let mut index_file = std::fs::File::open(&segment.index_path)?;
+        let footer = SegmentFooter::read_from(&mut index_file)?.ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, "Segment index has no footer")
+        })?;
+
+        let mut expected_offset = SEGMENT_HEADER_SIZE;
+        for block in &footer.index_directory {
+            let entries = Self::read_index_block(
+                &mut index_file,
+                &segment.index_path,
+                block,
+                segment.format_version,
+            )?;
+
+            for entry in entries {
+                if entry.offset != expected_offset {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("Data block at offset {} is not contiguous", entry.offset),
+                    ));
+                }
+
+                valid_events.extend(Self::read_data_block(
+                    &mut data_file,
+                    &segment.data_path,
+                    &entry,
+                    segment.codec,
+                )?);
+                expected_offset += entry.length as u64;
+            }
+        }
+
+        if expected_offset != data_len {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{} bytes after the last indexed data block", data_len - expected_offset),
+            ));
+        }
+
+        if let Some(location) = &footer.secondary_index {
+            Self::read_secondary_index(&mut index_file, &segment.index_path, location)?;
+        }
+        if let Some(location) = &footer.rollups {
+            Self::read_rollups(&mut index_file, &segment.index_path, location)?;
+        }
+        if let Some(location) = &footer.bloom_filter {
+            Self::read_bloom_filter(&mut index_file, &segment.index_path, location)?;
+        }
+        if let Some(location) = &footer.zone_map {
+            Self::read_zone_map(&mut index_file, &segment.index_path, location)?;
+        }
+
+        if valid_events.len() as u64 != footer.record_count {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!(
+                    "Segment holds {} records but its footer records {}",
+                    valid_events.len(),
+                    footer.record_count
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+
+    // Legacy segments carry no checksums, so only torn or out-of-order records can be detected.
+    fn check_legacy_segment(
+        data_file: &mut std::fs::File,
+        data_len: u64,
+        valid_events: &mut Vec<Event>,
+    ) -> std::io::Result<()> {
+        let mut buffer = Vec::new();
+        data_file.read_to_end(&mut buffer)?;
+
+        let mut previous_timestamp = 0u64;
+        for (position, record) in buffer.chunks_exact(RECORD_SIZE).enumerate() {
+            let (timestamp, subject, predicate, object, graph) =
+                decode_record(record.try_into().unwrap());
+            if timestamp < previous_timestamp {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Record at offset {} is out of order", position * RECORD_SIZE),
+                ));
+            }
+            previous_timestamp = timestamp;
+            valid_events.push(Event { timestamp, subject, predicate, object, graph });
+        }
+
+        if data_len % RECORD_SIZE as u64 != 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Torn record at the end of a {} byte data file", data_len),
+            ));
+        }
+        Ok(())
+    }
+
+    // Replace a corrupt segment with a new segment holding only its intact records.
+    fn truncate_segment(
+        &self,
+        segment: &EnhancedSegmentMetadata,
+        mut valid_events: Vec<Event>,
+    ) -> std::io::Result<()> {
+        // The replacement is newer than the tombstones covering the segment.
+        if let Some(erased) = self.tombstones.read().unwrap().erased_in(segment.segment_id()) {
+            valid_events.retain(|event| !erased.matches(event));
+        }
+        let replaces: Vec<u64> = segment.segment_id().into_iter().collect();
+        let replacement = if valid_events.is_empty() {
+            None
+        } else {
+            Some(Self::write_segment(
+                &self.config,
+                valid_events,
+                segment.stream.as_deref(),
+                &replaces,
+                segment.out_of_order,
+                &self.dictionary,
+                segment.tier,
+            )?)
+        };
+
+        {
+            let mut segments = self.segments.write().unwrap();
+            segments.retain(|s| s.data_path != segment.data_path);
+            segments.extend(replacement);
+            segments.sort_by_key(|s| s.start_timstamp);
+        }
+
+        self.mmap_cache.evict(&segment.data_path);
+        Self::remove_segment_files(segment)
+    }
+
+    // Stop serving a corrupt segment and move its files into the quarantine directory.
+    fn quarantine_segment(&self, segment: &EnhancedSegmentMetadata) -> std::io::Result<()> {
+        {
+            let mut segments = self.segments.write().unwrap();
+            segments.retain(|s| s.data_path != segment.data_path);
+        }
+        self.mmap_cache.evict(&segment.data_path);
+
+        let quarantine_dir =
+            std::path::Path::new(&self.config.segment_base_path).join("quarantine");
+        std::fs::create_dir_all(&quarantine_dir)?;
+
+        for path in [&segment.data_path, &segment.index_path] {
+            let path = std::path::Path::new(path);
+            if let Some(file_name) = path.file_name() {
+                if path.exists() {
+                    std::fs::rename(path, quarantine_dir.join(file_name))?;
+                }
+            }
+        }
+        Ok(())
     }
 
-    // Shutdown the storage system gracefully, ensuring all data is flushed to disk.
+    // Shutdown the storage system gracefully, ensuring all data is flushed to disk. The
+    // directory is unlocked, so it can be reopened for writing while this storage is still
+    // around to be queried.
     pub fn shutdown(&mut self) -> std::io::Result<()> {
         let background_error = self.ensure_background_flush_healthy().err();
         *self.shutdown_signal.lock().unwrap() = true;
@@ -799,6 +2972,7 @@ impl StreamingSegmentedStorage {
         if let Some(handle) = self.flush_handle.take() {
             handle.join().unwrap();
         }
+        self.writer_lock = None;
 
         if let Some(err) = background_error {
             return Err(err);
@@ -807,48 +2981,36 @@ impl StreamingSegmentedStorage {
         Ok(())
     }
 
-    // Serialize an event to fixed-size byte array
-    fn serialize_event_to_fixed_size(&self, event: &Event) -> Vec<u8> {
-        Self::serialize_event_to_fixed_size_static(event)
-    }
-
-    // Static version of serialize_event_to_fixed_size for use in static contexts
-    fn serialize_event_to_fixed_size_static(event: &Event) -> Vec<u8> {
-        let mut record = [0u8; RECORD_SIZE];
-        encode_record(
-            &mut record,
-            event.timestamp,
-            event.subject,
-            event.predicate,
-            event.object,
-            event.graph,
-        );
-        record.to_vec()
-    }
-
     // Static version of flush_index_block for use in static contexts
     fn flush_index_block_static(
         index_file: &mut BufWriter<std::fs::File>,
-        entries: &[(u64, u64)],
-        min_ts: u64,
+        entries: &[IndexEntry],
         max_ts: u64,
     ) -> std::io::Result<IndexBlock> {
         let file_offset = index_file.stream_position()?;
 
-        for (timestamp, offset) in entries {
-            index_file.write_all(&timestamp.to_le_bytes())?;
-            index_file.write_all(&offset.to_be_bytes())?;
+        let mut block = Vec::with_capacity(entries.len() * INDEX_ENTRY_SIZE);
+        for entry in entries {
+            block.extend_from_slice(&entry.encode());
         }
+        index_file.write_all(&block)?;
 
         Ok(IndexBlock {
-            min_timestamp: min_ts,
+            min_timestamp: entries[0].timestamp,
             max_timestamp: max_ts,
             file_offset,
             entry_count: entries.len() as u32,
+            checksum: crc32fast::hash(&block),
         })
     }
-    // Generate a unique segment ID based on the current timestamp
+
+    // Generate a unique segment ID based on the current timestamp.
+    // IDs are strictly increasing so two flushes within the same millisecond never share files.
     fn generate_segment_id() -> u64 {
-        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+        let now = Self::current_timestamp();
+        let previous = LAST_SEGMENT_ID
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |last| Some(now.max(last + 1)))
+            .unwrap();
+        now.max(previous + 1)
     }
 }
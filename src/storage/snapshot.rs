@@ -0,0 +1,104 @@
+//! Consistent online snapshots of a storage directory.
+//!
+//! Copying a storage directory while it is being written can capture a segment that is
+//! half renamed into place, or a dictionary that lacks the strings of the newest segment.
+//! A snapshot instead flushes the buffered events and, while no segment can be added,
+//! merged or deleted, hard-links every listed segment into the destination (falling back
+//! to a copy across file systems). It then writes a checkpoint of the dictionary and the
+//! pending tombstones, and finally `snapshot.json`, the [`SnapshotManifest`]:
+//!
+//! ```text
+//! snapshot/
+//!   segment-<id>.log, segment-<id>.idx   linked or copied segments
+//!   archive/segment-<id>.log, ...        linked or copied archived segments
+//!   dictionary.bin                       dictionary checkpoint covering every segment
+//!   tombstones.json                      pending erasures, if any
+//!   snapshot.json                        manifest, written last
+//! ```
+//!
+//! Segments are immutable once renamed into place, so a hard link keeps the snapshot
+//! intact when the source later compacts or deletes them. The destination is a regular
+//! storage directory that [`StreamingSegmentedStorage::new`] opens; a directory without
+//! a manifest is an incomplete snapshot. Archived segments stay archived in the default
+//! archive directory of the destination, whatever the `archive_path` of the source.
+//!
+//! [`StreamingSegmentedStorage::new`]: crate::storage::segmented_storage::StreamingSegmentedStorage::new
+
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::storage::util::StorageTier;
+
+const MANIFEST_FILE: &str = "snapshot.json";
+const MANIFEST_TMP_FILE: &str = "snapshot.json.tmp";
+
+/// Description of a completed snapshot.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotManifest {
+    /// Wall-clock time the segment list was frozen, in milliseconds since the epoch.
+    pub created_at: u64,
+    pub segments: Vec<SnapshotSegment>,
+    pub record_count: u64,
+    pub dictionary_entries: usize,
+    pub pending_tombstones: usize,
+    /// Segment files shared with the source through hard links.
+    pub files_linked: usize,
+    pub files_copied: usize,
+}
+
+/// A segment captured by a snapshot.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotSegment {
+    pub data_file: String,
+    pub index_file: String,
+    pub start_timestamp: u64,
+    pub end_timestamp: u64,
+    pub record_count: u64,
+    pub stream: Option<String>,
+    #[serde(default)]
+    pub tier: StorageTier,
+}
+
+impl SnapshotManifest {
+    /// Read the manifest of the snapshot in `directory`.
+    pub fn read(directory: &Path) -> std::io::Result<Self> {
+        let data = std::fs::read(directory.join(MANIFEST_FILE))?;
+        serde_json::from_slice(&data)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+
+    /// Write the manifest into `directory`, marking the snapshot complete.
+    pub fn write(&self, directory: &Path) -> std::io::Result<()> {
+        let encoded = serde_json::to_vec_pretty(self).map_err(std::io::Error::other)?;
+        let tmp_path = directory.join(MANIFEST_TMP_FILE);
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&encoded)?;
+        file.sync_all()?;
+        std::fs::rename(tmp_path, directory.join(MANIFEST_FILE))
+    }
+}
+
+/// Create `directory` for a snapshot, failing unless it is new or empty.
+pub fn prepare_destination(directory: &Path) -> std::io::Result<()> {
+    if directory.exists() && std::fs::read_dir(directory)?.next().is_some() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            format!("Snapshot destination {} is not empty", directory.display()),
+        ));
+    }
+    std::fs::create_dir_all(directory)
+}
+
+/// Hard-link `source` as `destination`, or copy it when the file system refuses the link.
+/// Returns whether the file was linked.
+pub fn link_or_copy(source: &Path, destination: &Path) -> std::io::Result<bool> {
+    if std::fs::hard_link(source, destination).is_ok() {
+        return Ok(true);
+    }
+    std::fs::copy(source, destination)?;
+    File::open(destination)?.sync_all()?;
+    Ok(false)
+}
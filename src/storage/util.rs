@@ -3,14 +3,25 @@ use std::sync::{Arc, Mutex, RwLock};
 use std::thread::JoinHandle;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
+use serde::{Deserialize, Serialize};
+
 use crate::core::Event;
+use crate::storage::codec::SegmentCodec;
+use crate::storage::event_time::{LateEventPolicy, OutOfOrderRange};
+use crate::storage::indexing::bloom::BloomFilter;
+use crate::storage::indexing::zone_map::ZoneMap;
+use crate::storage::segment_format::SectionLocation;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Default, Serialize)]
 /// Storage component memory usage breakdown
 pub struct StorageComponentSizes {
     pub batch_buffer_bytes: usize,
     pub segments_count: usize,
     pub dictionary_bytes: usize,
+    /// Segment metadata held in memory, dominated by the sparse index directories.
+    pub index_directory_bytes: usize,
+    /// Length of the mapped segment data files; only the pages read are resident.
+    pub mmap_bytes: usize,
     pub estimated_total_bytes: usize,
 }
 
@@ -18,17 +29,38 @@ pub struct StorageComponentSizes {
 /// In-memory buffer that batches events before persisting them to disk
 pub struct BatchBuffer {
     pub events: VecDeque<Event>,
+    /// Dictionary ID of the stream each buffered event belongs to, parallel to `events`
+    pub streams: VecDeque<Option<u32>>,
     pub total_bytes: usize,
     pub oldest_timestamp_bound: Option<u64>,
     pub newest_timestamp_bound: Option<u64>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct IndexBlock {
     pub min_timestamp: u64,
     pub max_timestamp: u64,
     pub file_offset: u64,
     pub entry_count: u32,
+    /// CRC32 of the block's index entries; zero for legacy segments.
+    #[serde(default)]
+    pub checksum: u32,
+}
+
+/// Subdirectory of `segment_base_path` holding the archive tier unless `archive_path` is set.
+pub const ARCHIVE_DIRECTORY: &str = "archive";
+
+/// Storage tier a segment's files are kept in.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub enum StorageTier {
+    /// The storage directory, encoded with `segment_codec`.
+    #[default]
+    Hot,
+    /// The archive directory, recompressed with `archive_codec` once the segment grew older
+    /// than `archive_after_seconds`.
+    Archive,
 }
 
 #[derive(Debug, Clone)]
@@ -39,9 +71,70 @@ pub struct EnhancedSegmentMetadata {
     pub index_path: String,
     pub record_count: u64,
     pub index_directory: Vec<IndexBlock>,
+    /// On-disk format version read from the segment header.
+    pub format_version: u32,
+    /// Encoding of the records in the segment's data blocks.
+    pub codec: SegmentCodec,
+    /// Stream whose log this segment belongs to; `None` for events written without a stream.
+    pub stream: Option<String>,
+    /// Location of the segment's secondary index in the index file, if it has one.
+    pub secondary_index: Option<SectionLocation>,
+    /// Part of the segment's range that overlaps events persisted before it in its stream.
+    pub out_of_order: Option<OutOfOrderRange>,
+    /// Location of the segment's rollups in the index file, if it has them.
+    pub rollups: Option<SectionLocation>,
+    /// IDs of the segments this one was merged or rewritten from.
+    pub replaces: Vec<u64>,
+    /// The segment's Bloom filter over subject and object IDs, kept in memory to skip the
+    /// segment without reading it.
+    pub bloom_filter: Option<Arc<BloomFilter>>,
+    /// The segment's statistics of the values of each predicate, kept in memory to skip
+    /// the segment when a pattern's value range rules it out.
+    pub zone_map: Option<Arc<ZoneMap>>,
+    /// Tier the segment is kept in, given by the directory holding its files.
+    pub tier: StorageTier,
+}
+
+impl EnhancedSegmentMetadata {
+    /// Estimated bytes this metadata occupies in memory, index directory included.
+    pub fn heap_size_bytes(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.index_directory.capacity() * std::mem::size_of::<IndexBlock>()
+            + self.data_path.capacity()
+            + self.index_path.capacity()
+            + self.stream.as_ref().map_or(0, String::capacity)
+            + self.replaces.capacity() * std::mem::size_of::<u64>()
+            + self.bloom_filter.as_ref().map_or(0, |filter| filter.heap_size_bytes())
+            + self.zone_map.as_ref().map_or(0, |zone_map| zone_map.heap_size_bytes())
+    }
+
+    /// ID of the segment, parsed from its `segment-<id>.log` file name.
+    pub fn segment_id(&self) -> Option<u64> {
+        std::path::Path::new(&self.data_path)
+            .file_name()?
+            .to_str()?
+            .strip_prefix("segment-")?
+            .strip_suffix(".log")?
+            .parse()
+            .ok()
+    }
+}
+
+/// What a write does when buffering its event would exceed the memory budget.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BackpressurePolicy {
+    /// Wait until a flush frees room in the buffer. Without a background flush thread the
+    /// writer flushes the buffer itself.
+    #[default]
+    Block,
+    /// Fail the write with `ErrorKind::WouldBlock` and leave retrying to the caller.
+    WouldBlock,
+    /// Flush the buffer to a segment in the writing thread, then buffer the event.
+    SyncFlush,
 }
 
 #[derive(Clone)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct StreamingConfig {
     /// Maximum number of events to buffer before flushing to disk
     pub max_batch_events: u64,
@@ -52,6 +145,90 @@ pub struct StreamingConfig {
     pub sparse_interval: usize,
     pub entries_per_index_block: usize,
     pub segment_base_path: String,
+    /// Append every write to a write-ahead log before it is buffered in memory
+    pub wal_enabled: bool,
+    /// Sync the write-ahead log to disk after every append instead of relying on the OS cache
+    pub wal_sync_on_write: bool,
+    /// Delete segments whose newest event is older than this many seconds, measured against
+    /// the newest persisted event
+    pub retention_max_age_seconds: Option<u64>,
+    /// Delete the oldest segments while the segment files take more than this many bytes
+    pub retention_max_bytes: Option<u64>,
+    /// Delete the oldest segments while more than this many segments are kept
+    pub retention_max_segments: Option<usize>,
+    /// Move segments whose newest event is older than this many seconds, measured against
+    /// the newest persisted event, to the archive tier
+    pub archive_after_seconds: Option<u64>,
+    /// Directory of the archive tier; `None` keeps it in the `archive` subdirectory of
+    /// `segment_base_path`
+    pub archive_path: Option<String>,
+    /// Encoding of the records in archived segments
+    pub archive_codec: SegmentCodec,
+    /// Merge runs of adjacent small segments in the background flush thread
+    pub compaction_enabled: bool,
+    /// Minimum number of adjacent segments merged by one compaction
+    pub compaction_min_segments: usize,
+    /// Upper bound for the number of records in a segment produced by compaction
+    pub compaction_max_segment_records: u64,
+    /// Write subject and predicate posting lists with every segment for `query_filtered`
+    pub secondary_indexes_enabled: bool,
+    /// Write the minimum and maximum numeric value of each predicate with every segment,
+    /// see [`crate::storage::indexing::zone_map`]
+    pub zone_maps_enabled: bool,
+    /// Encoding of the records in newly written segments; existing segments keep theirs
+    pub segment_codec: SegmentCodec,
+    /// Number of dictionary log entries after which the whole dictionary is checkpointed
+    pub dictionary_checkpoint_interval: usize,
+    /// Number of segment data files kept memory-mapped for queries; 0 reads them through file IO
+    pub mmap_cache_capacity: usize,
+    /// Hard limit for the bytes of events buffered in memory; the background flush starts
+    /// once half of it is in use. Must hold at least one event. `None` leaves the buffer
+    /// unbounded
+    pub memory_budget_bytes: Option<usize>,
+    /// What a write does when the memory budget is exhausted
+    pub backpressure_policy: BackpressurePolicy,
+    /// Milliseconds an event may lag behind the newest event of its stream before it is
+    /// late, see [`crate::storage::event_time`]
+    pub allowed_lateness_ms: u64,
+    /// What a write does with an event that is later than the allowed lateness
+    pub late_event_policy: LateEventPolicy,
+    /// Width in milliseconds of the time buckets rolled up with every new segment, see
+    /// [`crate::storage::rollup`]; 0 writes no rollups
+    pub rollup_bucket_ms: u64,
+    /// False positive rate of the Bloom filter over the subjects and objects written with
+    /// every segment, see [`crate::storage::indexing::bloom`]; 0 writes no filters
+    pub bloom_filter_false_positive_rate: f64,
+    /// Number of threads scanning segments in parallel for queries, see
+    /// [`crate::storage::scan_pool`]; 1 reads them in the querying thread
+    pub query_scan_threads: usize,
+}
+
+impl StreamingConfig {
+    /// Whether any retention limit is configured.
+    pub fn retention_enabled(&self) -> bool {
+        self.retention_max_age_seconds.is_some()
+            || self.retention_max_bytes.is_some()
+            || self.retention_max_segments.is_some()
+    }
+
+    /// Directory holding the segment files of a tier.
+    pub fn tier_directory(&self, tier: StorageTier) -> String {
+        match (tier, &self.archive_path) {
+            (StorageTier::Hot, _) => self.segment_base_path.clone(),
+            (StorageTier::Archive, Some(path)) => path.clone(),
+            (StorageTier::Archive, None) => {
+                format!("{}/{}", self.segment_base_path, ARCHIVE_DIRECTORY)
+            }
+        }
+    }
+
+    /// Encoding of the records in segments written to a tier.
+    pub fn tier_codec(&self, tier: StorageTier) -> SegmentCodec {
+        match tier {
+            StorageTier::Hot => self.segment_codec,
+            StorageTier::Archive => self.archive_codec,
+        }
+    }
 }
 
 impl Default for StreamingConfig {
@@ -63,6 +240,29 @@ impl Default for StreamingConfig {
             sparse_interval: 1000,
             entries_per_index_block: 1024,
             segment_base_path: "./data".to_string(),
+            wal_enabled: true,
+            wal_sync_on_write: false,
+            retention_max_age_seconds: None,
+            retention_max_bytes: None,
+            retention_max_segments: None,
+            archive_after_seconds: None,
+            archive_path: None,
+            archive_codec: SegmentCodec::CompressedColumnar,
+            compaction_enabled: true,
+            compaction_min_segments: 4,
+            compaction_max_segment_records: 1_000_000,
+            secondary_indexes_enabled: true,
+            zone_maps_enabled: true,
+            segment_codec: SegmentCodec::FixedWidth,
+            dictionary_checkpoint_interval: 100_000,
+            mmap_cache_capacity: 64,
+            memory_budget_bytes: None,
+            backpressure_policy: BackpressurePolicy::Block,
+            allowed_lateness_ms: 0,
+            late_event_policy: LateEventPolicy::Accept,
+            rollup_bucket_ms: 0,
+            bloom_filter_false_positive_rate: 0.01,
+            query_scan_threads: 1,
         }
     }
 }
@@ -0,0 +1,254 @@
+//! Write-ahead log for the streaming storage batch buffer.
+//!
+//! Every event accepted by `StreamingSegmentedStorage::write` is appended to the
+//! log before it becomes visible in the in-memory `BatchBuffer`, so a crash no
+//! longer loses the events that were waiting for the background flush.
+//!
+//! The log is split into numbered generations (`wal-<generation>.log`). A flush
+//! seals the active generation while it drains the batch buffer, and the sealed
+//! generations are released (deleted) once the segment holding their events and
+//! the dictionary have been persisted. Writes that arrive during the flush go to
+//! the next generation and are therefore never truncated by accident.
+//!
+//! Each record is framed as `[payload length: u32][crc32: u32][payload]`, which
+//! lets replay stop cleanly at a torn tail left behind by a crash.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::core::{
+    encoding::{decode_record, encode_record, RECORD_SIZE},
+    Event,
+};
+
+const WAL_FILE_PREFIX: &str = "wal-";
+const WAL_FILE_SUFFIX: &str = ".log";
+
+const RECORD_KIND_EVENT: u8 = 1;
+const RECORD_KIND_TERM: u8 = 2;
+const RECORD_KIND_STREAM_EVENT: u8 = 3;
+
+/// Upper bound for a single record; anything larger can only come from a torn header.
+const MAX_RECORD_BYTES: usize = 16 * 1024 * 1024;
+
+/// Everything recovered from the write-ahead log when the storage is reopened.
+#[derive(Debug, Default)]
+pub struct WalReplay {
+    /// Events that were accepted but not yet flushed to a segment, in write order.
+    pub events: Vec<Event>,
+    /// Dictionary ID of the stream each replayed event was written to, parallel to `events`.
+    pub streams: Vec<Option<u32>>,
+    /// Dictionary entries created after the last dictionary checkpoint.
+    pub terms: Vec<(u32, String)>,
+}
+
+/// Append-only, generation-based write-ahead log stored next to the segments.
+pub struct WriteAheadLog {
+    directory: PathBuf,
+    generation: u64,
+    writer: Option<BufWriter<File>>,
+    sync_on_write: bool,
+}
+
+impl WriteAheadLog {
+    /// Opens the write-ahead log in `directory` and replays every generation left
+    /// behind by a previous process.
+    ///
+    /// Generations that still hold records stay on disk until the next successful
+    /// flush releases them; empty ones are removed straight away.
+    pub fn open(directory: &Path, sync_on_write: bool) -> std::io::Result<(Self, WalReplay)> {
+        let mut replay = WalReplay::default();
+        let generations = Self::existing_generations(directory)?;
+
+        for generation in &generations {
+            let path = Self::generation_path(directory, *generation);
+            if std::fs::metadata(&path)?.len() == 0 {
+                std::fs::remove_file(&path)?;
+                continue;
+            }
+            Self::replay_file(&path, &mut replay)?;
+        }
+
+        let mut wal = Self {
+            directory: directory.to_path_buf(),
+            generation: generations.last().map_or(1, |last| last + 1),
+            writer: None,
+            sync_on_write,
+        };
+        wal.open_active_generation()?;
+
+        Ok((wal, replay))
+    }
+
+    /// Append a single encoded event, and the stream it was written to, to the active generation.
+    pub fn append_event(&mut self, event: &Event, stream: Option<u32>) -> std::io::Result<()> {
+        let mut payload = Vec::with_capacity(RECORD_SIZE + 5);
+        match stream {
+            Some(stream_id) => {
+                payload.push(RECORD_KIND_STREAM_EVENT);
+                payload.extend_from_slice(&stream_id.to_le_bytes());
+            }
+            None => payload.push(RECORD_KIND_EVENT),
+        }
+        let mut record = [0u8; RECORD_SIZE];
+        encode_record(
+            &mut record,
+            event.timestamp,
+            event.subject,
+            event.predicate,
+            event.object,
+            event.graph,
+        );
+        payload.extend_from_slice(&record);
+        self.append_payload(&payload)
+    }
+
+    /// Append newly assigned dictionary entries so that replayed events stay decodable
+    /// even when the process dies before the dictionary is saved.
+    pub fn append_terms<'a>(
+        &mut self,
+        terms: impl IntoIterator<Item = (u32, &'a str)>,
+    ) -> std::io::Result<()> {
+        for (id, value) in terms {
+            let mut payload = Vec::with_capacity(9 + value.len());
+            payload.push(RECORD_KIND_TERM);
+            payload.extend_from_slice(&id.to_le_bytes());
+            payload.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            payload.extend_from_slice(value.as_bytes());
+            self.append_payload(&payload)?;
+        }
+        Ok(())
+    }
+
+    /// Seal the active generation and start a new one.
+    ///
+    /// Returns the generation that was sealed; pass it to [`Self::release_through`]
+    /// once the drained events are safely stored in a segment.
+    pub fn seal(&mut self) -> std::io::Result<u64> {
+        if let Some(mut writer) = self.writer.take() {
+            writer.flush()?;
+            writer.get_ref().sync_data()?;
+        }
+        let sealed = self.generation;
+        self.generation += 1;
+        self.open_active_generation()?;
+        Ok(sealed)
+    }
+
+    /// Delete every sealed generation up to and including `generation`.
+    pub fn release_through(&self, generation: u64) -> std::io::Result<()> {
+        for existing in Self::existing_generations(&self.directory)? {
+            if existing <= generation {
+                match std::fs::remove_file(Self::generation_path(&self.directory, existing)) {
+                    Ok(()) => {}
+                    Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn open_active_generation(&mut self) -> std::io::Result<()> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(Self::generation_path(&self.directory, self.generation))?;
+        self.writer = Some(BufWriter::new(file));
+        Ok(())
+    }
+
+    fn append_payload(&mut self, payload: &[u8]) -> std::io::Result<()> {
+        if self.writer.is_none() {
+            self.open_active_generation()?;
+        }
+        let writer = self.writer.as_mut().unwrap();
+
+        writer.write_all(&(payload.len() as u32).to_le_bytes())?;
+        writer.write_all(&crc32fast::hash(payload).to_le_bytes())?;
+        writer.write_all(payload)?;
+        writer.flush()?;
+
+        if self.sync_on_write {
+            writer.get_ref().sync_data()?;
+        }
+        Ok(())
+    }
+
+    fn replay_file(path: &Path, replay: &mut WalReplay) -> std::io::Result<()> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut header = [0u8; 8];
+
+        loop {
+            if reader.read_exact(&mut header).is_err() {
+                break;
+            }
+            let length = u32::from_le_bytes(header[0..4].try_into().unwrap()) as usize;
+            let checksum = u32::from_le_bytes(header[4..8].try_into().unwrap());
+
+            if length > MAX_RECORD_BYTES {
+                eprintln!("Warning: Ignoring torn write-ahead log tail in {:?}", path);
+                break;
+            }
+
+            let mut payload = vec![0u8; length];
+            if reader.read_exact(&mut payload).is_err() || crc32fast::hash(&payload) != checksum {
+                eprintln!("Warning: Ignoring torn write-ahead log tail in {:?}", path);
+                break;
+            }
+
+            match payload.first() {
+                Some(&RECORD_KIND_EVENT) if payload.len() == RECORD_SIZE + 1 => {
+                    let record: &[u8; RECORD_SIZE] = payload[1..].try_into().unwrap();
+                    let (timestamp, subject, predicate, object, graph) = decode_record(record);
+                    replay.events.push(Event { timestamp, subject, predicate, object, graph });
+                    replay.streams.push(None);
+                }
+                Some(&RECORD_KIND_STREAM_EVENT) if payload.len() == RECORD_SIZE + 5 => {
+                    let stream_id = u32::from_le_bytes(payload[1..5].try_into().unwrap());
+                    let record: &[u8; RECORD_SIZE] = payload[5..].try_into().unwrap();
+                    let (timestamp, subject, predicate, object, graph) = decode_record(record);
+                    replay.events.push(Event { timestamp, subject, predicate, object, graph });
+                    replay.streams.push(Some(stream_id));
+                }
+                Some(&RECORD_KIND_TERM) if payload.len() >= 9 => {
+                    let id = u32::from_le_bytes(payload[1..5].try_into().unwrap());
+                    let value = String::from_utf8(payload[9..].to_vec())
+                        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+                    replay.terms.push((id, value));
+                }
+                _ => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("Unknown write-ahead log record in {:?}", path),
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn existing_generations(directory: &Path) -> std::io::Result<Vec<u64>> {
+        let mut generations = Vec::new();
+        for entry in std::fs::read_dir(directory)? {
+            let entry = entry?;
+            if let Some(generation) = entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.strip_prefix(WAL_FILE_PREFIX))
+                .and_then(|name| name.strip_suffix(WAL_FILE_SUFFIX))
+                .and_then(|id| id.parse::<u64>().ok())
+            {
+                generations.push(generation);
+            }
+        }
+        generations.sort_unstable();
+        Ok(generations)
+    }
+
+    fn generation_path(directory: &Path, generation: u64) -> PathBuf {
+        directory.join(format!("{}{}{}", WAL_FILE_PREFIX, generation, WAL_FILE_SUFFIX))
+    }
+}
@@ -4,7 +4,7 @@
 //! from message brokers and feed them to the live query processor.
 
 use crate::{
-    core::RDFEvent,
+    core::{RDFEvent, RdfTerm},
     parsing::rdf_parser,
     stream::live_stream_processing::{LiveStreamProcessing, LiveStreamProcessingError},
 };
@@ -149,7 +149,7 @@ impl MqttSubscriber {
                                         .as_millis() as u64;
 
                                     // Use empty graph - rsp-rs will assign it to the window's graph automatically
-                                    event.graph = String::new();
+                                    event.graph = RdfTerm::DefaultGraph;
 
                                     println!(
                                         "Parsed RDF event: subject={}, predicate={}, object={}, timestamp={}",
@@ -1,14 +1,21 @@
 use crate::core::Event;
 use crate::parsing::janusql_parser::WindowDefinition;
-use crate::storage::segmented_storage::StreamingSegmentedStorage;
+use crate::storage::event_store::EventStore;
+use crate::storage::query_iter::EventChunks;
 use std::rc::Rc;
 
 /// Operator for processing historical data with a fixed window.
 /// Unlike sliding windows, this queries a single fixed time range [start, end].
+///
+/// By default the whole window is yielded at once. An operator created with
+/// [`HistoricalFixedWindowOperator::with_chunk_size`] reads the window lazily and
+/// yields it as consecutive chunks of events in timestamp order instead.
 pub struct HistoricalFixedWindowOperator {
-    storage: Rc<StreamingSegmentedStorage>,
+    storage: Rc<dyn EventStore>,
     window_def: WindowDefinition,
     has_yielded: bool,
+    chunk_size: Option<usize>,
+    chunks: Option<EventChunks>,
 }
 
 impl HistoricalFixedWindowOperator {
@@ -18,8 +25,32 @@ impl HistoricalFixedWindowOperator {
     ///
     /// * `storage` - The storage backend to query.
     /// * `window_def` - The window definition with start and end timestamps.
-    pub fn new(storage: Rc<StreamingSegmentedStorage>, window_def: WindowDefinition) -> Self {
-        HistoricalFixedWindowOperator { storage, window_def, has_yielded: false }
+    pub fn new(storage: Rc<dyn EventStore>, window_def: WindowDefinition) -> Self {
+        HistoricalFixedWindowOperator {
+            storage,
+            window_def,
+            has_yielded: false,
+            chunk_size: None,
+            chunks: None,
+        }
+    }
+
+    /// Creates a HistoricalFixedWindowOperator that yields the window in chunks.
+    ///
+    /// # Arguments
+    ///
+    /// * `storage` - The storage backend to query.
+    /// * `window_def` - The window definition with start and end timestamps.
+    /// * `chunk_size` - The maximum number of events per yielded chunk.
+    pub fn with_chunk_size(
+        storage: Rc<dyn EventStore>,
+        window_def: WindowDefinition,
+        chunk_size: usize,
+    ) -> Self {
+        HistoricalFixedWindowOperator {
+            chunk_size: Some(chunk_size),
+            ..Self::new(storage, window_def)
+        }
     }
 }
 
@@ -27,7 +58,18 @@ impl Iterator for HistoricalFixedWindowOperator {
     type Item = Vec<Event>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        // Fixed window only yields once
+        if let Some(chunks) = &mut self.chunks {
+            return match chunks.next()? {
+                Ok(events) => Some(events),
+                Err(e) => {
+                    eprintln!("Error reading storage for fixed window: {}", e);
+                    self.chunks = None;
+                    None
+                }
+            };
+        }
+
+        // Fixed window only queries the storage once
         if self.has_yielded {
             return None;
         }
@@ -36,8 +78,22 @@ impl Iterator for HistoricalFixedWindowOperator {
         let start = self.window_def.start.expect("Start must be defined for HistoricalFixedWindow");
         let end = self.window_def.end.expect("End must be defined for HistoricalFixedWindow");
 
+        if let Some(chunk_size) = self.chunk_size {
+            self.has_yielded = true;
+            return match self.storage.query_stream_iter(&self.window_def.stream_name, start, end) {
+                Ok(events) => {
+                    self.chunks = Some(events.chunks(chunk_size));
+                    self.next()
+                }
+                Err(e) => {
+                    eprintln!("Error querying storage for fixed window: {}", e);
+                    None
+                }
+            };
+        }
+
         // Query the storage for events in the fixed window
-        let events_result = self.storage.query(start, end);
+        let events_result = self.storage.query_stream(&self.window_def.stream_name, start, end);
 
         self.has_yielded = true;
 
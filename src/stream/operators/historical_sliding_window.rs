@@ -1,12 +1,12 @@
 use crate::core::Event;
 use crate::parsing::janusql_parser::WindowDefinition;
-use crate::storage::segmented_storage::StreamingSegmentedStorage;
+use crate::storage::event_store::EventStore;
 use std::rc::Rc;
 
 /// Operator for processing historical data with a sliding window.
 /// It iterates over the storage and yields events for each window.
 pub struct HistoricalSlidingWindowOperator {
-    storage: Rc<StreamingSegmentedStorage>,
+    storage: Rc<dyn EventStore>,
     window_def: WindowDefinition,
     current_start: u64,
     end_bound: u64,
@@ -19,7 +19,7 @@ impl HistoricalSlidingWindowOperator {
     ///
     /// * `storage` - The storage backend to query.
     /// * `window_def` - The window definition (width, slide, offset, etc.).
-    pub fn new(storage: Rc<StreamingSegmentedStorage>, window_def: WindowDefinition) -> Self {
+    pub fn new(storage: Rc<dyn EventStore>, window_def: WindowDefinition) -> Self {
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
@@ -58,7 +58,9 @@ impl Iterator for HistoricalSlidingWindowOperator {
         // Note: query() is inclusive, so we might need to adjust if we want [start, end)
         // For now, we assume the storage query semantics match what we want or we accept inclusive.
         // Usually windows are [start, end).
-        let events_result = self.storage.query(window_start, window_end);
+        let events_result =
+            self.storage
+                .query_stream(&self.window_def.stream_name, window_start, window_end);
 
         match events_result {
             Ok(events) => {
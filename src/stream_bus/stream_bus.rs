@@ -8,12 +8,13 @@
 
 use crate::core::RDFEvent;
 use crate::parsing::rdf_parser;
-use crate::storage::segmented_storage::StreamingSegmentedStorage;
+use crate::storage::event_store::EventStore;
+use crate::storage::memory_tracker::MemoryTracker;
 use core::str;
 use rumqttc::{AsyncClient, MqttOptions, QoS};
 use std::fmt::write;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, ErrorKind};
 use std::path::Path;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
@@ -49,6 +50,9 @@ pub struct StreamBusConfig {
     pub loop_file: bool,
     pub add_timestamps: bool,
     pub mqtt_config: Option<MqttConfig>,
+    /// Stream log the replayed events are stored in, matching `ON LOG <stream>` in JanusQL.
+    /// Events are stored without a stream when unset.
+    pub stream_name: Option<String>,
 }
 
 impl Default for MqttConfig {
@@ -69,6 +73,8 @@ pub struct StreamBusMetrics {
     pub events_stored: u64,
     pub publish_errors: u64,
     pub storage_errors: u64,
+    /// Writes delayed because the storage's memory budget was filling up.
+    pub throttled_writes: u64,
     pub elapsed_seconds: f64,
 }
 
@@ -101,13 +107,14 @@ impl StreamBusMetrics {
 /// Main Stream Bus's Architecture
 pub struct StreamBus {
     config: StreamBusConfig,
-    storage: Arc<StreamingSegmentedStorage>,
+    storage: Arc<dyn EventStore>,
     runtime: Arc<Runtime>,
     pub events_read: Arc<AtomicU64>,
     pub events_published: Arc<AtomicU64>,
     pub events_stored: Arc<AtomicU64>,
     pub publish_errors: Arc<AtomicU64>,
     pub storage_errors: Arc<AtomicU64>,
+    pub throttled_writes: Arc<AtomicU64>,
     should_stop: Arc<AtomicBool>,
 }
 
@@ -132,7 +139,7 @@ impl std::fmt::Display for StreamBusError {
 impl std::error::Error for StreamBusError {}
 
 impl StreamBus {
-    pub fn new(config: StreamBusConfig, storage: Arc<StreamingSegmentedStorage>) -> Self {
+    pub fn new(config: StreamBusConfig, storage: Arc<dyn EventStore>) -> Self {
         let runtime = Arc::new(
             tokio::runtime::Builder::new_multi_thread()
                 .worker_threads(4)
@@ -150,6 +157,7 @@ impl StreamBus {
             events_stored: Arc::new(AtomicU64::new(0)),
             publish_errors: Arc::new(AtomicU64::new(0)),
             storage_errors: Arc::new(AtomicU64::new(0)),
+            throttled_writes: Arc::new(AtomicU64::new(0)),
             should_stop: Arc::new(AtomicBool::new(false)),
         }
     }
@@ -195,6 +203,7 @@ impl StreamBus {
             events_stored: self.events_stored.load(Ordering::Relaxed),
             publish_errors: self.publish_errors.load(Ordering::Relaxed),
             storage_errors: self.storage_errors.load(Ordering::Relaxed),
+            throttled_writes: self.throttled_writes.load(Ordering::Relaxed),
             elapsed_seconds: elapsed,
         })
     }
@@ -208,6 +217,7 @@ impl StreamBus {
         let events_stored = Arc::clone(&self.events_stored);
         let publish_errors = Arc::clone(&self.publish_errors);
         let storage_errors = Arc::clone(&self.storage_errors);
+        let throttled_writes = Arc::clone(&self.throttled_writes);
         let should_stop = Arc::clone(&self.should_stop);
         let runtime = Arc::clone(&self.runtime);
 
@@ -221,6 +231,7 @@ impl StreamBus {
                 events_stored,
                 publish_errors,
                 storage_errors,
+                throttled_writes,
                 should_stop,
             };
             bus.start()
@@ -240,6 +251,7 @@ impl StreamBus {
             events_stored: self.events_stored.load(Ordering::Relaxed),
             publish_errors: self.publish_errors.load(Ordering::Relaxed),
             storage_errors: self.storage_errors.load(Ordering::Relaxed),
+            throttled_writes: self.throttled_writes.load(Ordering::Relaxed),
             elapsed_seconds: start_time.elapsed().as_secs_f64(),
         }
     }
@@ -303,16 +315,45 @@ impl StreamBus {
         self.process_file(|_event, _line| async {}).await
     }
 
+    // Write an event to the storage, slowing down while its memory budget fills up instead
+    // of piling up events in memory. Writes refused with `WouldBlock` are retried.
+    async fn store_event(&self, event: RDFEvent) -> std::io::Result<()> {
+        let throttle_delay =
+            || self.storage.memory_tracker().and_then(MemoryTracker::throttle_delay);
+        loop {
+            if let Some(delay) = throttle_delay() {
+                self.throttled_writes.fetch_add(1, Ordering::Relaxed);
+                sleep(delay).await;
+            }
+
+            let stored = match &self.config.stream_name {
+                Some(stream_name) => {
+                    self.storage.write_rdf_event_to_stream(stream_name, event.clone())
+                }
+                None => self.storage.write_rdf_event(event.clone()),
+            };
+            match stored {
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    if self.should_stop.load(Ordering::Relaxed) {
+                        return Err(e);
+                    }
+                    if throttle_delay().is_none() {
+                        sleep(Duration::from_millis(1)).await;
+                    }
+                }
+                stored => return stored,
+            }
+        }
+    }
+
     async fn process_file<F, Fut>(&self, publish_fn: F) -> Result<(), StreamBusError>
     where
         F: Fn(RDFEvent, String) -> Fut + Send + Sync,
         Fut: std::future::Future<Output = ()> + Send,
     {
-        let interval = if self.config.rate_of_publishing > 0 {
-            Some(Duration::from_micros(1_000_000 / self.config.rate_of_publishing))
-        } else {
-            None
-        };
+        let interval = 1_000_000u64
+            .checked_div(self.config.rate_of_publishing)
+            .map(Duration::from_micros);
 
         let mut last_report = Instant::now();
         let report_interval = Duration::from_secs(1);
@@ -344,7 +385,7 @@ impl StreamBus {
 
                         publish_fn(event.clone(), line.clone()).await;
 
-                        match self.storage.write_rdf_event(event) {
+                        match self.store_event(event).await {
                             Ok(_) => {
                                 self.events_stored.fetch_add(1, Ordering::Relaxed);
                             }
@@ -0,0 +1,66 @@
+//! Fixtures shared by the storage tests.
+//!
+//! Each test crate includes this module with `mod common;` and uses only part of it.
+#![allow(dead_code)]
+
+use janus::storage::segmented_storage::StreamingSegmentedStorage;
+use janus::storage::util::StreamingConfig;
+use std::fmt::Display;
+use std::path::Path;
+
+pub const TEMPERATURE: &str = "http://example.org/temperature";
+pub const GRAPH: &str = "http://example.org/graph1";
+
+/// Storage configuration in `path` for tests: batches only flush when a test flushes them
+/// or reaches `max_batch_events`, and the index blocks are small so that queries span
+/// several of them. Tests override the fields they exercise with `..test_config(path)`.
+pub fn test_config(path: &Path) -> StreamingConfig {
+    StreamingConfig {
+        segment_base_path: path.to_string_lossy().into_owned(),
+        max_batch_events: 10_000,
+        max_batch_age_seconds: 60,
+        max_batch_bytes: 10 * 1024 * 1024,
+        sparse_interval: 4,
+        entries_per_index_block: 2,
+        ..StreamingConfig::default()
+    }
+}
+
+/// Write `value` as the temperature of `http://example.org/sensor{sensor}` in [`GRAPH`].
+pub fn write_reading(
+    storage: &StreamingSegmentedStorage,
+    timestamp: u64,
+    sensor: impl Display,
+    value: impl Display,
+) {
+    storage
+        .write_rdf(
+            timestamp,
+            &format!("http://example.org/sensor{}", sensor),
+            TEMPERATURE,
+            &value.to_string(),
+            GRAPH,
+        )
+        .expect("failed to write event");
+}
+
+/// Write `value` as the temperature of `http://example.org/sensor{sensor}` in the default
+/// graph of `stream`.
+pub fn write_stream_reading(
+    storage: &StreamingSegmentedStorage,
+    stream: &str,
+    timestamp: u64,
+    sensor: impl Display,
+    value: impl Display,
+) {
+    storage
+        .write_rdf_to_stream(
+            stream,
+            timestamp,
+            &format!("http://example.org/sensor{}", sensor),
+            TEMPERATURE,
+            &value.to_string(),
+            "",
+        )
+        .expect("failed to write event");
+}
@@ -389,10 +389,10 @@ fn test_sparse_index_with_dictionary_integration() -> std::io::Result<()> {
     assert_eq!(results.len(), 100);
 
     // Verify first result has resolved URIs
-    assert!(results[0].subject.starts_with("https://rsp.js/event/"));
-    assert!(results[0].predicate.starts_with("http://www.w3.org/ns/saref#"));
-    assert!(results[0].object.starts_with("reading-"));
-    assert_eq!(results[0].graph, graph_uri);
+    assert!(results[0].subject.value().starts_with("https://rsp.js/event/"));
+    assert!(results[0].predicate.value().starts_with("http://www.w3.org/ns/saref#"));
+    assert!(results[0].object.value().starts_with("reading-"));
+    assert_eq!(results[0].graph.value(), graph_uri);
 
     // Verify timestamps are in order
     for (i, event) in results.iter().enumerate() {
@@ -64,10 +64,10 @@ fn test_clean_rdf_api() {
     let decoded_event = encoded_event.decode(&dict);
 
     // Verify the round-trip works
-    assert_eq!(decoded_event.subject, "http://example.org/person/Alice");
-    assert_eq!(decoded_event.predicate, "http://example.org/knows");
-    assert_eq!(decoded_event.object, "http://example.org/person/Bob");
-    assert_eq!(decoded_event.graph, "http://example.org/graph1");
+    assert_eq!(decoded_event.subject.value(), "http://example.org/person/Alice");
+    assert_eq!(decoded_event.predicate.value(), "http://example.org/knows");
+    assert_eq!(decoded_event.object.value(), "http://example.org/person/Bob");
+    assert_eq!(decoded_event.graph.value(), "http://example.org/graph1");
     assert_eq!(decoded_event.timestamp, 1_234_567_890);
 
     println!("Clean API test passed!");
@@ -0,0 +1,204 @@
+mod common;
+
+use janus::core::{RDFEvent, RdfTerm};
+use janus::execution::historical_executor::HistoricalExecutor;
+use janus::parsing::janusql_parser::{SourceKind, WindowDefinition, WindowType};
+use janus::querying::oxigraph_adapter::OxigraphAdapter;
+use janus::storage::event_store::EventStore;
+use janus::storage::in_memory_store::InMemoryEventStore;
+use janus::storage::indexing::secondary::TriplePattern;
+use janus::storage::oxigraph_store::{OxigraphEventStore, EVENTS_GRAPH};
+use janus::storage::segmented_storage::StreamingSegmentedStorage;
+use janus::storage::util::StreamingConfig;
+use oxigraph::model::{GraphNameRef, NamedNodeRef};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+const STREAM_A: &str = "http://example.org/streamA";
+const STREAM_B: &str = "http://example.org/streamB";
+const TEMPERATURE: &str = "http://example.org/temperature";
+const HUMIDITY: &str = "http://example.org/humidity";
+
+fn segmented_store(path: &std::path::Path) -> Arc<dyn EventStore> {
+    let config = StreamingConfig { entries_per_index_block: 4, ..common::test_config(path) };
+    Arc::new(StreamingSegmentedStorage::new(config).expect("failed to create storage"))
+}
+
+// Every backend paired with a name for assertion messages.
+fn backends(temp_dir: &TempDir) -> Vec<(&'static str, Arc<dyn EventStore>)> {
+    vec![
+        ("segmented", segmented_store(temp_dir.path())),
+        ("in-memory", Arc::new(InMemoryEventStore::new())),
+        ("oxigraph", Arc::new(OxigraphEventStore::new().expect("failed to create store"))),
+    ]
+}
+
+// Readings of both streams and some without a stream, written out of timestamp order.
+fn write_readings(store: &dyn EventStore) {
+    for i in (0..30u64).rev() {
+        let event = RDFEvent::new(
+            1_000 + i,
+            &format!("http://example.org/sensor{}", i % 3),
+            if i % 2 == 0 { TEMPERATURE } else { HUMIDITY },
+            &format!("\"{}\"^^<http://www.w3.org/2001/XMLSchema#integer>", i),
+            "http://example.org/graph1",
+        );
+        let written = match i % 3 {
+            0 => store.write_rdf_event_to_stream(STREAM_A, event),
+            1 => store.write_rdf_event_to_stream(STREAM_B, event),
+            _ => store.write_rdf_event(event),
+        };
+        written.expect("failed to write event");
+    }
+    store.flush().expect("failed to flush store");
+}
+
+fn timestamps(events: &[janus::core::Event]) -> Vec<u64> {
+    events.iter().map(|event| event.timestamp).collect()
+}
+
+#[test]
+fn test_backends_answer_queries_alike() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    for (name, store) in backends(&temp_dir) {
+        write_readings(store.as_ref());
+
+        let all = store.query(0, u64::MAX).expect("failed to query store");
+        assert_eq!(timestamps(&all), (1_000..1_030).collect::<Vec<_>>(), "{}", name);
+
+        let stream_a = store.query_stream(STREAM_A, 1_000, 1_011).expect("failed to query stream");
+        assert_eq!(
+            timestamps(&stream_a),
+            vec![1_000, 1_002, 1_003, 1_005, 1_006, 1_008, 1_009, 1_011],
+            "{}",
+            name
+        );
+
+        let unknown = store.query_stream("http://example.org/nowhere", 0, u64::MAX).unwrap();
+        assert_eq!(unknown.len(), 10, "{}", name);
+
+        let humidity: Vec<_> = store
+            .query_stream_filtered_iter(
+                STREAM_B,
+                0,
+                u64::MAX,
+                &[TriplePattern::new(None, Some(HUMIDITY))],
+            )
+            .expect("failed to query stream")
+            .collect::<std::io::Result<_>>()
+            .expect("failed to read events");
+        assert_eq!(
+            timestamps(&humidity),
+            vec![1_001, 1_005, 1_007, 1_011, 1_013, 1_017, 1_019, 1_023, 1_025, 1_029],
+            "{}",
+            name
+        );
+
+        let dictionary = store.get_dictionary().read().unwrap();
+        let decoded = all[3].decode(&dictionary);
+        assert_eq!(decoded.subject, RdfTerm::named_node("http://example.org/sensor0"), "{}", name);
+        assert_eq!(
+            decoded.object,
+            RdfTerm::typed_literal("3", "http://www.w3.org/2001/XMLSchema#integer"),
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn test_historical_executor_runs_on_every_backend() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let window = WindowDefinition {
+        window_name: "http://example.org/window/backends".to_string(),
+        source_kind: SourceKind::Log,
+        stream_name: STREAM_A.to_string(),
+        width: 0,
+        slide: 0,
+        offset: None,
+        start: Some(1_000),
+        end: Some(1_029),
+        window_type: WindowType::HistoricalFixed,
+    };
+
+    for (name, store) in backends(&temp_dir) {
+        write_readings(store.as_ref());
+        let executor = HistoricalExecutor::new(store, OxigraphAdapter::new());
+        let bindings = executor
+            .execute_fixed_window(
+                &window,
+                "SELECT ?sensor ?value WHERE { GRAPH ?g { ?sensor <http://example.org/temperature> ?value } }",
+            )
+            .expect("failed to execute window");
+        // Stream A and the events without a stream hold twenty readings, half of them
+        // temperatures.
+        assert_eq!(bindings.len(), 10, "{}", name);
+    }
+}
+
+#[test]
+fn test_oxigraph_store_keeps_events_as_rdf() {
+    let store = OxigraphEventStore::new().expect("failed to create store");
+    store
+        .write_rdf_event_to_stream(
+            STREAM_A,
+            RDFEvent::new(1_000, "_:reading", TEMPERATURE, "21", "default"),
+        )
+        .expect("failed to write event");
+    store
+        .write_rdf(
+            1_000,
+            "http://example.org/sensor1",
+            TEMPERATURE,
+            "22",
+            "http://example.org/graph1",
+        )
+        .expect("failed to write event");
+
+    let oxigraph = store.store();
+    let graph1 = NamedNodeRef::new_unchecked("http://example.org/graph1");
+    assert_eq!(oxigraph.quads_for_pattern(None, None, None, Some(graph1.into())).count(), 1);
+    assert_eq!(
+        oxigraph
+            .quads_for_pattern(None, None, None, Some(GraphNameRef::DefaultGraph))
+            .count(),
+        1
+    );
+    let events_graph = NamedNodeRef::new_unchecked(EVENTS_GRAPH);
+    assert!(oxigraph.quads_for_pattern(None, None, None, Some(events_graph.into())).count() > 0);
+
+    // Events with equal timestamps come back in write order.
+    let encoded = store.query(0, u64::MAX).expect("failed to query store");
+    let dictionary = store.get_dictionary().read().unwrap();
+    let events: Vec<RDFEvent> = encoded.iter().map(|event| event.decode(&dictionary)).collect();
+    drop(dictionary);
+    assert_eq!(events[0].subject, RdfTerm::blank_node("reading"));
+    assert_eq!(events[0].graph, RdfTerm::DefaultGraph);
+    assert_eq!(events[1].subject, RdfTerm::named_node("http://example.org/sensor1"));
+
+    // A second store over the same Oxigraph data picks up where the first left off.
+    let reopened = OxigraphEventStore::with_store(oxigraph.clone()).expect("failed to reopen");
+    reopened
+        .write_rdf(
+            1_000,
+            "http://example.org/sensor2",
+            TEMPERATURE,
+            "23",
+            "http://example.org/graph1",
+        )
+        .expect("failed to write event");
+    assert_eq!(reopened.query(0, u64::MAX).expect("failed to query store").len(), 3);
+}
+
+#[cfg(feature = "rocksdb")]
+#[test]
+fn test_oxigraph_store_persists_in_rocksdb() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    {
+        let store = OxigraphEventStore::open(temp_dir.path()).expect("failed to open store");
+        write_readings(&store);
+    }
+    let store = OxigraphEventStore::open(temp_dir.path()).expect("failed to reopen store");
+    let events = store.query(0, u64::MAX).expect("failed to query store");
+    assert_eq!(timestamps(&events), (1_000..1_030).collect::<Vec<_>>());
+}
@@ -13,6 +13,7 @@ fn create_test_config(path: &str) -> StreamingConfig {
         max_batch_age_seconds: 1,
         sparse_interval: 2,
         entries_per_index_block: 2,
+        ..StreamingConfig::default()
     }
 }
 
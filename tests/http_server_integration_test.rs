@@ -1,7 +1,7 @@
 use futures_util::StreamExt;
 use janus::{
     api::janus_api::{JanusApi, QueryResult, ResultSource},
-    http::server::{create_server_with_state, AppState, QueryResultBroadcast},
+    http::server::{create_server_with_options, AppState, QueryResultBroadcast, ServerOptions},
     parsing::janusql_parser::JanusQLParser,
     registry::query_registry::QueryRegistry,
     storage::{segmented_storage::StreamingSegmentedStorage, util::StreamingConfig},
@@ -52,6 +52,10 @@ fn historical_query(query_id: &str) -> Value {
 }
 
 async fn spawn_test_server() -> TestServer {
+    spawn_test_server_with_options(ServerOptions::default()).await
+}
+
+async fn spawn_test_server_with_options(options: ServerOptions) -> TestServer {
     let temp_dir = TempDir::new().expect("failed to create temp dir");
     let storage_dir = temp_dir.path().to_path_buf();
     let mut storage = StreamingSegmentedStorage::new(StreamingConfig {
@@ -61,6 +65,7 @@ async fn spawn_test_server() -> TestServer {
         max_batch_bytes: 1024 * 1024,
         sparse_interval: 10,
         entries_per_index_block: 100,
+        ..StreamingConfig::default()
     })
     .expect("failed to create storage");
     storage.start_background_flushing();
@@ -81,12 +86,12 @@ async fn spawn_test_server() -> TestServer {
         JanusApi::new(
             JanusQLParser::new().expect("failed to create parser"),
             Arc::clone(&registry),
-            Arc::clone(&storage),
+            storage.clone(),
         )
         .expect("failed to create api"),
     );
 
-    let (app, state) = create_server_with_state(janus_api, registry, storage);
+    let (app, state) = create_server_with_options(janus_api, registry, storage, options);
     let listener = TcpListener::bind("127.0.0.1:0").await.expect("failed to bind listener");
     let addr = listener.local_addr().expect("failed to read local addr");
     let server_task = tokio::spawn(async move {
@@ -157,12 +162,142 @@ async fn test_ops_status_endpoint_reports_runtime_counts() {
     let body: Value = response.json().await.expect("invalid ops status response");
     assert_eq!(body["status"], "ok");
     assert_eq!(body["storage"]["status"], "ok");
+    assert_eq!(body["storage"]["retention_horizon"], Value::Null);
+    let memory = &body["storage"]["memory"];
+    assert!(memory["dictionary_bytes"].as_u64().is_some());
+    assert!(memory["index_directory_bytes"].as_u64().is_some());
+    assert!(memory["estimated_total_bytes"].as_u64() >= memory["mmap_bytes"].as_u64());
+    if cfg!(target_os = "linux") {
+        assert!(body["storage"]["process_memory"]["resident_bytes"].as_u64().unwrap() > 0);
+    }
     assert_eq!(body["replay"]["is_running"], false);
     assert_eq!(body["queries"]["total_registered_queries"], 1);
     assert_eq!(body["queries"]["active_runtime_queries"], 1);
     assert_eq!(body["queries"]["running_queries"], 1);
 }
 
+#[tokio::test]
+async fn test_erasure_endpoint_erases_subject_and_reports_progress() {
+    let server = spawn_test_server().await;
+
+    let empty = server
+        .client
+        .post(format!("{}/api/admin/erasure", server.base_url))
+        .json(&json!({}))
+        .send()
+        .await
+        .expect("erasure request failed");
+    assert_eq!(empty.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let response = server
+        .client
+        .post(format!("{}/api/admin/erasure", server.base_url))
+        .json(&json!({ "subjects": ["http://example.org/sensor1"] }))
+        .send()
+        .await
+        .expect("erasure request failed");
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+    // The events are hidden as soon as the erasure is accepted.
+    assert!(server.state.storage.query(0, u64::MAX).expect("failed to query").is_empty());
+
+    let mut progress = Value::Null;
+    for _ in 0..50 {
+        let response = server
+            .client
+            .get(format!("{}/api/admin/erasure", server.base_url))
+            .send()
+            .await
+            .expect("erasure status request failed");
+        assert!(response.status().is_success());
+        progress =
+            response.json::<Value>().await.expect("invalid erasure response")["progress"].clone();
+        if progress["state"] == "completed" {
+            break;
+        }
+        sleep(Duration::from_millis(20)).await;
+    }
+    assert_eq!(progress["state"], "completed");
+    assert_eq!(progress["pending_tombstones"], 0);
+    assert_eq!(progress["events_removed"], 1);
+    let dictionary = server.state.storage.get_dictionary().read().unwrap();
+    assert!(!dictionary.string_to_id.contains_key("http://example.org/sensor1"));
+}
+
+#[tokio::test]
+async fn test_snapshot_endpoint_writes_a_reopenable_copy() {
+    let backup_dir = TempDir::new().expect("failed to create temp dir");
+    let server = spawn_test_server_with_options(ServerOptions {
+        snapshot_dir: Some(backup_dir.path().to_path_buf()),
+    })
+    .await;
+    let destination = backup_dir.path().join("daily/snapshot");
+
+    let response = server
+        .client
+        .post(format!("{}/api/admin/snapshot", server.base_url))
+        .json(&json!({ "destination": "daily/snapshot" }))
+        .send()
+        .await
+        .expect("snapshot request failed");
+    assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+    let body: Value = response.json().await.expect("invalid snapshot response");
+    assert_eq!(body["manifest"]["record_count"], 1);
+
+    // The destination is no longer empty, so a second snapshot into it is refused.
+    let again = server
+        .client
+        .post(format!("{}/api/admin/snapshot", server.base_url))
+        .json(&json!({ "destination": "daily/snapshot" }))
+        .send()
+        .await
+        .expect("snapshot request failed");
+    assert_eq!(again.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let copy = StreamingSegmentedStorage::new(StreamingConfig {
+        segment_base_path: destination.to_string_lossy().into_owned(),
+        ..StreamingConfig::default()
+    })
+    .expect("failed to open snapshot");
+    assert_eq!(copy.query(0, u64::MAX).expect("failed to query snapshot").len(), 1);
+}
+
+#[tokio::test]
+async fn test_snapshot_endpoint_stays_inside_the_snapshot_directory() {
+    let server = spawn_test_server().await;
+    let response = server
+        .client
+        .post(format!("{}/api/admin/snapshot", server.base_url))
+        .json(&json!({ "destination": "snapshot" }))
+        .send()
+        .await
+        .expect("snapshot request failed");
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+    let backup_dir = TempDir::new().expect("failed to create temp dir");
+    let snapshot_dir = backup_dir.path().join("snapshots");
+    let server =
+        spawn_test_server_with_options(ServerOptions { snapshot_dir: Some(snapshot_dir.clone()) })
+            .await;
+    let outside = backup_dir.path().join("outside");
+    for destination in [
+        outside.to_string_lossy().into_owned(),
+        "../outside".to_string(),
+        "daily/../../outside".to_string(),
+        " ".to_string(),
+    ] {
+        let response = server
+            .client
+            .post(format!("{}/api/admin/snapshot", server.base_url))
+            .json(&json!({ "destination": destination }))
+            .send()
+            .await
+            .expect("snapshot request failed");
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST, "{}", destination);
+    }
+    assert!(!outside.exists());
+    assert!(!snapshot_dir.exists());
+}
+
 #[tokio::test]
 async fn test_health_endpoint_reports_storage_degradation() {
     let server = spawn_test_server().await;
@@ -23,6 +23,7 @@ fn test_basic_functionality() {
         max_batch_bytes: 1024 * 1024,
         sparse_interval: 10,
         entries_per_index_block: 100,
+        ..StreamingConfig::default()
     })
     .expect("failed to create storage");
     storage.start_background_flushing();
@@ -8,25 +8,36 @@ use janus::parsing::janusql_parser::JanusQLParser;
 use janus::registry::query_registry::QueryRegistry;
 use janus::storage::segmented_storage::StreamingSegmentedStorage;
 use janus::storage::util::StreamingConfig;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 
+static NEXT_TEST_DIR: AtomicUsize = AtomicUsize::new(0);
+
+/// A storage directory of its own for every test, since a storage locks the directory it writes to
+fn unique_test_path() -> String {
+    format!(
+        "./test_data/janus_api_test_{}_{}_{}",
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis(),
+        std::process::id(),
+        NEXT_TEST_DIR.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
 /// Helper function to create a test storage with sample data
 fn create_test_storage_with_data() -> Result<Arc<StreamingSegmentedStorage>, std::io::Error> {
     let config = StreamingConfig {
-        segment_base_path: format!(
-            "./test_data/janus_api_test_{}",
-            std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_millis()
-        ),
+        segment_base_path: unique_test_path(),
         max_batch_events: 10, // Small batch to force frequent flushes
         max_batch_age_seconds: 1,
         max_batch_bytes: 1024,
         sparse_interval: 10,
         entries_per_index_block: 100,
+        ..StreamingConfig::default()
     };
 
     let mut storage = StreamingSegmentedStorage::new(config)?;
@@ -57,8 +68,11 @@ fn test_janus_api_creation() {
     let parser = JanusQLParser::new().expect("Failed to create parser");
     let registry = Arc::new(QueryRegistry::new());
     let storage = Arc::new(
-        StreamingSegmentedStorage::new(StreamingConfig::default())
-            .expect("Failed to create storage"),
+        StreamingSegmentedStorage::new(StreamingConfig {
+            segment_base_path: unique_test_path(),
+            ..StreamingConfig::default()
+        })
+        .expect("Failed to create storage"),
     );
 
     let api = JanusApi::new(parser, registry, storage);
@@ -70,8 +84,11 @@ fn test_register_query() {
     let parser = JanusQLParser::new().expect("Failed to create parser");
     let registry = Arc::new(QueryRegistry::new());
     let storage = Arc::new(
-        StreamingSegmentedStorage::new(StreamingConfig::default())
-            .expect("Failed to create storage"),
+        StreamingSegmentedStorage::new(StreamingConfig {
+            segment_base_path: unique_test_path(),
+            ..StreamingConfig::default()
+        })
+        .expect("Failed to create storage"),
     );
 
     let api = JanusApi::new(parser, registry, storage).expect("Failed to create API");
@@ -101,8 +118,11 @@ fn test_register_invalid_query() {
     let parser = JanusQLParser::new().expect("Failed to create parser");
     let registry = Arc::new(QueryRegistry::new());
     let storage = Arc::new(
-        StreamingSegmentedStorage::new(StreamingConfig::default())
-            .expect("Failed to create storage"),
+        StreamingSegmentedStorage::new(StreamingConfig {
+            segment_base_path: unique_test_path(),
+            ..StreamingConfig::default()
+        })
+        .expect("Failed to create storage"),
     );
 
     let api = JanusApi::new(parser, registry, storage).expect("Failed to create API");
@@ -119,8 +139,11 @@ fn test_start_query_not_registered() {
     let parser = JanusQLParser::new().expect("Failed to create parser");
     let registry = Arc::new(QueryRegistry::new());
     let storage = Arc::new(
-        StreamingSegmentedStorage::new(StreamingConfig::default())
-            .expect("Failed to create storage"),
+        StreamingSegmentedStorage::new(StreamingConfig {
+            segment_base_path: unique_test_path(),
+            ..StreamingConfig::default()
+        })
+        .expect("Failed to create storage"),
     );
 
     let api = JanusApi::new(parser, registry, storage).expect("Failed to create API");
@@ -252,8 +275,11 @@ fn test_historical_sliding_window_query() {
 #[test]
 fn test_query_already_running() {
     let storage = Arc::new(
-        StreamingSegmentedStorage::new(StreamingConfig::default())
-            .expect("Failed to create storage"),
+        StreamingSegmentedStorage::new(StreamingConfig {
+            segment_base_path: unique_test_path(),
+            ..StreamingConfig::default()
+        })
+        .expect("Failed to create storage"),
     );
     let parser = JanusQLParser::new().expect("Failed to create parser");
     let registry = Arc::new(QueryRegistry::new());
@@ -286,8 +312,11 @@ fn test_query_already_running() {
 #[test]
 fn test_is_running() {
     let storage = Arc::new(
-        StreamingSegmentedStorage::new(StreamingConfig::default())
-            .expect("Failed to create storage"),
+        StreamingSegmentedStorage::new(StreamingConfig {
+            segment_base_path: unique_test_path(),
+            ..StreamingConfig::default()
+        })
+        .expect("Failed to create storage"),
     );
     let parser = JanusQLParser::new().expect("Failed to create parser");
     let registry = Arc::new(QueryRegistry::new());
@@ -319,8 +348,11 @@ fn test_is_running() {
 #[test]
 fn test_stop_query() {
     let storage = Arc::new(
-        StreamingSegmentedStorage::new(StreamingConfig::default())
-            .expect("Failed to create storage"),
+        StreamingSegmentedStorage::new(StreamingConfig {
+            segment_base_path: unique_test_path(),
+            ..StreamingConfig::default()
+        })
+        .expect("Failed to create storage"),
     );
     let parser = JanusQLParser::new().expect("Failed to create parser");
     let registry = Arc::new(QueryRegistry::new());
@@ -358,8 +390,11 @@ fn test_stop_query() {
 #[test]
 fn test_execution_count_and_status_update_across_lifecycle() {
     let storage = Arc::new(
-        StreamingSegmentedStorage::new(StreamingConfig::default())
-            .expect("Failed to create storage"),
+        StreamingSegmentedStorage::new(StreamingConfig {
+            segment_base_path: unique_test_path(),
+            ..StreamingConfig::default()
+        })
+        .expect("Failed to create storage"),
     );
     let parser = JanusQLParser::new().expect("Failed to create parser");
     let registry = Arc::new(QueryRegistry::new());
@@ -538,8 +573,11 @@ WHERE {
 #[test]
 fn test_only_live_window() {
     let storage = Arc::new(
-        StreamingSegmentedStorage::new(StreamingConfig::default())
-            .expect("Failed to create storage"),
+        StreamingSegmentedStorage::new(StreamingConfig {
+            segment_base_path: unique_test_path(),
+            ..StreamingConfig::default()
+        })
+        .expect("Failed to create storage"),
     );
     let parser = JanusQLParser::new().expect("Failed to create parser");
     let registry = Arc::new(QueryRegistry::new());
@@ -47,3 +47,40 @@ fn test_janus_default_output_points_to_real_binaries() {
     assert!(stdout.contains("stream_bus_cli"));
     assert!(stdout.contains("http_client_example"));
 }
+
+#[test]
+fn test_janus_storage_export_and_import_round_trip() {
+    let temp_dir = tempfile::TempDir::new().expect("failed to create temp dir");
+    let archive = temp_dir.path().join("events.nq");
+    std::fs::write(
+        &archive,
+        "1000 <http://example.org/s> <http://example.org/p> \"1\" .\n\
+         # stream http://example.org/stream1\n\
+         1001 <http://example.org/s> <http://example.org/p> \"2\" <http://example.org/g> .\n",
+    )
+    .expect("failed to write archive");
+    let source = temp_dir.path().join("source");
+    let exported = temp_dir.path().join("exported.trig");
+
+    let output = Command::new(get_janus_binary())
+        .args(["storage", "import", "--storage-dir"])
+        .arg(&source)
+        .arg("--input")
+        .arg(&archive)
+        .output()
+        .expect("failed to run janus storage import");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Imported 2 events"));
+
+    let output = Command::new(get_janus_binary())
+        .args(["storage", "export", "--storage-dir"])
+        .arg(&source)
+        .arg("--output")
+        .arg(&exported)
+        .output()
+        .expect("failed to run janus storage export");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("Exported 2 events"));
+    let trig = std::fs::read_to_string(&exported).expect("failed to read export");
+    assert!(trig.contains("janus:events"));
+}
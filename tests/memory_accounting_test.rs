@@ -0,0 +1,89 @@
+mod common;
+
+use janus::storage::memory_tracker::ProcessMemory;
+use janus::storage::segmented_storage::StreamingSegmentedStorage;
+use tempfile::TempDir;
+
+const STATM: &str = "52000 3100 1200 300 0 2500 0\n";
+
+const SMAPS_ROLLUP: &str = "\
+55d0c8a00000-7ffd2b5f9000 ---p 00000000 00:00 0                          [rollup]
+Rss:               12400 kB
+Pss:                9876 kB
+Pss_Anon:           7000 kB
+Shared_Clean:       3000 kB
+Anonymous:          7100 kB
+Swap:                 12 kB
+SwapPss:              12 kB
+";
+
+#[test]
+fn test_process_memory_from_proc_files() {
+    let memory = ProcessMemory::from_proc(STATM, Some(SMAPS_ROLLUP), 4096).unwrap();
+    assert_eq!(memory.virtual_bytes, 52_000 * 4096);
+    assert_eq!(memory.resident_bytes, 3_100 * 4096);
+    assert_eq!(memory.shared_bytes, 1_200 * 4096);
+    assert_eq!(memory.data_bytes, 2_500 * 4096);
+    // Pss_Anon and SwapPss must not be mistaken for Pss and Swap.
+    assert_eq!(memory.proportional_bytes, Some(9_876 * 1024));
+    assert_eq!(memory.anonymous_bytes, Some(7_100 * 1024));
+    assert_eq!(memory.swap_bytes, Some(12 * 1024));
+
+    let without_rollup = ProcessMemory::from_proc(STATM, None, 16_384).unwrap();
+    assert_eq!(without_rollup.resident_bytes, 3_100 * 16_384);
+    assert_eq!(without_rollup.proportional_bytes, None);
+
+    assert_eq!(ProcessMemory::from_proc("52000 3100", None, 4096), None);
+    assert_eq!(ProcessMemory::from_proc("not statm at all", None, 4096), None);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_process_memory_reads_current_process() {
+    let memory = ProcessMemory::current().expect("/proc/self/statm must be readable");
+    assert!(memory.resident_bytes > 0);
+    assert!(memory.virtual_bytes >= memory.resident_bytes);
+}
+
+#[test]
+fn test_component_sizes_follow_buffer_segments_and_mappings() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = StreamingSegmentedStorage::new(common::test_config(temp_dir.path()))
+        .expect("failed to create storage");
+    let empty = storage.component_sizes();
+    assert_eq!(empty.segments_count, 0);
+    assert_eq!(empty.index_directory_bytes, 0);
+    assert_eq!(empty.mmap_bytes, 0);
+
+    for i in 0..200u64 {
+        storage
+            .write_rdf(
+                1_000 + i,
+                &format!("http://example.org/sensor{}", i % 20),
+                "http://example.org/temperature",
+                &format!("{}", i),
+                "http://example.org/graph1",
+            )
+            .expect("failed to write event");
+    }
+    let buffered = storage.component_sizes();
+    assert!(buffered.batch_buffer_bytes > empty.batch_buffer_bytes);
+    assert!(buffered.dictionary_bytes > empty.dictionary_bytes);
+
+    storage.flush().expect("failed to flush storage");
+    storage.query(0, u64::MAX).expect("failed to query storage");
+    let flushed = storage.component_sizes();
+    assert_eq!(flushed.segments_count, 1);
+    assert!(flushed.index_directory_bytes > 0);
+    let data_len = std::fs::metadata(&storage.segment_metadata()[0].data_path)
+        .expect("segment data file must exist")
+        .len();
+    assert_eq!(flushed.mmap_bytes as u64, data_len);
+    assert_eq!(
+        flushed.estimated_total_bytes,
+        flushed.batch_buffer_bytes
+            + flushed.dictionary_bytes
+            + flushed.index_directory_bytes
+            + flushed.mmap_bytes
+    );
+}
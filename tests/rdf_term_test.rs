@@ -0,0 +1,283 @@
+mod common;
+
+use janus::core::{RDFEvent, RdfTerm};
+use janus::execution::historical_executor::HistoricalExecutor;
+use janus::parsing::janusql_parser::{SourceKind, WindowDefinition, WindowType};
+use janus::querying::oxigraph_adapter::OxigraphAdapter;
+use janus::storage::segmented_storage::StreamingSegmentedStorage;
+use janus::storage::util::StreamingConfig;
+use oxigraph::model::{BlankNode, GraphName, Literal, NamedNode, Quad};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+const XSD: &str = "http://www.w3.org/2001/XMLSchema#";
+
+fn term_test_config(path: &std::path::Path) -> StreamingConfig {
+    StreamingConfig {
+        sparse_interval: 10,
+        entries_per_index_block: 100,
+        ..common::test_config(path)
+    }
+}
+
+fn iri(value: &str) -> NamedNode {
+    NamedNode::new(value).unwrap()
+}
+
+fn sample_quads() -> Vec<Quad> {
+    let sensor = iri("http://example.org/sensor1");
+    let graph = GraphName::from(iri("http://example.org/graph1"));
+    vec![
+        Quad::new(
+            sensor.clone(),
+            iri("http://example.org/temperature"),
+            Literal::new_typed_literal("23.5", iri(&format!("{}double", XSD))),
+            graph.clone(),
+        ),
+        Quad::new(
+            sensor.clone(),
+            iri("http://example.org/count"),
+            Literal::new_typed_literal("42", iri(&format!("{}integer", XSD))),
+            graph.clone(),
+        ),
+        Quad::new(
+            sensor.clone(),
+            iri("http://example.org/label"),
+            Literal::new_language_tagged_literal("capteur \"nord\"\nétage 2", "fr").unwrap(),
+            graph.clone(),
+        ),
+        Quad::new(
+            sensor.clone(),
+            iri("http://example.org/label"),
+            Literal::new_simple_literal("http://not-an-iri.example.org"),
+            graph.clone(),
+        ),
+        Quad::new(
+            BlankNode::new("reading7").unwrap(),
+            iri("http://example.org/observedBy"),
+            sensor.clone(),
+            GraphName::DefaultGraph,
+        ),
+        Quad::new(
+            sensor,
+            iri("http://example.org/location"),
+            BlankNode::new("place").unwrap(),
+            BlankNode::new("g1").unwrap(),
+        ),
+    ]
+}
+
+#[test]
+fn test_parse_terms_from_strings() {
+    assert_eq!(RdfTerm::parse("<urn:sensor:1>"), RdfTerm::named_node("urn:sensor:1"));
+    assert_eq!(
+        RdfTerm::parse("https://example.org/a"),
+        RdfTerm::named_node("https://example.org/a")
+    );
+    assert_eq!(RdfTerm::parse("_:b0"), RdfTerm::blank_node("b0"));
+    assert_eq!(
+        RdfTerm::parse("\"23.5\"^^<http://www.w3.org/2001/XMLSchema#double>"),
+        RdfTerm::typed_literal("23.5", "http://www.w3.org/2001/XMLSchema#double")
+    );
+    assert_eq!(
+        RdfTerm::parse("\"hello\"@EN-gb"),
+        RdfTerm::language_tagged_literal("hello", "en-gb")
+    );
+    assert_eq!(
+        RdfTerm::parse("\"plain\"^^<http://www.w3.org/2001/XMLSchema#string>"),
+        RdfTerm::simple_literal("plain")
+    );
+
+    // Bare values keep the classification historical queries have always relied on.
+    assert_eq!(
+        RdfTerm::parse("30"),
+        RdfTerm::typed_literal("30", "http://www.w3.org/2001/XMLSchema#decimal")
+    );
+    assert_eq!(RdfTerm::parse("12:30 sharp"), RdfTerm::simple_literal("12:30 sharp"));
+    assert_eq!(RdfTerm::parse_graph("default"), RdfTerm::DefaultGraph);
+    assert_eq!(RdfTerm::parse_graph(""), RdfTerm::DefaultGraph);
+}
+
+#[test]
+fn test_terms_round_trip_through_storage_into_oxigraph() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let quads = sample_quads();
+    {
+        let storage = StreamingSegmentedStorage::new(term_test_config(temp_dir.path()))
+            .expect("failed to create storage");
+        for (i, quad) in quads.iter().enumerate() {
+            storage
+                .write_rdf_event(RDFEvent::from_quad(1_000 + i as u64, quad))
+                .expect("failed to write event");
+        }
+        storage.flush().expect("failed to flush storage");
+    }
+
+    let storage = StreamingSegmentedStorage::new(term_test_config(temp_dir.path()))
+        .expect("failed to reopen storage");
+    let read_back: Vec<Quad> = storage
+        .query_rdf(0, u64::MAX)
+        .expect("failed to query storage")
+        .iter()
+        .map(|event| event.to_quad().expect("stored event must convert to a quad"))
+        .collect();
+    assert_eq!(read_back, quads);
+}
+
+#[test]
+fn test_write_rdf_accepts_ntriples_terms() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = StreamingSegmentedStorage::new(term_test_config(temp_dir.path()))
+        .expect("failed to create storage");
+    storage
+        .write_rdf(
+            1_000,
+            "_:reading1",
+            "http://example.org/label",
+            "\"Température\"@fr",
+            "http://example.org/graph1",
+        )
+        .expect("failed to write event");
+    storage
+        .write_rdf(
+            1_001,
+            "_:reading1",
+            "http://example.org/label",
+            "\"Température\"",
+            "http://example.org/graph1",
+        )
+        .expect("failed to write event");
+
+    let events = storage.query_rdf(0, u64::MAX).expect("failed to query storage");
+    assert_eq!(events[0].subject, RdfTerm::blank_node("reading1"));
+    assert_eq!(events[0].object, RdfTerm::language_tagged_literal("Température", "fr"));
+    assert_eq!(events[1].object, RdfTerm::simple_literal("Température"));
+    assert_ne!(
+        storage.get_dictionary().read().unwrap().string_to_id.get("\"Température\"@fr"),
+        storage.get_dictionary().read().unwrap().string_to_id.get("\"Température\"")
+    );
+}
+
+#[test]
+fn test_historical_queries_see_datatypes_and_languages() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = Arc::new(
+        StreamingSegmentedStorage::new(term_test_config(temp_dir.path()))
+            .expect("failed to create storage"),
+    );
+    for (i, quad) in sample_quads().iter().enumerate() {
+        storage
+            .write_rdf_event(RDFEvent::from_quad(1_000 + i as u64, quad))
+            .expect("failed to write event");
+    }
+    storage.flush().expect("failed to flush storage");
+
+    let window = WindowDefinition {
+        window_name: "http://example.org/window/terms".to_string(),
+        source_kind: SourceKind::Log,
+        stream_name: "http://example.org/stream".to_string(),
+        width: 0,
+        slide: 0,
+        offset: None,
+        start: Some(1_000),
+        end: Some(1_099),
+        window_type: WindowType::HistoricalFixed,
+    };
+    let executor = HistoricalExecutor::new(storage.clone(), OxigraphAdapter::new());
+
+    let french = executor
+        .execute_fixed_window(
+            &window,
+            "SELECT ?label WHERE { GRAPH ?g { ?s <http://example.org/label> ?label FILTER(lang(?label) = \"fr\") } }",
+        )
+        .expect("failed to execute window");
+    assert_eq!(french.len(), 1);
+
+    let doubles = executor
+        .execute_fixed_window(
+            &window,
+            "SELECT ?value WHERE { GRAPH ?g { ?s ?p ?value FILTER(datatype(?value) = <http://www.w3.org/2001/XMLSchema#double>) } }",
+        )
+        .expect("failed to execute window");
+    assert_eq!(doubles.len(), 1);
+
+    let blank_subjects = executor
+        .execute_fixed_window(
+            &window,
+            "SELECT ?reading WHERE { ?reading <http://example.org/observedBy> ?sensor FILTER(isBlank(?reading)) }",
+        )
+        .expect("failed to execute window");
+    assert_eq!(blank_subjects.len(), 1);
+}
+
+#[test]
+fn test_bare_objects_are_only_iris_with_an_http_scheme() {
+    for value in ["status:ok", "ratio:3", "a:{x}", "urn:sensor:1", "http://example.org/a b"] {
+        assert_eq!(RdfTerm::parse(value), RdfTerm::simple_literal(value), "{}", value);
+    }
+    assert_eq!(
+        RdfTerm::parse("http://example.org/a"),
+        RdfTerm::named_node("http://example.org/a")
+    );
+
+    // Bare subjects, predicates and graphs are IRIs whatever their scheme.
+    let event = RDFEvent::new(1_000, "urn:sensor:1", "urn:ex:status", "status:ok", "urn:graph:1");
+    assert_eq!(event.subject, RdfTerm::named_node("urn:sensor:1"));
+    assert_eq!(event.predicate, RdfTerm::named_node("urn:ex:status"));
+    assert_eq!(event.graph, RdfTerm::named_node("urn:graph:1"));
+    assert_eq!(event.object, RdfTerm::simple_literal("status:ok"));
+}
+
+#[test]
+fn test_bare_values_that_look_like_iris_are_queried_as_literals() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = Arc::new(
+        StreamingSegmentedStorage::new(term_test_config(temp_dir.path()))
+            .expect("failed to create storage"),
+    );
+    for (i, value) in ["status:ok", "ratio:3", "a:{x}", "<urn:sensor:2>"].iter().enumerate() {
+        storage
+            .write_rdf(
+                1_000 + i as u64,
+                "urn:sensor:1",
+                "http://example.org/status",
+                value,
+                "http://example.org/graph1",
+            )
+            .expect("failed to write event");
+    }
+    storage.flush().expect("failed to flush storage");
+
+    let events = storage.query_rdf(0, u64::MAX).expect("failed to query storage");
+    let objects: Vec<RdfTerm> = events.iter().map(|event| event.object.clone()).collect();
+    assert_eq!(
+        objects,
+        vec![
+            RdfTerm::simple_literal("status:ok"),
+            RdfTerm::simple_literal("ratio:3"),
+            RdfTerm::simple_literal("a:{x}"),
+            RdfTerm::named_node("urn:sensor:2"),
+        ]
+    );
+    assert!(events.iter().all(|event| event.subject == RdfTerm::named_node("urn:sensor:1")));
+
+    let window = WindowDefinition {
+        window_name: "http://example.org/window/statuses".to_string(),
+        source_kind: SourceKind::Log,
+        stream_name: "http://example.org/stream".to_string(),
+        width: 0,
+        slide: 0,
+        offset: None,
+        start: Some(1_000),
+        end: Some(1_099),
+        window_type: WindowType::HistoricalFixed,
+    };
+    let executor = HistoricalExecutor::new(storage.clone(), OxigraphAdapter::new());
+    let literals = executor
+        .execute_fixed_window(
+            &window,
+            "SELECT ?status WHERE { GRAPH ?g { ?s <http://example.org/status> ?status FILTER(isLiteral(?status)) } }",
+        )
+        .expect("failed to execute window");
+    assert_eq!(literals.len(), 3);
+}
@@ -0,0 +1,137 @@
+mod common;
+
+use janus::core::RDFEvent;
+use janus::parsing::rdf_parser::parse_rdf_line;
+use janus::storage::archive::ArchiveFormat;
+use janus::storage::event_store::EventStore;
+use janus::storage::oxigraph_store::OxigraphEventStore;
+use janus::storage::segmented_storage::StreamingSegmentedStorage;
+use oxigraph::io::{RdfFormat, RdfParser};
+use oxigraph::store::Store;
+use std::path::Path;
+use tempfile::TempDir;
+
+const STREAM_A: &str = "http://example.org/streamA";
+const STREAM_B: &str = "http://example.org/streamB";
+
+// Events of two streams and without a stream, with literals of every kind, blank nodes
+// and the default graph. The last five stay buffered.
+fn write_events(storage: &StreamingSegmentedStorage) {
+    for i in 0..30u64 {
+        let object = match i % 3 {
+            0 => format!("\"{}\"^^<http://www.w3.org/2001/XMLSchema#integer>", i),
+            1 => "\"line \\\"one\\\"\\nline two\"@en".to_string(),
+            _ => "_:reading".to_string(),
+        };
+        let graph = if i % 4 == 0 {
+            "default"
+        } else {
+            "http://example.org/graph1"
+        };
+        let event = RDFEvent::new(
+            1_000 + i,
+            &format!("http://example.org/sensor{}", i % 2),
+            "http://example.org/observes",
+            &object,
+            graph,
+        );
+        match i % 3 {
+            0 => storage.write_rdf_event_to_stream(STREAM_A, event),
+            1 => storage.write_rdf_event_to_stream(STREAM_B, event),
+            _ => storage.write_rdf_event(event),
+        }
+        .expect("failed to write event");
+        if i == 24 {
+            storage.flush().expect("failed to flush storage");
+        }
+    }
+}
+
+fn decoded_stream(storage: &dyn EventStore, stream: &str) -> Vec<RDFEvent> {
+    let events = storage.query_stream(stream, 0, u64::MAX).expect("failed to query stream");
+    let dictionary = storage.get_dictionary().read().unwrap();
+    events.iter().map(|event| event.decode(&dictionary)).collect()
+}
+
+fn assert_same_events(source: &dyn EventStore, target: &dyn EventStore) {
+    for stream in [STREAM_A, STREAM_B] {
+        assert_eq!(decoded_stream(source, stream), decoded_stream(target, stream), "{}", stream);
+    }
+    assert_eq!(decoded_stream(target, STREAM_A).len(), 20);
+}
+
+#[test]
+fn test_nquads_archive_round_trips_events_and_streams() {
+    let source_dir = TempDir::new().expect("failed to create temp dir");
+    let source = StreamingSegmentedStorage::new(common::test_config(source_dir.path()))
+        .expect("failed to create storage");
+    write_events(&source);
+
+    let mut archive = Vec::new();
+    assert_eq!(source.export(0, u64::MAX, &mut archive, ArchiveFormat::NQuads).unwrap(), 30);
+
+    // Every event line is a timestamped statement that the replay parser reads.
+    let text = String::from_utf8(archive.clone()).expect("archive is not UTF-8");
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines.iter().filter(|line| line.starts_with("# stream ")).count(), 2);
+    let first = parse_rdf_line(lines[0], false).expect("failed to parse archive line");
+    assert_eq!(first.timestamp, 1_002);
+    assert!(lines.contains(&"# stream http://example.org/streamA"));
+
+    let target_dir = TempDir::new().expect("failed to create temp dir");
+    let target = StreamingSegmentedStorage::new(common::test_config(target_dir.path()))
+        .expect("failed to create storage");
+    assert_eq!(target.import(archive.as_slice(), ArchiveFormat::NQuads).unwrap(), 30);
+    assert_same_events(&source, &target);
+    assert_eq!(target.segment_metadata().len(), 3);
+
+    // A range exports only its events.
+    let mut range = Vec::new();
+    assert_eq!(source.export(1_010, 1_019, &mut range, ArchiveFormat::NQuads).unwrap(), 10);
+}
+
+#[test]
+fn test_trig_archive_round_trips_and_loads_into_oxigraph() {
+    let source_dir = TempDir::new().expect("failed to create temp dir");
+    let source = StreamingSegmentedStorage::new(common::test_config(source_dir.path()))
+        .expect("failed to create storage");
+    write_events(&source);
+    source
+        .delete_by_subject("http://example.org/sensor1")
+        .expect("failed to delete subject");
+
+    let mut archive = Vec::new();
+    assert_eq!(source.export(0, u64::MAX, &mut archive, ArchiveFormat::TriG).unwrap(), 15);
+
+    let target_dir = TempDir::new().expect("failed to create temp dir");
+    let target = StreamingSegmentedStorage::new(common::test_config(target_dir.path()))
+        .expect("failed to create storage");
+    assert_eq!(target.import(archive.as_slice(), ArchiveFormat::TriG).unwrap(), 15);
+    for stream in [STREAM_A, STREAM_B] {
+        assert_eq!(decoded_stream(&source, stream), decoded_stream(&target, stream));
+    }
+
+    // The archive is a plain RDF dataset the Oxigraph backend can read.
+    let store = Store::new().expect("failed to create store");
+    let quads = RdfParser::from_format(RdfFormat::TriG).for_slice(&archive);
+    store
+        .extend(quads.collect::<Result<Vec<_>, _>>().expect("failed to parse archive"))
+        .expect("failed to load archive");
+    let oxigraph = OxigraphEventStore::with_store(store).expect("failed to open store");
+    assert_eq!(decoded_stream(&oxigraph, STREAM_A), decoded_stream(&source, STREAM_A));
+}
+
+#[test]
+fn test_import_reports_the_line_of_a_malformed_statement() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = StreamingSegmentedStorage::new(common::test_config(temp_dir.path()))
+        .expect("failed to create storage");
+    let archive =
+        "# exported\n1000 <http://example.org/s> <http://example.org/p> \"1\" .\n1001 not rdf .\n";
+
+    let err = storage.import(archive.as_bytes(), ArchiveFormat::NQuads).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(err.to_string().starts_with("Line 3:"), "{}", err);
+    assert_eq!("trig".parse::<ArchiveFormat>(), Ok(ArchiveFormat::TriG));
+    assert_eq!(ArchiveFormat::from_path(Path::new("backup.nq")), Some(ArchiveFormat::NQuads));
+}
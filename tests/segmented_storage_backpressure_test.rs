@@ -0,0 +1,171 @@
+mod common;
+
+use janus::core::Event;
+use janus::storage::memory_tracker::MemoryTracker;
+use janus::storage::segmented_storage::StreamingSegmentedStorage;
+use janus::storage::util::{BackpressurePolicy, StreamingConfig};
+use std::io::ErrorKind;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+
+const EVENT_BYTES: usize = std::mem::size_of::<Event>();
+
+fn backpressure_test_config(path: &Path, policy: BackpressurePolicy) -> StreamingConfig {
+    StreamingConfig {
+        compaction_enabled: false,
+        memory_budget_bytes: Some(10 * EVENT_BYTES),
+        backpressure_policy: policy,
+        ..common::test_config(path)
+    }
+}
+
+fn write_reading(storage: &StreamingSegmentedStorage, timestamp: u64) -> std::io::Result<()> {
+    storage.write_rdf(
+        timestamp,
+        "http://example.org/sensor1",
+        "http://example.org/temperature",
+        &format!("{}", timestamp),
+        "http://example.org/graph1",
+    )
+}
+
+#[test]
+fn test_would_block_policy_rejects_writes_over_budget() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = StreamingSegmentedStorage::new(backpressure_test_config(
+        temp_dir.path(),
+        BackpressurePolicy::WouldBlock,
+    ))
+    .expect("failed to create storage");
+
+    for i in 0..10 {
+        write_reading(&storage, 1_000 + i).expect("write within budget must succeed");
+    }
+    assert_eq!(storage.memory_tracker().buffered_bytes(), 10 * EVENT_BYTES);
+    let err = write_reading(&storage, 1_010).expect_err("write over budget must fail");
+    assert_eq!(err.kind(), ErrorKind::WouldBlock);
+
+    storage.flush().expect("failed to flush storage");
+    assert_eq!(storage.memory_tracker().buffered_bytes(), 0);
+    write_reading(&storage, 1_010).expect("write after flush must succeed");
+
+    // The rejected write never reached the buffer or the write-ahead log.
+    drop(storage);
+    let storage = StreamingSegmentedStorage::new(backpressure_test_config(
+        temp_dir.path(),
+        BackpressurePolicy::WouldBlock,
+    ))
+    .expect("failed to reopen storage");
+    let timestamps: Vec<u64> = storage
+        .query(0, u64::MAX)
+        .expect("failed to query storage")
+        .iter()
+        .map(|event| event.timestamp)
+        .collect();
+    assert_eq!(timestamps, (1_000..=1_010).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_sync_flush_policy_flushes_in_the_writer() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = StreamingSegmentedStorage::new(backpressure_test_config(
+        temp_dir.path(),
+        BackpressurePolicy::SyncFlush,
+    ))
+    .expect("failed to create storage");
+
+    for i in 0..35 {
+        write_reading(&storage, 1_000 + i).expect("failed to write event");
+        assert!(storage.memory_tracker().buffered_bytes() <= 10 * EVENT_BYTES);
+    }
+    assert_eq!(storage.segment_metadata().len(), 3);
+    assert_eq!(storage.query(0, u64::MAX).expect("failed to query storage").len(), 35);
+}
+
+#[test]
+fn test_block_policy_waits_for_background_flush() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let mut storage = StreamingSegmentedStorage::new(backpressure_test_config(
+        temp_dir.path(),
+        BackpressurePolicy::Block,
+    ))
+    .expect("failed to create storage");
+    storage.start_background_flushing();
+    let storage = Arc::new(storage);
+
+    let writer = {
+        let storage = Arc::clone(&storage);
+        std::thread::spawn(move || {
+            for i in 0..60 {
+                write_reading(&storage, 1_000 + i).expect("blocked write must succeed");
+                assert!(storage.memory_tracker().buffered_bytes() <= 10 * EVENT_BYTES);
+            }
+        })
+    };
+    writer.join().expect("writer panicked");
+
+    assert!(storage.segment_metadata().len() >= 5, "background flush should have made room");
+    assert_eq!(storage.query(0, u64::MAX).expect("failed to query storage").len(), 60);
+}
+
+#[test]
+fn test_block_policy_without_background_flush_flushes_in_the_writer() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = StreamingSegmentedStorage::new(backpressure_test_config(
+        temp_dir.path(),
+        BackpressurePolicy::Block,
+    ))
+    .expect("failed to create storage");
+
+    for i in 0..25 {
+        write_reading(&storage, 1_000 + i).expect("failed to write event");
+    }
+    assert_eq!(storage.segment_metadata().len(), 2);
+    assert_eq!(storage.memory_tracker().buffered_bytes(), 5 * EVENT_BYTES);
+}
+
+#[test]
+fn test_budgets_below_one_event_are_rejected() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    for budget in [0, 16, EVENT_BYTES - 1] {
+        let config = StreamingConfig {
+            memory_budget_bytes: Some(budget),
+            ..backpressure_test_config(temp_dir.path(), BackpressurePolicy::Block)
+        };
+        let err = StreamingSegmentedStorage::new(config).err().expect("budget must be rejected");
+        assert_eq!(err.kind(), ErrorKind::InvalidInput, "budget of {}", budget);
+    }
+
+    let config = StreamingConfig {
+        memory_budget_bytes: Some(EVENT_BYTES),
+        ..backpressure_test_config(temp_dir.path(), BackpressurePolicy::SyncFlush)
+    };
+    let storage = StreamingSegmentedStorage::new(config).expect("failed to create storage");
+    for i in 0..3 {
+        write_reading(&storage, 1_000 + i).expect("write within budget must succeed");
+    }
+    assert_eq!(storage.query(0, u64::MAX).unwrap().len(), 3);
+}
+
+#[test]
+fn test_throttle_delay_grows_with_budget_usage() {
+    let unbounded = MemoryTracker::new();
+    unbounded.set_buffered_bytes(usize::MAX / 2);
+    assert_eq!(unbounded.throttle_delay(), None);
+    assert!(unbounded.has_room_for(1_000));
+
+    let tracker = MemoryTracker::with_budget(Some(1_000));
+    tracker.set_buffered_bytes(499);
+    assert_eq!(tracker.throttle_delay(), None);
+
+    tracker.set_buffered_bytes(750);
+    let halfway = tracker.throttle_delay().expect("tracker should throttle");
+    tracker.set_buffered_bytes(1_000);
+    let full = tracker.throttle_delay().expect("tracker should throttle");
+    assert!(halfway < full);
+    assert_eq!(full, Duration::from_millis(50));
+    assert!(!tracker.has_room_for(1));
+    assert!(tracker.clone().has_room_for(0));
+}
@@ -0,0 +1,121 @@
+mod common;
+
+use janus::storage::indexing::bloom::BloomFilter;
+use janus::storage::segmented_storage::StreamingSegmentedStorage;
+use janus::storage::util::StreamingConfig;
+use std::path::Path;
+use tempfile::TempDir;
+
+const STREAM: &str = "http://example.org/stream/sensors";
+
+fn bloom_test_config(path: &Path) -> StreamingConfig {
+    StreamingConfig {
+        compaction_min_segments: 2,
+        mmap_cache_capacity: 0,
+        ..common::test_config(path)
+    }
+}
+
+// One segment per sensor, each covering the same time range.
+fn write_sensor_segments(storage: &StreamingSegmentedStorage, sensors: &[u64]) {
+    for sensor in sensors {
+        for timestamp in (1_000..1_100).step_by(5) {
+            common::write_stream_reading(storage, STREAM, timestamp, sensor, timestamp % 23);
+        }
+        storage.flush().expect("failed to flush");
+    }
+}
+
+#[test]
+fn test_bloom_filter_has_no_false_negatives_and_few_false_positives() {
+    let mut filter = BloomFilter::with_capacity(10_000, 0.01);
+    for id in 0..10_000 {
+        filter.insert(id * 2);
+    }
+    assert!((0..10_000).all(|id| filter.may_contain(id * 2)));
+    let false_positives = (0..10_000).filter(|id| filter.may_contain(id * 2 + 1)).count();
+    assert!(false_positives < 300, "{} false positives", false_positives);
+
+    let decoded = BloomFilter::decode(&filter.encode()).expect("failed to decode filter");
+    assert_eq!(decoded, filter);
+    let encoded = filter.encode();
+    assert!(BloomFilter::decode(&encoded[..encoded.len() - 1]).is_err());
+}
+
+#[test]
+fn test_subject_queries_skip_segments_ruled_out_by_their_filter() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = StreamingSegmentedStorage::new(bloom_test_config(temp_dir.path()))
+        .expect("failed to create storage");
+    write_sensor_segments(&storage, &[1, 2, 3]);
+    assert!(storage.segment_metadata().iter().all(|segment| segment.bloom_filter.is_some()));
+
+    let sensor2 = storage
+        .query_stream_subject(STREAM, "http://example.org/sensor2", 0, u64::MAX)
+        .expect("failed to query subject");
+    assert_eq!(sensor2.len(), 20);
+    let sensor2_id =
+        storage.get_dictionary().read().unwrap().string_to_id["http://example.org/sensor2"];
+    assert!(sensor2.iter().all(|event| event.subject == sensor2_id));
+    assert!(storage
+        .query_subject("http://example.org/unknown", 0, u64::MAX)
+        .unwrap()
+        .is_empty());
+
+    // The filters are read back from the index files, and the segments of the other
+    // sensors are never opened.
+    drop(storage);
+    let storage = StreamingSegmentedStorage::new(bloom_test_config(temp_dir.path()))
+        .expect("failed to reopen storage");
+    let mut segments = storage.segment_metadata();
+    assert!(segments.iter().all(|segment| segment.bloom_filter.is_some()));
+    segments.sort_by_key(|segment| segment.segment_id());
+    for segment in [&segments[0], &segments[2]] {
+        std::fs::remove_file(&segment.data_path).expect("failed to remove segment");
+    }
+    assert_eq!(
+        storage.query_subject("http://example.org/sensor2", 1_020, 1_039).unwrap().len(),
+        4
+    );
+    assert!(storage.query(0, u64::MAX).is_err());
+}
+
+#[test]
+fn test_merged_segments_get_a_filter_over_every_input() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = StreamingSegmentedStorage::new(bloom_test_config(temp_dir.path()))
+        .expect("failed to create storage");
+    write_sensor_segments(&storage, &[1, 2]);
+
+    storage.compact().expect("failed to compact");
+    let segments = storage.segment_metadata();
+    assert_eq!(segments.len(), 1);
+    let filter = segments[0].bloom_filter.as_ref().expect("merged segment has no filter");
+    let dictionary = storage.get_dictionary().read().unwrap();
+    for term in ["http://example.org/sensor1", "http://example.org/sensor2"] {
+        assert!(filter.may_contain(dictionary.string_to_id[term]));
+    }
+    drop(dictionary);
+    assert_eq!(
+        storage.query_subject("http://example.org/sensor1", 0, u64::MAX).unwrap().len(),
+        20
+    );
+    assert!(storage.verify(None).expect("failed to verify").is_clean());
+}
+
+#[test]
+fn test_filters_are_only_written_when_configured() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let config = StreamingConfig {
+        bloom_filter_false_positive_rate: 0.0,
+        ..bloom_test_config(temp_dir.path())
+    };
+    let storage = StreamingSegmentedStorage::new(config).expect("failed to create storage");
+    write_sensor_segments(&storage, &[1, 2]);
+
+    assert!(storage.segment_metadata().iter().all(|segment| segment.bloom_filter.is_none()));
+    assert_eq!(
+        storage.query_subject("http://example.org/sensor1", 0, u64::MAX).unwrap().len(),
+        20
+    );
+}
@@ -0,0 +1,152 @@
+mod common;
+
+use janus::core::Event;
+use janus::storage::codec::SegmentCodec;
+use janus::storage::segmented_storage::StreamingSegmentedStorage;
+use janus::storage::util::StreamingConfig;
+use std::fs;
+use std::io::ErrorKind;
+use tempfile::TempDir;
+
+fn codec_test_config(path: &std::path::Path, codec: SegmentCodec) -> StreamingConfig {
+    StreamingConfig {
+        sparse_interval: 16,
+        entries_per_index_block: 4,
+        segment_codec: codec,
+        compaction_min_segments: 2,
+        ..common::test_config(path)
+    }
+}
+
+fn write_readings(storage: &StreamingSegmentedStorage, first_timestamp: u64, count: u64) {
+    for i in 0..count {
+        storage
+            .write_rdf(
+                first_timestamp + i * 7,
+                &format!("http://example.org/sensor{}", i % 13),
+                if i % 50 < 40 {
+                    "http://example.org/temperature"
+                } else {
+                    "http://example.org/humidity"
+                },
+                &format!("{}", i % 97),
+                "http://example.org/graph1",
+            )
+            .expect("failed to write event");
+    }
+}
+
+fn timestamps(events: &[Event]) -> Vec<u64> {
+    events.iter().map(|event| event.timestamp).collect()
+}
+
+fn records(events: &[Event]) -> Vec<(u64, u32, u32, u32, u32)> {
+    events
+        .iter()
+        .map(|e| (e.timestamp, e.subject, e.predicate, e.object, e.graph))
+        .collect()
+}
+
+#[test]
+fn test_columnar_segments_are_smaller_and_read_back_identically() {
+    let fixed_dir = TempDir::new().expect("failed to create temp dir");
+    let columnar_dir = TempDir::new().expect("failed to create temp dir");
+
+    let fixed = StreamingSegmentedStorage::new(codec_test_config(
+        fixed_dir.path(),
+        SegmentCodec::FixedWidth,
+    ))
+    .expect("failed to create storage");
+    let columnar = StreamingSegmentedStorage::new(codec_test_config(
+        columnar_dir.path(),
+        SegmentCodec::Columnar,
+    ))
+    .expect("failed to create storage");
+    for storage in [&fixed, &columnar] {
+        write_readings(storage, 10_000, 1_000);
+        storage.flush().expect("failed to flush storage");
+    }
+
+    let fixed_segment = &fixed.segment_metadata()[0];
+    let columnar_segment = &columnar.segment_metadata()[0];
+    assert_eq!(fixed_segment.codec, SegmentCodec::FixedWidth);
+    assert_eq!(columnar_segment.codec, SegmentCodec::Columnar);
+
+    let fixed_size = fs::metadata(&fixed_segment.data_path).unwrap().len();
+    let columnar_size = fs::metadata(&columnar_segment.data_path).unwrap().len();
+    assert!(
+        columnar_size * 3 < fixed_size,
+        "columnar segment of {} bytes should be far smaller than {} bytes",
+        columnar_size,
+        fixed_size
+    );
+
+    assert_eq!(
+        records(&fixed.query(0, u64::MAX).expect("failed to query")),
+        records(&columnar.query(0, u64::MAX).expect("failed to query"))
+    );
+
+    // Range queries seek into the middle of the segment through the sparse index.
+    let range = columnar.query(12_001, 12_700).expect("failed to query range");
+    assert_eq!(
+        records(&range),
+        records(&fixed.query(12_001, 12_700).expect("failed to query range"))
+    );
+    assert_eq!(timestamps(&range).first(), Some(&12_002));
+    assert_eq!(timestamps(&range).last(), Some(&12_695));
+
+    assert!(columnar.verify(None).expect("failed to verify storage").is_clean());
+}
+
+#[test]
+fn test_codecs_coexist_across_reopen_and_compaction() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    {
+        let storage = StreamingSegmentedStorage::new(codec_test_config(
+            temp_dir.path(),
+            SegmentCodec::FixedWidth,
+        ))
+        .expect("failed to create storage");
+        write_readings(&storage, 10_000, 300);
+        storage.flush().expect("failed to flush storage");
+    }
+
+    let storage =
+        StreamingSegmentedStorage::new(codec_test_config(temp_dir.path(), SegmentCodec::Columnar))
+            .expect("failed to reopen storage");
+    write_readings(&storage, 20_000, 300);
+    storage.flush().expect("failed to flush storage");
+
+    let codecs: Vec<SegmentCodec> = storage.segment_metadata().iter().map(|s| s.codec).collect();
+    assert_eq!(codecs, vec![SegmentCodec::FixedWidth, SegmentCodec::Columnar]);
+    let before = storage.query(0, u64::MAX).expect("failed to query");
+    assert_eq!(before.len(), 600);
+
+    assert_eq!(storage.compact().expect("failed to compact"), 1);
+    let segments = storage.segment_metadata();
+    assert_eq!(segments.len(), 1);
+    assert_eq!(segments[0].codec, SegmentCodec::Columnar);
+    assert_eq!(records(&storage.query(0, u64::MAX).expect("failed to query")), records(&before));
+}
+
+#[test]
+fn test_corrupt_columnar_block_is_detected() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage =
+        StreamingSegmentedStorage::new(codec_test_config(temp_dir.path(), SegmentCodec::Columnar))
+            .expect("failed to create storage");
+    write_readings(&storage, 10_000, 200);
+    storage.flush().expect("failed to flush storage");
+
+    let data_path = storage.segment_metadata()[0].data_path.clone();
+    let mut bytes = fs::read(&data_path).expect("failed to read segment");
+    let last = bytes.len() - 1;
+    bytes[last] ^= 0xFF;
+    fs::write(&data_path, bytes).expect("failed to write segment");
+
+    let err = storage.query(0, u64::MAX).expect_err("corrupt block must not be decoded");
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    let report = storage.verify(None).expect("failed to verify storage");
+    assert_eq!(report.corrupt_segments.len(), 1);
+    assert_eq!(report.corrupt_segments[0].valid_records, 192);
+}
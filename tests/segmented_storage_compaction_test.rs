@@ -0,0 +1,167 @@
+mod common;
+
+use janus::storage::segmented_storage::StreamingSegmentedStorage;
+use janus::storage::util::StreamingConfig;
+use std::fs;
+use std::path::Path;
+use std::thread::sleep;
+use std::time::Duration;
+use tempfile::TempDir;
+
+fn compaction_test_config(path: &Path) -> StreamingConfig {
+    StreamingConfig { compaction_min_segments: 4, ..common::test_config(path) }
+}
+
+// Flush `segments` segments of ten events each, with consecutive timestamps.
+fn write_small_segments(storage: &StreamingSegmentedStorage, segments: u64) {
+    for segment in 0..segments {
+        for i in 0..10u64 {
+            let timestamp = 1_000 + segment * 10 + i;
+            common::write_reading(storage, timestamp, i % 3, timestamp);
+        }
+        storage.flush().expect("failed to flush storage");
+    }
+}
+
+fn assert_contiguous(timestamps: &[u64], count: u64) {
+    let expected: Vec<u64> = (1_000..1_000 + count).collect();
+    assert_eq!(timestamps, expected.as_slice());
+}
+
+#[test]
+fn test_compaction_merges_adjacent_segments() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = StreamingSegmentedStorage::new(compaction_test_config(temp_dir.path()))
+        .expect("failed to create storage");
+    write_small_segments(&storage, 6);
+    let inputs = storage.segment_metadata();
+
+    assert_eq!(storage.compact().expect("failed to compact"), 5);
+
+    let segments = storage.segment_metadata();
+    assert_eq!(segments.len(), 1);
+    assert_eq!(segments[0].record_count, 60);
+    assert!(segments[0].index_directory.len() > 1);
+    for input in &inputs {
+        assert!(!Path::new(&input.data_path).exists());
+        assert!(!Path::new(&input.index_path).exists());
+    }
+
+    let timestamps: Vec<u64> = storage
+        .query(0, u64::MAX)
+        .expect("failed to query")
+        .iter()
+        .map(|e| e.timestamp)
+        .collect();
+    assert_contiguous(&timestamps, 60);
+
+    let range = storage.query(1_013, 1_047).expect("failed to query range");
+    assert_eq!(range.len(), 35);
+    assert!(storage.verify(None).expect("failed to verify").is_clean());
+}
+
+#[test]
+fn test_compaction_respects_record_limit_and_minimum_run() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let config = StreamingConfig {
+        compaction_min_segments: 2,
+        compaction_max_segment_records: 25,
+        ..compaction_test_config(temp_dir.path())
+    };
+    let storage = StreamingSegmentedStorage::new(config).expect("failed to create storage");
+    write_small_segments(&storage, 5);
+
+    assert_eq!(storage.compact().expect("failed to compact"), 2);
+
+    let record_counts: Vec<u64> =
+        storage.segment_metadata().iter().map(|s| s.record_count).collect();
+    assert_eq!(record_counts, vec![20, 20, 10]);
+    assert_eq!(storage.query(0, u64::MAX).expect("failed to query").len(), 50);
+}
+
+#[test]
+fn test_inputs_left_by_interrupted_swap_are_dropped_on_reopen() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let backup_dir = TempDir::new().expect("failed to create backup dir");
+
+    {
+        let storage = StreamingSegmentedStorage::new(compaction_test_config(temp_dir.path()))
+            .expect("failed to create storage");
+        write_small_segments(&storage, 4);
+        let inputs = storage.segment_metadata();
+        for input in &inputs {
+            for path in [&input.data_path, &input.index_path] {
+                let name = Path::new(path).file_name().unwrap();
+                fs::copy(path, backup_dir.path().join(name)).expect("failed to back up segment");
+            }
+        }
+
+        assert_eq!(storage.compact().expect("failed to compact"), 3);
+
+        // Put the inputs back, as if the process died before deleting them.
+        for entry in fs::read_dir(backup_dir.path()).expect("failed to read backup dir") {
+            let entry = entry.expect("failed to read backup entry");
+            fs::copy(entry.path(), temp_dir.path().join(entry.file_name()))
+                .expect("failed to restore segment");
+        }
+    }
+
+    let storage = StreamingSegmentedStorage::new(compaction_test_config(temp_dir.path()))
+        .expect("failed to reopen storage");
+    assert_eq!(storage.segment_metadata().len(), 1);
+
+    let timestamps: Vec<u64> = storage
+        .query(0, u64::MAX)
+        .expect("failed to query")
+        .iter()
+        .map(|e| e.timestamp)
+        .collect();
+    assert_contiguous(&timestamps, 40);
+
+    let segment_files = fs::read_dir(temp_dir.path())
+        .expect("failed to read storage dir")
+        .filter(|entry| {
+            entry.as_ref().unwrap().file_name().to_string_lossy().starts_with("segment-")
+        })
+        .count();
+    assert_eq!(segment_files, 2);
+}
+
+#[test]
+fn test_background_compaction_never_exposes_duplicates_or_gaps() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let config =
+        StreamingConfig { max_batch_events: 10, ..compaction_test_config(temp_dir.path()) };
+    let mut storage = StreamingSegmentedStorage::new(config).expect("failed to create storage");
+    storage.start_background_flushing();
+
+    let mut written = 0u64;
+    for _ in 0..20 {
+        for _ in 0..7 {
+            storage
+                .write_rdf(
+                    1_000 + written,
+                    "http://example.org/sensor1",
+                    "http://example.org/temperature",
+                    "1",
+                    "http://example.org/graph1",
+                )
+                .expect("failed to write event");
+            written += 1;
+        }
+
+        for _ in 0..5 {
+            let timestamps: Vec<u64> = storage
+                .query(0, u64::MAX)
+                .expect("failed to query")
+                .iter()
+                .map(|e| e.timestamp)
+                .collect();
+            assert_contiguous(&timestamps, written);
+            sleep(Duration::from_millis(25));
+        }
+    }
+
+    assert!(storage.segment_metadata().len() < 14, "background compaction should have run");
+    storage.shutdown().expect("failed to shut down storage");
+}
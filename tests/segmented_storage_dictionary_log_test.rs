@@ -0,0 +1,160 @@
+mod common;
+
+use janus::core::RdfTerm;
+use janus::storage::segmented_storage::StreamingSegmentedStorage;
+use janus::storage::util::StreamingConfig;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::Path;
+use tempfile::TempDir;
+
+fn dictionary_test_config(path: &Path) -> StreamingConfig {
+    StreamingConfig {
+        sparse_interval: 10,
+        entries_per_index_block: 100,
+        ..common::test_config(path)
+    }
+}
+
+fn write_readings(storage: &StreamingSegmentedStorage, first: u64, count: u64) {
+    for i in first..first + count {
+        common::write_reading(storage, 1_000 + i, i, i);
+    }
+}
+
+fn file_len(path: &Path) -> u64 {
+    fs::metadata(path).map(|metadata| metadata.len()).unwrap_or(0)
+}
+
+#[test]
+fn test_flush_appends_only_new_dictionary_entries() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let log_path = temp_dir.path().join("dictionary.log");
+    let storage = StreamingSegmentedStorage::new(dictionary_test_config(temp_dir.path()))
+        .expect("failed to create storage");
+
+    write_readings(&storage, 0, 10);
+    storage.flush().expect("failed to flush storage");
+    let after_first_flush = file_len(&log_path);
+    assert!(after_first_flush > 0);
+    assert!(!temp_dir.path().join("dictionary.bin").exists());
+
+    // Events reusing known terms add nothing to the log.
+    write_readings(&storage, 0, 10);
+    storage.flush().expect("failed to flush storage");
+    assert_eq!(file_len(&log_path), after_first_flush);
+
+    write_readings(&storage, 10, 1);
+    storage.flush().expect("failed to flush storage");
+    let new_terms = ["http://example.org/sensor10", "10"]
+        .iter()
+        .map(|term| RdfTerm::parse(term).dictionary_key().len())
+        .sum::<usize>();
+    assert_eq!(file_len(&log_path), after_first_flush + 2 * 12 + new_terms as u64);
+    drop(storage);
+
+    let storage = StreamingSegmentedStorage::new(dictionary_test_config(temp_dir.path()))
+        .expect("failed to reopen storage");
+    let events = storage.query_rdf(0, u64::MAX).expect("failed to query storage");
+    assert_eq!(events.len(), 21);
+    assert_eq!(events[20].subject.value(), "http://example.org/sensor10");
+    assert_eq!(events[20].object.value(), "10");
+}
+
+#[test]
+fn test_dictionary_checkpoint_truncates_log() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let config = StreamingConfig {
+        dictionary_checkpoint_interval: 20,
+        ..dictionary_test_config(temp_dir.path())
+    };
+    {
+        let storage =
+            StreamingSegmentedStorage::new(config.clone()).expect("failed to create storage");
+        write_readings(&storage, 0, 15);
+        storage.flush().expect("failed to flush storage");
+        assert!(temp_dir.path().join("dictionary.bin").exists());
+        assert_eq!(file_len(&temp_dir.path().join("dictionary.log")), 0);
+
+        write_readings(&storage, 15, 2);
+        storage.flush().expect("failed to flush storage");
+        assert!(file_len(&temp_dir.path().join("dictionary.log")) > 0);
+    }
+    assert!(!temp_dir.path().join("dictionary.bin.tmp").exists());
+
+    let storage = StreamingSegmentedStorage::new(config).expect("failed to reopen storage");
+    let events = storage.query_rdf(0, u64::MAX).expect("failed to query storage");
+    assert_eq!(events.len(), 17);
+    assert_eq!(events[3].subject.value(), "http://example.org/sensor3");
+    assert_eq!(events[16].subject.value(), "http://example.org/sensor16");
+}
+
+#[test]
+fn test_torn_dictionary_log_tail_is_dropped() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let log_path = temp_dir.path().join("dictionary.log");
+    {
+        let storage = StreamingSegmentedStorage::new(dictionary_test_config(temp_dir.path()))
+            .expect("failed to create storage");
+        write_readings(&storage, 0, 5);
+        storage.flush().expect("failed to flush storage");
+    }
+    let valid_len = file_len(&log_path);
+
+    // Half a record header, as left behind by a crash during an append.
+    OpenOptions::new()
+        .append(true)
+        .open(&log_path)
+        .and_then(|mut file| file.write_all(&[42, 0, 0]))
+        .expect("failed to tear dictionary log");
+
+    {
+        let storage = StreamingSegmentedStorage::new(dictionary_test_config(temp_dir.path()))
+            .expect("torn tail must not prevent reopening");
+        assert_eq!(file_len(&log_path), valid_len);
+        write_readings(&storage, 5, 5);
+        storage.flush().expect("failed to flush storage");
+    }
+
+    let storage = StreamingSegmentedStorage::new(dictionary_test_config(temp_dir.path()))
+        .expect("failed to reopen storage");
+    let events = storage.query_rdf(0, u64::MAX).expect("failed to query storage");
+    assert_eq!(events.len(), 10);
+    assert_eq!(events[9].subject.value(), "http://example.org/sensor9");
+}
+
+#[test]
+fn test_corrupt_dictionary_is_a_hard_error() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let config = StreamingConfig {
+        dictionary_checkpoint_interval: 10,
+        ..dictionary_test_config(temp_dir.path())
+    };
+    {
+        let storage =
+            StreamingSegmentedStorage::new(config.clone()).expect("failed to create storage");
+        write_readings(&storage, 0, 5);
+        storage.flush().expect("failed to flush storage");
+        write_readings(&storage, 5, 2);
+        storage.flush().expect("failed to flush storage");
+    }
+
+    // A damaged record followed by valid ones is corruption, not a torn tail.
+    let log_path = temp_dir.path().join("dictionary.log");
+    let log = fs::read(&log_path).expect("failed to read dictionary log");
+    let mut corrupted = log.clone();
+    corrupted[10] ^= 0xFF;
+    fs::write(&log_path, &corrupted).expect("failed to write dictionary log");
+    let err = StreamingSegmentedStorage::new(config.clone())
+        .err()
+        .expect("corrupt dictionary log must fail to load");
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    fs::write(&log_path, &log).expect("failed to restore dictionary log");
+
+    let checkpoint_path = temp_dir.path().join("dictionary.bin");
+    fs::write(&checkpoint_path, [0xFF; 7]).expect("failed to truncate dictionary checkpoint");
+    let err = StreamingSegmentedStorage::new(config)
+        .err()
+        .expect("corrupt dictionary checkpoint must fail to load");
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+}
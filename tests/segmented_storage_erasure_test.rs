@@ -0,0 +1,169 @@
+mod common;
+
+use janus::storage::erasure::ErasureState;
+use janus::storage::event_store::EventStore;
+use janus::storage::in_memory_store::InMemoryEventStore;
+use janus::storage::oxigraph_store::OxigraphEventStore;
+use janus::storage::segmented_storage::StreamingSegmentedStorage;
+use janus::storage::util::StreamingConfig;
+use std::path::Path;
+use tempfile::TempDir;
+
+const ALICE: &str = "http://example.org/alice";
+const BOB: &str = "http://example.org/bob";
+const PRIVATE: &str = "http://example.org/private";
+const PUBLIC: &str = "http://example.org/public";
+
+fn erasure_test_config(path: &Path) -> StreamingConfig {
+    StreamingConfig { compaction_min_segments: 2, ..common::test_config(path) }
+}
+
+// Three flushed segments of heart rates, alternating between Alice and Bob. Alice's
+// readings carry a value no other event uses.
+fn write_readings(storage: &dyn EventStore) {
+    for segment in 0..3u64 {
+        for i in 0..10u64 {
+            let timestamp = 1_000 + segment * 10 + i;
+            let (subject, value) = if i % 2 == 0 {
+                (ALICE, format!("\"alice-{}\"", timestamp))
+            } else {
+                (BOB, format!("{}", timestamp))
+            };
+            let graph = if i % 4 == 1 { PRIVATE } else { PUBLIC };
+            storage
+                .write_rdf(timestamp, subject, "http://example.org/heartRate", &value, graph)
+                .expect("failed to write event");
+        }
+        storage.flush().expect("failed to flush storage");
+    }
+}
+
+fn subjects(storage: &dyn EventStore) -> Vec<String> {
+    let events = storage.query(0, u64::MAX).expect("failed to query");
+    let dictionary = storage.get_dictionary().read().unwrap();
+    events
+        .iter()
+        .map(|event| event.decode(&dictionary).subject.to_string())
+        .collect()
+}
+
+fn data_files_contain(path: &Path, needle: &[u8]) -> bool {
+    std::fs::read_dir(path).unwrap().any(|entry| {
+        let data = std::fs::read(entry.unwrap().path()).unwrap_or_default();
+        data.windows(needle.len()).any(|window| window == needle)
+    })
+}
+
+#[test]
+fn test_deleted_subject_disappears_from_queries_immediately() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = StreamingSegmentedStorage::new(erasure_test_config(temp_dir.path()))
+        .expect("failed to create storage");
+    write_readings(&storage);
+    // Buffered events are covered as well.
+    storage
+        .write_rdf(1_100, ALICE, "http://example.org/heartRate", "\"buffered\"", PUBLIC)
+        .expect("failed to write event");
+
+    storage.delete_by_subject(ALICE).expect("failed to delete subject");
+
+    let remaining = subjects(&storage);
+    assert_eq!(remaining.len(), 15);
+    assert!(remaining.iter().all(|subject| subject == &format!("<{}>", BOB)));
+    assert_eq!(storage.erasure_progress().pending_tombstones, 1);
+
+    // Deleting a term that was never written records nothing.
+    storage
+        .delete_by_subject("http://example.org/nobody")
+        .expect("failed to delete subject");
+    assert_eq!(storage.erasure_progress().pending_tombstones, 1);
+}
+
+#[test]
+fn test_deletion_survives_reopen_and_compaction() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    {
+        let storage = StreamingSegmentedStorage::new(erasure_test_config(temp_dir.path()))
+            .expect("failed to create storage");
+        write_readings(&storage);
+        storage.delete_by_graph(PRIVATE).expect("failed to delete graph");
+        assert_eq!(storage.query(0, u64::MAX).unwrap().len(), 21);
+    }
+
+    let storage = StreamingSegmentedStorage::new(erasure_test_config(temp_dir.path()))
+        .expect("failed to reopen storage");
+    assert_eq!(storage.query(0, u64::MAX).unwrap().len(), 21);
+    assert_eq!(storage.erasure_progress().pending_tombstones, 1);
+
+    // Events written after the deletion are kept, even in the deleted graph.
+    storage
+        .write_rdf(1_050, BOB, "http://example.org/heartRate", "60", PRIVATE)
+        .expect("failed to write event");
+    storage.flush().expect("failed to flush storage");
+
+    storage.compact().expect("failed to compact");
+    assert_eq!(storage.segment_metadata().len(), 1);
+    assert_eq!(storage.query(0, u64::MAX).unwrap().len(), 22);
+    assert_eq!(storage.erasure_progress().pending_tombstones, 0);
+    assert!(storage.verify(None).expect("failed to verify").is_clean());
+}
+
+#[test]
+fn test_purge_rewrites_segments_and_dictionary() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = StreamingSegmentedStorage::new(erasure_test_config(temp_dir.path()))
+        .expect("failed to create storage");
+    write_readings(&storage);
+    let before = storage.segment_metadata();
+    let dictionary_size = storage.get_dictionary().read().unwrap().size();
+    assert!(data_files_contain(temp_dir.path(), b"alice-1004"));
+
+    storage.delete_by_subject(ALICE).expect("failed to delete subject");
+    let progress = storage.purge_erased().expect("failed to purge");
+
+    assert_eq!(progress.state, ErasureState::Completed);
+    assert_eq!(progress.pending_tombstones, 0);
+    assert_eq!(progress.segments_total, 3);
+    assert_eq!(progress.segments_scanned, 3);
+    assert_eq!(progress.segments_rewritten, 3);
+    assert_eq!(progress.events_removed, 15);
+    // Alice and her fifteen values; the predicate and graphs are still in use.
+    assert_eq!(progress.terms_purged, 16);
+    assert_eq!(storage.erasure_progress(), progress);
+
+    for segment in &before {
+        assert!(!Path::new(&segment.data_path).exists());
+    }
+    let after = storage.segment_metadata();
+    assert_eq!(after.iter().map(|segment| segment.record_count).sum::<u64>(), 15);
+    assert_eq!(storage.get_dictionary().read().unwrap().size(), dictionary_size - 16);
+    assert!(!storage.get_dictionary().read().unwrap().string_to_id.contains_key(ALICE));
+    assert!(!data_files_contain(temp_dir.path(), ALICE.as_bytes()));
+    assert!(!data_files_contain(temp_dir.path(), b"alice-1004"));
+
+    // The purged dictionary is what a reopened storage decodes with.
+    drop(storage);
+    let storage = StreamingSegmentedStorage::new(erasure_test_config(temp_dir.path()))
+        .expect("failed to reopen storage");
+    let remaining = subjects(&storage);
+    assert_eq!(remaining.len(), 15);
+    assert!(remaining.iter().all(|subject| subject == &format!("<{}>", BOB)));
+    assert_eq!(storage.erasure_progress().pending_tombstones, 0);
+}
+
+#[test]
+fn test_other_backends_delete_immediately() {
+    let backends: Vec<(&str, Box<dyn EventStore>)> = vec![
+        ("in-memory", Box::new(InMemoryEventStore::new())),
+        ("oxigraph", Box::new(OxigraphEventStore::new().expect("failed to create store"))),
+    ];
+    for (name, store) in backends {
+        write_readings(store.as_ref());
+
+        store.delete_by_subject(ALICE).expect("failed to delete subject");
+        assert_eq!(store.query(0, u64::MAX).unwrap().len(), 15, "{}", name);
+        store.delete_by_graph(PRIVATE).expect("failed to delete graph");
+        assert_eq!(store.query(0, u64::MAX).unwrap().len(), 6, "{}", name);
+        assert_eq!(store.purge_erased().unwrap().pending_tombstones, 0, "{}", name);
+    }
+}
@@ -17,6 +17,7 @@ fn test_background_flush_failure_surfaces_as_storage_error() {
         max_batch_bytes: 1024 * 1024,
         sparse_interval: 10,
         entries_per_index_block: 100,
+        ..StreamingConfig::default()
     })
     .expect("failed to create storage");
 
@@ -0,0 +1,171 @@
+mod common;
+
+use janus::execution::historical_executor::{CompletenessPolicy, HistoricalExecutor};
+use janus::parsing::janusql_parser::{SourceKind, WindowDefinition, WindowType};
+use janus::querying::oxigraph_adapter::OxigraphAdapter;
+use janus::storage::event_time::{LateEventPolicy, OutOfOrderRange};
+use janus::storage::segmented_storage::StreamingSegmentedStorage;
+use janus::storage::util::StreamingConfig;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+const STREAM: &str = "http://example.org/heartRate";
+
+fn event_time_test_config(path: &Path, late_event_policy: LateEventPolicy) -> StreamingConfig {
+    StreamingConfig {
+        compaction_min_segments: 2,
+        allowed_lateness_ms: 100,
+        late_event_policy,
+        ..common::test_config(path)
+    }
+}
+
+fn write_reading(storage: &StreamingSegmentedStorage, timestamp: u64) -> std::io::Result<()> {
+    storage.write_rdf_to_stream(
+        STREAM,
+        timestamp,
+        "http://example.org/patient1",
+        "http://example.org/bpm",
+        &timestamp.to_string(),
+        "http://example.org/graph1",
+    )
+}
+
+#[test]
+fn test_late_events_are_accepted_and_mark_their_segment_out_of_order() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = StreamingSegmentedStorage::new(event_time_test_config(
+        temp_dir.path(),
+        LateEventPolicy::Accept,
+    ))
+    .expect("failed to create storage");
+    assert_eq!(storage.watermark(Some(STREAM)), None);
+
+    for timestamp in (1_000..=2_000).step_by(100) {
+        write_reading(&storage, timestamp).expect("failed to write event");
+    }
+    storage.flush().expect("failed to flush storage");
+    assert_eq!(storage.watermark(Some(STREAM)), Some(1_900));
+    assert_eq!(storage.watermark(None), None);
+
+    // Within the allowed lateness, then later than it.
+    write_reading(&storage, 1_950).expect("failed to write event");
+    write_reading(&storage, 1_500).expect("failed to write event");
+    write_reading(&storage, 2_100).expect("failed to write event");
+    storage.flush().expect("failed to flush storage");
+    assert_eq!(storage.late_events().accepted, 1);
+    assert_eq!(storage.watermark(Some(STREAM)), Some(2_000));
+
+    let segments = storage.segment_metadata();
+    assert_eq!(segments.len(), 2);
+    assert_eq!(segments[0].out_of_order, None);
+    assert_eq!(segments[1].out_of_order, Some(OutOfOrderRange { start: 1_500, end: 2_000 }));
+
+    let timestamps: Vec<u64> = storage
+        .query(0, u64::MAX)
+        .unwrap()
+        .iter()
+        .map(|event| event.timestamp)
+        .collect();
+    assert!(timestamps.windows(2).all(|pair| pair[0] <= pair[1]));
+
+    // The range is kept in the footer and the watermark is restored from the segments.
+    drop(storage);
+    let storage = StreamingSegmentedStorage::new(event_time_test_config(
+        temp_dir.path(),
+        LateEventPolicy::Accept,
+    ))
+    .expect("failed to reopen storage");
+    assert_eq!(storage.segment_metadata()[1].out_of_order, segments[1].out_of_order);
+    assert_eq!(storage.watermark(Some(STREAM)), Some(2_000));
+
+    storage.compact().expect("failed to compact");
+    assert_eq!(storage.segment_metadata().len(), 1);
+    assert_eq!(storage.segment_metadata()[0].out_of_order, segments[1].out_of_order);
+}
+
+#[test]
+fn test_late_events_can_be_dropped_or_rejected() {
+    for policy in [LateEventPolicy::Drop, LateEventPolicy::Reject] {
+        let temp_dir = TempDir::new().expect("failed to create temp dir");
+        let storage =
+            StreamingSegmentedStorage::new(event_time_test_config(temp_dir.path(), policy))
+                .expect("failed to create storage");
+        write_reading(&storage, 2_000).expect("failed to write event");
+        write_reading(&storage, 1_900).expect("failed to write event");
+
+        let late = write_reading(&storage, 1_899);
+        let counts = storage.late_events();
+        match policy {
+            LateEventPolicy::Drop => {
+                assert!(late.is_ok());
+                assert_eq!(counts.dropped, 1);
+            }
+            _ => {
+                assert_eq!(late.unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+                assert_eq!(counts.rejected, 1);
+            }
+        }
+        assert_eq!(storage.query(0, u64::MAX).unwrap().len(), 2, "{:?}", policy);
+    }
+}
+
+#[test]
+fn test_historical_window_waits_for_or_reports_the_watermark() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = Arc::new(
+        StreamingSegmentedStorage::new(event_time_test_config(
+            temp_dir.path(),
+            LateEventPolicy::Accept,
+        ))
+        .expect("failed to create storage"),
+    );
+    for timestamp in (1_000..=1_500).step_by(100) {
+        write_reading(&storage, timestamp).expect("failed to write event");
+    }
+    let window = WindowDefinition {
+        window_name: "http://example.org/window/completed".to_string(),
+        source_kind: SourceKind::Log,
+        stream_name: STREAM.to_string(),
+        width: 0,
+        slide: 0,
+        offset: None,
+        start: Some(1_000),
+        end: Some(1_600),
+        window_type: WindowType::HistoricalFixed,
+    };
+    let query = "SELECT ?bpm WHERE { GRAPH ?g { ?patient <http://example.org/bpm> ?bpm } }";
+
+    let required = HistoricalExecutor::new(storage.clone(), OxigraphAdapter::new())
+        .with_completeness(CompletenessPolicy::Require);
+    let progress = required.window_progress(STREAM, 1_600);
+    assert_eq!(progress.watermark, Some(1_400));
+    assert!(!progress.complete);
+    assert!(required.execute_fixed_window(&window, query).is_err());
+
+    // A late reading inside the window arrives while the executor waits.
+    let writer = {
+        let storage = Arc::clone(&storage);
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            write_reading(&storage, 1_600).expect("failed to write event");
+            write_reading(&storage, 1_800).expect("failed to write event");
+        })
+    };
+    let waiting = HistoricalExecutor::new(storage.clone(), OxigraphAdapter::new())
+        .with_completeness(CompletenessPolicy::Wait(Duration::from_secs(10)));
+    let started = Instant::now();
+    let bindings = waiting.execute_fixed_window(&window, query).expect("failed to execute window");
+    writer.join().unwrap();
+    assert!(started.elapsed() < Duration::from_secs(10));
+    assert_eq!(bindings.len(), 7);
+    assert!(required.window_progress(STREAM, 1_600).complete);
+
+    // Waiting for a watermark that never comes gives up after the timeout.
+    assert_eq!(
+        storage.wait_for_watermark(Some(STREAM), 5_000, Duration::from_millis(20)),
+        Some(1_700)
+    );
+}
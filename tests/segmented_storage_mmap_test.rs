@@ -0,0 +1,109 @@
+mod common;
+
+use janus::core::encoding::{encode_record, RECORD_SIZE};
+use janus::core::Event;
+use janus::storage::codec::SegmentCodec;
+use janus::storage::segmented_storage::StreamingSegmentedStorage;
+use janus::storage::util::StreamingConfig;
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+fn mmap_test_config(path: &Path, mmap_cache_capacity: usize) -> StreamingConfig {
+    StreamingConfig { compaction_min_segments: 2, mmap_cache_capacity, ..common::test_config(path) }
+}
+
+// Flush `segments` segments of twenty events each, with consecutive timestamps.
+fn write_segments(storage: &StreamingSegmentedStorage, segments: u64) {
+    for segment in 0..segments {
+        for i in 0..20u64 {
+            let timestamp = 1_000 + segment * 20 + i;
+            common::write_reading(storage, timestamp, i % 3, timestamp);
+        }
+        storage.flush().expect("failed to flush storage");
+    }
+}
+
+fn records(events: &[Event]) -> Vec<(u64, u32, u32, u32, u32)> {
+    events
+        .iter()
+        .map(|e| (e.timestamp, e.subject, e.predicate, e.object, e.graph))
+        .collect()
+}
+
+#[test]
+fn test_mapped_reads_match_file_reads() {
+    for codec in [SegmentCodec::FixedWidth, SegmentCodec::Columnar] {
+        let temp_dir = TempDir::new().expect("failed to create temp dir");
+        let config =
+            StreamingConfig { segment_codec: codec, ..mmap_test_config(temp_dir.path(), 0) };
+        {
+            let storage =
+                StreamingSegmentedStorage::new(config.clone()).expect("failed to create storage");
+            write_segments(&storage, 3);
+        }
+
+        let file_io = StreamingSegmentedStorage::new(config.clone()).expect("failed to reopen");
+        let mapped = StreamingSegmentedStorage::open_read_only(StreamingConfig {
+            mmap_cache_capacity: 8,
+            ..config
+        })
+        .expect("failed to reopen");
+
+        for (start, end) in [(0, u64::MAX), (1_005, 1_005), (1_013, 1_047), (1_059, 1_080)] {
+            let expected = file_io.query(start, end).expect("failed to query storage");
+            let actual = mapped.query(start, end).expect("failed to query storage");
+            assert_eq!(records(&actual), records(&expected), "{:?} {}..={}", codec, start, end);
+        }
+        assert_eq!(file_io.mapped_segment_count(), 0);
+        assert_eq!(mapped.mapped_segment_count(), 3);
+    }
+}
+
+#[test]
+fn test_mmap_cache_keeps_most_recently_used_segments() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = StreamingSegmentedStorage::new(mmap_test_config(temp_dir.path(), 2))
+        .expect("failed to create storage");
+    write_segments(&storage, 4);
+
+    for segment in 0..4u64 {
+        let start = 1_000 + segment * 20;
+        let events = storage.query(start, start + 19).expect("failed to query storage");
+        assert_eq!(events.len(), 20);
+        assert!(storage.mapped_segment_count() <= 2);
+    }
+    assert_eq!(storage.mapped_segment_count(), 2);
+    assert_eq!(storage.query(0, u64::MAX).expect("failed to query storage").len(), 80);
+
+    // Merged inputs are dropped from the cache along with their files.
+    assert_eq!(storage.compact().expect("failed to compact"), 3);
+    assert_eq!(storage.mapped_segment_count(), 0);
+    assert_eq!(storage.query(0, u64::MAX).expect("failed to query storage").len(), 80);
+    assert_eq!(storage.mapped_segment_count(), 1);
+}
+
+#[test]
+fn test_legacy_records_are_decoded_from_the_mapping() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+
+    // A segment from before the segment header: raw fixed-width records and no index.
+    let mut data = Vec::new();
+    let mut record = [0u8; RECORD_SIZE];
+    for i in 0..50u64 {
+        encode_record(&mut record, 2_000 + i, 1, 2, i as u32, 3);
+        data.extend_from_slice(&record);
+    }
+    fs::write(temp_dir.path().join("segment-1.log"), &data).expect("failed to write segment");
+
+    let storage = StreamingSegmentedStorage::new(mmap_test_config(temp_dir.path(), 4))
+        .expect("failed to create storage");
+    let events = storage.query(2_010, 2_019).expect("failed to query storage");
+    assert_eq!(storage.mapped_segment_count(), 1);
+    assert_eq!(events.len(), 10);
+    assert!(events
+        .iter()
+        .enumerate()
+        .all(|(i, e)| e.timestamp == 2_010 + i as u64 && e.object == 10 + i as u32));
+    assert_eq!(storage.query(0, u64::MAX).expect("failed to query storage").len(), 50);
+}
@@ -0,0 +1,154 @@
+mod common;
+
+use janus::core::Event;
+use janus::storage::indexing::secondary::TriplePattern;
+use janus::storage::query_iter::SCAN_CHUNK_EVENTS;
+use janus::storage::segmented_storage::StreamingSegmentedStorage;
+use janus::storage::util::StreamingConfig;
+use std::path::Path;
+use tempfile::TempDir;
+
+const STREAM: &str = "http://example.org/stream/sensors";
+
+fn parallel_test_config(path: &Path, query_scan_threads: usize) -> StreamingConfig {
+    StreamingConfig {
+        entries_per_index_block: 8,
+        mmap_cache_capacity: 0,
+        query_scan_threads,
+        ..common::test_config(path)
+    }
+}
+
+// Segments that follow each other, segments that overlap, and a buffered tail.
+fn write_segments(storage: &StreamingSegmentedStorage) {
+    for segment in 0..12u64 {
+        let start = if segment % 3 == 0 {
+            1_000
+        } else {
+            1_000 + segment * 200
+        };
+        for timestamp in (start..start + 400).step_by(7) {
+            common::write_stream_reading(storage, STREAM, timestamp, segment % 4, timestamp % 31);
+        }
+        storage.flush().expect("failed to flush");
+    }
+    for timestamp in (3_000..3_100).step_by(3) {
+        storage
+            .write_rdf_to_stream(
+                STREAM,
+                timestamp,
+                "http://example.org/sensor1",
+                "http://example.org/humidity",
+                "50",
+                "",
+            )
+            .expect("failed to write event");
+    }
+}
+
+fn keys(events: &[Event]) -> Vec<(u64, u32, u32, u32, u32)> {
+    events
+        .iter()
+        .map(|event| (event.timestamp, event.subject, event.predicate, event.object, event.graph))
+        .collect()
+}
+
+#[test]
+fn test_parallel_scans_return_the_sequential_results() {
+    let sequential_dir = TempDir::new().expect("failed to create temp dir");
+    let parallel_dir = TempDir::new().expect("failed to create temp dir");
+    let sequential = StreamingSegmentedStorage::new(parallel_test_config(sequential_dir.path(), 1))
+        .expect("failed to create storage");
+    let parallel = StreamingSegmentedStorage::new(parallel_test_config(parallel_dir.path(), 4))
+        .expect("failed to create storage");
+    write_segments(&sequential);
+    write_segments(&parallel);
+    assert_eq!(parallel.segment_metadata().len(), 12);
+
+    for (start, end) in [(0, u64::MAX), (1_150, 2_450), (2_900, 3_050), (5_000, 6_000)] {
+        let expected = sequential.query(start, end).expect("failed to query");
+        let events = parallel.query(start, end).expect("failed to query");
+        assert!(events.windows(2).all(|pair| pair[0].timestamp <= pair[1].timestamp));
+        assert_eq!(keys(&events), keys(&expected), "range {}..={}", start, end);
+    }
+
+    let patterns = [TriplePattern::new(Some("http://example.org/sensor2"), None)];
+    assert_eq!(
+        keys(&parallel.query_stream_filtered(STREAM, 0, u64::MAX, &patterns).unwrap()),
+        keys(&sequential.query_stream_filtered(STREAM, 0, u64::MAX, &patterns).unwrap())
+    );
+
+    for storage in [&sequential, &parallel] {
+        storage
+            .delete_by_subject("http://example.org/sensor3")
+            .expect("failed to delete subject");
+    }
+    let expected = sequential.query(0, u64::MAX).unwrap();
+    assert_eq!(keys(&parallel.query(0, u64::MAX).unwrap()), keys(&expected));
+}
+
+#[test]
+fn test_partially_consumed_parallel_iterators_stop_cleanly() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = StreamingSegmentedStorage::new(parallel_test_config(temp_dir.path(), 3))
+        .expect("failed to create storage");
+    write_segments(&storage);
+    let expected = storage.query(0, u64::MAX).expect("failed to query");
+
+    // Several iterators share the pool, and dropping one with scans in flight
+    // leaves the others unaffected.
+    let mut first = storage.query_iter(0, u64::MAX).expect("failed to query");
+    let head: Vec<Event> = first.by_ref().take(10).collect::<Result<_, _>>().unwrap();
+    assert_eq!(keys(&head), keys(&expected[..10]));
+    let second = storage.query_iter(0, u64::MAX).expect("failed to query");
+    drop(first);
+    let events: Vec<Event> = second.collect::<Result<_, _>>().expect("failed to iterate");
+    assert_eq!(keys(&events), keys(&expected));
+
+    // A segment that vanished under a scan fails the query instead of hanging it.
+    let mut segments = storage.segment_metadata();
+    segments.sort_by_key(|segment| segment.segment_id());
+    std::fs::remove_file(&segments[5].data_path).expect("failed to remove segment");
+    assert!(storage.query(0, u64::MAX).is_err());
+}
+
+#[test]
+fn test_overlapping_segments_are_scanned_in_bounded_chunks() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let config =
+        StreamingConfig { max_batch_events: 100_000, ..parallel_test_config(temp_dir.path(), 4) };
+    let storage = StreamingSegmentedStorage::new(config).expect("failed to create storage");
+    // Eight segments over the same range, each of several chunks.
+    let events_per_segment = 5 * SCAN_CHUNK_EVENTS as u64;
+    for segment in 0..8u64 {
+        for i in 0..events_per_segment {
+            storage
+                .write_rdf_to_stream(
+                    STREAM,
+                    1_000 + i,
+                    &format!("http://example.org/sensor{}", segment),
+                    "http://example.org/temperature",
+                    &(i % 31).to_string(),
+                    "",
+                )
+                .expect("failed to write event");
+        }
+        storage.flush().expect("failed to flush");
+    }
+    assert_eq!(storage.segment_metadata().len(), 8);
+
+    // Every segment joins the merge at once, and none is read much ahead of it.
+    let mut events = storage.query_iter(0, u64::MAX).expect("failed to query");
+    let (mut count, mut peak, mut previous) = (0, 0, 0);
+    while let Some(event) = events.next() {
+        let event = event.expect("failed to iterate");
+        assert!(event.timestamp >= previous);
+        previous = event.timestamp;
+        count += 1;
+        peak = peak.max(events.scanned_events_held());
+    }
+    assert_eq!(count, 8 * events_per_segment);
+    assert!(peak > 0);
+    assert!(peak <= 8 * 2 * SCAN_CHUNK_EVENTS, "{} events held", peak);
+    assert_eq!(events.scanned_events_held(), 0);
+}
@@ -0,0 +1,209 @@
+mod common;
+
+use janus::execution::historical_executor::HistoricalExecutor;
+use janus::parsing::janusql_parser::{SourceKind, WindowDefinition, WindowType};
+use janus::querying::oxigraph_adapter::OxigraphAdapter;
+use janus::storage::segmented_storage::StreamingSegmentedStorage;
+use janus::storage::util::StreamingConfig;
+use janus::stream::operators::historical_fixed_window::HistoricalFixedWindowOperator;
+use std::collections::HashMap;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn query_iter_test_config(path: &Path) -> StreamingConfig {
+    StreamingConfig { compaction_min_segments: 2, ..common::test_config(path) }
+}
+
+// Three segments whose timestamps interleave, plus interleaving buffered events.
+fn write_interleaved(storage: &StreamingSegmentedStorage) -> Vec<u64> {
+    let mut timestamps = Vec::new();
+    for sensor in 0..4u64 {
+        for i in 0..30u64 {
+            let timestamp = 1_000 + i * 4 + sensor;
+            common::write_reading(storage, timestamp, sensor, timestamp);
+            timestamps.push(timestamp);
+        }
+        if sensor < 3 {
+            storage.flush().expect("failed to flush storage");
+        }
+    }
+    timestamps.sort_unstable();
+    timestamps
+}
+
+fn iter_timestamps(storage: &StreamingSegmentedStorage, start: u64, end: u64) -> Vec<u64> {
+    storage
+        .query_iter(start, end)
+        .expect("failed to create iterator")
+        .map(|event| event.expect("failed to read event").timestamp)
+        .collect()
+}
+
+#[test]
+fn test_query_iter_merges_segments_and_buffer_in_order() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = StreamingSegmentedStorage::new(query_iter_test_config(temp_dir.path()))
+        .expect("failed to create storage");
+    let timestamps = write_interleaved(&storage);
+    assert_eq!(storage.segment_metadata().len(), 3);
+
+    for (start, end) in [(0, u64::MAX), (1_000, 1_119), (1_013, 1_013), (1_037, 1_090)] {
+        let expected: Vec<u64> =
+            timestamps.iter().copied().filter(|ts| (start..=end).contains(ts)).collect();
+        assert_eq!(iter_timestamps(&storage, start, end), expected, "range {}..={}", start, end);
+
+        let queried = storage.query(start, end).expect("failed to query storage");
+        let iterated: Vec<_> = storage
+            .query_iter(start, end)
+            .expect("failed to create iterator")
+            .collect::<std::io::Result<_>>()
+            .expect("failed to read events");
+        assert_eq!(iterated.len(), queried.len());
+        assert!(iterated.iter().zip(&queried).all(|(a, b)| a.timestamp == b.timestamp
+            && a.subject == b.subject
+            && a.object == b.object));
+    }
+}
+
+#[test]
+fn test_query_iter_finds_duplicate_timestamps_across_index_blocks() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = StreamingSegmentedStorage::new(query_iter_test_config(temp_dir.path()))
+        .expect("failed to create storage");
+
+    // Forty events share one timestamp, spanning many data blocks and index blocks.
+    for i in 0..10u64 {
+        common::write_reading(&storage, 1_000 + i, i, 1_000 + i);
+    }
+    for sensor in 0..40u64 {
+        common::write_reading(&storage, 1_010, sensor, 1_010);
+    }
+    for i in 11..20u64 {
+        common::write_reading(&storage, 1_000 + i, i, 1_000 + i);
+    }
+    storage.flush().expect("failed to flush storage");
+
+    assert_eq!(iter_timestamps(&storage, 1_010, 1_010).len(), 40);
+    assert_eq!(iter_timestamps(&storage, 1_010, 1_011).len(), 41);
+    assert_eq!(iter_timestamps(&storage, 1_009, 1_010).len(), 41);
+    assert_eq!(iter_timestamps(&storage, 0, u64::MAX).len(), 59);
+}
+
+#[test]
+fn test_query_iter_outlives_compaction_of_its_segments() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = StreamingSegmentedStorage::new(query_iter_test_config(temp_dir.path()))
+        .expect("failed to create storage");
+    let timestamps = write_interleaved(&storage);
+
+    let mut events = storage.query_iter(0, u64::MAX).expect("failed to create iterator");
+    let first = events.next().expect("iterator is empty").expect("failed to read event");
+    assert!(storage.compact().expect("failed to compact") >= 2);
+
+    let mut seen = vec![first.timestamp];
+    seen.extend(events.map(|event| event.expect("failed to read event").timestamp));
+    assert_eq!(seen, timestamps);
+    assert_eq!(iter_timestamps(&storage, 0, u64::MAX), timestamps);
+}
+
+#[test]
+fn test_fixed_window_operator_yields_chunks() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = Rc::new(
+        StreamingSegmentedStorage::new(query_iter_test_config(temp_dir.path()))
+            .expect("failed to create storage"),
+    );
+    let timestamps = write_interleaved(&storage);
+
+    let window = WindowDefinition {
+        window_name: "http://example.org/window/chunks".to_string(),
+        source_kind: SourceKind::Log,
+        stream_name: "http://example.org/stream".to_string(),
+        width: 0,
+        slide: 0,
+        offset: None,
+        start: Some(1_010),
+        end: Some(1_099),
+        window_type: WindowType::HistoricalFixed,
+    };
+
+    let chunks: Vec<_> =
+        HistoricalFixedWindowOperator::with_chunk_size(storage.clone(), window.clone(), 16)
+            .collect();
+    assert_eq!(chunks.len(), 6);
+    assert!(chunks.iter().all(|chunk| chunk.len() <= 16));
+    let chunked: Vec<u64> = chunks.iter().flatten().map(|event| event.timestamp).collect();
+    let expected: Vec<u64> =
+        timestamps.iter().copied().filter(|ts| (1_010..=1_099).contains(ts)).collect();
+    assert_eq!(chunked, expected);
+
+    let whole: Vec<_> = HistoricalFixedWindowOperator::new(storage, window).collect();
+    assert_eq!(whole.len(), 1);
+    assert_eq!(whole[0].len(), expected.len());
+}
+
+// The solutions of a query as a sorted multiset, independent of their order.
+fn solution_multiset(bindings: Vec<HashMap<String, String>>) -> Vec<Vec<(String, String)>> {
+    let mut solutions: Vec<Vec<(String, String)>> = bindings
+        .into_iter()
+        .map(|binding| {
+            let mut solution: Vec<_> = binding.into_iter().collect();
+            solution.sort();
+            solution
+        })
+        .collect();
+    solutions.sort();
+    solutions
+}
+
+#[test]
+fn test_decomposed_queries_run_per_chunk_with_the_solutions_of_the_whole_window() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = Arc::new(
+        StreamingSegmentedStorage::new(query_iter_test_config(temp_dir.path()))
+            .expect("failed to create storage"),
+    );
+    // The same readings repeat across every chunk, and in two graphs.
+    for i in 0..25_000u64 {
+        storage
+            .write_rdf(
+                1_000 + i,
+                &format!("http://example.org/sensor{}", i % 5),
+                "http://example.org/temperature",
+                &format!("{}", (i / 7) % 4),
+                &format!("http://example.org/graph{}", i % 2),
+            )
+            .expect("failed to write event");
+    }
+
+    let window = WindowDefinition {
+        window_name: "http://example.org/window/readings".to_string(),
+        source_kind: SourceKind::Log,
+        stream_name: "http://example.org/stream".to_string(),
+        width: 0,
+        slide: 0,
+        offset: None,
+        start: Some(1_000),
+        end: Some(26_000),
+        window_type: WindowType::HistoricalFixed,
+    };
+    let executor = HistoricalExecutor::new(storage.clone(), OxigraphAdapter::new());
+    let execute = |query: &str| {
+        solution_multiset(executor.execute_fixed_window(&window, query).expect("query failed"))
+    };
+
+    // An ORDER BY keeps the query from being decomposed, so it reads the whole window at once.
+    let pattern = "GRAPH ?g { ?sensor <http://example.org/temperature> ?value }";
+    let per_chunk = execute(&format!("SELECT ?sensor ?value WHERE {{ {} }}", pattern));
+    let whole = execute(&format!("SELECT ?sensor ?value WHERE {{ {} }} ORDER BY ?g", pattern));
+    assert_eq!(per_chunk.len(), 5 * 4 * 2);
+    assert_eq!(per_chunk, whole);
+
+    let per_chunk = execute(&format!("SELECT DISTINCT ?sensor WHERE {{ {} }}", pattern));
+    let whole =
+        execute(&format!("SELECT DISTINCT ?sensor WHERE {{ {} }} ORDER BY ?sensor", pattern));
+    assert_eq!(per_chunk.len(), 5);
+    assert_eq!(per_chunk, whole);
+}
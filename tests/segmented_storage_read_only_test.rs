@@ -0,0 +1,160 @@
+mod common;
+
+use janus::storage::segmented_storage::StreamingSegmentedStorage;
+use janus::storage::util::StreamingConfig;
+use std::path::Path;
+use std::time::Duration;
+use tempfile::TempDir;
+
+const STREAM: &str = "http://example.org/stream/sensors";
+
+fn read_only_test_config(path: &Path) -> StreamingConfig {
+    StreamingConfig { compaction_min_segments: 2, ..common::test_config(path) }
+}
+
+fn write_readings(storage: &StreamingSegmentedStorage, sensor: u64, from: u64, to: u64) {
+    for timestamp in (from..to).step_by(10) {
+        common::write_stream_reading(storage, STREAM, timestamp, sensor, timestamp % 37);
+    }
+}
+
+fn subjects(storage: &StreamingSegmentedStorage) -> Vec<String> {
+    let mut subjects: Vec<String> = storage
+        .query_rdf(0, u64::MAX)
+        .expect("failed to query")
+        .into_iter()
+        .map(|event| event.subject.value().to_string())
+        .collect();
+    subjects.sort();
+    subjects.dedup();
+    subjects
+}
+
+#[test]
+fn test_only_one_writer_opens_a_directory() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = StreamingSegmentedStorage::new(read_only_test_config(temp_dir.path()))
+        .expect("failed to create storage");
+
+    let second = StreamingSegmentedStorage::new(read_only_test_config(temp_dir.path()));
+    let err = second.err().expect("a second writer must be refused");
+    assert_eq!(err.kind(), std::io::ErrorKind::ResourceBusy);
+    assert!(err.to_string().contains(&std::process::id().to_string()), "{}", err);
+
+    // The lock goes with the storage.
+    drop(storage);
+    StreamingSegmentedStorage::new(read_only_test_config(temp_dir.path()))
+        .expect("failed to reopen storage");
+}
+
+#[test]
+fn test_shutdown_unlocks_the_directory() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let mut storage = StreamingSegmentedStorage::new(read_only_test_config(temp_dir.path()))
+        .expect("failed to create storage");
+    write_readings(&storage, 1, 1_000, 1_100);
+    storage.shutdown().expect("failed to shut down");
+
+    // The shut down storage can still be queried while the directory is reopened for writing.
+    let reopened = StreamingSegmentedStorage::new(read_only_test_config(temp_dir.path()))
+        .expect("failed to reopen storage");
+    assert!(!storage.is_read_only());
+    assert_eq!(subjects(&storage), vec!["http://example.org/sensor1"]);
+    let err = storage
+        .write_rdf_to_stream(STREAM, 2_000, "http://example.org/sensor2", "p", "1", "")
+        .expect_err("a shut down storage must refuse writes");
+    assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+    write_readings(&reopened, 2, 2_000, 2_100);
+    assert_eq!(reopened.query_rdf(0, u64::MAX).unwrap().len(), 20);
+}
+
+#[test]
+fn test_reader_follows_segments_and_dictionary_of_the_writer() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let writer = StreamingSegmentedStorage::new(read_only_test_config(temp_dir.path()))
+        .expect("failed to create storage");
+    write_readings(&writer, 1, 1_000, 1_200);
+    writer.flush().expect("failed to flush");
+
+    let reader = StreamingSegmentedStorage::open_read_only(read_only_test_config(temp_dir.path()))
+        .expect("failed to open storage read-only");
+    assert!(reader.is_read_only());
+    assert_eq!(reader.query(0, u64::MAX).unwrap().len(), 20);
+    assert_eq!(reader.watermark(Some(STREAM)), Some(1_190));
+
+    // Buffered events are not visible until the writer flushes them.
+    write_readings(&writer, 2, 1_200, 1_400);
+    assert!(!reader.refresh().expect("failed to refresh"));
+    assert_eq!(subjects(&reader), vec!["http://example.org/sensor1"]);
+    writer.flush().expect("failed to flush");
+    assert!(reader.refresh().expect("failed to refresh"));
+    assert_eq!(
+        subjects(&reader),
+        vec!["http://example.org/sensor1", "http://example.org/sensor2"]
+    );
+    assert_eq!(reader.query(0, u64::MAX).unwrap().len(), 40);
+    assert_eq!(reader.watermark(Some(STREAM)), Some(1_390));
+
+    // Merged segments replace their inputs and erased subjects disappear.
+    writer.compact().expect("failed to compact");
+    writer
+        .delete_by_subject("http://example.org/sensor1")
+        .expect("failed to delete subject");
+    assert!(reader.refresh().expect("failed to refresh"));
+    assert_eq!(reader.segment_metadata().len(), 1);
+    assert_eq!(subjects(&reader), vec!["http://example.org/sensor2"]);
+    assert_eq!(reader.erasure_progress().pending_tombstones, 1);
+
+    // A purge checkpoints the dictionary, which the reader loads again.
+    writer.purge_erased().expect("failed to purge");
+    write_readings(&writer, 3, 1_400, 1_500);
+    writer.flush().expect("failed to flush");
+    reader.refresh().expect("failed to refresh");
+    assert_eq!(
+        subjects(&reader),
+        vec!["http://example.org/sensor2", "http://example.org/sensor3"]
+    );
+    assert!(!reader
+        .get_dictionary()
+        .read()
+        .unwrap()
+        .string_to_id
+        .contains_key("http://example.org/sensor1"));
+}
+
+#[test]
+fn test_reader_refuses_writes_and_tails_in_the_background() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let writer = StreamingSegmentedStorage::new(read_only_test_config(temp_dir.path()))
+        .expect("failed to create storage");
+    let mut reader =
+        StreamingSegmentedStorage::open_read_only(read_only_test_config(temp_dir.path()))
+            .expect("failed to open storage read-only");
+
+    let denied = std::io::ErrorKind::PermissionDenied;
+    assert_eq!(
+        reader
+            .write_rdf(1_000, "http://example.org/s", "http://example.org/p", "o", "")
+            .unwrap_err()
+            .kind(),
+        denied
+    );
+    assert_eq!(reader.flush().unwrap_err().kind(), denied);
+    assert_eq!(reader.compact().unwrap_err().kind(), denied);
+    assert_eq!(reader.delete_by_subject("http://example.org/s").unwrap_err().kind(), denied);
+    assert!(reader.get_dictionary().read().unwrap().string_to_id.is_empty());
+
+    reader
+        .start_tailing(Duration::from_millis(20))
+        .expect("failed to start tailing");
+    write_readings(&writer, 1, 1_000, 1_500);
+    writer.flush().expect("failed to flush");
+    assert_eq!(
+        reader.wait_for_watermark(Some(STREAM), 1_400, Duration::from_secs(10)),
+        Some(1_490)
+    );
+    assert_eq!(reader.query(0, u64::MAX).unwrap().len(), 50);
+    assert_eq!(subjects(&reader), vec!["http://example.org/sensor1"]);
+    assert_eq!(reader.background_flush_error(), None);
+    reader.shutdown().expect("failed to shut down reader");
+}
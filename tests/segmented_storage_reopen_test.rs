@@ -0,0 +1,53 @@
+mod common;
+
+use janus::storage::segmented_storage::StreamingSegmentedStorage;
+use janus::storage::util::StreamingConfig;
+use tempfile::TempDir;
+
+fn reopen_test_config(path: &std::path::Path) -> StreamingConfig {
+    StreamingConfig { sparse_interval: 3, entries_per_index_block: 4, ..common::test_config(path) }
+}
+
+#[test]
+fn test_reopened_segment_keeps_exact_index_directory() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+
+    let (written_segments, written_events) = {
+        let storage = StreamingSegmentedStorage::new(reopen_test_config(temp_dir.path()))
+            .expect("failed to create storage");
+        for i in 0..100u64 {
+            storage
+                .write_rdf(
+                    10_000 + i * 10,
+                    &format!("http://example.org/sensor{}", i % 7),
+                    "http://example.org/temperature",
+                    &format!("{}", i),
+                    "http://example.org/graph1",
+                )
+                .expect("failed to write event");
+        }
+        storage.flush().expect("failed to flush storage");
+        (storage.segment_metadata(), storage.query(0, u64::MAX).expect("failed to query"))
+    };
+
+    let storage = StreamingSegmentedStorage::new(reopen_test_config(temp_dir.path()))
+        .expect("failed to reopen storage");
+    let reopened_segments = storage.segment_metadata();
+
+    assert_eq!(written_segments.len(), 1);
+    assert_eq!(reopened_segments.len(), 1);
+    assert!(written_segments[0].index_directory.len() > 1, "expected several index blocks");
+    assert_eq!(reopened_segments[0].index_directory, written_segments[0].index_directory);
+    assert_eq!(reopened_segments[0].start_timstamp, 10_000);
+    assert_eq!(reopened_segments[0].end_timestamp, 10_990);
+    assert_eq!(reopened_segments[0].record_count, 100);
+
+    let reopened_events = storage.query(0, u64::MAX).expect("failed to query reopened storage");
+    assert_eq!(reopened_events.len(), written_events.len());
+
+    let range = storage.query(10_455, 10_705).expect("failed to query range");
+    let timestamps: Vec<u64> = range.iter().map(|e| e.timestamp).collect();
+    assert_eq!(timestamps.first(), Some(&10_460));
+    assert_eq!(timestamps.last(), Some(&10_700));
+    assert_eq!(timestamps.len(), 25);
+}
@@ -0,0 +1,129 @@
+mod common;
+
+use janus::storage::segmented_storage::StreamingSegmentedStorage;
+use janus::storage::util::StreamingConfig;
+use std::path::Path;
+use std::thread::sleep;
+use std::time::Duration;
+use tempfile::TempDir;
+
+fn retention_test_config(path: &Path) -> StreamingConfig {
+    StreamingConfig {
+        sparse_interval: 10,
+        entries_per_index_block: 100,
+        ..common::test_config(path)
+    }
+}
+
+// Flush one segment per start timestamp, each holding ten events one millisecond apart.
+fn write_segments(storage: &StreamingSegmentedStorage, starts: &[u64]) {
+    for &start in starts {
+        for i in 0..10u64 {
+            common::write_reading(storage, start + i, 1, i);
+        }
+        storage.flush().expect("failed to flush storage");
+    }
+}
+
+#[test]
+fn test_retention_disabled_keeps_every_segment() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = StreamingSegmentedStorage::new(retention_test_config(temp_dir.path()))
+        .expect("failed to create storage");
+    write_segments(&storage, &[1_000, 2_000, 3_000]);
+
+    assert_eq!(storage.enforce_retention().expect("failed to enforce retention"), 0);
+    assert_eq!(storage.segment_metadata().len(), 3);
+    assert_eq!(storage.retention_horizon(), None);
+}
+
+#[test]
+fn test_segment_count_limit_deletes_oldest_segments() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let config = StreamingConfig {
+        retention_max_segments: Some(2),
+        ..retention_test_config(temp_dir.path())
+    };
+    let storage = StreamingSegmentedStorage::new(config).expect("failed to create storage");
+    write_segments(&storage, &[1_000, 2_000, 3_000, 4_000]);
+    let oldest = storage.segment_metadata()[0].clone();
+
+    assert_eq!(storage.enforce_retention().expect("failed to enforce retention"), 2);
+
+    let segments = storage.segment_metadata();
+    assert_eq!(segments.len(), 2);
+    assert_eq!(segments[0].start_timstamp, 3_000);
+    assert!(!Path::new(&oldest.data_path).exists());
+    assert!(!Path::new(&oldest.index_path).exists());
+    assert_eq!(storage.retention_horizon(), Some(3_000));
+    assert_eq!(storage.query(0, u64::MAX).expect("failed to query").len(), 20);
+}
+
+#[test]
+fn test_age_limit_is_measured_against_newest_event() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let config = StreamingConfig {
+        retention_max_age_seconds: Some(50),
+        ..retention_test_config(temp_dir.path())
+    };
+    let storage = StreamingSegmentedStorage::new(config).expect("failed to create storage");
+    write_segments(&storage, &[1_000, 120_000, 200_000]);
+
+    // The newest event is at 200_009, so only segments ending before 150_009 expire.
+    assert_eq!(storage.enforce_retention().expect("failed to enforce retention"), 2);
+    assert_eq!(storage.retention_horizon(), Some(200_000));
+}
+
+#[test]
+fn test_byte_limit_deletes_oldest_segments() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = StreamingSegmentedStorage::new(retention_test_config(temp_dir.path()))
+        .expect("failed to create storage");
+    write_segments(&storage, &[1_000]);
+    let segment = &storage.segment_metadata()[0];
+    let segment_bytes = std::fs::metadata(&segment.data_path).unwrap().len()
+        + std::fs::metadata(&segment.index_path).unwrap().len();
+    drop(storage);
+
+    let config = StreamingConfig {
+        retention_max_bytes: Some(segment_bytes * 2),
+        ..retention_test_config(temp_dir.path())
+    };
+    let storage = StreamingSegmentedStorage::new(config).expect("failed to reopen storage");
+    write_segments(&storage, &[2_000, 3_000]);
+
+    assert_eq!(storage.enforce_retention().expect("failed to enforce retention"), 1);
+    assert_eq!(storage.retention_horizon(), Some(2_000));
+}
+
+#[test]
+fn test_background_flush_enforces_retention() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let config = StreamingConfig {
+        max_batch_events: 10,
+        retention_max_segments: Some(1),
+        ..retention_test_config(temp_dir.path())
+    };
+    let mut storage = StreamingSegmentedStorage::new(config).expect("failed to create storage");
+    storage.start_background_flushing();
+
+    for batch in 0..3u64 {
+        for i in 0..10u64 {
+            storage
+                .write_rdf(
+                    batch * 1_000 + i,
+                    "http://example.org/sensor1",
+                    "http://example.org/temperature",
+                    "1",
+                    "http://example.org/graph1",
+                )
+                .expect("failed to write event");
+        }
+        sleep(Duration::from_millis(300));
+    }
+
+    let segments = storage.segment_metadata();
+    assert_eq!(segments.len(), 1);
+    assert_eq!(segments[0].start_timstamp, 2_000);
+    storage.shutdown().expect("failed to shut down storage");
+}
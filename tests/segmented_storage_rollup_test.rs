@@ -0,0 +1,237 @@
+mod common;
+
+use janus::core::RdfTerm;
+use janus::execution::historical_executor::HistoricalExecutor;
+use janus::execution::pushdown::{readings_query, ReadingsQuery};
+use janus::parsing::janusql_parser::{SourceKind, WindowDefinition, WindowType};
+use janus::querying::oxigraph_adapter::OxigraphAdapter;
+use janus::storage::rollup::{numeric_value, Rollup, RollupStats};
+use janus::storage::segmented_storage::StreamingSegmentedStorage;
+use janus::storage::util::StreamingConfig;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+const STREAM: &str = "http://example.org/stream/sensors";
+const TEMPERATURE: &str = "http://example.org/temperature";
+const STATUS: &str = "http://example.org/status";
+const GRAPH: &str = "http://example.org/graph1";
+
+fn rollup_test_config(path: &Path) -> StreamingConfig {
+    StreamingConfig {
+        sparse_interval: 8,
+        entries_per_index_block: 4,
+        compaction_min_segments: 2,
+        rollup_bucket_ms: 100,
+        ..common::test_config(path)
+    }
+}
+
+// Readings every 7 ms from three sensors, with a status event every 50 ms in a named graph.
+fn write_readings(storage: &StreamingSegmentedStorage, from: u64, to: u64) {
+    for timestamp in (from..to).step_by(7) {
+        let sensor = format!("http://example.org/sensor{}", timestamp % 3);
+        let value = format!("{}", timestamp % 41);
+        storage
+            .write_rdf_to_stream(STREAM, timestamp, &sensor, TEMPERATURE, &value, "")
+            .expect("failed to write event");
+        if timestamp % 50 < 7 {
+            storage
+                .write_rdf_to_stream(STREAM, timestamp, &sensor, STATUS, "ok", GRAPH)
+                .expect("failed to write event");
+        }
+    }
+}
+
+type RollupKey = (u64, String, String, String);
+
+fn keyed(rollups: Vec<Rollup>) -> BTreeMap<RollupKey, RollupStats> {
+    rollups
+        .into_iter()
+        .map(|rollup| {
+            (
+                (rollup.bucket_start, rollup.predicate, rollup.subject, rollup.graph),
+                rollup.stats,
+            )
+        })
+        .collect()
+}
+
+// Roll up the events of the range by hand.
+fn rolled_up_events(
+    storage: &StreamingSegmentedStorage,
+    start: u64,
+    end: u64,
+) -> BTreeMap<RollupKey, RollupStats> {
+    let dictionary = storage.get_dictionary().read().unwrap();
+    let mut expected: BTreeMap<RollupKey, RollupStats> = BTreeMap::new();
+    for event in storage.query_stream(STREAM, start, end).expect("failed to query") {
+        let event = event.decode(&dictionary);
+        let key = (
+            event.timestamp - event.timestamp % 100,
+            event.predicate.value().to_string(),
+            event.subject.value().to_string(),
+            event.graph.dictionary_key(),
+        );
+        expected.entry(key).or_default().add(numeric_value(&event.object));
+    }
+    expected
+}
+
+fn assert_rollups_match_events(storage: &StreamingSegmentedStorage, start: u64, end: u64) {
+    let rollups = storage
+        .query_rollups(Some(STREAM), start, end)
+        .expect("failed to read rollups")
+        .expect("rollups are configured");
+    assert_eq!(keyed(rollups), rolled_up_events(storage, start, end), "{}..={}", start, end);
+}
+
+#[test]
+fn test_rollups_are_exact_over_segments_buffer_and_partial_buckets() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = StreamingSegmentedStorage::new(rollup_test_config(temp_dir.path()))
+        .expect("failed to create storage");
+    write_readings(&storage, 1_000, 1_500);
+    storage.flush().expect("failed to flush");
+    write_readings(&storage, 1_500, 2_000);
+    storage.flush().expect("failed to flush");
+    write_readings(&storage, 2_000, 2_250);
+
+    let segments = storage.segment_metadata();
+    assert_eq!(segments.len(), 2);
+    assert!(segments.iter().all(|segment| segment.rollups.is_some()));
+
+    for (start, end) in [(0, u64::MAX), (1_000, 1_999), (1_033, 2_171), (1_210, 1_260)] {
+        assert_rollups_match_events(&storage, start, end);
+    }
+    let status = storage
+        .query_rollups(Some(STREAM), 1_000, 1_099)
+        .unwrap()
+        .unwrap()
+        .into_iter()
+        .find(|rollup| rollup.predicate == STATUS)
+        .expect("missing status rollup");
+    assert_eq!(status.stats.count, 1);
+    assert_eq!(status.stats.numeric_count, 0);
+    assert_eq!(status.graph, GRAPH);
+    assert!(storage
+        .query_rollups(Some("http://example.org/stream/other"), 0, u64::MAX)
+        .unwrap()
+        .unwrap()
+        .is_empty());
+
+    // The rollups are read back from the footers, and merged segments get their own.
+    drop(storage);
+    let storage = StreamingSegmentedStorage::new(rollup_test_config(temp_dir.path()))
+        .expect("failed to reopen storage");
+    assert_rollups_match_events(&storage, 1_033, 2_171);
+    storage.compact().expect("failed to compact");
+    assert_eq!(storage.segment_metadata().len(), 1);
+    assert!(storage.segment_metadata()[0].rollups.is_some());
+    assert_rollups_match_events(&storage, 0, u64::MAX);
+    assert!(storage.verify(None).expect("failed to verify").is_clean());
+}
+
+#[test]
+fn test_rollups_leave_out_erased_subjects() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = StreamingSegmentedStorage::new(rollup_test_config(temp_dir.path()))
+        .expect("failed to create storage");
+    write_readings(&storage, 1_000, 2_000);
+    storage.flush().expect("failed to flush");
+
+    storage
+        .delete_by_subject("http://example.org/sensor1")
+        .expect("failed to delete subject");
+    let erased = |storage: &StreamingSegmentedStorage| {
+        keyed(storage.query_rollups(Some(STREAM), 0, u64::MAX).unwrap().unwrap())
+            .keys()
+            .any(|(_, _, subject, _)| subject == "http://example.org/sensor1")
+    };
+    assert!(!erased(&storage));
+    assert_rollups_match_events(&storage, 1_000, 1_999);
+
+    storage.purge_erased().expect("failed to purge");
+    assert!(!erased(&storage));
+    assert_rollups_match_events(&storage, 1_000, 1_999);
+}
+
+#[test]
+fn test_rollups_are_only_kept_when_configured() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let config = StreamingConfig { rollup_bucket_ms: 0, ..rollup_test_config(temp_dir.path()) };
+    let storage = StreamingSegmentedStorage::new(config).expect("failed to create storage");
+    write_readings(&storage, 1_000, 1_200);
+    storage.flush().expect("failed to flush");
+
+    assert!(storage.segment_metadata()[0].rollups.is_none());
+    assert!(storage.query_rollups(Some(STREAM), 0, u64::MAX).unwrap().is_none());
+}
+
+#[test]
+fn test_fixed_window_reads_rollups_for_readings_queries() {
+    let query = "SELECT ?sensor ?temp WHERE {\n  GRAPH ?__janus_log_graph {\n    \
+                 ?sensor <http://example.org/temperature> ?temp\n  }\n}";
+    assert_eq!(
+        readings_query(query),
+        Some(ReadingsQuery {
+            predicate: TEMPERATURE.to_string(),
+            subject_variable: "sensor".to_string(),
+            value_variable: "temp".to_string(),
+            in_named_graph: true,
+        })
+    );
+    assert!(
+        readings_query("SELECT * WHERE { GRAPH ?g { ?s <http://example.org/t> ?o } }").is_none()
+    );
+    assert!(
+        readings_query("SELECT ?s ?o WHERE { ?s <http://example.org/t> ?o . ?s ?p ?x }").is_none()
+    );
+    assert!(readings_query("SELECT ?s ?o WHERE { ?s ?p ?o }").is_none());
+
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = Arc::new(
+        StreamingSegmentedStorage::new(rollup_test_config(temp_dir.path()))
+            .expect("failed to create storage"),
+    );
+    write_readings(&storage, 1_000, 1_500);
+    let executor = HistoricalExecutor::new(storage.clone(), OxigraphAdapter::new());
+    let window = WindowDefinition {
+        window_name: "http://example.org/window/rollups".to_string(),
+        source_kind: SourceKind::Log,
+        stream_name: STREAM.to_string(),
+        width: 0,
+        slide: 0,
+        offset: None,
+        start: Some(1_050),
+        end: Some(1_349),
+        window_type: WindowType::HistoricalFixed,
+    };
+
+    let rollups = executor
+        .fixed_window_rollups(&window)
+        .expect("failed to read rollups")
+        .expect("rollups are configured");
+    assert_eq!(keyed(rollups), rolled_up_events(&storage, 1_050, 1_349));
+}
+
+#[test]
+fn test_only_finite_xsd_numeric_literals_are_numeric() {
+    for (input, expected) in [
+        ("35", Some(35.0)),
+        ("\"-2.5e1\"^^<http://www.w3.org/2001/XMLSchema#double>", Some(-25.0)),
+        ("\"7\"^^<http://www.w3.org/2001/XMLSchema#unsignedByte>", Some(7.0)),
+        ("\"35\"^^<http://www.w3.org/2001/XMLSchema#string>", None),
+        ("\"35\"^^<http://example.org/celsius>", None),
+        ("\"12\"@en", None),
+        ("\"12\"", None),
+        ("NaN", None),
+        ("inf", None),
+        ("\"INF\"^^<http://www.w3.org/2001/XMLSchema#double>", None),
+        ("\"n/a\"^^<http://www.w3.org/2001/XMLSchema#decimal>", None),
+        ("http://example.org/35", None),
+    ] {
+        assert_eq!(numeric_value(&RdfTerm::parse(input)), expected, "{}", input);
+    }
+}
@@ -0,0 +1,233 @@
+mod common;
+
+use janus::execution::historical_executor::HistoricalExecutor;
+use janus::execution::pushdown::{decompose_query, pushdown_patterns};
+use janus::parsing::janusql_parser::{SourceKind, WindowDefinition, WindowType};
+use janus::querying::oxigraph_adapter::OxigraphAdapter;
+use janus::storage::indexing::secondary::TriplePattern;
+use janus::storage::segmented_storage::StreamingSegmentedStorage;
+use janus::storage::util::StreamingConfig;
+use std::fs;
+use std::io::ErrorKind;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+const TEMPERATURE: &str = "http://example.org/temperature";
+const HUMIDITY: &str = "http://example.org/humidity";
+
+fn secondary_index_test_config(path: &std::path::Path) -> StreamingConfig {
+    StreamingConfig { sparse_interval: 10, entries_per_index_block: 4, ..common::test_config(path) }
+}
+
+// Write 50 temperature readings followed by 50 humidity readings, so each predicate
+// fills its own five data blocks.
+fn write_readings(storage: &StreamingSegmentedStorage) {
+    for i in 0..100u64 {
+        storage
+            .write_rdf(
+                1_000 + i,
+                &format!("http://example.org/sensor{}", i % 5),
+                if i < 50 { TEMPERATURE } else { HUMIDITY },
+                &format!("{}", i),
+                "http://example.org/graph1",
+            )
+            .expect("failed to write event");
+    }
+}
+
+fn flip_byte(path: &str, offset: usize) {
+    let mut bytes = fs::read(path).expect("failed to read segment file");
+    bytes[offset] ^= 0xFF;
+    fs::write(path, bytes).expect("failed to write segment file");
+}
+
+fn filtered_timestamps(
+    storage: &StreamingSegmentedStorage,
+    start: u64,
+    end: u64,
+    patterns: &[TriplePattern],
+) -> Vec<u64> {
+    storage
+        .query_filtered(start, end, patterns)
+        .expect("failed to run filtered query")
+        .iter()
+        .map(|event| event.timestamp)
+        .collect()
+}
+
+#[test]
+fn test_filtered_query_matches_patterns_in_buffer_and_segments() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = StreamingSegmentedStorage::new(secondary_index_test_config(temp_dir.path()))
+        .expect("failed to create storage");
+    write_readings(&storage);
+
+    let by_predicate = [TriplePattern::new(None, Some(HUMIDITY))];
+    let by_subject_and_predicate =
+        [TriplePattern::new(Some("http://example.org/sensor2"), Some(TEMPERATURE))];
+    let either = [
+        TriplePattern::new(Some("http://example.org/sensor0"), None),
+        TriplePattern::new(None, Some(HUMIDITY)),
+    ];
+
+    let buffered: Vec<Vec<u64>> = [&by_predicate[..], &by_subject_and_predicate, &either]
+        .iter()
+        .map(|patterns| filtered_timestamps(&storage, 1_020, 1_079, patterns))
+        .collect();
+
+    storage.flush().expect("failed to flush storage");
+    assert!(storage.segment_metadata()[0].secondary_index.is_some());
+
+    let flushed: Vec<Vec<u64>> = [&by_predicate[..], &by_subject_and_predicate, &either]
+        .iter()
+        .map(|patterns| filtered_timestamps(&storage, 1_020, 1_079, patterns))
+        .collect();
+
+    assert_eq!(buffered, flushed);
+    assert_eq!(flushed[0], (1_050..=1_079).collect::<Vec<u64>>());
+    assert_eq!(flushed[1], vec![1_022, 1_027, 1_032, 1_037, 1_042, 1_047]);
+    let mut expected_either: Vec<u64> = (1_020..1_050).filter(|ts| ts % 5 == 0).collect();
+    expected_either.extend(1_050..=1_079);
+    assert_eq!(flushed[2], expected_either);
+
+    let unknown = [TriplePattern::new(None, Some("http://example.org/pressure"))];
+    assert!(filtered_timestamps(&storage, 0, u64::MAX, &unknown).is_empty());
+}
+
+#[test]
+fn test_filtered_query_skips_blocks_without_the_predicate() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = StreamingSegmentedStorage::new(secondary_index_test_config(temp_dir.path()))
+        .expect("failed to create storage");
+    write_readings(&storage);
+    storage.flush().expect("failed to flush storage");
+
+    // 16-byte header, then blocks of 10 records of 24 bytes: corrupt a humidity block.
+    let data_path = storage.segment_metadata()[0].data_path.clone();
+    flip_byte(&data_path, 16 + 7 * 240 + 5);
+
+    let err = storage.query(0, u64::MAX).expect_err("corrupt block must not be decoded");
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+    let temperature = [TriplePattern::new(None, Some(TEMPERATURE))];
+    assert_eq!(
+        filtered_timestamps(&storage, 0, u64::MAX, &temperature),
+        (1_000..1_050).collect::<Vec<u64>>()
+    );
+}
+
+#[test]
+fn test_filtered_query_without_secondary_index_scans_segments() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let config = StreamingConfig {
+        secondary_indexes_enabled: false,
+        ..secondary_index_test_config(temp_dir.path())
+    };
+    let storage = StreamingSegmentedStorage::new(config).expect("failed to create storage");
+    write_readings(&storage);
+    storage.flush().expect("failed to flush storage");
+
+    assert!(storage.segment_metadata()[0].secondary_index.is_none());
+    let humidity = [TriplePattern::new(None, Some(HUMIDITY))];
+    assert_eq!(
+        filtered_timestamps(&storage, 1_040, 1_060, &humidity),
+        (1_050..=1_060).collect::<Vec<u64>>()
+    );
+}
+
+#[test]
+fn test_pushdown_patterns_from_sparql() {
+    let patterns = pushdown_patterns(
+        "PREFIX ex: <http://example.org/>
+         SELECT ?sensor ?value WHERE {
+           GRAPH ?g {
+             ?sensor ex:temperature ?value .
+             OPTIONAL { ex:sensor1 ?p ?o }
+           }
+         }",
+    )
+    .expect("query should be restricted");
+    assert_eq!(
+        patterns,
+        vec![
+            TriplePattern::new(None, Some(TEMPERATURE)),
+            TriplePattern::new(Some("http://example.org/sensor1"), None),
+        ]
+    );
+
+    assert!(pushdown_patterns("SELECT * WHERE { ?s ?p ?o }").is_none());
+    assert!(pushdown_patterns(
+        "SELECT * WHERE { ?s <http://example.org/a> ?o FILTER NOT EXISTS { ?o ?p ?x } }"
+    )
+    .is_none());
+    assert!(pushdown_patterns("SELECT * WHERE { ?s <http://example.org/a>+ ?o }").is_none());
+}
+
+#[test]
+fn test_decompose_query_from_sparql() {
+    let decomposed = decompose_query(
+        "PREFIX ex: <http://example.org/>
+         SELECT ?sensor ?value WHERE {
+           GRAPH ?g { ?sensor ex:temperature ?value FILTER(?value > 20) }
+         }",
+    )
+    .expect("query should be decomposed");
+    assert_eq!(decomposed.variables, vec!["sensor", "value"]);
+    assert!(!decomposed.distinct);
+    // The graph is selected too, so that each solution stands for a single quad.
+    let selected = decompose_query(&decomposed.query).expect("query should be decomposed");
+    assert_eq!(selected.variables, vec!["sensor", "value", "g"]);
+
+    let distinct = decompose_query("SELECT DISTINCT ?s WHERE { ?s <http://example.org/a> ?o }")
+        .expect("query should be decomposed");
+    assert!(distinct.distinct);
+    assert_eq!(distinct.variables, vec!["s"]);
+
+    for query in [
+        "SELECT * WHERE { ?s <http://example.org/a> ?o . ?o <http://example.org/b> ?x }",
+        "SELECT (COUNT(?o) AS ?count) WHERE { ?s <http://example.org/a> ?o }",
+        "SELECT ?s WHERE { ?s <http://example.org/a> ?o } ORDER BY ?o",
+        "SELECT ?s WHERE { ?s <http://example.org/a> ?o } LIMIT 10",
+        "SELECT ?s WHERE { ?s <http://example.org/a> ?o FILTER EXISTS { ?o ?p ?x } }",
+        "SELECT ?o WHERE { [] <http://example.org/a> ?o }",
+        "ASK { ?s <http://example.org/a> ?o }",
+    ] {
+        assert!(decompose_query(query).is_none(), "{}", query);
+    }
+}
+
+#[test]
+fn test_executor_pushes_down_constant_predicates() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = Arc::new(
+        StreamingSegmentedStorage::new(secondary_index_test_config(temp_dir.path()))
+            .expect("failed to create storage"),
+    );
+    write_readings(&storage);
+    storage.flush().expect("failed to flush storage");
+
+    // Without pushdown the window would have to decode the corrupt humidity block.
+    let data_path = storage.segment_metadata()[0].data_path.clone();
+    flip_byte(&data_path, 16 + 7 * 240 + 5);
+
+    let window = WindowDefinition {
+        window_name: "http://example.org/window/temperature".to_string(),
+        source_kind: SourceKind::Log,
+        stream_name: "http://example.org/stream".to_string(),
+        width: 0,
+        slide: 0,
+        offset: None,
+        start: Some(1_000),
+        end: Some(1_099),
+        window_type: WindowType::HistoricalFixed,
+    };
+    let executor = HistoricalExecutor::new(storage.clone(), OxigraphAdapter::new());
+    let bindings = executor
+        .execute_fixed_window(
+            &window,
+            "SELECT ?sensor ?value WHERE { GRAPH ?g { ?sensor <http://example.org/temperature> ?value } }",
+        )
+        .expect("failed to execute window");
+
+    assert_eq!(bindings.len(), 50);
+}
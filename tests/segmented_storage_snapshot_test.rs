@@ -0,0 +1,100 @@
+mod common;
+
+use janus::storage::segmented_storage::StreamingSegmentedStorage;
+use janus::storage::snapshot::SnapshotManifest;
+use janus::storage::util::StreamingConfig;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tempfile::TempDir;
+
+fn snapshot_test_config(path: &Path) -> StreamingConfig {
+    StreamingConfig {
+        max_batch_events: 50,
+        entries_per_index_block: 8,
+        compaction_min_segments: 2,
+        ..common::test_config(path)
+    }
+}
+
+fn write_reading(storage: &StreamingSegmentedStorage, i: u64) {
+    common::write_reading(storage, 1_000 + i, i, format!("\"reading-{}\"", i));
+}
+
+#[test]
+fn test_snapshot_during_writes_reopens_with_a_consistent_dictionary() {
+    let source_dir = TempDir::new().expect("failed to create temp dir");
+    let backup_dir = TempDir::new().expect("failed to create temp dir");
+    let destination = backup_dir.path().join("snapshot");
+
+    let mut storage = StreamingSegmentedStorage::new(snapshot_test_config(source_dir.path()))
+        .expect("failed to create storage");
+    storage.start_background_flushing();
+    let storage = Arc::new(storage);
+    for i in 0..200 {
+        write_reading(&storage, i);
+    }
+
+    // Every event names a new subject and value, so a dictionary lagging behind the
+    // segments would leave events undecodable.
+    let done = Arc::new(AtomicBool::new(false));
+    let writer = {
+        let storage = Arc::clone(&storage);
+        let done = Arc::clone(&done);
+        std::thread::spawn(move || {
+            let mut i = 200;
+            while !done.load(Ordering::SeqCst) {
+                write_reading(&storage, i);
+                i += 1;
+            }
+        })
+    };
+    let manifest = storage.snapshot(&destination).expect("failed to snapshot");
+    done.store(true, Ordering::SeqCst);
+    writer.join().unwrap();
+
+    assert!(manifest.record_count >= 200);
+    assert_eq!(manifest.files_linked + manifest.files_copied, manifest.segments.len() * 2);
+    assert_eq!(SnapshotManifest::read(&destination).expect("failed to read manifest"), manifest);
+
+    // Compacting the source afterwards deletes the snapshotted files there.
+    storage.compact().expect("failed to compact");
+
+    let copy = StreamingSegmentedStorage::new(snapshot_test_config(&destination))
+        .expect("failed to open snapshot");
+    assert!(copy.verify(None).expect("failed to verify").is_clean());
+    let events = copy.query(0, u64::MAX).expect("failed to query snapshot");
+    assert_eq!(events.len() as u64, manifest.record_count);
+    let dictionary = copy.get_dictionary().read().unwrap();
+    for event in &events {
+        let decoded = event.decode(&dictionary);
+        let i = decoded.timestamp - 1_000;
+        assert_eq!(decoded.object.to_string(), format!("\"reading-{}\"", i));
+    }
+}
+
+#[test]
+fn test_snapshot_keeps_pending_erasures_and_needs_an_empty_destination() {
+    let source_dir = TempDir::new().expect("failed to create temp dir");
+    let backup_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = StreamingSegmentedStorage::new(snapshot_test_config(source_dir.path()))
+        .expect("failed to create storage");
+    for i in 0..20 {
+        write_reading(&storage, i);
+    }
+    storage
+        .delete_by_subject("http://example.org/sensor3")
+        .expect("failed to delete subject");
+
+    let refused = storage.snapshot(source_dir.path()).unwrap_err();
+    assert_eq!(refused.kind(), std::io::ErrorKind::AlreadyExists);
+
+    let manifest = storage.snapshot(backup_dir.path()).expect("failed to snapshot");
+    assert_eq!(manifest.pending_tombstones, 1);
+    assert_eq!(manifest.record_count, 20);
+
+    let copy = StreamingSegmentedStorage::new(snapshot_test_config(backup_dir.path()))
+        .expect("failed to open snapshot");
+    assert_eq!(copy.query(0, u64::MAX).expect("failed to query snapshot").len(), 19);
+    assert_eq!(copy.erasure_progress().pending_tombstones, 1);
+}
@@ -0,0 +1,168 @@
+mod common;
+
+use janus::parsing::janusql_parser::{SourceKind, WindowDefinition, WindowType};
+use janus::storage::segmented_storage::StreamingSegmentedStorage;
+use janus::storage::util::StreamingConfig;
+use janus::stream::operators::historical_fixed_window::HistoricalFixedWindowOperator;
+use std::rc::Rc;
+use tempfile::TempDir;
+
+const TEMPERATURE_STREAM: &str = "http://example.org/stream/temperature";
+const HUMIDITY_STREAM: &str = "http://example.org/stream/humidity";
+
+fn partition_test_config(path: &std::path::Path) -> StreamingConfig {
+    StreamingConfig {
+        entries_per_index_block: 4,
+        compaction_enabled: false,
+        ..common::test_config(path)
+    }
+}
+
+fn write_readings(storage: &StreamingSegmentedStorage) {
+    for i in 0..20u64 {
+        storage
+            .write_rdf_to_stream(
+                TEMPERATURE_STREAM,
+                1_000 + i * 10,
+                &format!("http://example.org/thermometer{}", i % 3),
+                "http://example.org/temperature",
+                &format!("{}", 20 + i),
+                "http://example.org/graph1",
+            )
+            .expect("failed to write temperature");
+        storage
+            .write_rdf_to_stream(
+                HUMIDITY_STREAM,
+                1_005 + i * 10,
+                &format!("http://example.org/hygrometer{}", i % 3),
+                "http://example.org/humidity",
+                &format!("{}", 50 + i),
+                "http://example.org/graph1",
+            )
+            .expect("failed to write humidity");
+    }
+}
+
+fn predicates(storage: &StreamingSegmentedStorage, stream: &str) -> Vec<String> {
+    storage
+        .query_stream(stream, 0, u64::MAX)
+        .expect("failed to query stream")
+        .iter()
+        .map(|event| {
+            storage
+                .get_dictionary()
+                .read()
+                .unwrap()
+                .decode(event.predicate)
+                .unwrap()
+                .to_string()
+        })
+        .collect()
+}
+
+#[test]
+fn test_stream_queries_only_return_their_own_events() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = StreamingSegmentedStorage::new(partition_test_config(temp_dir.path()))
+        .expect("failed to create storage");
+    write_readings(&storage);
+
+    // Buffered events are filtered by stream before anything is flushed.
+    let temperature = predicates(&storage, TEMPERATURE_STREAM);
+    assert_eq!(temperature.len(), 20);
+    assert!(temperature.iter().all(|p| p == "http://example.org/temperature"));
+
+    storage.flush().expect("failed to flush storage");
+
+    let segments = storage.segment_metadata();
+    assert_eq!(segments.len(), 2, "expected one segment per stream");
+    assert!(segments.iter().any(|s| s.stream.as_deref() == Some(TEMPERATURE_STREAM)));
+    assert!(segments.iter().any(|s| s.stream.as_deref() == Some(HUMIDITY_STREAM)));
+
+    let humidity = predicates(&storage, HUMIDITY_STREAM);
+    assert_eq!(humidity.len(), 20);
+    assert!(humidity.iter().all(|p| p == "http://example.org/humidity"));
+
+    assert_eq!(storage.query(0, u64::MAX).expect("failed to query").len(), 40);
+    assert!(predicates(&storage, "http://example.org/stream/unknown").is_empty());
+}
+
+#[test]
+fn test_events_without_stream_belong_to_every_log() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = StreamingSegmentedStorage::new(partition_test_config(temp_dir.path()))
+        .expect("failed to create storage");
+    write_readings(&storage);
+    storage
+        .write_rdf(
+            1_050,
+            "http://example.org/building",
+            "http://example.org/located",
+            "http://example.org/ghent",
+            "http://example.org/graph1",
+        )
+        .expect("failed to write untagged event");
+
+    for stream in [TEMPERATURE_STREAM, HUMIDITY_STREAM, "http://example.org/stream/unknown"] {
+        assert!(predicates(&storage, stream).iter().any(|p| p == "http://example.org/located"));
+    }
+
+    storage.flush().expect("failed to flush storage");
+    assert_eq!(storage.segment_metadata().len(), 3);
+    assert_eq!(predicates(&storage, TEMPERATURE_STREAM).len(), 21);
+    assert_eq!(predicates(&storage, HUMIDITY_STREAM).len(), 21);
+}
+
+#[test]
+fn test_stream_partitions_survive_wal_replay_and_reopen() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    {
+        let storage = StreamingSegmentedStorage::new(partition_test_config(temp_dir.path()))
+            .expect("failed to create storage");
+        write_readings(&storage);
+        // Dropped without a flush: the events only survive in the write-ahead log.
+    }
+
+    {
+        let storage = StreamingSegmentedStorage::new(partition_test_config(temp_dir.path()))
+            .expect("failed to reopen storage");
+        assert_eq!(predicates(&storage, TEMPERATURE_STREAM).len(), 20);
+        assert_eq!(predicates(&storage, HUMIDITY_STREAM).len(), 20);
+        storage.flush().expect("failed to flush storage");
+    }
+
+    let storage = StreamingSegmentedStorage::new(partition_test_config(temp_dir.path()))
+        .expect("failed to reopen storage");
+    let humidity = predicates(&storage, HUMIDITY_STREAM);
+    assert_eq!(humidity.len(), 20);
+    assert!(humidity.iter().all(|p| p == "http://example.org/humidity"));
+}
+
+#[test]
+fn test_log_window_reads_only_its_stream() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = Rc::new(
+        StreamingSegmentedStorage::new(partition_test_config(temp_dir.path()))
+            .expect("failed to create storage"),
+    );
+    write_readings(&storage);
+    storage.flush().expect("failed to flush storage");
+
+    let window_def = WindowDefinition {
+        window_name: "http://example.org/window/humidity".to_string(),
+        source_kind: SourceKind::Log,
+        stream_name: HUMIDITY_STREAM.to_string(),
+        width: 0,
+        slide: 0,
+        offset: None,
+        start: Some(1_000),
+        end: Some(1_100),
+        window_type: WindowType::HistoricalFixed,
+    };
+
+    let mut operator = HistoricalFixedWindowOperator::new(storage.clone(), window_def);
+    let events = operator.next().expect("fixed window should yield once");
+
+    let timestamps: Vec<u64> = events.iter().map(|e| e.timestamp).collect();
+    assert_eq!(timestamps, (0..10).map(|i| 1_005 + i * 10).collect::<Vec<u64>>());
+}
@@ -0,0 +1,190 @@
+mod common;
+
+use janus::core::Event;
+use janus::storage::codec::SegmentCodec;
+use janus::storage::segmented_storage::StreamingSegmentedStorage;
+use janus::storage::util::{EnhancedSegmentMetadata, StorageTier, StreamingConfig};
+use std::fs;
+use std::path::Path;
+use tempfile::TempDir;
+
+const DAY_MS: u64 = 24 * 60 * 60 * 1_000;
+
+fn tiering_test_config(path: &Path) -> StreamingConfig {
+    StreamingConfig {
+        max_batch_events: 100_000,
+        sparse_interval: 64,
+        entries_per_index_block: 4,
+        compaction_min_segments: 2,
+        archive_after_seconds: Some(2 * 24 * 60 * 60),
+        ..common::test_config(path)
+    }
+}
+
+// One segment per day of readings from a handful of sensors, every ten seconds.
+fn write_days(storage: &StreamingSegmentedStorage, days: u64) {
+    for day in 0..days {
+        for i in 0..1_000u64 {
+            common::write_reading(storage, day * DAY_MS + i * 10_000, i % 7, 15 + (i * 3) % 20);
+        }
+        storage.flush().expect("failed to flush");
+    }
+}
+
+fn records(events: &[Event]) -> Vec<(u64, u32, u32, u32, u32)> {
+    events
+        .iter()
+        .map(|e| (e.timestamp, e.subject, e.predicate, e.object, e.graph))
+        .collect()
+}
+
+fn tiers(storage: &StreamingSegmentedStorage) -> Vec<StorageTier> {
+    storage.segment_metadata().iter().map(|segment| segment.tier).collect()
+}
+
+fn data_size(segment: &EnhancedSegmentMetadata) -> u64 {
+    fs::metadata(&segment.data_path).expect("missing segment file").len()
+}
+
+#[test]
+fn test_old_segments_move_to_the_archive_and_read_back_identically() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let config = StreamingConfig {
+        segment_codec: SegmentCodec::Columnar,
+        ..tiering_test_config(temp_dir.path())
+    };
+    let storage = StreamingSegmentedStorage::new(config.clone()).expect("failed to create storage");
+    write_days(&storage, 6);
+    let before = storage.query(0, u64::MAX).expect("failed to query");
+    let columnar_size = data_size(&storage.segment_metadata()[0]);
+
+    // Days 0 to 2 ended more than two days before the last one did.
+    assert_eq!(storage.archive().expect("failed to archive"), 3);
+    assert_eq!(storage.archive().expect("failed to archive"), 0);
+    assert_eq!(
+        tiers(&storage),
+        vec![
+            StorageTier::Archive,
+            StorageTier::Archive,
+            StorageTier::Archive,
+            StorageTier::Hot,
+            StorageTier::Hot,
+            StorageTier::Hot
+        ]
+    );
+    let archived = &storage.segment_metadata()[0];
+    assert_eq!(archived.codec, SegmentCodec::CompressedColumnar);
+    assert!(Path::new(&archived.data_path).starts_with(temp_dir.path().join("archive")));
+    assert!(
+        data_size(archived) * 2 < columnar_size,
+        "archived segment of {} bytes should be far smaller than {} bytes",
+        data_size(archived),
+        columnar_size
+    );
+    assert_eq!(fs::read_dir(temp_dir.path().join("archive")).unwrap().count(), 3 * 2);
+
+    assert_eq!(records(&storage.query(0, u64::MAX).unwrap()), records(&before));
+    let range = storage.query(DAY_MS + 500_000, 3 * DAY_MS + 500_000).unwrap();
+    assert_eq!(range.len(), 2 * 1_000 + 1);
+    assert!(storage.verify(None).expect("failed to verify").is_clean());
+
+    // The tiers are found again on reopen, and archived segments only merge with each other.
+    drop(storage);
+    let storage = StreamingSegmentedStorage::new(config).expect("failed to reopen storage");
+    assert_eq!(tiers(&storage)[..3], [StorageTier::Archive; 3]);
+    assert_eq!(storage.compact().expect("failed to compact"), 4);
+    assert_eq!(tiers(&storage), vec![StorageTier::Archive, StorageTier::Hot]);
+    assert_eq!(storage.segment_metadata()[0].codec, SegmentCodec::CompressedColumnar);
+    assert_eq!(records(&storage.query(0, u64::MAX).unwrap()), records(&before));
+}
+
+#[test]
+fn test_erasure_and_purges_cover_archived_segments() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = StreamingSegmentedStorage::new(tiering_test_config(temp_dir.path()))
+        .expect("failed to create storage");
+    write_days(&storage, 5);
+
+    // A segment erased from before it is archived does not bring the events back.
+    storage
+        .delete_by_subject("http://example.org/sensor3")
+        .expect("failed to delete subject");
+    assert_eq!(storage.archive().expect("failed to archive"), 2);
+    let remaining = storage.query(0, u64::MAX).unwrap().len();
+    assert_eq!(remaining, 5 * (1_000 - 143));
+
+    storage
+        .delete_by_subject("http://example.org/sensor4")
+        .expect("failed to delete subject");
+    storage.purge_erased().expect("failed to purge");
+    assert_eq!(tiers(&storage)[..2], [StorageTier::Archive; 2]);
+    assert_eq!(storage.query(0, u64::MAX).unwrap().len(), remaining - 5 * 143);
+    assert!(!storage
+        .get_dictionary()
+        .read()
+        .unwrap()
+        .string_to_id
+        .contains_key("http://example.org/sensor4"));
+}
+
+#[test]
+fn test_archive_directory_is_followed_by_readers_and_snapshots() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let archive_dir = TempDir::new().expect("failed to create temp dir");
+    let config = StreamingConfig {
+        archive_path: Some(archive_dir.path().to_string_lossy().into_owned()),
+        ..tiering_test_config(temp_dir.path())
+    };
+    let writer = StreamingSegmentedStorage::new(config.clone()).expect("failed to create storage");
+    write_days(&writer, 4);
+    let reader =
+        StreamingSegmentedStorage::open_read_only(config).expect("failed to open read-only");
+
+    assert_eq!(writer.archive().expect("failed to archive"), 1);
+    assert!(Path::new(&writer.segment_metadata()[0].data_path).starts_with(archive_dir.path()));
+    assert!(reader.refresh().expect("failed to refresh"));
+    assert_eq!(
+        tiers(&reader),
+        vec![StorageTier::Archive, StorageTier::Hot, StorageTier::Hot, StorageTier::Hot]
+    );
+    assert_eq!(reader.query(0, u64::MAX).unwrap().len(), 4_000);
+
+    // A snapshot keeps archived segments in its own default archive directory.
+    let snapshot_dir = temp_dir.path().join("snapshot");
+    let manifest = writer.snapshot(&snapshot_dir).expect("failed to snapshot");
+    assert_eq!(manifest.segments[0].tier, StorageTier::Archive);
+    assert!(manifest.segments[0].data_file.starts_with("archive"));
+    drop(reader);
+    let restored = StreamingSegmentedStorage::new(tiering_test_config(&snapshot_dir))
+        .expect("failed to open snapshot");
+    assert_eq!(tiers(&restored)[..2], [StorageTier::Archive, StorageTier::Hot]);
+    assert_eq!(
+        records(&restored.query(0, u64::MAX).unwrap()),
+        records(&writer.query(0, u64::MAX).unwrap())
+    );
+}
+
+#[test]
+fn test_corrupt_compressed_blocks_are_rejected() {
+    let events: Vec<Event> = (0..500u32)
+        .map(|i| Event {
+            timestamp: 1_000 + u64::from(i) * 10,
+            subject: i % 7,
+            predicate: 1,
+            object: 100 + i % 20,
+            graph: 2,
+        })
+        .collect();
+    let codec = SegmentCodec::CompressedColumnar;
+    let block = codec.encode_block(&events);
+    assert!(block.len() * 2 < SegmentCodec::Columnar.encode_block(&events).len());
+    assert_eq!(records(&codec.decode_block(&block).unwrap()), records(&events));
+    assert_eq!(SegmentCodec::from_id(codec.id()).unwrap(), codec);
+
+    for length in [0, 1, block.len() / 2, block.len() - 1] {
+        assert!(codec.decode_block(&block[..length]).is_err(), "truncated to {}", length);
+    }
+    let mut trailing = block.clone();
+    trailing.push(0);
+    assert!(codec.decode_block(&trailing).is_err());
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use janus::storage::segment_format::SegmentRepair;
+use janus::storage::segmented_storage::StreamingSegmentedStorage;
+use janus::storage::util::StreamingConfig;
+use std::fs;
+use std::io::ErrorKind;
+use tempfile::TempDir;
+
+fn verify_test_config(path: &std::path::Path) -> StreamingConfig {
+    StreamingConfig { sparse_interval: 10, entries_per_index_block: 4, ..common::test_config(path) }
+}
+
+// Write 100 events into a single flushed segment and return the storage with its data path.
+fn storage_with_one_segment(path: &std::path::Path) -> (StreamingSegmentedStorage, String) {
+    let storage =
+        StreamingSegmentedStorage::new(verify_test_config(path)).expect("failed to create storage");
+    for i in 0..100u64 {
+        storage
+            .write_rdf(
+                1_000 + i,
+                &format!("http://example.org/sensor{}", i % 5),
+                "http://example.org/temperature",
+                &format!("{}", i),
+                "http://example.org/graph1",
+            )
+            .expect("failed to write event");
+    }
+    storage.flush().expect("failed to flush storage");
+
+    let data_path = storage.segment_metadata()[0].data_path.clone();
+    (storage, data_path)
+}
+
+fn flip_byte(path: &str, offset: usize) {
+    let mut bytes = fs::read(path).expect("failed to read segment file");
+    bytes[offset] ^= 0xFF;
+    fs::write(path, bytes).expect("failed to write segment file");
+}
+
+#[test]
+fn test_clean_segments_pass_verification() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let (storage, _) = storage_with_one_segment(temp_dir.path());
+
+    let report = storage.verify(None).expect("failed to verify storage");
+    assert_eq!(report.segments_checked, 1);
+    assert!(report.is_clean());
+}
+
+#[test]
+fn test_corrupt_block_is_reported_and_truncated() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let (storage, data_path) = storage_with_one_segment(temp_dir.path());
+
+    // 16-byte header, then blocks of 10 records of 24 bytes: corrupt the fourth block.
+    flip_byte(&data_path, 16 + 3 * 240 + 5);
+
+    let err = storage.query(0, u64::MAX).expect_err("corrupt block must not be decoded");
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+
+    let report = storage.verify(None).expect("failed to verify storage");
+    assert_eq!(report.corrupt_segments.len(), 1);
+    assert_eq!(report.corrupt_segments[0].valid_records, 30);
+    assert_eq!(report.corrupt_segments[0].repair, None);
+
+    let report = storage.verify(Some(SegmentRepair::Truncate)).expect("failed to repair storage");
+    assert_eq!(report.corrupt_segments[0].repair, Some(SegmentRepair::Truncate));
+    assert!(!std::path::Path::new(&data_path).exists());
+
+    let events = storage.query(0, u64::MAX).expect("failed to query repaired storage");
+    assert_eq!(events.len(), 30);
+    assert_eq!(events.last().unwrap().timestamp, 1_029);
+    assert!(storage.verify(None).expect("failed to verify storage").is_clean());
+
+    drop(storage);
+    let reopened = StreamingSegmentedStorage::new(verify_test_config(temp_dir.path()))
+        .expect("failed to reopen storage");
+    assert_eq!(reopened.query(0, u64::MAX).expect("failed to query").len(), 30);
+}
+
+#[test]
+fn test_corrupt_footer_is_quarantined() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let index_path = {
+        let (storage, _) = storage_with_one_segment(temp_dir.path());
+        storage.segment_metadata()[0].index_path.clone()
+    };
+
+    // The footer JSON sits right before the 16-byte trailer.
+    let index_len = fs::metadata(&index_path).unwrap().len() as usize;
+    flip_byte(&index_path, index_len - 20);
+
+    let storage = StreamingSegmentedStorage::new(verify_test_config(temp_dir.path()))
+        .expect("a corrupt segment must not prevent the storage from opening");
+    assert!(storage.query(0, u64::MAX).is_err());
+
+    let report = storage
+        .verify(Some(SegmentRepair::Quarantine))
+        .expect("failed to repair storage");
+    assert_eq!(report.corrupt_segments.len(), 1);
+    assert_eq!(report.corrupt_segments[0].repair, Some(SegmentRepair::Quarantine));
+
+    let quarantined: Vec<_> = fs::read_dir(temp_dir.path().join("quarantine"))
+        .expect("quarantine directory should exist")
+        .collect();
+    assert_eq!(quarantined.len(), 2);
+    assert!(storage.query(0, u64::MAX).expect("failed to query").is_empty());
+}
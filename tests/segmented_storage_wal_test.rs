@@ -0,0 +1,85 @@
+mod common;
+
+use janus::storage::segmented_storage::StreamingSegmentedStorage;
+use janus::storage::util::StreamingConfig;
+use std::fs;
+use tempfile::TempDir;
+
+fn wal_test_config(path: &std::path::Path) -> StreamingConfig {
+    StreamingConfig {
+        max_batch_events: 1_000,
+        max_batch_bytes: 1024 * 1024,
+        sparse_interval: 10,
+        entries_per_index_block: 100,
+        ..common::test_config(path)
+    }
+}
+
+fn wal_files(path: &std::path::Path) -> Vec<String> {
+    fs::read_dir(path)
+        .expect("failed to read storage directory")
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|name| name.starts_with("wal-"))
+        .collect()
+}
+
+#[test]
+fn test_unflushed_events_are_replayed_after_crash() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+
+    {
+        let storage = StreamingSegmentedStorage::new(wal_test_config(temp_dir.path()))
+            .expect("failed to create storage");
+        for i in 0..5u64 {
+            storage
+                .write_rdf(
+                    1_000 + i,
+                    &format!("http://example.org/sensor{}", i),
+                    "http://example.org/temperature",
+                    &format!("{}", 20 + i),
+                    "http://example.org/graph1",
+                )
+                .expect("failed to write event");
+        }
+        // Dropped without flush or shutdown, as if the process had crashed.
+    }
+
+    let storage = StreamingSegmentedStorage::new(wal_test_config(temp_dir.path()))
+        .expect("failed to reopen storage");
+    let events = storage.query_rdf(0, 10_000).expect("failed to query storage");
+
+    assert_eq!(events.len(), 5);
+    assert_eq!(events[0].subject.value(), "http://example.org/sensor0");
+    assert_eq!(events[4].object.value(), "24");
+    assert_eq!(events[4].graph.value(), "http://example.org/graph1");
+}
+
+#[test]
+fn test_wal_is_released_after_flush() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+
+    {
+        let storage = StreamingSegmentedStorage::new(wal_test_config(temp_dir.path()))
+            .expect("failed to create storage");
+        storage
+            .write_rdf(
+                1_000,
+                "http://example.org/sensor1",
+                "http://example.org/temperature",
+                "21",
+                "http://example.org/graph1",
+            )
+            .expect("failed to write event");
+        storage.flush().expect("failed to flush storage");
+
+        for name in wal_files(temp_dir.path()) {
+            let size = fs::metadata(temp_dir.path().join(&name)).unwrap().len();
+            assert_eq!(size, 0, "flushed events should not remain in {}", name);
+        }
+    }
+
+    let storage = StreamingSegmentedStorage::new(wal_test_config(temp_dir.path()))
+        .expect("failed to reopen storage");
+    let events = storage.query(0, 10_000).expect("failed to query storage");
+    assert_eq!(events.len(), 1, "flushed events must not be replayed a second time");
+}
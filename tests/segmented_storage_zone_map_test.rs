@@ -0,0 +1,201 @@
+mod common;
+
+use janus::execution::historical_executor::HistoricalExecutor;
+use janus::execution::pushdown::pushdown_patterns;
+use janus::parsing::janusql_parser::{SourceKind, WindowDefinition, WindowType};
+use janus::querying::oxigraph_adapter::OxigraphAdapter;
+use janus::storage::indexing::secondary::{TriplePattern, ValueRange};
+use janus::storage::segmented_storage::StreamingSegmentedStorage;
+use janus::storage::util::{EnhancedSegmentMetadata, StreamingConfig};
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+const TEMPERATURE: &str = "http://example.org/temperature";
+const HUMIDITY: &str = "http://example.org/humidity";
+
+fn zone_map_test_config(path: &Path) -> StreamingConfig {
+    StreamingConfig {
+        compaction_min_segments: 2,
+        mmap_cache_capacity: 0,
+        ..common::test_config(path)
+    }
+}
+
+// Four segments of ten temperature readings each, segment `n` holding the values
+// `10n..10n + 9`, together with a constant humidity.
+fn write_segments(storage: &StreamingSegmentedStorage) {
+    for segment in 0..4u64 {
+        for i in 0..10u64 {
+            let timestamp = 1_000 + segment * 100 + i;
+            for (predicate, value) in [(TEMPERATURE, segment * 10 + i), (HUMIDITY, 50)] {
+                storage
+                    .write_rdf(
+                        timestamp,
+                        &format!("http://example.org/sensor{}", i % 2),
+                        predicate,
+                        &value.to_string(),
+                        "http://example.org/graph1",
+                    )
+                    .expect("failed to write event");
+            }
+        }
+        storage.flush().expect("failed to flush");
+    }
+}
+
+fn sorted_segments(storage: &StreamingSegmentedStorage) -> Vec<EnhancedSegmentMetadata> {
+    let mut segments = storage.segment_metadata();
+    segments.sort_by_key(|segment| segment.segment_id());
+    segments
+}
+
+fn above(value: f64) -> [TriplePattern; 1] {
+    [TriplePattern::new(None, Some(TEMPERATURE))
+        .with_object_range(ValueRange::new(value, f64::INFINITY))]
+}
+
+#[test]
+fn test_value_ranges_skip_segments_outside_their_zone_maps() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = StreamingSegmentedStorage::new(zone_map_test_config(temp_dir.path()))
+        .expect("failed to create storage");
+    write_segments(&storage);
+
+    let temperature_id = storage.get_dictionary().read().unwrap().string_to_id[TEMPERATURE];
+    let segments = sorted_segments(&storage);
+    let stats =
+        segments[1].zone_map.as_ref().expect("segment has no zone map").predicates[&temperature_id];
+    assert_eq!((stats.count, stats.numeric_count, stats.min, stats.max), (10, 10, 10.0, 19.0));
+
+    // The zone maps are read back from the index files, and the segments whose readings
+    // all lie below the range are never opened.
+    drop(storage);
+    let storage = StreamingSegmentedStorage::new(zone_map_test_config(temp_dir.path()))
+        .expect("failed to reopen storage");
+    let segments = sorted_segments(&storage);
+    assert!(segments.iter().all(|segment| segment.zone_map.is_some()));
+    for segment in &segments[..2] {
+        std::fs::remove_file(&segment.data_path).expect("failed to remove segment");
+    }
+    // Events are not filtered on their values, only segments are skipped.
+    let events = storage.query_filtered(0, u64::MAX, &above(25.0)).expect("failed to query");
+    assert_eq!(events.len(), 20);
+    assert!(events.iter().all(|event| event.timestamp >= 1_200));
+    assert!(storage.query_filtered(0, u64::MAX, &above(40.0)).unwrap().is_empty());
+
+    // Without a range, or with one the removed segments overlap, they are read.
+    let unbounded = [TriplePattern::new(None, Some(TEMPERATURE))];
+    assert!(storage.query_filtered(0, u64::MAX, &unbounded).is_err());
+    assert!(storage.query_filtered(0, u64::MAX, &above(15.0)).is_err());
+}
+
+#[test]
+fn test_segments_with_non_numeric_values_are_not_skipped() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = StreamingSegmentedStorage::new(zone_map_test_config(temp_dir.path()))
+        .expect("failed to create storage");
+    write_segments(&storage);
+    storage
+        .write_rdf(1_050, "http://example.org/sensor0", TEMPERATURE, "n/a", "")
+        .expect("failed to write event");
+    storage.flush().expect("failed to flush");
+
+    let temperature_id = storage.get_dictionary().read().unwrap().string_to_id[TEMPERATURE];
+    let segments = sorted_segments(&storage);
+    let stats = segments[4].zone_map.as_ref().unwrap().predicates[&temperature_id];
+    assert_eq!((stats.count, stats.numeric_count), (1, 0));
+    assert_eq!(storage.query_filtered(0, u64::MAX, &above(40.0)).unwrap().len(), 1);
+
+    // A merged segment gets a zone map over every input.
+    storage.compact().expect("failed to compact");
+    let segments = storage.segment_metadata();
+    assert_eq!(segments.len(), 1);
+    let stats = segments[0].zone_map.as_ref().unwrap().predicates[&temperature_id];
+    assert_eq!((stats.count, stats.numeric_count, stats.min, stats.max), (41, 40, 0.0, 39.0));
+    assert!(storage.verify(None).expect("failed to verify").is_clean());
+}
+
+#[test]
+fn test_zone_maps_are_only_written_when_enabled() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let config =
+        StreamingConfig { zone_maps_enabled: false, ..zone_map_test_config(temp_dir.path()) };
+    let storage = StreamingSegmentedStorage::new(config).expect("failed to create storage");
+    write_segments(&storage);
+
+    assert!(storage.segment_metadata().iter().all(|segment| segment.zone_map.is_none()));
+    assert_eq!(storage.query_filtered(0, u64::MAX, &above(40.0)).unwrap().len(), 40);
+}
+
+#[test]
+fn test_pushdown_patterns_bound_filtered_objects() {
+    let patterns = pushdown_patterns(
+        "PREFIX ex: <http://example.org/>
+         SELECT ?sensor ?value WHERE {
+           GRAPH ?g {
+             ?sensor ex:temperature ?value .
+             ?sensor ex:humidity ?humidity .
+             FILTER(?value > 30 && 50.5 >= ?value && ?humidity = 40)
+           }
+         }",
+    )
+    .expect("query should be restricted");
+    assert_eq!(
+        patterns,
+        vec![
+            TriplePattern::new(None, Some(HUMIDITY)).with_object_range(ValueRange::new(40.0, 40.0)),
+            TriplePattern::new(None, Some(TEMPERATURE))
+                .with_object_range(ValueRange::new(30.0, 50.5)),
+        ]
+    );
+
+    // Disjunctions, optional patterns and subqueries are not bounded.
+    for query in [
+        "SELECT * WHERE { ?s <http://example.org/temperature> ?v FILTER(?v > 30 || ?v < 0) }",
+        "SELECT * WHERE { ?s <http://example.org/humidity> ?h
+           OPTIONAL { ?s <http://example.org/temperature> ?v } FILTER(?v > 30) }",
+        "SELECT * WHERE {
+           { SELECT ?s ?v WHERE { ?s <http://example.org/temperature> ?v } LIMIT 5 }
+           FILTER(?v > 30) }",
+        "SELECT * WHERE { ?s <http://example.org/temperature> ?v FILTER(?v > \"30\") }",
+    ] {
+        let patterns = pushdown_patterns(query).expect("query should be restricted");
+        assert!(patterns.iter().all(|pattern| pattern.object_range.is_none()), "{}", query);
+    }
+}
+
+#[test]
+fn test_executor_skips_segments_ruled_out_by_a_filter() {
+    let temp_dir = TempDir::new().expect("failed to create temp dir");
+    let storage = Arc::new(
+        StreamingSegmentedStorage::new(zone_map_test_config(temp_dir.path()))
+            .expect("failed to create storage"),
+    );
+    write_segments(&storage);
+    for segment in &sorted_segments(&storage)[..3] {
+        std::fs::remove_file(&segment.data_path).expect("failed to remove segment");
+    }
+
+    let window = WindowDefinition {
+        window_name: "http://example.org/window/hot".to_string(),
+        source_kind: SourceKind::Log,
+        stream_name: "http://example.org/stream".to_string(),
+        width: 0,
+        slide: 0,
+        offset: None,
+        start: Some(1_000),
+        end: Some(1_400),
+        window_type: WindowType::HistoricalFixed,
+    };
+    let executor = HistoricalExecutor::new(storage.clone(), OxigraphAdapter::new());
+    let bindings = executor
+        .execute_fixed_window(
+            &window,
+            "SELECT ?sensor ?value WHERE { GRAPH ?g {
+               ?sensor <http://example.org/temperature> ?value FILTER(?value >= 35)
+             } }",
+        )
+        .expect("failed to execute window");
+    assert_eq!(bindings.len(), 5);
+}
@@ -9,7 +9,7 @@
 
 use janus::parsing::rdf_parser;
 use janus::storage::segmented_storage::StreamingSegmentedStorage;
-use janus::storage::util::StreamingConfig;
+use janus::storage::util::{BackpressurePolicy, StreamingConfig};
 use janus::stream_bus::{BrokerType, StreamBus, StreamBusConfig};
 use std::fs::{self, File};
 use std::io::Write;
@@ -36,6 +36,7 @@ fn create_test_storage(test_dir: &str) -> std::io::Result<Arc<StreamingSegmented
         sparse_interval: 100,
         entries_per_index_block: 10,
         segment_base_path: format!("{}/storage", test_dir),
+        ..StreamingConfig::default()
     };
 
     let mut storage = StreamingSegmentedStorage::new(config)?;
@@ -62,6 +63,7 @@ fn test_parse_ntriples_basic() {
         loop_file: false,
         add_timestamps: true,
         mqtt_config: None,
+        stream_name: None,
     };
 
     let storage = create_test_storage(&test_dir).unwrap();
@@ -72,10 +74,10 @@ fn test_parse_ntriples_basic() {
 
     assert!(event.is_ok());
     let event = event.unwrap();
-    assert_eq!(event.subject, "http://example.org/sensor1");
-    assert_eq!(event.predicate, "http://example.org/temperature");
-    assert_eq!(event.object, "23.5");
-    assert_eq!(event.graph, "http://example.org/graph1");
+    assert_eq!(event.subject.value(), "http://example.org/sensor1");
+    assert_eq!(event.predicate.value(), "http://example.org/temperature");
+    assert_eq!(event.object.value(), "23.5");
+    assert_eq!(event.graph.value(), "http://example.org/graph1");
 
     cleanup_test_environment(&test_dir);
 }
@@ -92,6 +94,7 @@ fn test_parse_ntriples_without_graph() {
         loop_file: false,
         add_timestamps: true,
         mqtt_config: None,
+        stream_name: None,
     };
 
     let storage = create_test_storage(&test_dir).unwrap();
@@ -102,10 +105,10 @@ fn test_parse_ntriples_without_graph() {
 
     assert!(event.is_ok());
     let event = event.unwrap();
-    assert_eq!(event.subject, "http://example.org/alice");
-    assert_eq!(event.predicate, "http://example.org/knows");
-    assert_eq!(event.object, "http://example.org/bob");
-    assert_eq!(event.graph, "");
+    assert_eq!(event.subject.value(), "http://example.org/alice");
+    assert_eq!(event.predicate.value(), "http://example.org/knows");
+    assert_eq!(event.object.value(), "http://example.org/bob");
+    assert_eq!(event.graph.value(), "");
 
     cleanup_test_environment(&test_dir);
 }
@@ -122,6 +125,7 @@ fn test_parse_invalid_rdf_line() {
         loop_file: false,
         add_timestamps: true,
         mqtt_config: None,
+        stream_name: None,
     };
 
     let storage = create_test_storage(&test_dir).unwrap();
@@ -156,11 +160,12 @@ fn test_storage_only_mode() {
         loop_file: false,
         add_timestamps: true,
         mqtt_config: None,
+        stream_name: None,
     };
 
     let storage = create_test_storage(&test_dir).unwrap();
 
-    let bus = StreamBus::new(config, Arc::clone(&storage));
+    let bus = StreamBus::new(config, storage.clone());
     let metrics = bus.start().unwrap();
 
     assert_eq!(metrics.events_read, 3);
@@ -174,6 +179,57 @@ fn test_storage_only_mode() {
     cleanup_test_environment(&test_dir);
 }
 
+#[test]
+fn test_replay_slows_down_for_storage_memory_budget() {
+    let test_dir = setup_test_environment("memory_budget").unwrap();
+
+    let test_file = format!("{}/test_budget.nq", &test_dir);
+    let mut rdf_data = String::new();
+    for i in 0..200 {
+        rdf_data.push_str(&format!(
+            "<http://example.org/sensor{}> <http://example.org/temperature> \"{}\" <http://example.org/graph1> .\n",
+            i % 5,
+            i
+        ));
+    }
+    create_test_rdf_file(&test_file, &rdf_data).unwrap();
+
+    // Writes beyond 40 buffered events are refused until the background flush catches up.
+    let config = StreamingConfig {
+        max_batch_events: 1000,
+        max_batch_age_seconds: 60,
+        max_batch_bytes: 1_000_000,
+        sparse_interval: 100,
+        entries_per_index_block: 10,
+        segment_base_path: format!("{}/storage", test_dir),
+        memory_budget_bytes: Some(40 * std::mem::size_of::<janus::core::Event>()),
+        backpressure_policy: BackpressurePolicy::WouldBlock,
+        ..StreamingConfig::default()
+    };
+    let mut storage = StreamingSegmentedStorage::new(config).unwrap();
+    storage.start_background_flushing();
+    let storage = Arc::new(storage);
+
+    let bus_config = StreamBusConfig {
+        input_file: test_file,
+        broker_type: BrokerType::None,
+        topics: vec![],
+        rate_of_publishing: 0,
+        loop_file: false,
+        add_timestamps: true,
+        mqtt_config: None,
+        stream_name: None,
+    };
+    let metrics = StreamBus::new(bus_config, storage.clone()).start().unwrap();
+
+    assert_eq!(metrics.events_read, 200);
+    assert_eq!(metrics.events_stored, 200);
+    assert_eq!(metrics.storage_errors, 0);
+    assert!(metrics.throttled_writes > 0);
+    assert_eq!(storage.query_rdf(0, u64::MAX).unwrap().len(), 200);
+    cleanup_test_environment(&test_dir);
+}
+
 #[test]
 fn test_empty_lines_and_comments_skipped() {
     let test_dir = setup_test_environment("empty_lines_comments").unwrap();
@@ -197,10 +253,11 @@ fn test_empty_lines_and_comments_skipped() {
         loop_file: false,
         add_timestamps: true,
         mqtt_config: None,
+        stream_name: None,
     };
 
     let storage = create_test_storage(&test_dir).unwrap();
-    let bus = StreamBus::new(config, Arc::clone(&storage));
+    let bus = StreamBus::new(config, storage.clone());
     let metrics = bus.start().unwrap();
 
     assert_eq!(metrics.events_read, 2);
@@ -232,10 +289,11 @@ fn test_rate_limiting() {
         loop_file: false,
         add_timestamps: true,
         mqtt_config: None,
+        stream_name: None,
     };
 
     let storage = create_test_storage(&test_dir).unwrap();
-    let bus = StreamBus::new(config, Arc::clone(&storage));
+    let bus = StreamBus::new(config, storage.clone());
 
     let start = std::time::Instant::now();
     let metrics = bus.start().unwrap();
@@ -255,6 +313,7 @@ fn test_metrics_calculation() {
         events_stored: 98,
         publish_errors: 5,
         storage_errors: 2,
+        throttled_writes: 0,
         elapsed_seconds: 2.0,
     };
 
@@ -271,6 +330,7 @@ fn test_metrics_zero_events() {
         events_stored: 0,
         publish_errors: 0,
         storage_errors: 0,
+        throttled_writes: 0,
         elapsed_seconds: 0.0,
     };
 
@@ -302,10 +362,11 @@ fn test_stop_signal() {
         loop_file: false,
         add_timestamps: true,
         mqtt_config: None,
+        stream_name: None,
     };
 
     let storage = create_test_storage(&test_dir).unwrap();
-    let bus = StreamBus::new(config, Arc::clone(&storage));
+    let bus = StreamBus::new(config, storage.clone());
 
     let handle = bus.start_async();
 
@@ -339,10 +400,11 @@ fn test_file_loop_mode() {
         loop_file: true,
         add_timestamps: true,
         mqtt_config: None,
+        stream_name: None,
     };
 
     let storage = create_test_storage(&test_dir).unwrap();
-    let bus = StreamBus::new(config, Arc::clone(&storage));
+    let bus = StreamBus::new(config, storage.clone());
 
     let handle = bus.start_async();
 
@@ -368,10 +430,11 @@ fn test_timestamp_parsing() {
         loop_file: false,
         add_timestamps: true,
         mqtt_config: None,
+        stream_name: None,
     };
 
     let storage = create_test_storage(&test_dir).unwrap();
-    let _bus_with_ts = StreamBus::new(config_with_timestamps, Arc::clone(&storage));
+    let _bus_with_ts = StreamBus::new(config_with_timestamps, storage.clone());
 
     let line = "<http://example.org/sensor1> <http://example.org/temperature> \"23.5\" <http://example.org/graph1> .";
     let event = rdf_parser::parse_rdf_line(line, true).unwrap();
@@ -385,6 +448,7 @@ fn test_timestamp_parsing() {
         loop_file: false,
         add_timestamps: false,
         mqtt_config: None,
+        stream_name: None,
     };
 
     let _bus_without_ts = StreamBus::new(config_without_timestamps, storage);
@@ -449,10 +513,11 @@ fn test_malformed_rdf_lines_handling() {
         loop_file: false,
         add_timestamps: true,
         mqtt_config: None,
+        stream_name: None,
     };
 
     let storage = create_test_storage(&test_dir).unwrap();
-    let bus = StreamBus::new(config, Arc::clone(&storage));
+    let bus = StreamBus::new(config, storage.clone());
     let metrics = bus.start().unwrap();
 
     assert_eq!(metrics.events_read, 4);
@@ -485,11 +550,12 @@ fn test_large_file_processing() {
         loop_file: false,
         add_timestamps: true,
         mqtt_config: None,
+        stream_name: None,
     };
 
     let storage = create_test_storage(&test_dir).unwrap();
 
-    let bus = StreamBus::new(config, Arc::clone(&storage));
+    let bus = StreamBus::new(config, storage.clone());
     let metrics = bus.start().unwrap();
 
     assert_eq!(metrics.events_read, 500);